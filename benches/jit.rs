@@ -0,0 +1,55 @@
+//! Compare the interpreted and compiled backtracker.
+use regress::{Engine, Regex, RegexBuilder};
+use std::time::{Duration, Instant};
+
+fn build(pattern: &str, flags: &str, engine: Engine) -> Regex {
+    RegexBuilder::new(pattern)
+        .flags(flags)
+        .engine(engine)
+        .build()
+        .unwrap()
+}
+
+fn time(re: &Regex, text: &str) -> (Duration, usize) {
+    let count = re.find_iter(text).count();
+    let start = Instant::now();
+    for _ in 0..5 {
+        re.find_iter(text).count();
+    }
+    (start.elapsed() / 5, count)
+}
+
+fn main() {
+    let log: String = (0..12_000)
+        .map(|i| {
+            format!(
+                "2024-01-{:02} 12:{:02}:{:02} ERROR user=u{i} code {}\n",
+                i % 28 + 1,
+                i % 60,
+                i % 60,
+                i * 7
+            )
+        })
+        .collect();
+    let ab = "ab".repeat(300_000) + "c";
+    let lines = ("ab".repeat(200) + "c\n").repeat(1_000);
+    let cases: &[(&str, &str, &str)] = &[
+        (r"(a|b)*c", "", &ab),
+        (r"^(a|b)*c", "m", &ab),
+        (r"^(a|b)*c", "m", &lines),
+        (r"^(?:a|b)*c", "m", &lines),
+        (r"^(.)*c", "m", &lines),
+        (r"(\w+)\s+(\w+)$", "m", &log),
+        (r"^(.*) (.*)=(.*)$", "m", &log),
+        (r"^(\d+)-(\d+)-(\d+) ERROR user=(\w+)", "m", &log),
+        (r"(\w+)=(\w+) code (\d+)", "", &log),
+        (r"(\d+):(\d+):(\d+) (?:ERROR|WARN) user=(\w+)", "", &log),
+        (r"(\w+)\s(\w+)", "", &log),
+    ];
+    for &(pattern, flags, text) in cases {
+        let (interpreted, count) = time(&build(pattern, flags, Engine::Backtrack), text);
+        let (jit, jit_count) = time(&build(pattern, flags, Engine::Jit), text);
+        assert_eq!(count, jit_count);
+        println!("{pattern:45} /{flags:2} {interpreted:>10.2?} interpreted {jit:>10.2?} jit");
+    }
+}
@@ -0,0 +1,1012 @@
+//! Static analysis of patterns for catastrophic backtracking.
+//!
+//! The backtracking engine may take time exponential, or polynomial of high
+//! degree, in the length of the input when a pattern can match the same text
+//! in many ways, and the match then fails. Patterns accepted from untrusted
+//! sources may be checked with [`redos_risk`] before they are used.
+
+use crate::api::{Error, Flags, Regex};
+use crate::charclasses;
+use crate::codepointset::{CODE_POINT_MAX, CodePoint, CodePointSet, Interval};
+use crate::ir::{Node, Quantifier};
+use crate::parse;
+use crate::unicode;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, VecDeque};
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use {
+    alloc::{collections::VecDeque, string::String, vec::Vec},
+    hashbrown::HashMap,
+};
+
+/// How the work of a failing match grows with the number of repeats of the
+/// pump.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum RiskKind {
+    /// The work doubles with each repeat, as for `^(a+)+$`.
+    Exponential,
+
+    /// The work grows as a power of the number of repeats, as for `^a*a*$`.
+    Polynomial,
+}
+
+/// A sub-pattern which may backtrack catastrophically, with an input which
+/// makes it do so.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Risk {
+    /// How the work grows.
+    pub kind: RiskKind,
+
+    /// The span of the sub-pattern at fault, in bytes.
+    pub span: Range<usize>,
+
+    /// Text which leads the match to the sub-pattern.
+    pub prefix: String,
+
+    /// Text which the sub-pattern can match in more than one way.
+    pub pump: String,
+
+    /// Text which makes the match fail, after any number of pumps.
+    pub suffix: String,
+}
+
+impl Risk {
+    /// \return an attack string: the prefix, then the pump \p repeats times,
+    /// then the suffix.
+    pub fn witness(&self, repeats: usize) -> String {
+        let mut result = String::with_capacity(
+            self.prefix.len() + self.pump.len() * repeats + self.suffix.len(),
+        );
+        result.push_str(&self.prefix);
+        for _ in 0..repeats {
+            result.push_str(&self.pump);
+        }
+        result.push_str(&self.suffix);
+        result
+    }
+}
+
+/// Check `pattern` with `flags` for sub-patterns which may backtrack
+/// catastrophically, returning the risks found in pattern order.
+///
+/// A risk is a loop which can match some pump text in two ways, as for nested
+/// quantifiers like `(a+)+`, or overlapping alternatives like `(a|\w)*`; or
+/// two loops in sequence which can both match the pump, as for `a*a*`. Each
+/// risk comes with a witness, which has been checked to fail to match. Spans
+/// are in bytes.
+///
+/// This is a static analysis of the IR and so is approximate. Repetitions
+/// bounded at more than 8 are treated as unbounded. Backreferences and
+/// assertions are assumed to always pass, and the contents of lookbehinds are
+/// not checked. The search is bounded, so very large patterns may be only
+/// partly checked. Matches are considered only from the start of the witness.
+///
+/// ```rust
+/// use regress::analysis::{self, RiskKind};
+/// let pattern = r"^(\w+\s?)+$";
+/// let risks = analysis::redos_risk(pattern, "").unwrap();
+/// assert_eq!(risks[0].kind, RiskKind::Exponential);
+/// assert_eq!(&pattern[risks[0].span.clone()], r"(\w+\s?)+");
+/// assert_eq!(risks[0].witness(3), "aaaa!");
+/// assert!(analysis::redos_risk(r"^(\w+\s)+$", "").unwrap().is_empty());
+/// ```
+pub fn redos_risk<F: Into<Flags>>(pattern: &str, flags: F) -> Result<Vec<Risk>, Error> {
+    let flags = flags.into();
+    let (ire, loop_spans) = parse::try_parse_with_loop_spans(pattern.chars().map(u32::from), flags)
+        .map_err(|err| err.with_utf8_span(pattern))?;
+    let mut regex = Regex::with_flags(pattern, flags)?;
+    regex.cr.backtrack_limit = Some(CONFIRM_BACKTRACK_LIMIT);
+
+    let mut builder = Builder { states: Vec::new() };
+    let accept = builder.push(State::Accept);
+    let start = builder.build(&ire.node, accept, 0);
+    debug_assert!(
+        count_loops(&ire.node) as usize == loop_spans.len(),
+        "Loop spans do not match the IR"
+    );
+    let nfa = Nfa::new(builder.states, start);
+
+    // Convert loop spans from code points to bytes.
+    let byte_offsets: Vec<usize> = pattern
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(core::iter::once(pattern.len()))
+        .collect();
+    let loop_spans: Vec<Range<usize>> = loop_spans
+        .into_iter()
+        .map(|span| byte_offsets[span.start]..byte_offsets[span.end])
+        .collect();
+
+    let mut analyzer = Analyzer {
+        nfa: &nfa,
+        regex: &regex,
+        loop_spans: &loop_spans,
+        work: MAX_WORK,
+        risks: Vec::new(),
+    };
+    analyzer.run();
+    // Drop risks within another which is at least as bad.
+    let mut risks = analyzer.risks;
+    risks.sort_by_key(|risk| {
+        (
+            risk.span.start,
+            core::cmp::Reverse(risk.span.end),
+            risk.kind,
+        )
+    });
+    let mut result: Vec<Risk> = Vec::new();
+    for risk in risks {
+        let covered = result.iter().any(|outer| {
+            outer.span.end >= risk.span.end
+                && (outer.kind == RiskKind::Exponential || risk.kind == RiskKind::Polynomial)
+        });
+        if !covered {
+            result.push(risk);
+        }
+    }
+    Ok(result)
+}
+
+/// Bounded repetitions with a maximum of at most this are unrolled; larger
+/// bounds are treated as unbounded.
+const MAX_UNROLL: usize = 8;
+
+/// Stop unrolling repetitions once the automaton has this many states.
+const MAX_UNROLL_STATES: usize = 4096;
+
+/// The most steps taken to find the successors of a single state.
+const MAX_CLOSURE_WORK: usize = 1 << 14;
+
+/// The most steps taken in total by the analysis.
+const MAX_WORK: usize = 1 << 20;
+
+/// The number of pumps in the text used to confirm a risk.
+const CONFIRM_REPEATS: usize = 4;
+
+/// The backtrack limit when confirming a risk. Exceeding it confirms the risk.
+const CONFIRM_BACKTRACK_LIMIT: usize = 1 << 14;
+
+/// \return the number of loops in \p node, including those in lookarounds.
+fn count_loops(node: &Node) -> u32 {
+    match node {
+        Node::Empty
+        | Node::Goal
+        | Node::Char { .. }
+        | Node::ByteSequence(..)
+        | Node::ByteSet(..)
+        | Node::CharSet(..)
+        | Node::StringSet { .. }
+        | Node::MatchAny
+        | Node::MatchAnyExceptLineTerminator
+        | Node::Anchor { .. }
+        | Node::WordBoundary { .. }
+        | Node::BackRef { .. }
+        | Node::Bracket(..) => 0,
+        Node::Cat(nodes) => nodes.iter().map(count_loops).sum(),
+        Node::Alt(left, right) => count_loops(left) + count_loops(right),
+        Node::CaptureGroup { contents, .. } | Node::LookaroundAssertion { contents, .. } => {
+            count_loops(contents)
+        }
+        Node::Loop { loopee, .. } | Node::Loop1CharBody { loopee, .. } => count_loops(loopee) + 1,
+    }
+}
+
+/// A state of a Thompson automaton built from the IR.
+#[derive(Debug)]
+enum State {
+    /// Consume a code point in the set, then go to the next state.
+    Consume(CodePointSet, usize),
+
+    /// Go to any of the states, without consuming.
+    Split(Vec<usize>),
+
+    /// Begin an iteration of the loop with the given index.
+    Enter(u32, usize),
+
+    /// End an iteration of the loop with the given index, returning to its
+    /// head. This may only be taken if the iteration consumed something.
+    Back(u32, usize),
+
+    /// The match, or a lookahead, succeeds.
+    Accept,
+}
+
+/// Builds the automaton for a regex.
+struct Builder {
+    states: Vec<State>,
+}
+
+impl Builder {
+    /// Add \p state, returning its index.
+    fn push(&mut self, state: State) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    /// Add a state consuming a code point in \p cps.
+    fn consume(&mut self, cps: CodePointSet, next: usize) -> usize {
+        self.push(State::Consume(cps, next))
+    }
+
+    /// Add a state consuming the code points \p cps in sequence.
+    fn consume_sequence<T: DoubleEndedIterator<Item = CodePoint>>(
+        &mut self,
+        cps: T,
+        icase: bool,
+        mut next: usize,
+    ) -> usize {
+        for cp in cps.rev() {
+            let mut set = CodePointSet::new();
+            set.add_one(cp);
+            if icase {
+                set = unicode::add_icase_code_points(set);
+            }
+            next = self.consume(set, next);
+        }
+        next
+    }
+
+    /// Build states for \p node, which continue to \p next.
+    /// Loops in \p node are numbered from \p first_loop.
+    /// \return the start state.
+    fn build(&mut self, node: &Node, next: usize, first_loop: u32) -> usize {
+        match node {
+            // Assertions and backreferences are assumed to pass without consuming.
+            Node::Empty
+            | Node::Anchor { .. }
+            | Node::WordBoundary { .. }
+            | Node::BackRef { .. } => next,
+            Node::Goal => self.push(State::Accept),
+            &Node::Char { c } => self.consume_sequence(core::iter::once(c), false, next),
+            Node::ByteSequence(bytes) => {
+                self.consume_sequence(bytes.iter().map(|&b| b as CodePoint), false, next)
+            }
+            Node::ByteSet(bytes) => {
+                let mut set = CodePointSet::new();
+                bytes.iter().for_each(|&b| set.add_one(b as CodePoint));
+                self.consume(set, next)
+            }
+            Node::CharSet(chars) => {
+                let mut set = CodePointSet::new();
+                chars.iter().for_each(|&c| set.add_one(c));
+                self.consume(set, next)
+            }
+            Node::Bracket(contents) => {
+                let set = match contents.invert {
+                    true => contents.cps.inverted(),
+                    false => contents.cps.clone(),
+                };
+                self.consume(set, next)
+            }
+            Node::MatchAny => self.consume(
+                CodePointSet::from_sorted_disjoint_intervals(vec![Interval::new(
+                    0,
+                    CODE_POINT_MAX,
+                )]),
+                next,
+            ),
+            Node::MatchAnyExceptLineTerminator => self.consume(
+                CodePointSet::from_sorted_disjoint_intervals(charclasses::LINE_TERMINATOR.to_vec())
+                    .inverted(),
+                next,
+            ),
+            Node::StringSet {
+                alternatives,
+                icase,
+            } => {
+                let starts = alternatives
+                    .iter()
+                    .map(|alt| self.consume_sequence(alt.iter().copied(), *icase, next))
+                    .collect();
+                self.push(State::Split(starts))
+            }
+            Node::Cat(nodes) => {
+                let mut loop_idx = first_loop + nodes.iter().map(count_loops).sum::<u32>();
+                let mut next = next;
+                for node in nodes.iter().rev() {
+                    loop_idx -= count_loops(node);
+                    next = self.build(node, next, loop_idx);
+                }
+                next
+            }
+            Node::Alt(left, right) => {
+                let left_start = self.build(left, next, first_loop);
+                let right_start = self.build(right, next, first_loop + count_loops(left));
+                self.push(State::Split(vec![left_start, right_start]))
+            }
+            Node::CaptureGroup { contents, .. } => self.build(contents, next, first_loop),
+            Node::LookaroundAssertion {
+                backwards,
+                contents,
+                ..
+            } => {
+                // Lookbehinds have had their contents reversed, so are not checked.
+                if *backwards {
+                    return next;
+                }
+                let accept = self.push(State::Accept);
+                let contents_start = self.build(contents, accept, first_loop);
+                self.push(State::Split(vec![contents_start, next]))
+            }
+            Node::Loop { loopee, quant, .. } | Node::Loop1CharBody { loopee, quant } => {
+                let loop_idx = first_loop + count_loops(loopee);
+                self.build_loop(loopee, *quant, next, first_loop, loop_idx)
+            }
+        }
+    }
+
+    /// Build states for a loop of \p loopee with quantifier \p quant, which
+    /// continue to \p next. The loop has index \p loop_idx, and loops within
+    /// it are numbered from \p first_loop.
+    /// \return the start state.
+    fn build_loop(
+        &mut self,
+        loopee: &Node,
+        quant: Quantifier,
+        next: usize,
+        first_loop: u32,
+        loop_idx: u32,
+    ) -> usize {
+        match quant.max {
+            Some(max) if max <= MAX_UNROLL && self.states.len() < MAX_UNROLL_STATES => {
+                // Unroll as x{min}(x(x)?)? so that each count is matched one way.
+                let mut start = next;
+                for _ in quant.min..max {
+                    let body = self.build(loopee, start, first_loop);
+                    start = self.push(State::Split(vec![body, next]));
+                }
+                for _ in 0..quant.min {
+                    start = self.build(loopee, start, first_loop);
+                }
+                start
+            }
+            _ => {
+                let head = self.push(State::Split(Vec::new()));
+                let back = self.push(State::Back(loop_idx, head));
+                let body = self.build(loopee, back, first_loop);
+                let enter = self.push(State::Enter(loop_idx, body));
+                self.states[head] = State::Split(vec![enter, next]);
+                // A loop with a minimum starts with an iteration.
+                if quant.min > 0 { enter } else { head }
+            }
+        }
+    }
+}
+
+/// A transition between consuming states, which consumes a code point of the
+/// target.
+#[derive(Debug)]
+struct Edge {
+    /// The target, as an index of a consuming state.
+    to: usize,
+
+    /// Whether there is more than one path to the target.
+    multiple: bool,
+
+    /// The loops whose iterations are ended on the way to the target.
+    loops: Vec<u32>,
+}
+
+/// The automaton, as a graph of consuming states.
+struct Nfa {
+    /// The code points consumed by each state.
+    sets: Vec<CodePointSet>,
+
+    /// The transitions from each state.
+    edges: Vec<Vec<Edge>>,
+
+    /// The transitions from the start.
+    start_edges: Vec<Edge>,
+
+    /// A char which no state consumes, if any.
+    unused: Option<char>,
+
+    /// The strongly connected component of each state.
+    components: Vec<usize>,
+
+    /// Whether each component contains a cycle.
+    cyclic: Vec<bool>,
+}
+
+impl Nfa {
+    fn new(states: Vec<State>, start: usize) -> Nfa {
+        // Number the consuming states.
+        let mut consume_idx = vec![usize::MAX; states.len()];
+        let mut sets = Vec::new();
+        for (idx, state) in states.iter().enumerate() {
+            if let State::Consume(set, _) = state {
+                consume_idx[idx] = sets.len();
+                sets.push(set.clone());
+            }
+        }
+        let closure = |from: usize| epsilon_closure(&states, &consume_idx, from);
+        let edges: Vec<Vec<Edge>> = states
+            .iter()
+            .filter_map(|state| match state {
+                State::Consume(_, next) => Some(closure(*next)),
+                _ => None,
+            })
+            .collect();
+        let start_edges = closure(start);
+        let (components, cyclic) = strongly_connected_components(&edges);
+        let mut all = CodePointSet::new();
+        for set in &sets {
+            all.add_set(set.clone());
+        }
+        Nfa {
+            unused: pick(&all.inverted()),
+            sets,
+            edges,
+            start_edges,
+            components,
+            cyclic,
+        }
+    }
+
+    /// \return the number of consuming states.
+    fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// \return the shortest text which leads from the start to consuming
+    /// \p target, or None if there is none.
+    fn text_to(&self, target: usize) -> Option<String> {
+        let mut parents: Vec<Option<usize>> = vec![None; self.len()];
+        let mut queue = VecDeque::new();
+        for edge in &self.start_edges {
+            if parents[edge.to].is_none() {
+                parents[edge.to] = Some(usize::MAX);
+                queue.push_back(edge.to);
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            if state == target {
+                let mut text = Vec::new();
+                let mut cursor = state;
+                while cursor != usize::MAX {
+                    text.push(pick(&self.sets[cursor])?);
+                    cursor = parents[cursor]?;
+                }
+                return Some(text.into_iter().rev().collect());
+            }
+            for edge in &self.edges[state] {
+                if parents[edge.to].is_none() {
+                    parents[edge.to] = Some(state);
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        None
+    }
+
+    /// \return the states which can reach a state in \p component.
+    fn reaching(&self, component: usize) -> Vec<bool> {
+        let mut reverse: Vec<Vec<usize>> = vec![Vec::new(); self.len()];
+        for (from, edges) in self.edges.iter().enumerate() {
+            for edge in edges {
+                reverse[edge.to].push(from);
+            }
+        }
+        let mut result = vec![false; self.len()];
+        let mut stack: Vec<usize> = (0..self.len())
+            .filter(|&state| self.components[state] == component)
+            .collect();
+        while let Some(state) = stack.pop() {
+            if !core::mem::replace(&mut result[state], true) {
+                stack.extend(reverse[state].iter().copied());
+            }
+        }
+        result
+    }
+
+    /// \return the states reachable from a state in \p component.
+    fn reachable(&self, component: usize) -> Vec<bool> {
+        let mut result = vec![false; self.len()];
+        let mut stack: Vec<usize> = (0..self.len())
+            .filter(|&state| self.components[state] == component)
+            .collect();
+        while let Some(state) = stack.pop() {
+            if !core::mem::replace(&mut result[state], true) {
+                stack.extend(self.edges[state].iter().map(|edge| edge.to));
+            }
+        }
+        result
+    }
+}
+
+/// \return the transitions to consuming states from state \p from, following
+/// every path which does not consume.
+fn epsilon_closure(states: &[State], consume_idx: &[usize], from: usize) -> Vec<Edge> {
+    enum Step {
+        Visit(usize),
+        Leave,
+        LeaveBack,
+    }
+    let mut edges: Vec<Edge> = Vec::new();
+    // Loops entered, and loops whose iterations were ended, on the current path.
+    let mut entered: Vec<u32> = Vec::new();
+    let mut ended: Vec<u32> = Vec::new();
+    let mut stack = vec![Step::Visit(from)];
+    let mut work = MAX_CLOSURE_WORK;
+    while let Some(step) = stack.pop() {
+        if work == 0 {
+            break;
+        }
+        work -= 1;
+        let state = match step {
+            Step::Visit(state) => state,
+            Step::Leave => {
+                entered.pop();
+                continue;
+            }
+            Step::LeaveBack => {
+                ended.pop();
+                continue;
+            }
+        };
+        match &states[state] {
+            State::Consume(..) => {
+                let to = consume_idx[state];
+                match edges.iter_mut().find(|edge| edge.to == to) {
+                    Some(edge) => {
+                        edge.multiple = true;
+                        for &loop_idx in &ended {
+                            if !edge.loops.contains(&loop_idx) {
+                                edge.loops.push(loop_idx);
+                            }
+                        }
+                    }
+                    None => edges.push(Edge {
+                        to,
+                        multiple: false,
+                        loops: ended.clone(),
+                    }),
+                }
+            }
+            State::Split(nexts) => {
+                stack.extend(nexts.iter().rev().map(|&next| Step::Visit(next)));
+            }
+            &State::Enter(loop_idx, next) => {
+                entered.push(loop_idx);
+                stack.push(Step::Leave);
+                stack.push(Step::Visit(next));
+            }
+            &State::Back(loop_idx, next) => {
+                // An iteration which consumed nothing may not loop.
+                if !entered.contains(&loop_idx) {
+                    ended.push(loop_idx);
+                    stack.push(Step::LeaveBack);
+                    stack.push(Step::Visit(next));
+                }
+            }
+            State::Accept => {}
+        }
+    }
+    edges
+}
+
+/// Compute the strongly connected components of the graph with \p edges,
+/// using Tarjan's algorithm.
+/// \return the component of each node, and whether each component contains a
+/// cycle.
+fn strongly_connected_components(edges: &[Vec<Edge>]) -> (Vec<usize>, Vec<bool>) {
+    const UNVISITED: usize = usize::MAX;
+    let count = edges.len();
+    let mut index = vec![UNVISITED; count];
+    let mut lowlink = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = Vec::new();
+    let mut components = vec![UNVISITED; count];
+    let mut cyclic = Vec::new();
+    let mut next_index = 0;
+    for root in 0..count {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        // Each frame is a node and the position of its next edge.
+        let mut frames = vec![(root, 0)];
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some(&mut (node, ref mut edge_idx)) = frames.last_mut() {
+            if let Some(edge) = edges[node].get(*edge_idx) {
+                *edge_idx += 1;
+                let to = edge.to;
+                if index[to] == UNVISITED {
+                    index[to] = next_index;
+                    lowlink[to] = next_index;
+                    next_index += 1;
+                    stack.push(to);
+                    on_stack[to] = true;
+                    frames.push((to, 0));
+                } else if on_stack[to] {
+                    lowlink[node] = lowlink[node].min(index[to]);
+                }
+                continue;
+            }
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                let component = cyclic.len();
+                let mut size = 0;
+                loop {
+                    let member = stack.pop().expect("Node should be on the stack");
+                    on_stack[member] = false;
+                    components[member] = component;
+                    size += 1;
+                    if member == node {
+                        break;
+                    }
+                }
+                let self_loop = edges[node].iter().any(|edge| edge.to == node);
+                cyclic.push(size > 1 || self_loop);
+            }
+        }
+    }
+    (components, cyclic)
+}
+
+/// \return the number of steps in \p visits from the start to \p node.
+fn path_len(visits: &HashMap<ProductNode, Visit>, mut node: ProductNode) -> usize {
+    let mut len = 0;
+    while let Some(parent) = visits[&node].parent {
+        node = parent;
+        len += 1;
+    }
+    len
+}
+
+/// \return whether \p a and \p b share a code point.
+fn overlaps(a: &CodePointSet, b: &CodePointSet) -> bool {
+    let (mut lhs, mut rhs) = (a.intervals(), b.intervals());
+    while let (Some(l), Some(r)) = (lhs.first(), rhs.first()) {
+        if l.overlaps(*r) {
+            return true;
+        } else if l.last < r.first {
+            lhs = &lhs[1..];
+        } else {
+            rhs = &rhs[1..];
+        }
+    }
+    false
+}
+
+/// \return the code points in both \p a and \p b.
+fn intersection(a: &CodePointSet, b: &CodePointSet) -> CodePointSet {
+    let mut result = a.clone();
+    result.intersect(b.intervals());
+    result
+}
+
+/// \return a char in \p set, preferring a letter or digit, then any printable
+/// ASCII char; or None if the set has no chars.
+fn pick(set: &CodePointSet) -> Option<char> {
+    const PREFERRED: [(char, char); 4] = [('a', 'z'), ('0', '9'), ('A', 'Z'), (' ', '~')];
+    let ivs = set.intervals();
+    for (first, last) in PREFERRED {
+        for iv in ivs {
+            let cp = iv.first.max(first as CodePoint);
+            if cp <= iv.last.min(last as CodePoint) {
+                return char::from_u32(cp);
+            }
+        }
+    }
+    // Skip surrogates, which are not chars.
+    ivs.iter().find_map(|iv| {
+        char::from_u32(iv.first).or_else(|| {
+            let cp = iv.first.max(0xE000);
+            (cp <= iv.last).then(|| char::from_u32(cp)).flatten()
+        })
+    })
+}
+
+/// Searches the automaton for risks.
+struct Analyzer<'a> {
+    nfa: &'a Nfa,
+
+    /// The compiled regex, for confirming risks.
+    regex: &'a Regex,
+
+    /// The span in bytes of each loop.
+    loop_spans: &'a [Range<usize>],
+
+    /// The steps remaining.
+    work: usize,
+
+    /// The risks found.
+    risks: Vec<Risk>,
+}
+
+/// A node of a product of the automaton with itself: a state for each path,
+/// or for a pair of paths, two states and whether the paths have diverged.
+type ProductNode = [usize; 3];
+
+/// How a node of a product was reached.
+struct Visit {
+    /// The node visited before, or None for the start.
+    parent: Option<ProductNode>,
+
+    /// The transition taken by each path from the parent.
+    edges: [usize; 3],
+}
+
+impl Analyzer<'_> {
+    /// Take a step, returning false if out of work.
+    fn step(&mut self) -> bool {
+        self.work = self.work.saturating_sub(1);
+        self.work > 0
+    }
+
+    fn run(&mut self) {
+        let nfa = self.nfa;
+        let component_count = nfa.cyclic.len();
+        let exponential: Vec<bool> = (0..component_count)
+            .map(|component| nfa.cyclic[component] && self.find_exponential(component))
+            .collect();
+        // A polynomial risk is moot where there is an exponential one.
+        let candidate = |component: usize| nfa.cyclic[component] && !exponential[component];
+        let mut reaching: Vec<Option<Vec<bool>>> = vec![None; component_count];
+        for from in (0..component_count).filter(|&from| candidate(from)) {
+            let reachable = nfa.reachable(from);
+            for to in (0..component_count).filter(|&to| to != from && candidate(to)) {
+                if !(0..nfa.len()).any(|state| nfa.components[state] == to && reachable[state]) {
+                    continue;
+                }
+                let reaches_to = reaching[to].get_or_insert_with(|| nfa.reaching(to));
+                let between: Vec<bool> = (0..nfa.len())
+                    .map(|state| reachable[state] && reaches_to[state])
+                    .collect();
+                if between.iter().any(|&b| b) && self.find_polynomial(from, to, &between) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Look in \p component for a state which can loop back to itself on the
+    /// same text in two ways. Every repeat of that text doubles the paths
+    /// which backtracking must try. The shortest such text is preferred.
+    /// \return whether a risk was found.
+    fn find_exponential(&mut self, component: usize) -> bool {
+        let nfa = self.nfa;
+        let mut pivots = Vec::new();
+        for pivot in (0..nfa.len()).filter(|&state| nfa.components[state] == component) {
+            if let Some(visits) = self.search_pairs(pivot, component) {
+                pivots.push((path_len(&visits, [pivot, pivot, 1]), pivot));
+            }
+        }
+        pivots.sort_unstable();
+        for (_, pivot) in pivots {
+            let target = [pivot, pivot, 1];
+            if let Some(visits) = self.search_pairs(pivot, component)
+                && self.report(RiskKind::Exponential, pivot, &visits, target)
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Search pairs of paths from \p pivot within \p component, for two which
+    /// diverge and return to \p pivot. Nodes are pairs of states, and whether
+    /// the paths have diverged.
+    /// \return the nodes visited, if the paths were found.
+    fn search_pairs(
+        &mut self,
+        pivot: usize,
+        component: usize,
+    ) -> Option<HashMap<ProductNode, Visit>> {
+        let nfa = self.nfa;
+        let in_component = |state: usize| nfa.components[state] == component;
+        let start = [pivot, pivot, 0];
+        let target = [pivot, pivot, 1];
+        let mut visits: HashMap<ProductNode, Visit> = HashMap::new();
+        visits.insert(
+            start,
+            Visit {
+                parent: None,
+                edges: [0; 3],
+            },
+        );
+        let mut queue = VecDeque::from([start]);
+        while let Some(node @ [a, b, diverged]) = queue.pop_front() {
+            for (i, e1) in nfa.edges[a].iter().enumerate() {
+                if !in_component(e1.to) {
+                    continue;
+                }
+                for (j, e2) in nfa.edges[b].iter().enumerate() {
+                    if !self.step() {
+                        return None;
+                    }
+                    if !in_component(e2.to) || !overlaps(&nfa.sets[e1.to], &nfa.sets[e2.to]) {
+                        continue;
+                    }
+                    // Paths from the same state diverge by taking different
+                    // transitions, or one transition which has two paths.
+                    let diverges = diverged == 1 || i != j || e1.multiple;
+                    let next = [e1.to, e2.to, diverges as usize];
+                    if visits.contains_key(&next) {
+                        continue;
+                    }
+                    visits.insert(
+                        next,
+                        Visit {
+                            parent: Some(node),
+                            edges: [i, j, 0],
+                        },
+                    );
+                    if next == target {
+                        return Some(visits);
+                    }
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Look for states \p p in \p from and \p q in \p to, and a text on which
+    /// \p p can loop, go to \p q, and \p q can loop. Every repeat of that text
+    /// adds a choice of where to leave \p p for \p q. The states \p between
+    /// are those which may be on a path from \p from to \p to.
+    /// \return whether a risk was found.
+    fn find_polynomial(&mut self, from: usize, to: usize, between: &[bool]) -> bool {
+        let nfa = self.nfa;
+        let in_from = |state: usize| nfa.components[state] == from;
+        let in_to = |state: usize| nfa.components[state] == to;
+        for p in (0..nfa.len()).filter(|&state| in_from(state)) {
+            for q in (0..nfa.len()).filter(|&state| in_to(state)) {
+                // Search triples of paths: p to p, p to q, and q to q.
+                let start = [p, p, q];
+                let target = [p, q, q];
+                let mut visits: HashMap<ProductNode, Visit> = HashMap::new();
+                visits.insert(
+                    start,
+                    Visit {
+                        parent: None,
+                        edges: [0; 3],
+                    },
+                );
+                let mut queue = VecDeque::from([start]);
+                let mut found = false;
+                'search: while let Some(node @ [x, y, z]) = queue.pop_front() {
+                    for (i, e1) in nfa.edges[x].iter().enumerate() {
+                        if !in_from(e1.to) {
+                            continue;
+                        }
+                        for (j, e2) in nfa.edges[y].iter().enumerate() {
+                            if !between[e2.to] || !overlaps(&nfa.sets[e1.to], &nfa.sets[e2.to]) {
+                                continue;
+                            }
+                            let common = intersection(&nfa.sets[e1.to], &nfa.sets[e2.to]);
+                            for (k, e3) in nfa.edges[z].iter().enumerate() {
+                                if !self.step() {
+                                    return false;
+                                }
+                                if !in_to(e3.to) || !overlaps(&common, &nfa.sets[e3.to]) {
+                                    continue;
+                                }
+                                let next = [e1.to, e2.to, e3.to];
+                                if visits.contains_key(&next) {
+                                    continue;
+                                }
+                                visits.insert(
+                                    next,
+                                    Visit {
+                                        parent: Some(node),
+                                        edges: [i, j, k],
+                                    },
+                                );
+                                if next == target {
+                                    found = true;
+                                    break 'search;
+                                }
+                                queue.push_back(next);
+                            }
+                        }
+                    }
+                }
+                if found && self.report(RiskKind::Polynomial, p, &visits, target) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Build a witness from the path in \p visits to \p target, which pumps
+    /// at \p pivot, and report it if it is confirmed.
+    /// For an exponential risk the path is of pairs, so the third state of
+    /// each node is ignored.
+    /// \return whether the risk was reported.
+    fn report(
+        &mut self,
+        kind: RiskKind,
+        pivot: usize,
+        visits: &HashMap<ProductNode, Visit>,
+        target: [usize; 3],
+    ) -> bool {
+        let nfa = self.nfa;
+        let paths = match kind {
+            RiskKind::Exponential => 2,
+            RiskKind::Polynomial => 3,
+        };
+        let mut pump = Vec::new();
+        let mut loops: Vec<u32> = Vec::new();
+        let mut node = target;
+        while let Some(parent) = visits[&node].parent {
+            let visit = &visits[&node];
+            let mut common = nfa.sets[node[0]].clone();
+            for &state in &node[1..paths] {
+                common = intersection(&common, &nfa.sets[state]);
+            }
+            for (path, (&from, &edge_idx)) in
+                parent.iter().zip(&visit.edges).take(paths).enumerate()
+            {
+                // The loops at fault are those on the paths which cycle.
+                if kind == RiskKind::Exponential || path != 1 {
+                    loops.extend(nfa.edges[from][edge_idx].loops.iter().copied());
+                }
+            }
+            let Some(c) = pick(&common) else {
+                return false;
+            };
+            pump.push(c);
+            node = parent;
+        }
+        let pump: String = pump.into_iter().rev().collect();
+        let Some(prefix) = nfa.text_to(pivot) else {
+            return false;
+        };
+        let Some(suffix) = self.confirm(&prefix, &pump) else {
+            return false;
+        };
+        let Some(span) = loops
+            .iter()
+            .filter_map(|&loop_idx| self.loop_spans.get(loop_idx as usize).cloned())
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+        else {
+            return false;
+        };
+        self.risks.push(Risk {
+            kind,
+            span,
+            prefix,
+            pump,
+            suffix,
+        });
+        true
+    }
+
+    /// Confirm a risk by finding a suffix after which the text of \p prefix
+    /// and repeats of \p pump fails to match.
+    /// \return the suffix, or None if the match succeeded for every suffix tried.
+    fn confirm(&self, prefix: &str, pump: &str) -> Option<String> {
+        let candidates = self
+            .nfa
+            .unused
+            .into_iter()
+            .chain(['!', ' ', '\n', '\0'])
+            .map(String::from)
+            .chain(core::iter::once(String::new()));
+        let mut text = String::from(prefix);
+        for _ in 0..CONFIRM_REPEATS {
+            text.push_str(pump);
+        }
+        for suffix in candidates {
+            let mut attempt = text.clone();
+            attempt.push_str(&suffix);
+            // Abandoning the search is as good as failing.
+            if !matches!(self.regex.try_find_at_anchored(&attempt, 0), Ok(Some(_))) {
+                return Some(suffix);
+            }
+        }
+        None
+    }
+}
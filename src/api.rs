@@ -1,35 +1,72 @@
-use crate::classicalbacktrack;
+use crate::analysis;
+use crate::cache::Cache;
+use crate::classicalbacktrack::{self, MemoPlan};
 use crate::emit;
+use crate::engine::{Engine, EngineExecutor};
 use crate::exec;
-use crate::indexing;
-use crate::insn::CompiledRegex;
+use crate::indexing::{self, AsciiInput, InputIndexer, Utf8Input};
+use crate::insn::{CompiledRegex, StartPredicate};
+use crate::lazydfa::{self, LazyDfaExecutor};
 use crate::optimizer;
 use crate::parse;
+use crate::replace::{self, Replacer};
+use crate::requiredliterals::RequiredLiterals;
 use crate::types::MAX_CAPTURE_GROUPS;
 
 #[cfg(feature = "utf16")]
-use crate::{
-    classicalbacktrack::MatchAttempter,
-    indexing::{InputIndexer, Ucs2Input, Utf16Input},
-};
+use crate::indexing::{Ucs2Input, Utf16Input};
+#[cfg(feature = "utf16")]
+use crate::replace::ReplacerUtf16;
 
+#[cfg(feature = "backend-jit")]
+use crate::jit::JitProgram;
 #[cfg(feature = "backend-pikevm")]
 use crate::pikevm;
 use crate::util::to_char_sat;
 
 #[cfg(not(feature = "std"))]
 use alloc::{
+    borrow::Cow,
     boxed::Box,
     string::{String, ToString},
+    sync::Arc,
     vec::Vec,
 };
 use core::{fmt, iter::FusedIterator, str::FromStr};
+#[cfg(feature = "std")]
+use std::{borrow::Cow, sync::Arc};
+
+pub use parse::{Error, ErrorKind};
+
+/// An error produced when a search could not be completed.
+/// A failed search is not an error; it simply produces no match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MatchError {
+    /// The search exceeded the limit set by [`RegexBuilder::backtrack_limit`],
+    /// so it is unknown whether there is a match.
+    BacktrackLimitExceeded,
+
+    /// The search was cancelled by the hook set with
+    /// [`RegexBuilder::interrupt`].
+    Cancelled,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchError::BacktrackLimitExceeded => f.write_str("Backtrack limit exceeded"),
+            MatchError::Cancelled => f.write_str("Search cancelled"),
+        }
+    }
+}
 
-pub use parse::Error;
+#[cfg(feature = "std")]
+impl std::error::Error for MatchError {}
 
 /// Flags used to control regex parsing.
 /// The default flags are case-sensitive, not-multiline, and optimizing.
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub struct Flags {
     /// If set, make the regex case-insensitive.
     /// Equivalent to the 'i' flag in JavaScript.
@@ -53,11 +90,17 @@ pub struct Flags {
     /// If set, the regex is interpreted as a UnicodeSets regex.
     /// Equivalent to the 'v' flag in JavaScript.
     pub unicode_sets: bool,
+
+    /// If set, matches are only attempted at the start position, and never
+    /// searched for further along in the input.
+    /// Equivalent to the 'y' flag in JavaScript.
+    pub sticky: bool,
 }
 
 impl Flags {
     /// Construct a Flags from a Unicode codepoints iterator, using JavaScript field names.
-    /// 'i' means to ignore case, 'm' means multiline, 'u' means unicode.
+    /// 'i' means to ignore case, 'm' means multiline, 'u' means unicode,
+    /// 'y' means sticky.
     /// Note the 'g' flag implies a stateful regex and is not supported.
     /// Other flags are not implemented and are ignored.
     #[inline]
@@ -80,6 +123,9 @@ impl Flags {
                 'v' => {
                     result.unicode_sets = true;
                 }
+                'y' => {
+                    result.sticky = true;
+                }
                 _ => {
                     // Silently skip unsupported flags.
                 }
@@ -113,6 +159,12 @@ impl fmt::Display for Flags {
         if self.unicode {
             f.write_str("u")?;
         }
+        if self.unicode_sets {
+            f.write_str("v")?;
+        }
+        if self.sticky {
+            f.write_str("y")?;
+        }
         Ok(())
     }
 }
@@ -128,6 +180,10 @@ pub type Matches<'r, 't> = exec::Matches<backends::DefaultExecutor<'r, 't>>;
 /// only.
 pub type AsciiMatches<'r, 't> = exec::Matches<backends::DefaultAsciiExecutor<'r, 't>>;
 
+/// An iterator type which yields `Result<Match, MatchError>` for matches found
+/// in a string. It stops after the first error.
+pub type TryMatches<'r, 't> = exec::TryMatches<backends::DefaultExecutor<'r, 't>>;
+
 /// A Match represents a portion of a string which was found to match a Regex.
 #[derive(Debug, Clone)]
 pub struct Match {
@@ -227,6 +283,73 @@ impl Match {
     }
 }
 
+/// CaptureLocations holds the ranges of a match and its capture groups, in a
+/// buffer owned by the caller. It is filled by [`Regex::captures_read`], which
+/// unlike [`Regex::find`] does not allocate once the buffer is warm.
+///
+/// Create one with [`Regex::capture_locations`], and use it only with that
+/// regex. It also holds a [`Cache`] for the search.
+///
+/// Example:
+///
+/// ```rust
+///   use regress::Regex;
+///   let re = Regex::new(r"(?<key>\w+)=(\d+)").unwrap();
+///   let text = "a=1 b=22 c=333";
+///   let mut locs = re.capture_locations();
+///   let mut total = 0;
+///   let mut start = 0;
+///   while let Some(range) = re.captures_read_at(&mut locs, text, start) {
+///       total += text[locs.get(2).unwrap()].parse::<u32>().unwrap();
+///       start = range.end;
+///   }
+///   assert_eq!(total, 356);
+/// ```
+#[derive(Debug)]
+pub struct CaptureLocations {
+    // The range of the match, followed by the range of each capture group.
+    slots: Vec<Option<Range>>,
+
+    // The capture group names, as in `Match`.
+    group_names: Box<[Box<str>]>,
+
+    // Buffers for the search.
+    cache: Cache,
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl CaptureLocations {
+    /// Access a group by index. Index 0 is the total match, index 1 is the
+    /// first capture group. Returns None if the group did not participate, if
+    /// there is no such group, or if the last search did not match.
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<Range> {
+        self.slots.get(idx)?.clone()
+    }
+
+    /// Access a named group by name. If several groups have the name, the
+    /// one which participated is returned.
+    #[inline]
+    pub fn name(&self, name: &str) -> Option<Range> {
+        // Empty strings are used as sentinels to indicate unnamed group.
+        if name.is_empty() {
+            return None;
+        }
+        self.group_names
+            .iter()
+            .zip(self.slots[1..].iter())
+            .filter(|(n, _)| n.as_ref() == name)
+            .find_map(|(_, slot)| slot.clone())
+    }
+
+    /// Returns the number of slots: the number of capture groups, plus one
+    /// for the total match.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+}
+
 /// An iterator over the capture groups of a [`Match`]
 ///
 /// This struct is created by the [`groups`] method on [`Match`].
@@ -368,10 +491,60 @@ impl<'m> Iterator for NamedGroups<'m> {
 impl<'m> ExactSizeIterator for NamedGroups<'m> {}
 impl<'m> FusedIterator for NamedGroups<'m> {}
 
+/// An iterator over the names of the capture groups of a [`Regex`].
+///
+/// This struct is created by the [`capture_names`] method on [`Regex`].
+///
+/// [`Regex`]: ../struct.Regex.html
+/// [`capture_names`]: ../struct.Regex.html#method.capture_names
+#[derive(Debug, Clone)]
+pub struct CaptureNames<'r> {
+    // The group names, which is either empty or does not include the total
+    // match.
+    group_names: &'r [Box<str>],
+
+    // The next group index to return, where group 0 is the total match.
+    next_group_idx: usize,
+
+    // The number of groups, including the total match.
+    end: usize,
+}
+
+impl<'r> Iterator for CaptureNames<'r> {
+    type Item = Option<&'r str>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_group_idx == self.end {
+            return None;
+        }
+        let idx = self.next_group_idx;
+        self.next_group_idx += 1;
+        let name = match idx {
+            0 => None,
+            _ => self
+                .group_names
+                .get(idx - 1)
+                .map(|name| name.as_ref())
+                .filter(|name| !name.is_empty()),
+        };
+        Some(name)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.end - self.next_group_idx;
+        (size, Some(size))
+    }
+}
+
+impl<'r> ExactSizeIterator for CaptureNames<'r> {}
+impl<'r> FusedIterator for CaptureNames<'r> {}
+
 /// A Regex is the compiled version of a pattern.
 #[derive(Debug, Clone)]
 pub struct Regex {
-    cr: CompiledRegex,
+    pub(crate) cr: CompiledRegex,
 }
 
 impl From<CompiledRegex> for Regex {
@@ -401,6 +574,7 @@ impl Regex {
         F: Into<Flags>,
     {
         Self::from_unicode(pattern.chars().map(u32::from), flags)
+            .map_err(|err| err.with_utf8_span(pattern))
     }
 
     /// Construct a regex by parsing `pattern` with `flags`, where
@@ -422,12 +596,106 @@ impl Regex {
         Ok(Regex { cr })
     }
 
+    /// Returns whether `text` contains a match.
+    ///
+    /// This is faster than `find` when the match itself is not needed: no
+    /// Match is allocated, and capture groups are not recorded unless a
+    /// backreference depends on them. Nor does the search allocate once the
+    /// regex has been searched: the states of the lazy DFA and the buffers of
+    /// the engine are pooled by the regex. A search made while another
+    /// thread holds the pool allocates its own, as do searches without the
+    /// `std` feature or with `prohibit-unsafe`, which cannot keep them.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"^(\d{3})-(\d{4})$").unwrap();
+    ///   assert!(re.is_match("555-1234"));
+    ///   assert!(!re.is_match("555-12345"));
+    /// ```
+    #[inline]
+    pub fn is_match(&self, text: &str) -> bool {
+        if let Some(matched) = lazydfa::is_match_pooled(&self.cr, text) {
+            return matched;
+        }
+        self.is_match_input(Utf8Input::new(text, self.cr.flags.unicode))
+    }
+
+    /// Returns whether `text` contains a match.
+    /// The input text is expected to be ascii-only: only ASCII case-folding is
+    /// supported.
+    #[inline]
+    pub fn is_match_ascii(&self, text: &str) -> bool {
+        if let Some(matched) = lazydfa::is_match_pooled(&self.cr, text) {
+            return matched;
+        }
+        self.is_match_input(AsciiInput::new(text, self.cr.flags.unicode))
+    }
+
+    /// Returns whether the UTF-16 `text` contains a match.
+    /// This never uses the lazy DFA, which searches only UTF-8.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn is_match_utf16(&self, text: &[u16]) -> bool {
+        self.is_match_input(Utf16Input::new(text, self.cr.flags.unicode))
+    }
+
+    /// Returns whether the UCS-2 `text` contains a match.
+    /// This never uses the lazy DFA, which searches only UTF-8.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn is_match_ucs2(&self, text: &[u16]) -> bool {
+        self.is_match_input(Ucs2Input::new(text, self.cr.flags.unicode))
+    }
+
+    fn is_match_input<Input: InputIndexer>(&self, input: Input) -> bool {
+        self.try_is_match_input(input).unwrap_or(false)
+    }
+
+    fn try_is_match_input<Input: InputIndexer>(&self, input: Input) -> Result<bool, MatchError> {
+        use exec::MatchProducer;
+        let start = input.left_end();
+        let mut cache = self.cr.pool.get();
+        let mut executor = EngineExecutor::match_only_with_cache(&self.cr, input, &mut cache);
+        let result = match executor.is_match_from(start) {
+            false => executor.abandoned().map_or(Ok(false), Err),
+            true => Ok(true),
+        };
+        executor.into_cache(&mut cache);
+        self.cr.pool.put(cache);
+        result
+    }
+
     /// Searches `text` to find the first match.
     #[inline]
     pub fn find(&self, text: &str) -> Option<Match> {
         self.find_iter(text).next()
     }
 
+    /// Attempts to match `text` exactly at byte index `start`, without
+    /// searching forwards. This is "sticky" matching, as with the 'y' flag in
+    /// JavaScript, and is useful for tokenizers.
+    /// Example:
+    ///
+    ///  ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"\d+").unwrap();
+    ///   assert!(re.find_at_anchored("ab12", 1).is_none());
+    ///   assert_eq!(re.find_at_anchored("ab12", 2).unwrap().range(), 2..4);
+    ///   ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a UTF-8 character boundary of `text`. An
+    /// out-of-range `start` (`start > text.len()`) returns None.
+    #[inline]
+    pub fn find_at_anchored(&self, text: &str, start: usize) -> Option<Match> {
+        assert!(
+            start >= text.len() || text.is_char_boundary(start),
+            "start index is not on a char boundary"
+        );
+        backends::find_anchored::<backends::DefaultExecutor>(self, text, start)
+    }
+
     /// Searches `text`, returning an iterator over non-overlapping matches.
     /// Note that the resulting Iterator borrows both the regex `'r` and the
     /// input string as `'t`.
@@ -488,22 +756,24 @@ impl Regex {
         backends::find(self, text, start)
     }
 
+    /// Attempts to match `text` exactly at byte index `start`, without
+    /// searching forwards.
+    /// The input text is expected to be ascii-only: only ASCII case-folding is
+    /// supported.
+    #[inline]
+    pub fn find_at_anchored_ascii(&self, text: &str, start: usize) -> Option<Match> {
+        backends::find_anchored::<backends::DefaultAsciiExecutor>(self, text, start)
+    }
+
     /// Returns an iterator for matches found in 'text' starting at index `start`.
     #[cfg(feature = "utf16")]
     pub fn find_from_utf16<'r, 't>(
         &'r self,
         text: &'t [u16],
         start: usize,
-    ) -> exec::Matches<super::classicalbacktrack::BacktrackExecutor<'r, indexing::Utf16Input<'t>>>
-    {
+    ) -> exec::Matches<EngineExecutor<'r, Utf16Input<'t>>> {
         let input = Utf16Input::new(text, self.cr.flags.unicode);
-        exec::Matches::new(
-            super::classicalbacktrack::BacktrackExecutor::new(
-                input,
-                MatchAttempter::new(&self.cr, input.left_end()),
-            ),
-            start,
-        )
+        exec::Matches::new(EngineExecutor::new(&self.cr, input), start)
     }
 
     /// Returns an iterator for matches found in 'text' starting at index `start`.
@@ -512,24 +782,570 @@ impl Regex {
         &'r self,
         text: &'t [u16],
         start: usize,
-    ) -> exec::Matches<super::classicalbacktrack::BacktrackExecutor<'r, indexing::Ucs2Input<'t>>>
-    {
+    ) -> exec::Matches<EngineExecutor<'r, Ucs2Input<'t>>> {
+        let input = Ucs2Input::new(text, self.cr.flags.unicode);
+        exec::Matches::new(EngineExecutor::new(&self.cr, input), start)
+    }
+
+    /// Attempts to match UTF-16 `text` exactly at index `start`, without
+    /// searching forwards.
+    #[cfg(feature = "utf16")]
+    pub fn find_at_anchored_utf16(&self, text: &[u16], start: usize) -> Option<Match> {
+        let input = Utf16Input::new(text, self.cr.flags.unicode);
+        exec::try_find_anchored(EngineExecutor::new(&self.cr, input), start).unwrap_or(None)
+    }
+
+    /// Attempts to match UCS-2 `text` exactly at index `start`, without
+    /// searching forwards.
+    #[cfg(feature = "utf16")]
+    pub fn find_at_anchored_ucs2(&self, text: &[u16], start: usize) -> Option<Match> {
         let input = Ucs2Input::new(text, self.cr.flags.unicode);
-        exec::Matches::new(
-            super::classicalbacktrack::BacktrackExecutor::new(
-                input,
-                MatchAttempter::new(&self.cr, input.left_end()),
-            ),
+        exec::try_find_anchored(EngineExecutor::new(&self.cr, input), start).unwrap_or(None)
+    }
+
+    /// Returns whether `text` contains a match, or an error if the search was
+    /// abandoned. See [`RegexBuilder::backtrack_limit`] and
+    /// [`RegexBuilder::interrupt`].
+    #[inline]
+    pub fn try_is_match(&self, text: &str) -> Result<bool, MatchError> {
+        if let Some(matched) = lazydfa::is_match_pooled(&self.cr, text) {
+            return Ok(matched);
+        }
+        self.try_is_match_input(Utf8Input::new(text, self.cr.flags.unicode))
+    }
+
+    /// Searches `text` to find the first match, or returns an error if the
+    /// search was abandoned, because it exceeded the backtrack limit or was
+    /// cancelled. See [`RegexBuilder::backtrack_limit`] and
+    /// [`RegexBuilder::interrupt`].
+    ///
+    /// ```rust
+    ///   use regress::{MatchError, RegexBuilder};
+    ///   let re = RegexBuilder::new(r"(a+)+$").backtrack_limit(10_000).build().unwrap();
+    ///   assert!(re.try_find("aaaa").unwrap().is_some());
+    ///   let text = "a".repeat(40) + "b";
+    ///   assert!(matches!(re.try_find(&text), Err(MatchError::BacktrackLimitExceeded)));
+    /// ```
+    #[inline]
+    pub fn try_find(&self, text: &str) -> Result<Option<Match>, MatchError> {
+        self.try_find_iter(text).next().transpose()
+    }
+
+    /// Searches `text`, returning an iterator over non-overlapping matches.
+    /// If a search is abandoned, the iterator yields an error and then stops.
+    #[inline]
+    pub fn try_find_iter<'r, 't>(&'r self, text: &'t str) -> TryMatches<'r, 't> {
+        self.try_find_from(text, 0)
+    }
+
+    /// Returns an iterator for matches found in `text` starting at byte index
+    /// `start`, as [`Regex::find_from`]. If a search is abandoned, the
+    /// iterator yields an error and then stops.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a UTF-8 character boundary of `text`.
+    #[inline]
+    pub fn try_find_from<'r, 't>(&'r self, text: &'t str, start: usize) -> TryMatches<'r, 't> {
+        assert!(
+            start >= text.len() || text.is_char_boundary(start),
+            "start index is not on a char boundary"
+        );
+        exec::TryMatches::new(
+            <backends::DefaultExecutor as exec::Executor>::new(&self.cr, text),
             start,
         )
     }
 
+    /// Searches `text` to find the first match, or returns an error if the
+    /// search was abandoned.
+    /// The input text is expected to be ascii-only: only ASCII case-folding is
+    /// supported.
+    #[inline]
+    pub fn try_find_ascii(&self, text: &str) -> Result<Option<Match>, MatchError> {
+        exec::TryMatches::new(
+            <backends::DefaultAsciiExecutor as exec::Executor>::new(&self.cr, text),
+            0,
+        )
+        .next()
+        .transpose()
+    }
+
+    /// Attempts to match `text` exactly at byte index `start`, as
+    /// [`Regex::find_at_anchored`], or returns an error if the attempt
+    /// was abandoned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a UTF-8 character boundary of `text`.
+    #[inline]
+    pub fn try_find_at_anchored(
+        &self,
+        text: &str,
+        start: usize,
+    ) -> Result<Option<Match>, MatchError> {
+        assert!(
+            start >= text.len() || text.is_char_boundary(start),
+            "start index is not on a char boundary"
+        );
+        backends::try_find_anchored::<backends::DefaultExecutor>(self, text, start)
+    }
+
+    /// Returns an iterator for matches found in UTF-16 `text` starting at
+    /// index `start`. If a search is abandoned, the iterator yields an error
+    /// and then stops.
+    #[cfg(feature = "utf16")]
+    pub fn try_find_from_utf16<'r, 't>(
+        &'r self,
+        text: &'t [u16],
+        start: usize,
+    ) -> exec::TryMatches<EngineExecutor<'r, Utf16Input<'t>>> {
+        let input = Utf16Input::new(text, self.cr.flags.unicode);
+        exec::TryMatches::new(EngineExecutor::new(&self.cr, input), start)
+    }
+
+    /// Returns an iterator for matches found in UCS-2 `text` starting at
+    /// index `start`. If a search is abandoned, the iterator yields an error
+    /// and then stops.
+    #[cfg(feature = "utf16")]
+    pub fn try_find_from_ucs2<'r, 't>(
+        &'r self,
+        text: &'t [u16],
+        start: usize,
+    ) -> exec::TryMatches<EngineExecutor<'r, Ucs2Input<'t>>> {
+        let input = Ucs2Input::new(text, self.cr.flags.unicode);
+        exec::TryMatches::new(EngineExecutor::new(&self.cr, input), start)
+    }
+
+    /// Searches `text` to find the first match, using the buffers of `cache`
+    /// instead of allocating new ones. See [`Cache`].
+    #[inline]
+    pub fn find_with_cache(&self, text: &str, cache: &mut Cache) -> Option<Match> {
+        self.find_from_with_cache(text, 0, cache)
+    }
+
+    /// Searches `text` to find the first match at or after byte index
+    /// `start`, using the buffers of `cache`. See [`Regex::find_from`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a UTF-8 character boundary of `text`.
+    #[inline]
+    pub fn find_from_with_cache(
+        &self,
+        text: &str,
+        start: usize,
+        cache: &mut Cache,
+    ) -> Option<Match> {
+        self.try_find_from_with_cache(text, start, cache)
+            .unwrap_or(None)
+    }
+
+    /// Searches `text` to find the first match at or after byte index
+    /// `start`, using the buffers of `cache`, or returns an error if the search
+    /// was abandoned. See [`Regex::try_find`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a UTF-8 character boundary of `text`.
+    #[inline]
+    pub fn try_find_from_with_cache(
+        &self,
+        text: &str,
+        start: usize,
+        cache: &mut Cache,
+    ) -> Result<Option<Match>, MatchError> {
+        assert!(
+            start >= text.len() || text.is_char_boundary(start),
+            "start index is not on a char boundary"
+        );
+        let mut executor = LazyDfaExecutor::with_cache(&self.cr, text, cache);
+        let result = Self::try_find_with_executor(&mut executor, start);
+        executor.into_cache(cache);
+        result
+    }
+
+    /// Returns whether `text` contains a match, using the buffers of `cache`.
+    #[inline]
+    pub fn is_match_with_cache(&self, text: &str, cache: &mut Cache) -> bool {
+        if let Some(matched) = lazydfa::is_match(&self.cr, text, &mut cache.lazydfa) {
+            return matched;
+        }
+        let input = Utf8Input::new(text, self.cr.flags.unicode);
+        let mut executor = EngineExecutor::match_only_with_cache(&self.cr, input, cache);
+        let matched = executor.is_match_from(input.left_end());
+        executor.into_cache(cache);
+        matched
+    }
+
+    /// Searches UTF-16 `text` to find the first match at or after index
+    /// `start`, using the buffers of `cache`.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn find_from_utf16_with_cache(
+        &self,
+        text: &[u16],
+        start: usize,
+        cache: &mut Cache,
+    ) -> Option<Match> {
+        let input = Utf16Input::new(text, self.cr.flags.unicode);
+        self.try_find_input_with_cache(input, start, cache)
+            .unwrap_or(None)
+    }
+
+    /// Searches UCS-2 `text` to find the first match at or after index
+    /// `start`, using the buffers of `cache`.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn find_from_ucs2_with_cache(
+        &self,
+        text: &[u16],
+        start: usize,
+        cache: &mut Cache,
+    ) -> Option<Match> {
+        let input = Ucs2Input::new(text, self.cr.flags.unicode);
+        self.try_find_input_with_cache(input, start, cache)
+            .unwrap_or(None)
+    }
+
+    #[cfg(feature = "utf16")]
+    fn try_find_input_with_cache<Input: InputIndexer>(
+        &self,
+        input: Input,
+        start: usize,
+        cache: &mut Cache,
+    ) -> Result<Option<Match>, MatchError> {
+        let mut executor = EngineExecutor::with_cache(&self.cr, input, cache);
+        let result = Self::try_find_with_executor(&mut executor, start);
+        executor.into_cache(cache);
+        result
+    }
+
+    /// Search for the first match at or after the offset `start` with
+    /// `executor`.
+    fn try_find_with_executor<Producer: exec::MatchProducer>(
+        executor: &mut Producer,
+        start: usize,
+    ) -> Result<Option<Match>, MatchError> {
+        match executor.initial_position(start) {
+            None => Ok(None),
+            Some(pos) => match executor.next_match(pos, &mut None) {
+                None => executor.abandoned().map_or(Ok(None), Err),
+                m => Ok(m),
+            },
+        }
+    }
+
+    /// \return the number of capture groups in the regex, plus one for the
+    /// total match.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"(\d+)-(?<suffix>\w+)").unwrap();
+    ///   assert_eq!(re.captures_len(), 3);
+    /// ```
+    #[inline]
+    pub fn captures_len(&self) -> usize {
+        self.cr.groups as usize + 1
+    }
+
+    /// Returns an iterator over the names of the capture groups, in order.
+    /// The first item is for the total match, which has no name; the items for
+    /// unnamed capture groups are also None.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"(\d+)-(?<suffix>\w+)").unwrap();
+    ///   let names: Vec<_> = re.capture_names().collect();
+    ///   assert_eq!(names, [None, None, Some("suffix")]);
+    /// ```
+    #[inline]
+    pub fn capture_names(&self) -> CaptureNames<'_> {
+        CaptureNames {
+            group_names: &self.cr.group_names,
+            next_group_idx: 0,
+            end: self.captures_len(),
+        }
+    }
+
+    /// \return the minimum length in bytes of a match in UTF-8 text, or None
+    /// if the regex can never match.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"a{2,}é?").unwrap();
+    ///   assert_eq!(re.min_match_len(), Some(2));
+    /// ```
+    #[inline]
+    pub fn min_match_len(&self) -> Option<usize> {
+        self.cr.match_len.map(|len| len.min)
+    }
+
+    /// \return the maximum length in bytes of a match in UTF-8 text, or None
+    /// if matches may be arbitrarily long or the regex can never match.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   let re = Regex::new(r"a{2,3}é?").unwrap();
+    ///   assert_eq!(re.max_match_len(), Some(5));
+    ///   assert_eq!(Regex::new(r"a+").unwrap().max_match_len(), None);
+    /// ```
+    #[inline]
+    pub fn max_match_len(&self) -> Option<usize> {
+        self.cr.match_len.and_then(|len| len.max)
+    }
+
+    /// \return whether every match must begin at the start of the input.
+    /// This is true for patterns anchored with `^`, unless the multiline flag
+    /// lets `^` match after any line terminator.
+    ///
+    /// ```rust
+    ///   use regress::Regex;
+    ///   assert!(Regex::new(r"^abc").unwrap().is_start_anchored());
+    ///   assert!(!Regex::new(r"abc").unwrap().is_start_anchored());
+    ///   assert!(!Regex::with_flags(r"^abc", "m").unwrap().is_start_anchored());
+    /// ```
+    #[inline]
+    pub fn is_start_anchored(&self) -> bool {
+        matches!(self.cr.start_pred, StartPredicate::StartAnchored)
+    }
+
+    /// Returns literals which every match must contain. This may be used to
+    /// cheaply rule out text before searching it, for example with an index.
+    ///
+    /// ```rust
+    ///   use regress::{Regex, RequiredLiteral};
+    ///   let re = Regex::new(r"(?:error|warning): \w+ failed").unwrap();
+    ///   let literals = re.required_literals();
+    ///   let text = |lit: &RequiredLiteral| lit.text.clone();
+    ///   let prefixes: Vec<_> = literals.prefixes.iter().map(text).collect();
+    ///   assert_eq!(prefixes, ["error: ", "warning: "]);
+    ///   let suffixes: Vec<_> = literals.suffixes.iter().map(text).collect();
+    ///   assert_eq!(suffixes, [" failed"]);
+    /// ```
+    #[inline]
+    pub fn required_literals(&self) -> &RequiredLiterals {
+        &self.cr.literals
+    }
+
+    /// Returns a new CaptureLocations sized for this regex, for use with
+    /// [`Regex::captures_read`].
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations {
+            slots: vec![None; self.cr.groups as usize + 1],
+            group_names: self.cr.group_names.clone(),
+            cache: Cache::new(),
+        }
+    }
+
+    /// Searches `text` for the first match, writing the ranges of the match
+    /// and its capture groups to `locs`.
+    /// \return the range of the match, or None if there is none, in which case
+    /// every location is cleared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `locs` was not created by this regex.
+    #[inline]
+    pub fn captures_read(&self, locs: &mut CaptureLocations, text: &str) -> Option<Range> {
+        self.captures_read_at(locs, text, 0)
+    }
+
+    /// Searches `text` for the first match at or after byte index `start`,
+    /// writing the ranges of the match and its capture groups to `locs`. See
+    /// [`Regex::captures_read`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `locs` was not created by this regex, or if `start` is not on
+    /// a UTF-8 character boundary of `text`.
+    #[inline]
+    pub fn captures_read_at(
+        &self,
+        locs: &mut CaptureLocations,
+        text: &str,
+        start: usize,
+    ) -> Option<Range> {
+        assert!(
+            start >= text.len() || text.is_char_boundary(start),
+            "start index is not on a char boundary"
+        );
+        self.captures_read_input(locs, Utf8Input::new(text, self.cr.flags.unicode), start)
+    }
+
+    /// Searches UTF-16 `text` for the first match at or after index `start`,
+    /// writing the ranges of the match and its capture groups to `locs`. See
+    /// [`Regex::captures_read`].
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn captures_read_utf16_at(
+        &self,
+        locs: &mut CaptureLocations,
+        text: &[u16],
+        start: usize,
+    ) -> Option<Range> {
+        self.captures_read_input(locs, Utf16Input::new(text, self.cr.flags.unicode), start)
+    }
+
+    /// Searches UCS-2 `text` for the first match at or after index `start`,
+    /// writing the ranges of the match and its capture groups to `locs`. See
+    /// [`Regex::captures_read`].
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn captures_read_ucs2_at(
+        &self,
+        locs: &mut CaptureLocations,
+        text: &[u16],
+        start: usize,
+    ) -> Option<Range> {
+        self.captures_read_input(locs, Ucs2Input::new(text, self.cr.flags.unicode), start)
+    }
+
+    fn captures_read_input<Input: InputIndexer>(
+        &self,
+        locs: &mut CaptureLocations,
+        input: Input,
+        start: usize,
+    ) -> Option<Range> {
+        use exec::MatchProducer;
+        assert_eq!(
+            locs.slots.len(),
+            self.cr.groups as usize + 1,
+            "CaptureLocations was created by a different regex"
+        );
+        let mut executor = EngineExecutor::with_cache(&self.cr, input, &mut locs.cache);
+        match executor.initial_position(start) {
+            Some(pos) => executor.read_captures_from(pos, &mut locs.slots),
+            None => {
+                locs.slots.fill(None);
+                false
+            }
+        };
+        executor.into_cache(&mut locs.cache);
+        locs.slots[0].clone()
+    }
+
+    /// Splits `text` around matches of the regex, following the semantics of
+    /// JavaScript's `String.prototype.split`.
+    ///
+    /// The text of any capture groups in a separator is spliced into the
+    /// result, with None for groups that did not participate. Empty matches
+    /// at the start of a piece, and at the end of `text`, do not split.
+    /// If `text` is empty, the result is empty if the regex matches the empty
+    /// string, otherwise it is a single empty piece.
+    /// As in JavaScript, the sticky flag is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use regress::Regex;
+    ///
+    /// let re = Regex::new(r"\s*,\s*").unwrap();
+    /// assert_eq!(re.split("a , b,c"), vec![Some("a"), Some("b"), Some("c")]);
+    ///
+    /// let re = Regex::new(r"(-)|(\+)").unwrap();
+    /// assert_eq!(
+    ///     re.split("1-2+3"),
+    ///     vec![Some("1"), Some("-"), None, Some("2"), None, Some("+"), Some("3")]
+    /// );
+    ///
+    /// let re = Regex::new(r"").unwrap();
+    /// assert_eq!(re.split("abc"), vec![Some("a"), Some("b"), Some("c")]);
+    /// ```
+    #[inline]
+    pub fn split<'t>(&self, text: &'t str) -> Vec<Option<&'t str>> {
+        self.splitn(text, usize::MAX)
+    }
+
+    /// Splits `text` around matches of the regex, as in [`Regex::split`],
+    /// returning at most `limit` pieces. Spliced capture groups count towards
+    /// the limit.
+    ///
+    /// ```rust
+    /// use regress::Regex;
+    ///
+    /// let re = Regex::new(r"(,)").unwrap();
+    /// assert_eq!(re.splitn("a,b,c", 3), vec![Some("a"), Some(","), Some("b")]);
+    /// assert!(re.splitn("a,b,c", 0).is_empty());
+    /// ```
+    pub fn splitn<'t>(&self, text: &'t str, limit: usize) -> Vec<Option<&'t str>> {
+        let re = self.splitter();
+        split_ranges(re.find_iter(text), text.len(), limit)
+            .into_iter()
+            .map(|r| r.map(|r| &text[r]))
+            .collect()
+    }
+
+    /// Splits UTF-16 `text` around matches of the regex, as in
+    /// [`Regex::split`].
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn split_utf16<'t>(&self, text: &'t [u16]) -> Vec<Option<&'t [u16]>> {
+        self.splitn_utf16(text, usize::MAX)
+    }
+
+    /// Splits UTF-16 `text` around matches of the regex, as in
+    /// [`Regex::splitn`].
+    #[cfg(feature = "utf16")]
+    pub fn splitn_utf16<'t>(&self, text: &'t [u16], limit: usize) -> Vec<Option<&'t [u16]>> {
+        let re = self.splitter();
+        split_ranges(re.find_from_utf16(text, 0), text.len(), limit)
+            .into_iter()
+            .map(|r| r.map(|r| &text[r]))
+            .collect()
+    }
+
+    /// Splits UCS-2 `text` around matches of the regex, as in
+    /// [`Regex::split`].
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn split_ucs2<'t>(&self, text: &'t [u16]) -> Vec<Option<&'t [u16]>> {
+        self.splitn_ucs2(text, usize::MAX)
+    }
+
+    /// Splits UCS-2 `text` around matches of the regex, as in
+    /// [`Regex::splitn`].
+    #[cfg(feature = "utf16")]
+    pub fn splitn_ucs2<'t>(&self, text: &'t [u16], limit: usize) -> Vec<Option<&'t [u16]>> {
+        let re = self.splitter();
+        split_ranges(re.find_from_ucs2(text, 0), text.len(), limit)
+            .into_iter()
+            .map(|r| r.map(|r| &text[r]))
+            .collect()
+    }
+
+    /// \return the regex to use for splitting.
+    /// JavaScript's split always matches with a sticky copy of the regex, and
+    /// so it searches regardless of whether the original was sticky. Searching
+    /// is equivalent to that, so long as we are not sticky ourselves.
+    fn splitter(&self) -> Cow<'_, Regex> {
+        if self.cr.flags.sticky {
+            let mut re = self.clone();
+            re.cr.flags.sticky = false;
+            Cow::Owned(re)
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
     /// Replaces the first match of the regex in `text` with the replacement string.
     ///
-    /// The replacement string may contain capture group references in the form `$1`, `$2`, etc.,
-    /// where `$1` refers to the first capture group, `$2` to the second, and so on.
-    /// `$0` refers to the entire match. Use `$$` to insert a literal `$`.
+    /// The replacement string is expanded as by JavaScript's
+    /// `String.prototype.replace` (the GetSubstitution operation):
+    ///
+    /// - `$$` inserts a literal `$`.
+    /// - `$&` inserts the matched text.
+    /// - `` $` `` inserts the text before the match.
+    /// - `$'` inserts the text after the match.
+    /// - `$n` and `$nn` insert the capture group with that number, from 1 to
+    ///   99. If a two-digit number exceeds the number of capture groups, it is
+    ///   read as a one-digit number followed by a literal digit. A number that
+    ///   does not refer to a capture group is inserted literally.
+    /// - `$<name>` inserts the named capture group, or nothing if there is no
+    ///   group with that name. If the regex has no named groups, it is
+    ///   inserted literally.
     ///
+    /// Capture groups which did not participate in the match insert nothing.
     /// If no match is found, the original text is returned unchanged.
     ///
     /// # Examples
@@ -541,28 +1357,22 @@ impl Regex {
     /// let result = re.replace("hello world", "$2 $1");
     /// assert_eq!(result, "world hello");
     ///
-    /// let re = Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
-    /// let result = re.replace("2023-12-25", "$2/$3/$1");
+    /// let re = Regex::new(r"(?<year>\d{4})-(?<month>\d{2})-(?<day>\d{2})").unwrap();
+    /// let result = re.replace("2023-12-25", "$<month>/$<day>/$<year>");
     /// assert_eq!(result, "12/25/2023");
+    ///
+    /// let re = Regex::new(r"\d+").unwrap();
+    /// let result = re.replace("Price: 123", "[$&] was $$$&, after `$`'");
+    /// assert_eq!(result, "Price: [123] was $123, after `Price: '");
     /// ```
-    pub fn replace(&self, text: &str, replacement: &str) -> String {
-        match self.find(text) {
-            Some(m) => {
-                let mut result = String::with_capacity(text.len());
-                result.push_str(&text[..m.start()]);
-                self.expand_replacement(&m, text, replacement, &mut result);
-                result.push_str(&text[m.end()..]);
-                result
-            }
-            None => text.to_string(),
-        }
+    pub fn replace<R: Replacer>(&self, text: &str, replacement: R) -> String {
+        self.replacen(text, 1, replacement).into_owned()
     }
 
     /// Replaces all matches of the regex in `text` with the replacement string.
     ///
-    /// The replacement string may contain capture group references in the form `$1`, `$2`, etc.,
-    /// where `$1` refers to the first capture group, `$2` to the second, and so on.
-    /// `$0` refers to the entire match. Use `$$` to insert a literal `$`.
+    /// The replacement string is expanded as by JavaScript's
+    /// `String.prototype.replace`; see [`Regex::replace`] for the syntax.
     ///
     /// # Examples
     ///
@@ -577,7 +1387,140 @@ impl Regex {
     /// let result = re.replace_all("hello world", "$1.$2");
     /// assert_eq!(result, "h.ello w.orld");
     /// ```
-    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+    pub fn replace_all<R: Replacer>(&self, text: &str, replacement: R) -> String {
+        self.replacen(text, 0, replacement).into_owned()
+    }
+
+    /// Replaces the first `limit` matches of the regex in `text`, or all
+    /// matches if `limit` is 0.
+    ///
+    /// See [`Regex::replace`] for the replacement syntax. If nothing matched,
+    /// `text` is returned without allocating.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use regress::Regex;
+    /// use std::borrow::Cow;
+    ///
+    /// let re = Regex::new(r"\d").unwrap();
+    /// assert_eq!(re.replacen("1 2 3", 2, "#"), "# # 3");
+    /// assert_eq!(re.replacen("1 2 3", 0, "#"), "# # #");
+    /// assert!(matches!(re.replacen("a b c", 0, "#"), Cow::Borrowed("a b c")));
+    /// ```
+    pub fn replacen<'t, R: Replacer>(
+        &self,
+        text: &'t str,
+        limit: usize,
+        replacement: R,
+    ) -> Cow<'t, str> {
+        replace::replacen(text, self.find_iter(text), limit, replacement)
+    }
+
+    /// Replaces the first match of the regex in the UTF-16 `text`.
+    /// See [`Regex::replace`] for the replacement syntax.
+    ///
+    /// ```rust
+    /// use regress::Regex;
+    ///
+    /// let re = Regex::new(r"(\w+)@(\w+)").unwrap();
+    /// let text: Vec<u16> = "user@example!".encode_utf16().collect();
+    /// let template: Vec<u16> = "$2 at $1".encode_utf16().collect();
+    /// let result = re.replace_utf16(&text, &template);
+    /// assert_eq!(String::from_utf16(&result).unwrap(), "example at user!");
+    /// ```
+    #[cfg(feature = "utf16")]
+    pub fn replace_utf16<R: ReplacerUtf16>(&self, text: &[u16], replacement: R) -> Vec<u16> {
+        self.replacen_utf16(text, 1, replacement).into_owned()
+    }
+
+    /// Replaces all matches of the regex in the UTF-16 `text`.
+    /// See [`Regex::replace`] for the replacement syntax.
+    #[cfg(feature = "utf16")]
+    pub fn replace_all_utf16<R: ReplacerUtf16>(&self, text: &[u16], replacement: R) -> Vec<u16> {
+        self.replacen_utf16(text, 0, replacement).into_owned()
+    }
+
+    /// Replaces the first `limit` matches of the regex in the UTF-16 `text`,
+    /// or all matches if `limit` is 0.
+    /// See [`Regex::replace`] for the replacement syntax. If nothing matched,
+    /// `text` is returned without allocating.
+    #[cfg(feature = "utf16")]
+    pub fn replacen_utf16<'t, R: ReplacerUtf16>(
+        &self,
+        text: &'t [u16],
+        limit: usize,
+        replacement: R,
+    ) -> Cow<'t, [u16]> {
+        replace::replacen_utf16(text, self.find_from_utf16(text, 0), limit, replacement)
+    }
+
+    /// Replaces the first match of the regex in the UCS-2 `text`.
+    /// See [`Regex::replace`] for the replacement syntax.
+    #[cfg(feature = "utf16")]
+    pub fn replace_ucs2<R: ReplacerUtf16>(&self, text: &[u16], replacement: R) -> Vec<u16> {
+        self.replacen_ucs2(text, 1, replacement).into_owned()
+    }
+
+    /// Replaces all matches of the regex in the UCS-2 `text`.
+    /// See [`Regex::replace`] for the replacement syntax.
+    #[cfg(feature = "utf16")]
+    pub fn replace_all_ucs2<R: ReplacerUtf16>(&self, text: &[u16], replacement: R) -> Vec<u16> {
+        self.replacen_ucs2(text, 0, replacement).into_owned()
+    }
+
+    /// Replaces the first `limit` matches of the regex in the UCS-2 `text`,
+    /// or all matches if `limit` is 0.
+    /// See [`Regex::replace`] for the replacement syntax. If nothing matched,
+    /// `text` is returned without allocating.
+    #[cfg(feature = "utf16")]
+    pub fn replacen_ucs2<'t, R: ReplacerUtf16>(
+        &self,
+        text: &'t [u16],
+        limit: usize,
+        replacement: R,
+    ) -> Cow<'t, [u16]> {
+        replace::replacen_utf16(text, self.find_from_ucs2(text, 0), limit, replacement)
+    }
+
+    /// Replaces the first match of the regex in `text` with the replacement
+    /// string, using regress's original replacement syntax.
+    ///
+    /// The replacement string may contain capture group references in the form `$1`, `$2`, etc.,
+    /// where `$1` refers to the first capture group, `$2` to the second, and so on.
+    /// `$0` refers to the entire match, and `${name}` to a named group. Use `$$` to insert a
+    /// literal `$`.
+    ///
+    /// Prefer [`Regex::replace`], which follows JavaScript.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use regress::Regex;
+    ///
+    /// let re = Regex::new(r"(?<first>\w+)\s+(\w+)").unwrap();
+    /// let result = re.replace_legacy("hello world", "$2 ${first} [$0]");
+    /// assert_eq!(result, "world hello [hello world]");
+    /// ```
+    pub fn replace_legacy(&self, text: &str, replacement: &str) -> String {
+        match self.find(text) {
+            Some(m) => {
+                let mut result = String::with_capacity(text.len());
+                result.push_str(&text[..m.start()]);
+                self.expand_replacement(&m, text, replacement, &mut result);
+                result.push_str(&text[m.end()..]);
+                result
+            }
+            None => text.to_string(),
+        }
+    }
+
+    /// Replaces all matches of the regex in `text` with the replacement
+    /// string, using regress's original replacement syntax; see
+    /// [`Regex::replace_legacy`].
+    ///
+    /// Prefer [`Regex::replace_all`], which follows JavaScript.
+    pub fn replace_all_legacy(&self, text: &str, replacement: &str) -> String {
         let mut result = String::with_capacity(text.len());
         let mut last_end = 0;
 
@@ -662,7 +1605,8 @@ impl Regex {
         result
     }
 
-    /// Helper method to expand replacement strings with capture group substitutions.
+    /// Helper method to expand replacement strings with capture group substitutions,
+    /// in the legacy syntax.
     fn expand_replacement(&self, m: &Match, text: &str, replacement: &str, output: &mut String) {
         let mut chars = replacement.chars().peekable();
 
@@ -732,6 +1676,192 @@ impl Regex {
     }
 }
 
+/// Implement JavaScript's RegExp.prototype[@@split] on the given matches in
+/// a text of length `size`, which must be found by searching from the start.
+/// \return the ranges of the pieces, and of the spliced capture groups.
+fn split_ranges<I>(matches: I, size: usize, limit: usize) -> Vec<Option<Range>>
+where
+    I: Iterator<Item = Match>,
+{
+    let mut result = Vec::new();
+    if limit == 0 {
+        return result;
+    }
+    let mut matches = matches.peekable();
+    if size == 0 {
+        if matches.peek().is_none() {
+            result.push(Some(0..0));
+        }
+        return result;
+    }
+
+    // JS tries a sticky match at each position q, advancing q on failure or on
+    // an empty match at the start of the current piece p. Searching finds the
+    // same matches, and the iterator steps past empty matches just as JS does.
+    let mut p = 0;
+    for m in matches {
+        if m.start() >= size {
+            break;
+        }
+        if m.end() == p {
+            // An empty match at the start of the piece.
+            continue;
+        }
+        result.push(Some(p..m.start()));
+        if result.len() == limit {
+            return result;
+        }
+        p = m.end();
+        for cap in m.captures {
+            result.push(cap);
+            if result.len() == limit {
+                return result;
+            }
+        }
+    }
+    result.push(Some(p..size));
+    result
+}
+
+/// A RegexBuilder configures and compiles a [`Regex`].
+///
+/// Example:
+///
+/// ```rust
+///   use regress::RegexBuilder;
+///   let re = RegexBuilder::new(r"\d+")
+///       .flags("y")
+///       .backtrack_limit(1_000_000)
+///       .build()
+///       .unwrap();
+///   assert!(re.find("a1").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    pattern: String,
+    flags: Flags,
+    backtrack_limit: Option<usize>,
+    interrupt: Option<exec::Interrupt>,
+    engine: Engine,
+}
+
+impl RegexBuilder {
+    /// Construct a builder for `pattern`, with the default flags and no
+    /// limits.
+    pub fn new(pattern: &str) -> RegexBuilder {
+        RegexBuilder {
+            pattern: pattern.to_string(),
+            flags: Flags::default(),
+            backtrack_limit: None,
+            interrupt: None,
+            engine: Engine::default(),
+        }
+    }
+
+    /// Set the flags used to parse the pattern.
+    pub fn flags<F: Into<Flags>>(&mut self, flags: F) -> &mut RegexBuilder {
+        self.flags = flags.into();
+        self
+    }
+
+    /// Limit the work performed by the backtracking engine in each search
+    /// for a match. Every backtrack, and every iteration of a loop, consumes
+    /// one step; the limit applies across all start positions tried while
+    /// searching for a single match. The default is unlimited. The PikeVM
+    /// engine, whose searches take linear time, ignores the limit; the
+    /// memoizing backtracker observes it.
+    ///
+    /// When the limit is exceeded, the `try_` methods such as
+    /// [`Regex::try_find`] return [`MatchError::BacktrackLimitExceeded`],
+    /// while the infallible methods report no match.
+    pub fn backtrack_limit(&mut self, limit: usize) -> &mut RegexBuilder {
+        self.backtrack_limit = Some(limit);
+        self
+    }
+
+    /// Install a hook which is polled periodically during each search. If
+    /// it returns true the search is cancelled: the `try_` methods such as
+    /// [`Regex::try_find`] return [`MatchError::Cancelled`], while the
+    /// infallible methods report no match.
+    ///
+    /// The hook is polled every 1024 backtracks or loop iterations, or
+    /// positions of the input with the PikeVM engine, so short searches may
+    /// complete without polling it. It may be used to
+    /// cancel from another thread, or to enforce a deadline:
+    ///
+    /// ```rust
+    ///   use regress::{MatchError, RegexBuilder};
+    ///   use std::time::{Duration, Instant};
+    ///   let deadline = Instant::now() + Duration::from_millis(10);
+    ///   let re = RegexBuilder::new(r"(a+)+$")
+    ///       .interrupt(move || Instant::now() > deadline)
+    ///       .build()
+    ///       .unwrap();
+    ///   let text = "a".repeat(64) + "b";
+    ///   assert!(matches!(re.try_find(&text), Err(MatchError::Cancelled)));
+    /// ```
+    pub fn interrupt<F>(&mut self, hook: F) -> &mut RegexBuilder
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.interrupt = Some(exec::Interrupt(Arc::new(hook)));
+        self
+    }
+
+    /// Set the engine which searches with the regex. The default is
+    /// [`Engine::Backtrack`]. [`Engine::MemoizedBacktrack`] bounds the work
+    /// of the backtracker by memoizing. [`Engine::Auto`] chooses the PikeVM
+    /// for patterns prone to catastrophic backtracking. `Engine::Jit`, with
+    /// the `backend-jit` feature, compiles the backtracker to machine code:
+    ///
+    /// ```rust
+    ///   use regress::{Engine, RegexBuilder};
+    ///   let re = RegexBuilder::new(r"(a+)+$")
+    ///       .engine(Engine::Auto)
+    ///       .backtrack_limit(10_000)
+    ///       .build()
+    ///       .unwrap();
+    ///   let text = "a".repeat(10_000) + "b";
+    ///   assert!(matches!(re.try_find(&text), Ok(None)));
+    /// ```
+    pub fn engine(&mut self, engine: Engine) -> &mut RegexBuilder {
+        self.engine = engine;
+        self
+    }
+
+    /// Compile the regex.
+    /// An Error may be returned if the syntax is invalid.
+    pub fn build(&self) -> Result<Regex, Error> {
+        let mut re = Regex::with_flags(&self.pattern, self.flags)?;
+        re.cr.backtrack_limit = self.backtrack_limit;
+        re.cr.interrupt = self.interrupt.clone();
+        let risky = self.engine == Engine::Auto
+            && !analysis::redos_risk(&self.pattern, self.flags)?.is_empty();
+        re.cr.engine = match self.engine {
+            #[cfg(feature = "backend-pikevm")]
+            Engine::Auto if risky => Engine::PikeVM,
+            Engine::Auto if risky => Engine::MemoizedBacktrack,
+            Engine::Auto => Engine::Backtrack,
+            engine => engine,
+        };
+        re.cr.memo = match (self.engine, re.cr.engine) {
+            (_, Engine::MemoizedBacktrack) => MemoPlan::new(&re.cr, true),
+            (Engine::Auto, Engine::Backtrack) => MemoPlan::new(&re.cr, false),
+            _ => None,
+        }
+        .map(Box::new);
+        #[cfg(feature = "backend-jit")]
+        {
+            let interpreted = self.backtrack_limit.is_some() || self.interrupt.is_some();
+            re.cr.jit = match re.cr.engine {
+                Engine::Jit if !interpreted => JitProgram::new(&re.cr).map(Arc::new),
+                _ => None,
+            };
+        }
+        Ok(re)
+    }
+}
+
 impl FromStr for Regex {
     type Err = Error;
 
@@ -932,8 +2062,13 @@ pub mod backends {
     #[cfg(feature = "backend-pikevm")]
     pub type PikeVMExecutor<'r, 't> = super::pikevm::PikeVMExecutor<'r, indexing::Utf8Input<'t>>;
 
+    /// An Executor which finds the bounds of matches with a lazy DFA, where
+    /// the regex has no backreferences or lookarounds, and otherwise uses
+    /// the classical backtracking algorithm.
+    pub type LazyDfaExecutor<'r, 't> = super::lazydfa::LazyDfaExecutor<'r, 't>;
+
     /// An alias type to the default Executor.
-    pub type DefaultExecutor<'r, 't> = BacktrackExecutor<'r, 't>;
+    pub type DefaultExecutor<'r, 't> = LazyDfaExecutor<'r, 't>;
 
     /// An alias type to the default executor's ASCII form.
     pub type DefaultAsciiExecutor<'r, 't> =
@@ -957,6 +2092,25 @@ pub mod backends {
     ) -> exec::Matches<Executor::AsAscii> {
         find::<Executor::AsAscii>(re, text, start)
     }
+
+    /// Attempts a match exactly at `start` in `text`, without searching.
+    pub fn find_anchored<'r, 't, Executor: exec::Executor<'r, 't>>(
+        re: &'r Regex,
+        text: &'t str,
+        start: usize,
+    ) -> Option<super::Match> {
+        try_find_anchored::<Executor>(re, text, start).unwrap_or(None)
+    }
+
+    /// Attempts a match exactly at `start` in `text`, without searching,
+    /// reporting whether the search was abandoned.
+    pub fn try_find_anchored<'r, 't, Executor: exec::Executor<'r, 't>>(
+        re: &'r Regex,
+        text: &'t str,
+        start: usize,
+    ) -> Result<Option<super::Match>, super::MatchError> {
+        exec::try_find_anchored(Executor::new(&re.cr, text), start)
+    }
 }
 
 /// Escapes all special regex characters in a string to make it a literal match.
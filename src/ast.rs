@@ -0,0 +1,1265 @@
+//! A syntax tree for ECMAScript regular expressions.
+//!
+//! Unlike the IR used for compilation, this preserves the pattern as written:
+//! escapes keep their syntax, quantifiers their form, and every node carries
+//! the span of the pattern it was parsed from. It is intended for tools such
+//! as linters and code-mods.
+
+use crate::api::{Error, Flags};
+use crate::parse;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::ops::Range;
+
+/// A half-open interval of bytes in a pattern.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The offset of the first byte.
+    pub start: usize,
+
+    /// The offset one past the last byte.
+    pub end: usize,
+}
+
+impl Span {
+    /// \return the span as a Range, suitable for indexing the pattern.
+    pub fn range(self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+/// A parsed pattern.
+///
+/// Display prints the tree back to a pattern, which parses with the same
+/// flags to an equal tree, apart from spans. Code points which cannot be
+/// written as the tree says, like a verbatim `*`, are escaped. The alternate
+/// form `{:#}` prints a regex literal, with `/` and line terminators escaped.
+///
+/// ```rust
+/// use regress::ast::{self, GroupKind, NodeKind};
+/// let mut ast = ast::parse(r"(?<y>\d+)/", "u").unwrap();
+/// let NodeKind::Concatenation(nodes) = &mut ast.node.kind else { panic!() };
+/// let NodeKind::Group(group) = &mut nodes[0].kind else { panic!() };
+/// group.kind = GroupKind::Capture { index: 1, name: Some("year".into()) };
+/// assert_eq!(ast.to_string(), r"(?<year>\d+)/");
+/// assert_eq!(format!("{:#}", ast), r"/(?<year>\d+)\//u");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ast {
+    /// The flags the pattern was parsed with.
+    pub flags: Flags,
+
+    /// The root node, spanning the whole pattern.
+    pub node: Node,
+}
+
+/// A node in the syntax tree, with the span it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// What the node is.
+    pub kind: NodeKind,
+
+    /// The span of the node in the pattern.
+    pub span: Span,
+}
+
+/// The kinds of nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// Matches the empty string, for example an empty alternative.
+    Empty,
+
+    /// Alternatives separated by `|`.
+    Alternation(Vec<Node>),
+
+    /// A sequence of nodes.
+    Concatenation(Vec<Node>),
+
+    /// A single code point.
+    Literal(Literal),
+
+    /// The `.` wildcard.
+    Dot,
+
+    /// A zero-width assertion like `^` or `\b`.
+    Assertion(Assertion),
+
+    /// A class escape like `\d` or `\p{L}`, outside of a class.
+    ClassEscape(ClassEscape),
+
+    /// A character class in brackets.
+    Class(Class),
+
+    /// A group in parentheses.
+    Group(Group),
+
+    /// A quantified node.
+    Repetition(Repetition),
+
+    /// A backreference like `\1` or `\k<name>`.
+    Backreference(Backreference),
+}
+
+/// A code point, and how it was written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Literal {
+    /// The code point.
+    pub value: u32,
+
+    /// How the code point was written.
+    pub kind: LiteralKind,
+
+    /// The span of the code point in the pattern, including any escape.
+    pub span: Span,
+}
+
+/// The ways a code point may be written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LiteralKind {
+    /// The code point itself, unescaped.
+    Verbatim,
+
+    /// An identity escape like `\.`, standing for the escaped code point.
+    Identity,
+
+    /// A control escape: `\f`, `\n`, `\r`, `\t` or `\v`.
+    Control,
+
+    /// A control letter escape like `\cJ`.
+    ControlLetter,
+
+    /// The null escape `\0`.
+    Null,
+
+    /// A legacy octal escape like `\101`.
+    Octal,
+
+    /// A hex escape like `\x41`.
+    Hex,
+
+    /// A unicode escape like `\u0041`, or a surrogate pair like `\uD83D\uDE00`.
+    Unicode,
+
+    /// A braced unicode escape like `\u{41}`.
+    UnicodeBraced,
+
+    /// The backspace escape `\b`, within a class.
+    Backspace,
+}
+
+/// The kinds of zero-width assertions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Assertion {
+    /// `^`
+    StartOfLine,
+
+    /// `$`
+    EndOfLine,
+
+    /// `\b`
+    WordBoundary,
+
+    /// `\B`
+    NotWordBoundary,
+}
+
+/// A class escape, standing for a set of code points (or strings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassEscape {
+    /// Which set the escape stands for.
+    pub kind: ClassEscapeKind,
+
+    /// Whether the set is inverted, as in `\D` or `\P{L}`.
+    pub negated: bool,
+}
+
+/// The kinds of class escapes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClassEscapeKind {
+    /// `\d` or `\D`.
+    Digit,
+
+    /// `\s` or `\S`.
+    Space,
+
+    /// `\w` or `\W`.
+    Word,
+
+    /// A property escape like `\p{L}` or `\p{Script=Greek}`.
+    Property {
+        /// The property name before `=`, if any.
+        name: Option<String>,
+
+        /// The property value, or the lone name or value if there is no `=`.
+        value: String,
+    },
+}
+
+/// A character class in brackets.
+///
+/// In unicode sets (`v`) mode classes may nest, and may be combined with
+/// `&&` or `--`; otherwise the operation is always a union.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    /// Whether the class is inverted with `^`.
+    pub negated: bool,
+
+    /// How the items are combined.
+    pub op: ClassSetOp,
+
+    /// The items of the class, in order.
+    pub items: Vec<ClassItem>,
+}
+
+/// The ways the items of a class are combined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ClassSetOp {
+    /// Items are listed one after another.
+    Union,
+
+    /// Items are separated by `&&`.
+    Intersection,
+
+    /// Items are separated by `--`.
+    Subtraction,
+}
+
+/// An item of a character class, with the span it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassItem {
+    /// What the item is.
+    pub kind: ClassItemKind,
+
+    /// The span of the item in the pattern.
+    pub span: Span,
+}
+
+/// The kinds of class items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClassItemKind {
+    /// A single code point.
+    Literal(Literal),
+
+    /// A range of code points like `a-z`.
+    Range {
+        /// The first code point of the range.
+        first: Literal,
+
+        /// The last code point of the range.
+        last: Literal,
+    },
+
+    /// A class escape like `\d`.
+    ClassEscape(ClassEscape),
+
+    /// A nested class, in unicode sets mode.
+    Class(Class),
+
+    /// A string disjunction like `\q{abc|d}`, in unicode sets mode.
+    Strings(Vec<ClassString>),
+}
+
+/// One alternative of a `\q{...}` string disjunction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassString {
+    /// The code points of the string. This may be empty.
+    pub literals: Vec<Literal>,
+
+    /// The span of the string in the pattern.
+    pub span: Span,
+}
+
+/// A group in parentheses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// What sort of group this is.
+    pub kind: GroupKind,
+
+    /// The contents of the group.
+    pub node: Box<Node>,
+}
+
+/// The kinds of groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GroupKind {
+    /// A capture group like `(...)` or `(?<name>...)`.
+    Capture {
+        /// The 1-based index of the group.
+        index: u32,
+
+        /// The name of the group, if named, with any escapes decoded.
+        name: Option<String>,
+    },
+
+    /// A non-capturing group `(?:...)`.
+    NonCapture,
+
+    /// A modifier group like `(?i-m:...)`.
+    Modifiers {
+        /// The flags enabled, before any `-`.
+        enable: Modifiers,
+
+        /// The flags disabled, after a `-`.
+        disable: Modifiers,
+    },
+
+    /// A lookahead `(?=...)`, or `(?!...)` if negated.
+    Lookahead {
+        /// Whether the lookahead is negative.
+        negated: bool,
+    },
+
+    /// A lookbehind `(?<=...)`, or `(?<!...)` if negated.
+    Lookbehind {
+        /// Whether the lookbehind is negative.
+        negated: bool,
+    },
+}
+
+/// The flags which may appear in a modifier group.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    /// The `i` flag.
+    pub icase: bool,
+
+    /// The `m` flag.
+    pub multiline: bool,
+
+    /// The `s` flag.
+    pub dot_all: bool,
+}
+
+/// A quantified node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repetition {
+    /// The node which is repeated.
+    pub node: Box<Node>,
+
+    /// How it is repeated.
+    pub quantifier: Quantifier,
+}
+
+/// A quantifier like `*` or `{2,3}?`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Quantifier {
+    /// How the quantifier was written.
+    pub kind: QuantifierKind,
+
+    /// The minimum number of repetitions.
+    pub min: usize,
+
+    /// The maximum number of repetitions, or None if unbounded.
+    /// Counts too large to represent are usize::MAX.
+    pub max: Option<usize>,
+
+    /// Whether the quantifier is greedy, i.e. not followed by `?`.
+    pub greedy: bool,
+
+    /// The span of the quantifier in the pattern, including any `?`.
+    pub span: Span,
+}
+
+/// The ways a quantifier may be written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QuantifierKind {
+    /// `*`
+    ZeroOrMore,
+
+    /// `+`
+    OneOrMore,
+
+    /// `?`
+    ZeroOrOne,
+
+    /// `{n}`
+    Exactly,
+
+    /// `{n,}`
+    AtLeast,
+
+    /// `{n,m}`
+    Between,
+}
+
+/// A backreference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backreference {
+    /// A numbered backreference like `\1`.
+    Number(u32),
+
+    /// A named backreference like `\k<name>`, with any escapes decoded.
+    Name(String),
+}
+
+/// Parse `pattern` with `flags` into a syntax tree.
+/// An Error is returned if the syntax is invalid; it is the same as the
+/// Error from compiling the pattern.
+///
+/// ```rust
+/// use regress::ast::{self, GroupKind, NodeKind};
+/// let ast = ast::parse(r"(?<year>\d{4})-\x41", "").unwrap();
+/// let NodeKind::Concatenation(nodes) = &ast.node.kind else { panic!() };
+/// let NodeKind::Group(group) = &nodes[0].kind else { panic!() };
+/// assert!(matches!(&group.kind, GroupKind::Capture { index: 1, name: Some(n) } if n == "year"));
+/// assert_eq!(nodes[2].span.range(), 15..19);
+/// ```
+pub fn parse<F: Into<Flags>>(pattern: &str, flags: F) -> Result<Ast, Error> {
+    let flags = flags.into();
+    // Validate with the real parser, so the tree is only built for valid
+    // patterns, and errors are the same as when compiling.
+    parse::try_parse(pattern.chars().map(u32::from), flags)
+        .map_err(|err| err.with_utf8_span(pattern))?;
+    let (group_count_max, has_named_groups) =
+        parse::scan_capture_groups(pattern.chars().map(u32::from), flags);
+    let mut p = AstParser {
+        pattern,
+        pos: 0,
+        flags,
+        group_count: 0,
+        group_count_max,
+        has_named_groups,
+    };
+    let node = p.parse_disjunction();
+    // The tree must cover the pattern the compiling parser accepted; if it
+    // does not, the grammars have diverged.
+    assert!(
+        p.pos == pattern.len(),
+        "Pattern {pattern:?} was parsed only up to byte {}",
+        p.pos
+    );
+    Ok(Ast { flags, node })
+}
+
+/// Builds a syntax tree from a pattern which is known to be valid.
+/// This follows the same grammar as the compiling parser, but without
+/// error handling.
+struct AstParser<'p> {
+    /// The pattern.
+    pattern: &'p str,
+
+    /// The byte offset of the remaining input.
+    pos: usize,
+
+    /// Flags used.
+    flags: Flags,
+
+    /// Number of capture groups seen so far.
+    group_count: u32,
+
+    /// Number of capture groups in the pattern.
+    group_count_max: u32,
+
+    /// Whether the pattern has any named capture groups.
+    has_named_groups: bool,
+}
+
+impl AstParser<'_> {
+    /// \return the remaining input.
+    fn rest(&self) -> &str {
+        &self.pattern[self.pos..]
+    }
+
+    /// Peek at the next character.
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// \return the next character.
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Consume a character, which must be next.
+    fn consume(&mut self, c: char) {
+        let nc = self.next();
+        debug_assert!(nc == Some(c), "char was not next");
+    }
+
+    /// If the next character is \p c, consume it and return true.
+    fn try_consume(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// If the input begins with \p s, consume it and return true.
+    fn try_consume_str(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// \return the span from \p start to the current position.
+    fn span(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.pos,
+        }
+    }
+
+    /// \return a literal of \p kind from \p start to the current position.
+    fn literal(&self, value: u32, kind: LiteralKind, start: usize) -> Literal {
+        Literal {
+            value,
+            kind,
+            span: self.span(start),
+        }
+    }
+
+    fn parse_disjunction(&mut self) -> Node {
+        let start = self.pos;
+        let mut alternatives = vec![self.parse_term()];
+        while self.try_consume('|') {
+            alternatives.push(self.parse_term());
+        }
+        if alternatives.len() == 1 {
+            return alternatives.pop().unwrap();
+        }
+        Node {
+            kind: NodeKind::Alternation(alternatives),
+            span: self.span(start),
+        }
+    }
+
+    fn parse_term(&mut self) -> Node {
+        let start = self.pos;
+        let mut nodes: Vec<Node> = Vec::new();
+        loop {
+            let atom_start = self.pos;
+            let mut quantifee_offset = nodes.len();
+            let kind = match self.peek() {
+                None | Some(')' | '|') => break,
+                Some('^') => {
+                    self.consume('^');
+                    NodeKind::Assertion(Assertion::StartOfLine)
+                }
+                Some('$') => {
+                    self.consume('$');
+                    NodeKind::Assertion(Assertion::EndOfLine)
+                }
+                Some('\\') => {
+                    self.consume('\\');
+                    match self.peek() {
+                        Some('b') => {
+                            self.consume('b');
+                            NodeKind::Assertion(Assertion::WordBoundary)
+                        }
+                        Some('B') => {
+                            self.consume('B');
+                            NodeKind::Assertion(Assertion::NotWordBoundary)
+                        }
+                        Some('c') if !self.flags.unicode => {
+                            self.consume('c');
+                            match self.peek() {
+                                Some(c) if c.is_ascii_alphabetic() => {
+                                    self.consume(c);
+                                    NodeKind::Literal(self.literal(
+                                        c as u32 % 32,
+                                        LiteralKind::ControlLetter,
+                                        atom_start,
+                                    ))
+                                }
+                                _ => {
+                                    // A lone backslash, followed by a c.
+                                    let backslash = Literal {
+                                        value: u32::from('\\'),
+                                        kind: LiteralKind::Verbatim,
+                                        span: Span {
+                                            start: atom_start,
+                                            end: atom_start + 1,
+                                        },
+                                    };
+                                    nodes.push(Node {
+                                        kind: NodeKind::Literal(backslash),
+                                        span: backslash.span,
+                                    });
+                                    quantifee_offset += 1;
+                                    NodeKind::Literal(self.literal(
+                                        u32::from('c'),
+                                        LiteralKind::Verbatim,
+                                        atom_start + 1,
+                                    ))
+                                }
+                            }
+                        }
+                        _ => self.parse_atom_escape(atom_start),
+                    }
+                }
+                Some('.') => {
+                    self.consume('.');
+                    NodeKind::Dot
+                }
+                Some('(') => self.parse_group(),
+                Some('[') if self.flags.unicode_sets => NodeKind::Class(self.parse_class_set()),
+                Some('[') => NodeKind::Class(self.parse_class()),
+                Some(c) => {
+                    self.consume(c);
+                    NodeKind::Literal(self.literal(c as u32, LiteralKind::Verbatim, atom_start))
+                }
+            };
+            // The span of a literal may not start at the atom, for example
+            // the c of a lone \c.
+            let span = match &kind {
+                NodeKind::Literal(lit) => lit.span,
+                _ => self.span(atom_start),
+            };
+            nodes.push(Node { kind, span });
+
+            if let Some(quantifier) = self.try_parse_quantifier() {
+                let quantifee = nodes.split_off(quantifee_offset);
+                let node = make_cat(quantifee, quantifier.span.start);
+                let span = Span {
+                    start: node.span.start,
+                    end: quantifier.span.end,
+                };
+                nodes.push(Node {
+                    kind: NodeKind::Repetition(Repetition {
+                        node: Box::new(node),
+                        quantifier,
+                    }),
+                    span,
+                });
+            }
+        }
+        let mut node = make_cat(nodes, start);
+        node.span = self.span(start);
+        node
+    }
+
+    fn try_parse_quantifier(&mut self) -> Option<Quantifier> {
+        let start = self.pos;
+        let (kind, min, max) = match self.peek()? {
+            '*' => {
+                self.consume('*');
+                (QuantifierKind::ZeroOrMore, 0, None)
+            }
+            '+' => {
+                self.consume('+');
+                (QuantifierKind::OneOrMore, 1, None)
+            }
+            '?' => {
+                self.consume('?');
+                (QuantifierKind::ZeroOrOne, 0, Some(1))
+            }
+            '{' => self.try_parse_braced_quantifier()?,
+            _ => return None,
+        };
+        let greedy = !self.try_consume('?');
+        Some(Quantifier {
+            kind,
+            min,
+            max,
+            greedy,
+            span: self.span(start),
+        })
+    }
+
+    /// Parse a braced quantifier, returning its kind, min and max, or None
+    /// without consuming anything if it is not one.
+    fn try_parse_braced_quantifier(&mut self) -> Option<(QuantifierKind, usize, Option<usize>)> {
+        let start = self.pos;
+        self.consume('{');
+        let Some(min) = self.try_parse_decimal() else {
+            self.pos = start;
+            return None;
+        };
+        let (kind, max) = if self.try_consume(',') {
+            match self.try_parse_decimal() {
+                Some(max) => (QuantifierKind::Between, Some(max)),
+                None => (QuantifierKind::AtLeast, None),
+            }
+        } else {
+            (QuantifierKind::Exactly, Some(min))
+        };
+        if !self.try_consume('}') {
+            self.pos = start;
+            return None;
+        }
+        Some((kind, min, max))
+    }
+
+    /// Parse a decimal integer, saturating on overflow.
+    fn try_parse_decimal(&mut self) -> Option<usize> {
+        let digits = self.rest().bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        let mut result: usize = 0;
+        for b in self.rest()[..digits].bytes() {
+            result = result
+                .saturating_mul(10)
+                .saturating_add(usize::from(b - b'0'));
+        }
+        self.pos += digits;
+        Some(result)
+    }
+
+    fn parse_group(&mut self) -> NodeKind {
+        let kind = if self.try_consume_str("(?=") {
+            GroupKind::Lookahead { negated: false }
+        } else if self.try_consume_str("(?!") {
+            GroupKind::Lookahead { negated: true }
+        } else if self.try_consume_str("(?<=") {
+            GroupKind::Lookbehind { negated: false }
+        } else if self.try_consume_str("(?<!") {
+            GroupKind::Lookbehind { negated: true }
+        } else if self.try_consume_str("(?:") {
+            GroupKind::NonCapture
+        } else if self.rest().starts_with("(?") && !self.rest().starts_with("(?<") {
+            self.consume('(');
+            self.consume('?');
+            let mut enable = Modifiers::default();
+            let mut disable = Modifiers::default();
+            let mut target = &mut enable;
+            loop {
+                match self.next() {
+                    Some('i') => target.icase = true,
+                    Some('m') => target.multiline = true,
+                    Some('s') => target.dot_all = true,
+                    Some('-') => target = &mut disable,
+                    _ => break,
+                }
+            }
+            GroupKind::Modifiers { enable, disable }
+        } else {
+            self.consume('(');
+            self.group_count += 1;
+            let index = self.group_count;
+            let name = if self.try_consume('?') {
+                self.parse_group_name()
+            } else {
+                None
+            };
+            GroupKind::Capture { index, name }
+        };
+        let node = Box::new(self.parse_disjunction());
+        self.consume(')');
+        NodeKind::Group(Group { kind, node })
+    }
+
+    /// Parse a group name in angle brackets, decoding escapes.
+    fn parse_group_name(&mut self) -> Option<String> {
+        if !self.try_consume('<') {
+            return None;
+        }
+        let mut name = String::new();
+        loop {
+            let mut c = self.next()?;
+            if c == '\\' && self.try_consume('u') {
+                c = self
+                    .try_parse_unicode_escape()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+            } else if c == '>' {
+                return Some(name);
+            }
+            name.push(c);
+        }
+    }
+
+    /// Parse the rest of a unicode escape, after the `\u`.
+    /// This accepts the same syntax as the compiling parser, including
+    /// surrogate pairs. \return the code point, or None without consuming
+    /// anything if it is not one.
+    fn try_parse_unicode_escape(&mut self) -> Option<u32> {
+        let start = self.pos;
+        if self.try_consume('{') {
+            let Some(len) = self.rest().find('}') else {
+                self.pos = start;
+                return None;
+            };
+            let digits = &self.rest()[..len];
+            match u32::from_str_radix(digits, 16) {
+                Ok(u) if u <= 0x10_FFFF => {
+                    self.pos += len + 1;
+                    Some(u)
+                }
+                _ => {
+                    self.pos = start;
+                    None
+                }
+            }
+        } else {
+            let Some(u) = self.try_parse_hex4() else {
+                self.pos = start;
+                return None;
+            };
+            if (0xD800..=0xDBFF).contains(&u) && self.try_consume_str("\\u") {
+                // Try to combine with a low surrogate. On failure the \u
+                // stays consumed, as in the compiling parser.
+                let low_start = self.pos;
+                let pair = self
+                    .try_parse_hex4()
+                    .and_then(|uu| char::decode_utf16([u, uu]).next()?.ok());
+                match pair {
+                    Some(c) => return Some(u32::from(c)),
+                    None => self.pos = low_start,
+                }
+            }
+            Some(u32::from(u))
+        }
+    }
+
+    /// Parse four characters as a hex number.
+    fn try_parse_hex4(&mut self) -> Option<u16> {
+        let digits: String = self.rest().chars().take(4).collect();
+        if digits.chars().count() < 4 {
+            return None;
+        }
+        let u = u16::from_str_radix(&digits, 16).ok()?;
+        self.pos += digits.len();
+        Some(u)
+    }
+
+    /// Parse an atom escape, after the backslash at \p start.
+    fn parse_atom_escape(&mut self, start: usize) -> NodeKind {
+        let c = self.peek().expect("escape was validated");
+        match c {
+            'd' | 'D' | 's' | 'S' | 'w' | 'W' => {
+                NodeKind::ClassEscape(self.parse_class_escape().expect("escape is a class"))
+            }
+            'p' | 'P' if self.flags.unicode || self.flags.unicode_sets => {
+                NodeKind::ClassEscape(self.parse_class_escape().expect("escape is a class"))
+            }
+            '1'..='9' => {
+                let digits_start = self.pos;
+                let group = self.try_parse_decimal().expect("digit was next");
+                if self.flags.unicode || group <= self.group_count_max as usize {
+                    NodeKind::Backreference(Backreference::Number(group as u32))
+                } else {
+                    self.pos = digits_start;
+                    NodeKind::Literal(self.parse_character_escape(start))
+                }
+            }
+            'k' if self.flags.unicode || self.has_named_groups => {
+                self.consume('k');
+                let name = self
+                    .parse_group_name()
+                    .expect("backreference was validated");
+                NodeKind::Backreference(Backreference::Name(name))
+            }
+            'k' => {
+                self.consume('k');
+                NodeKind::Literal(self.literal(u32::from('k'), LiteralKind::Identity, start))
+            }
+            _ => NodeKind::Literal(self.parse_character_escape(start)),
+        }
+    }
+
+    /// If the next character begins a class escape like `d` or `p{L}`, after
+    /// the backslash, parse and return it.
+    fn parse_class_escape(&mut self) -> Option<ClassEscape> {
+        let c = self.peek()?;
+        let kind = match c.to_ascii_lowercase() {
+            'd' => ClassEscapeKind::Digit,
+            's' => ClassEscapeKind::Space,
+            'w' => ClassEscapeKind::Word,
+            'p' => {
+                self.consume(c);
+                self.consume('{');
+                let len = self.rest().find('}').expect("property was validated");
+                let contents = &self.rest()[..len];
+                let (name, value) = match contents.split_once('=') {
+                    Some((name, value)) => (Some(name.into()), value.into()),
+                    None => (None, contents.into()),
+                };
+                self.pos += len + 1;
+                return Some(ClassEscape {
+                    kind: ClassEscapeKind::Property { name, value },
+                    negated: c == 'P',
+                });
+            }
+            _ => return None,
+        };
+        self.consume(c);
+        Some(ClassEscape {
+            kind,
+            negated: c.is_ascii_uppercase(),
+        })
+    }
+
+    /// Parse a character escape, after the backslash at \p start.
+    fn parse_character_escape(&mut self, start: usize) -> Literal {
+        let c = self.next().expect("escape was validated");
+        let (value, kind) = match c {
+            'f' => (0xC, LiteralKind::Control),
+            'n' => (0xA, LiteralKind::Control),
+            'r' => (0xD, LiteralKind::Control),
+            't' => (0x9, LiteralKind::Control),
+            'v' => (0xB, LiteralKind::Control),
+            'c' => {
+                let letter = self.next().expect("control letter was validated");
+                (letter as u32 % 32, LiteralKind::ControlLetter)
+            }
+            '0' if !self.peek().is_some_and(|c| c.is_ascii_digit()) => (0, LiteralKind::Null),
+            'x' => {
+                let hex = self
+                    .rest()
+                    .get(..2)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+                match hex {
+                    Some(hex) => {
+                        let value = u32::from_str_radix(hex, 16).expect("hex digits");
+                        self.pos += 2;
+                        (value, LiteralKind::Hex)
+                    }
+                    None => (u32::from('x'), LiteralKind::Identity),
+                }
+            }
+            'u' => {
+                let braced = self.peek() == Some('{');
+                match self.try_parse_unicode_escape() {
+                    Some(value) if braced => (value, LiteralKind::UnicodeBraced),
+                    Some(value) => (value, LiteralKind::Unicode),
+                    None => (u32::from('u'), LiteralKind::Identity),
+                }
+            }
+            '0'..='7' if !self.flags.unicode => {
+                let d0 = c as u32 - '0' as u32;
+                let octal = |c: Option<char>| c.filter(|c| ('0'..='7').contains(c));
+                match (c, octal(self.peek())) {
+                    // 0 [lookahead ∈ { 8, 9 }]
+                    ('0', None) => (0, LiteralKind::Null),
+                    (_, None) => (d0, LiteralKind::Octal),
+                    ('4'..='7', Some(c1)) => {
+                        self.consume(c1);
+                        (d0 * 8 + (c1 as u32 - '0' as u32), LiteralKind::Octal)
+                    }
+                    (_, Some(c1)) => {
+                        self.consume(c1);
+                        let d1 = c1 as u32 - '0' as u32;
+                        match octal(self.peek()) {
+                            Some(c2) => {
+                                self.consume(c2);
+                                let d2 = c2 as u32 - '0' as u32;
+                                (d0 * 64 + d1 * 8 + d2, LiteralKind::Octal)
+                            }
+                            None => (d0 * 8 + d1, LiteralKind::Octal),
+                        }
+                    }
+                }
+            }
+            c => (c as u32, LiteralKind::Identity),
+        };
+        self.literal(value, kind, start)
+    }
+
+    /// Parse a class outside of unicode sets mode, at its `[`.
+    fn parse_class(&mut self) -> Class {
+        self.consume('[');
+        let negated = self.try_consume('^');
+        let mut items = Vec::new();
+        loop {
+            if self.try_consume(']') || self.peek().is_none() {
+                break;
+            }
+            let first_start = self.pos;
+            let Some(first) = self.parse_class_atom() else {
+                continue;
+            };
+            let dash_start = self.pos;
+            if !self.try_consume('-') {
+                items.push(first);
+                continue;
+            }
+            let dash = ClassItem {
+                kind: ClassItemKind::Literal(self.literal(
+                    u32::from('-'),
+                    LiteralKind::Verbatim,
+                    dash_start,
+                )),
+                span: self.span(dash_start),
+            };
+            let Some(second) = self.parse_class_atom() else {
+                // No second atom, as in [a-].
+                items.extend([first, dash]);
+                continue;
+            };
+            match (&first.kind, &second.kind) {
+                (ClassItemKind::Literal(first), ClassItemKind::Literal(last)) => {
+                    items.push(ClassItem {
+                        kind: ClassItemKind::Range {
+                            first: *first,
+                            last: *last,
+                        },
+                        span: self.span(first_start),
+                    });
+                }
+                _ => items.extend([first, dash, second]),
+            }
+        }
+        Class {
+            negated,
+            op: ClassSetOp::Union,
+            items,
+        }
+    }
+
+    /// Parse a code point or class escape within a class, or return None at
+    /// its end.
+    fn parse_class_atom(&mut self) -> Option<ClassItem> {
+        let start = self.pos;
+        let kind = match self.peek()? {
+            ']' => return None,
+            '\\' => {
+                self.consume('\\');
+                match self.peek().expect("escape was validated") {
+                    'b' => {
+                        self.consume('b');
+                        ClassItemKind::Literal(self.literal(0x08, LiteralKind::Backspace, start))
+                    }
+                    '-' if self.flags.unicode => {
+                        self.consume('-');
+                        ClassItemKind::Literal(self.literal(
+                            u32::from('-'),
+                            LiteralKind::Identity,
+                            start,
+                        ))
+                    }
+                    'c' if !self.flags.unicode => {
+                        let backslash_end = self.pos;
+                        self.consume('c');
+                        match self.peek() {
+                            Some(c) if c.is_ascii_alphanumeric() || c == '_' => {
+                                self.consume(c);
+                                // Digits and underscore are only allowed in classes.
+                                let value = if c.is_ascii_alphabetic() {
+                                    c as u32 % 32
+                                } else {
+                                    c as u32 & 0x1F
+                                };
+                                ClassItemKind::Literal(self.literal(
+                                    value,
+                                    LiteralKind::ControlLetter,
+                                    start,
+                                ))
+                            }
+                            _ => {
+                                // A lone backslash; the c is the next atom.
+                                self.pos = backslash_end;
+                                ClassItemKind::Literal(self.literal(
+                                    u32::from('\\'),
+                                    LiteralKind::Verbatim,
+                                    start,
+                                ))
+                            }
+                        }
+                    }
+                    'p' | 'P' if !self.flags.unicode => {
+                        ClassItemKind::Literal(self.parse_character_escape(start))
+                    }
+                    _ => match self.parse_class_escape() {
+                        Some(escape) => ClassItemKind::ClassEscape(escape),
+                        None => ClassItemKind::Literal(self.parse_character_escape(start)),
+                    },
+                }
+            }
+            c => {
+                self.consume(c);
+                ClassItemKind::Literal(self.literal(c as u32, LiteralKind::Verbatim, start))
+            }
+        };
+        Some(ClassItem {
+            kind,
+            span: self.span(start),
+        })
+    }
+
+    /// Parse a class in unicode sets mode, at its `[`.
+    fn parse_class_set(&mut self) -> Class {
+        self.consume('[');
+        let negated = self.try_consume('^');
+        let mut class = Class {
+            negated,
+            op: ClassSetOp::Union,
+            items: Vec::new(),
+        };
+        if self.try_consume(']') {
+            return class;
+        }
+        let first = self.parse_class_set_operand();
+        match self.peek() {
+            Some(']') => {
+                self.consume(']');
+                class.items.push(first);
+                return class;
+            }
+            Some('&') if self.rest().starts_with("&&") => {
+                self.consume('&');
+                self.consume('&');
+                class.op = ClassSetOp::Intersection;
+                class.items.push(first);
+            }
+            Some('-') if self.rest().starts_with("--") => {
+                self.consume('-');
+                self.consume('-');
+                class.op = ClassSetOp::Subtraction;
+                class.items.push(first);
+            }
+            _ => {
+                let item = self.parse_class_set_range(first);
+                class.items.push(item);
+            }
+        }
+        match class.op {
+            ClassSetOp::Union => {
+                while !self.try_consume(']') {
+                    let operand = self.parse_class_set_operand();
+                    let item = self.parse_class_set_range(operand);
+                    class.items.push(item);
+                }
+            }
+            ClassSetOp::Intersection | ClassSetOp::Subtraction => loop {
+                class.items.push(self.parse_class_set_operand());
+                if self.try_consume(']') {
+                    break;
+                }
+                // Consume the && or --.
+                self.next();
+                self.next();
+            },
+        }
+        class
+    }
+
+    /// If \p first is followed by `-`, parse a range starting with it.
+    /// Otherwise return it.
+    fn parse_class_set_range(&mut self, first: ClassItem) -> ClassItem {
+        let ClassItemKind::Literal(first_lit) = first.kind else {
+            return first;
+        };
+        if !self.try_consume('-') {
+            return first;
+        }
+        let last = self.parse_class_set_operand();
+        let ClassItemKind::Literal(last) = last.kind else {
+            unreachable!("class set range was validated");
+        };
+        ClassItem {
+            kind: ClassItemKind::Range {
+                first: first_lit,
+                last,
+            },
+            span: self.span(first.span.start),
+        }
+    }
+
+    fn parse_class_set_operand(&mut self) -> ClassItem {
+        let start = self.pos;
+        let kind = match self.peek() {
+            Some('[') => ClassItemKind::Class(self.parse_class_set()),
+            Some('\\') => {
+                self.consume('\\');
+                match self.peek().expect("escape was validated") {
+                    'q' => {
+                        self.consume('q');
+                        self.consume('{');
+                        let mut strings = Vec::new();
+                        let mut string = ClassString {
+                            literals: Vec::new(),
+                            span: self.span(self.pos),
+                        };
+                        loop {
+                            match self.peek() {
+                                Some('}') | Some('|') | None => {
+                                    string.span.end = self.pos;
+                                    strings.push(string);
+                                    if !self.try_consume('|') {
+                                        self.try_consume('}');
+                                        break;
+                                    }
+                                    string = ClassString {
+                                        literals: Vec::new(),
+                                        span: self.span(self.pos),
+                                    };
+                                }
+                                Some(_) => string.literals.push(self.parse_class_set_character()),
+                            }
+                        }
+                        ClassItemKind::Strings(strings)
+                    }
+                    _ => match self.parse_class_escape() {
+                        Some(escape) => ClassItemKind::ClassEscape(escape),
+                        None => {
+                            self.pos = start;
+                            ClassItemKind::Literal(self.parse_class_set_character())
+                        }
+                    },
+                }
+            }
+            _ => ClassItemKind::Literal(self.parse_class_set_character()),
+        };
+        ClassItem {
+            kind,
+            span: self.span(start),
+        }
+    }
+
+    fn parse_class_set_character(&mut self) -> Literal {
+        let start = self.pos;
+        match self.next().expect("class set was validated") {
+            '\\' => match self.peek().expect("escape was validated") {
+                'b' => {
+                    self.consume('b');
+                    self.literal(0x08, LiteralKind::Backspace, start)
+                }
+                c if is_class_set_reserved_punctuator(c) => {
+                    self.consume(c);
+                    self.literal(c as u32, LiteralKind::Identity, start)
+                }
+                _ => self.parse_character_escape(start),
+            },
+            c => self.literal(c as u32, LiteralKind::Verbatim, start),
+        }
+    }
+}
+
+/// \return whether \p c is a ClassSetReservedPunctuator.
+fn is_class_set_reserved_punctuator(c: char) -> bool {
+    matches!(
+        c,
+        '&' | '-' | '!' | '#' | '%' | ',' | ':' | ';' | '<' | '=' | '>' | '@' | '`' | '~'
+    )
+}
+
+/// \return a node for a sequence of nodes, which is empty at \p pos if
+/// there are none.
+fn make_cat(mut nodes: Vec<Node>, pos: usize) -> Node {
+    match nodes.len() {
+        0 => Node {
+            kind: NodeKind::Empty,
+            span: Span {
+                start: pos,
+                end: pos,
+            },
+        },
+        1 => nodes.pop().unwrap(),
+        _ => {
+            let span = Span {
+                start: nodes[0].span.start,
+                end: nodes[nodes.len() - 1].span.end,
+            };
+            Node {
+                kind: NodeKind::Concatenation(nodes),
+                span,
+            }
+        }
+    }
+}
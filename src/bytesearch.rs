@@ -340,6 +340,49 @@ impl ByteSearcher for EmptyString {
     }
 }
 
+/// A searcher for the first occurrence of any of a set of byte sequences.
+/// This uses a packed SIMD searcher (Teddy) where the target supports it, and
+/// an Aho-Corasick automaton otherwise.
+#[cfg(feature = "perf-literal")]
+#[derive(Debug, Clone)]
+pub enum LiteralSet {
+    Packed(aho_corasick::packed::Searcher),
+    Automaton(aho_corasick::AhoCorasick),
+}
+
+#[cfg(feature = "perf-literal")]
+impl LiteralSet {
+    /// \return a searcher for any of \p literals, or None if it could not be
+    /// built.
+    pub fn new<Literal: AsRef<[u8]>>(literals: &[Literal]) -> Option<LiteralSet> {
+        let packed = aho_corasick::packed::Config::new()
+            .match_kind(aho_corasick::packed::MatchKind::LeftmostFirst)
+            .builder()
+            .extend(literals)
+            .build();
+        if let Some(searcher) = packed {
+            return Some(LiteralSet::Packed(searcher));
+        }
+        aho_corasick::AhoCorasick::builder()
+            .match_kind(aho_corasick::MatchKind::LeftmostFirst)
+            .build(literals)
+            .ok()
+            .map(LiteralSet::Automaton)
+    }
+}
+
+#[cfg(feature = "perf-literal")]
+impl ByteSearcher for LiteralSet {
+    #[inline(always)]
+    fn find_in(&self, rhs: &[u8]) -> Option<usize> {
+        let found = match self {
+            LiteralSet::Packed(searcher) => searcher.find(rhs),
+            LiteralSet::Automaton(automaton) => automaton.find(rhs),
+        };
+        found.map(|m| m.start())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -384,4 +427,22 @@ mod tests {
         assert_eq!([0, 1, 2, 3].find_in(&[4, 5, 6, 7]), None);
         assert_eq!([0, 1, 2, 3].find_in(&[]), None);
     }
+
+    #[test]
+    #[cfg(feature = "perf-literal")]
+    fn literal_set_search() {
+        let set = LiteralSet::new(&["foo", "bar", "oba"]).unwrap();
+        assert_eq!(set.find_in(b""), None);
+        assert_eq!(set.find_in(b"fobaz"), Some(1));
+        assert_eq!(set.find_in(b"xxfoobar"), Some(2));
+        let haystack = b"a long haystack, which is searched in blocks, and then fo";
+        assert_eq!(set.find_in(haystack), None);
+        assert_eq!(set.find_in(&[&haystack[..], b"o"].concat()), Some(55));
+
+        // Too many literals to pack.
+        let literals: Vec<_> = (0..500).map(|i| format!("<{i}>")).collect();
+        let set = LiteralSet::new(&literals).unwrap();
+        assert!(matches!(set, LiteralSet::Automaton(_)));
+        assert_eq!(set.find_in(b"<500> <4999> <499>"), Some(13));
+    }
 }
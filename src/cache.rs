@@ -0,0 +1,43 @@
+//! Reusable scratch space for searches.
+
+use crate::classicalbacktrack;
+use crate::lazydfa;
+#[cfg(feature = "backend-pikevm")]
+use crate::pikevm;
+
+/// A Cache holds the buffers used while searching, so that they may be
+/// reused instead of allocated for every search.
+///
+/// A Cache is not tied to any particular regex: it may be passed to searches
+/// with any [`Regex`](crate::Regex) or `RegexSet`, growing to fit the largest.
+/// The DFA states built for a regex are kept until it is used with another.
+/// It is used by one search at a time, so use a Cache per thread.
+///
+/// Example:
+///
+/// ```rust
+///   use regress::{Cache, Regex};
+///   let re = Regex::new(r"(?<=\$)\d+").unwrap();
+///   let mut cache = Cache::new();
+///   let total: u32 = ["$12", "$3", "free"]
+///       .iter()
+///       .filter_map(|text| re.find_with_cache(text, &mut cache).map(|m| &text[m.range()]))
+///       .map(|price| price.parse::<u32>().unwrap())
+///       .sum();
+///   assert_eq!(total, 15);
+/// ```
+#[derive(Debug, Default)]
+pub struct Cache {
+    pub(crate) backtrack: classicalbacktrack::Scratch,
+    pub(crate) lazydfa: lazydfa::Cache,
+    #[cfg(feature = "backend-pikevm")]
+    pub(crate) pikevm: pikevm::Scratch,
+}
+
+impl Cache {
+    /// Construct an empty Cache. This does not allocate.
+    #[inline]
+    pub fn new() -> Cache {
+        Cache::default()
+    }
+}
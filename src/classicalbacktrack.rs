@@ -1,24 +1,35 @@
 //! Classical backtracking execution engine
 
-use crate::api::Match;
+use crate::api::{Match, MatchError};
 use crate::bytesearch;
 use crate::cursor;
 use crate::cursor::{Backward, Direction, Forward};
-use crate::exec;
+use crate::exec::{self, INTERRUPT_POLL_INTERVAL};
 use crate::indexing::{AsciiInput, ElementType, InputIndexer, Utf8Input};
-#[cfg(not(feature = "utf16"))]
-use crate::insn::StartPredicate;
-use crate::insn::{CompiledRegex, Insn, LoopFields};
+use crate::insn::{CompiledRegex, Insn, LoopFields, StartPredicate};
 use crate::matchers;
 use crate::matchers::CharProperties;
+use crate::onepass::OnePass;
 use crate::position::PositionType;
 use crate::scm;
 use crate::scm::SingleCharMatcher;
 use crate::types::{CaptureGroupID, GroupData, IP, LoopData, LoopID, MAX_CAPTURE_GROUPS};
-use crate::util::DebugCheckIndex;
+use crate::util::{DebugCheckIndex, SpareVec};
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec::Vec};
 use core::ops::Range;
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// The most keys of a memoized state. States whose loops may take more keys
+/// are not memoized.
+const MAX_MEMO_KEYS: usize = 1 << 12;
+
+/// The most entries of a memo table stored as a bitset. Larger tables hash
+/// the words of their bitset.
+const MAX_MEMO_BITS: usize = 1 << 26;
 
 #[derive(Clone, Debug)]
 enum BacktrackInsn<Input: InputIndexer> {
@@ -62,28 +73,460 @@ enum BacktrackInsn<Input: InputIndexer> {
     },
 }
 
+/// A point of the program at which the backtracker may memoize.
+#[derive(Debug, Clone)]
+enum MemoPoint {
+    /// Not memoized.
+    None,
+
+    /// A state which is memoized when it is reached, keyed by the state of
+    /// the loops enclosing it. Each loop is paired with the most iterations
+    /// its key distinguishes.
+    State {
+        slot: usize,
+        loops: Box<[(LoopID, usize)]>,
+    },
+
+    /// A lookaround, whose result depends only on the position if it has no
+    /// capture groups to report. The first of its two slots records whether
+    /// the result is known, and the second whether it matched.
+    Lookaround { slot: usize, has_groups: bool },
+}
+
+/// How the backtracker memoizes the states of a regex which failed to match,
+/// so that no state is explored twice.
+///
+/// The future of a state depends only on its instruction, its position, and
+/// the loops enclosing it, unless there are backreferences. Of a loop, all
+/// that matters is its iteration count up to the most its bounds distinguish,
+/// and whether the current iteration was entered at this position.
+#[derive(Debug, Clone)]
+pub struct MemoPlan {
+    // Whether to memoize from the start of each search. Otherwise a search
+    // memoizes once it has taken as many steps as its memo table has entries.
+    pub(crate) eager: bool,
+
+    // The memo point of each instruction.
+    points: Box<[MemoPoint]>,
+
+    // The number of memo slots at each position of the input.
+    slots: usize,
+}
+
+impl MemoPlan {
+    /// Plan the memoization of \p re.
+    /// \return the plan, or None if \p re has backreferences.
+    pub(crate) fn new(re: &CompiledRegex, eager: bool) -> Option<Self> {
+        if re.has_backrefs {
+            return None;
+        }
+        let mut enclosing: Vec<Vec<(LoopID, usize)>> = vec![Vec::new(); re.insns.len()];
+        for (ip, insn) in re.insns.iter().enumerate() {
+            if let Insn::EnterLoop(fields) = insn {
+                let most = if fields.max_iters == usize::MAX {
+                    fields.min_iters.saturating_add(1)
+                } else {
+                    fields.max_iters
+                };
+                for loops in &mut enclosing[ip + 1..fields.exit as usize] {
+                    loops.push((fields.loop_id, most));
+                }
+            }
+        }
+        let mut slots = 0;
+        let mut points = Vec::with_capacity(re.insns.len());
+        for (insn, loops) in re.insns.iter().zip(enclosing) {
+            let point = match insn {
+                Insn::Alt { .. }
+                | Insn::EnterLoop(..)
+                | Insn::LoopAgain { .. }
+                | Insn::Loop1CharBody {
+                    max_iters: usize::MAX,
+                    ..
+                } => {
+                    let keys = loops.iter().try_fold(1_usize, |keys, &(_, most)| {
+                        keys.checked_mul(most.checked_add(1)?.checked_mul(2)?)
+                            .filter(|&keys| keys <= MAX_MEMO_KEYS)
+                    });
+                    match keys {
+                        Some(keys) => {
+                            slots += keys;
+                            MemoPoint::State {
+                                slot: slots - keys,
+                                loops: loops.into_boxed_slice(),
+                            }
+                        }
+                        None => MemoPoint::None,
+                    }
+                }
+                &Insn::Lookahead {
+                    start_group,
+                    end_group,
+                    ..
+                }
+                | &Insn::Lookbehind {
+                    start_group,
+                    end_group,
+                    ..
+                } => {
+                    slots += 2;
+                    MemoPoint::Lookaround {
+                        slot: slots - 2,
+                        has_groups: start_group != end_group,
+                    }
+                }
+                _ => MemoPoint::None,
+            };
+            points.push(point);
+        }
+        Some(Self {
+            eager,
+            points: points.into_boxed_slice(),
+            slots,
+        })
+    }
+}
+
+/// The memo table of a search, whose entries are the states known to fail
+/// and the results of lookarounds.
+#[derive(Debug, Default)]
+pub(crate) struct Memo {
+    // Whether entries are being recorded.
+    active: bool,
+    // The number of entries of the table, or None if it cannot be indexed.
+    entries: Option<usize>,
+    // The table, as a bitset if it is small enough, and otherwise as the
+    // nonzero words of the bitset.
+    bits: Vec<u64>,
+    hashed: HashMap<usize, u64>,
+    // The range of words of the bitset which may be nonzero.
+    dirty: Range<usize>,
+    // The depth of lookarounds being run. Entries set within a lookaround
+    // are logged, so that they may be cleared if it matches.
+    lookaround_depth: usize,
+    log: Vec<usize>,
+}
+
+impl Memo {
+    /// Clear the table, and size it for a search of \p positions positions
+    /// with \p slots slots each. It is inactive until activated.
+    fn reset(&mut self, slots: usize, positions: usize) {
+        self.bits[self.dirty.clone()].fill(0);
+        self.dirty = 0..0;
+        self.hashed.clear();
+        self.log.clear();
+        self.lookaround_depth = 0;
+        self.active = false;
+        self.entries = slots.checked_mul(positions);
+    }
+
+    /// Start recording entries, unless the table cannot be indexed.
+    fn activate(&mut self) {
+        let Some(entries) = self.entries else {
+            return;
+        };
+        if entries <= MAX_MEMO_BITS {
+            let words = entries.div_ceil(64);
+            if self.bits.len() < words {
+                self.bits.resize(words, 0);
+            }
+        }
+        self.active = true;
+    }
+
+    #[inline(always)]
+    fn dense(&self) -> bool {
+        self.entries.is_some_and(|entries| entries <= MAX_MEMO_BITS)
+    }
+
+    /// \return whether the entry \p idx is set.
+    #[inline]
+    fn contains(&self, idx: usize) -> bool {
+        let word = if self.dense() {
+            self.bits[idx / 64]
+        } else {
+            self.hashed.get(&(idx / 64)).copied().unwrap_or(0)
+        };
+        word & (1 << (idx % 64)) != 0
+    }
+
+    /// Set the entry \p idx.
+    /// \return whether it was not already set.
+    #[inline]
+    fn insert(&mut self, idx: usize) -> bool {
+        let word_idx = idx / 64;
+        let word = if self.dense() {
+            if self.dirty.is_empty() {
+                self.dirty = word_idx..word_idx + 1;
+            } else {
+                self.dirty.start = self.dirty.start.min(word_idx);
+                self.dirty.end = self.dirty.end.max(word_idx + 1);
+            }
+            &mut self.bits[word_idx]
+        } else {
+            self.hashed.entry(word_idx).or_insert(0)
+        };
+        let bit = 1 << (idx % 64);
+        if *word & bit != 0 {
+            return false;
+        }
+        *word |= bit;
+        if self.lookaround_depth > 0 {
+            self.log.push(idx);
+        }
+        true
+    }
+
+    /// Note the start of a lookaround.
+    /// \return the length of the log, to roll back to.
+    fn enter_lookaround(&mut self) -> usize {
+        self.lookaround_depth += 1;
+        self.log.len()
+    }
+
+    /// Note the end of a lookaround. If it \p matched, clear the entries set
+    /// since the log had length \p len: they did not fail.
+    fn exit_lookaround(&mut self, len: usize, matched: bool) {
+        self.lookaround_depth -= 1;
+        if !matched {
+            if self.lookaround_depth == 0 {
+                self.log.clear();
+            }
+            return;
+        }
+        let dense = self.dense();
+        for idx in self.log.drain(len..) {
+            let word = if dense {
+                Some(&mut self.bits[idx / 64])
+            } else {
+                self.hashed.get_mut(&(idx / 64))
+            };
+            if let Some(word) = word {
+                *word &= !(1 << (idx % 64));
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct State<Position: PositionType> {
     loops: Vec<LoopData<Position>>,
     groups: Vec<GroupData<Position>>,
 }
 
+/// Buffers used by the MatchAttempter, which may be reused across searches.
+/// Positions are bound to the lifetime of their input, so the buffers are
+/// stored empty without their element type.
+#[derive(Debug, Default)]
+pub(crate) struct Scratch {
+    bts: SpareVec,
+    loops: SpareVec,
+    groups: SpareVec,
+    spare_bts: Vec<SpareVec>,
+    spare_groups: Vec<SpareVec>,
+    memo: Memo,
+    #[cfg(feature = "backend-jit")]
+    jit: Vec<u64>,
+}
+
 #[derive(Debug)]
 pub(crate) struct MatchAttempter<'a, Input: InputIndexer> {
     re: &'a CompiledRegex,
     bts: Vec<BacktrackInsn<Input>>,
     s: State<Input::Position>,
+    // Whether to record capture groups. If false, `s.groups` is empty and
+    // capture group instructions are no-ops.
+    track_captures: bool,
+    // The number of backtracks and loop iterations we may perform before
+    // checking the limit and polling the interrupt.
+    fuel: usize,
+    // The number of steps of the backtrack limit not yet loaded into `fuel`.
+    budget: usize,
+    // Set if the search was abandoned. Once set, every attempt fails.
+    abandoned: Option<MatchError>,
+    // The failed states, if the regex memoizes.
+    memo: Memo,
+    // The number of steps the search may take before it starts memoizing,
+    // or usize::MAX if it does not wait to.
+    memo_countdown: usize,
+    // Empty buffers for the backtrack stacks and saved groups of lookarounds.
+    // They are kept as they are stored in the Scratch, so that giving them
+    // back does not allocate a list of them.
+    spare_bts: Vec<SpareVec>,
+    spare_groups: Vec<SpareVec>,
+    // The buffer of the machine code of the regex, if it has any.
+    #[cfg(feature = "backend-jit")]
+    jit_buf: Vec<u64>,
 }
 
 impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
     pub(crate) fn new(re: &'a CompiledRegex, entry: Input::Position) -> Self {
+        Self::with_scratch(re, entry, Scratch::default())
+    }
+
+    /// Construct a MatchAttempter using the buffers of `scratch`.
+    pub(crate) fn with_scratch(
+        re: &'a CompiledRegex,
+        entry: Input::Position,
+        scratch: Scratch,
+    ) -> Self {
+        let mut bts = scratch.bts.into_vec();
+        bts.push(BacktrackInsn::Exhausted);
+        let mut loops = scratch.loops.into_vec();
+        loops.resize(re.loops as usize, LoopData::new(entry));
+        let mut groups = scratch.groups.into_vec();
+        groups.resize(re.groups as usize, GroupData::new());
         Self {
             re,
-            bts: vec![BacktrackInsn::Exhausted],
-            s: State {
-                loops: vec![LoopData::new(entry); re.loops as usize],
-                groups: vec![GroupData::new(); re.groups as usize],
-            },
+            bts,
+            s: State { loops, groups },
+            track_captures: true,
+            fuel: usize::MAX,
+            budget: 0,
+            abandoned: None,
+            memo: scratch.memo,
+            memo_countdown: usize::MAX,
+            spare_bts: scratch.spare_bts,
+            spare_groups: scratch.spare_groups,
+            #[cfg(feature = "backend-jit")]
+            jit_buf: scratch.jit,
+        }
+    }
+
+    /// Construct a MatchAttempter which only determines whether a match
+    /// exists, and not the capture groups, using the buffers of `scratch`.
+    /// Capture groups are still recorded if a backreference needs them.
+    pub(crate) fn match_only_with_scratch(
+        re: &'a CompiledRegex,
+        entry: Input::Position,
+        scratch: Scratch,
+    ) -> Self {
+        let mut result = Self::with_scratch(re, entry, scratch);
+        if !re.has_backrefs {
+            result.s.groups.clear();
+            result.track_captures = false;
+        }
+        result
+    }
+
+    /// Give up our buffers so that they may be reused.
+    pub(crate) fn into_scratch(self) -> Scratch {
+        Scratch {
+            bts: SpareVec::new(self.bts),
+            loops: SpareVec::new(self.s.loops),
+            groups: SpareVec::new(self.s.groups),
+            spare_bts: self.spare_bts,
+            spare_groups: self.spare_groups,
+            memo: self.memo,
+            #[cfg(feature = "backend-jit")]
+            jit: self.jit_buf,
+        }
+    }
+
+    /// Reset the budget and the memo table at the start of a search of
+    /// \p input.
+    #[inline]
+    fn reset_budget(&mut self, input: &Input) {
+        self.budget = self.re.backtrack_limit.unwrap_or(usize::MAX);
+        self.abandoned = None;
+        self.memo_countdown = usize::MAX;
+        if let Some(plan) = &self.re.memo {
+            let positions = input.pos_to_offset(input.right_end()) + 1;
+            self.memo.reset(plan.slots, positions);
+            if plan.eager {
+                self.memo.activate();
+            } else if let Some(entries) = self.memo.entries {
+                self.memo_countdown = entries;
+            }
+        }
+        self.refuel();
+    }
+
+    /// Load the next chunk of the budget into our fuel. If there is an
+    /// interrupt, the chunk is small enough that it is polled regularly, and
+    /// it ends when the search is due to start memoizing.
+    #[inline]
+    fn refuel(&mut self) {
+        let chunk = match self.re.interrupt {
+            Some(_) => self.budget.min(INTERRUPT_POLL_INTERVAL),
+            None => self.budget,
+        }
+        .min(self.memo_countdown);
+        self.fuel = chunk;
+        self.budget -= chunk;
+        if self.memo_countdown != usize::MAX {
+            self.memo_countdown -= chunk;
+        }
+    }
+
+    /// Consume one step of the budget.
+    /// \return false if the search should be abandoned.
+    #[inline(always)]
+    fn consume_budget(&mut self) -> bool {
+        if self.fuel == 0 {
+            return self.consume_budget_slow();
+        }
+        self.fuel -= 1;
+        true
+    }
+
+    #[cold]
+    fn consume_budget_slow(&mut self) -> bool {
+        if self.re.interrupt.as_ref().is_some_and(|i| i.is_set()) {
+            self.abandoned = Some(MatchError::Cancelled);
+            return false;
+        }
+        if self.memo_countdown == 0 {
+            // The search has taken long enough that it may be catastrophic.
+            self.memo.activate();
+            self.memo_countdown = usize::MAX;
+        }
+        if self.budget == 0 {
+            self.abandoned = Some(MatchError::BacktrackLimitExceeded);
+            return false;
+        }
+        self.refuel();
+        self.fuel -= 1;
+        true
+    }
+
+    /// Give up on the search, without backtracking. Capture groups are
+    /// restored as backtracking would have, so that only those set since the
+    /// backtrack stack was entered are affected.
+    fn abandon(&mut self) {
+        while self.bts.len() > 1 {
+            if let Some(BacktrackInsn::SetCaptureGroup { id, data }) = self.bts.pop() {
+                *self.s.groups.mat(id as usize) = data;
+            }
+        }
+    }
+
+    /// \return the entry of the memo table for the state at \p ip and \p pos,
+    /// or None if it is not memoized.
+    #[inline]
+    fn memo_entry(&self, input: &Input, ip: IP, pos: Input::Position) -> Option<usize> {
+        let plan = self.re.memo.as_ref()?;
+        let MemoPoint::State { slot, loops } = &plan.points[ip] else {
+            return None;
+        };
+        let mut key = 0;
+        for &(loop_id, most) in loops.iter() {
+            let ld = &self.s.loops[loop_id as usize];
+            let entered_here = ld.entry == pos;
+            key = key * (most + 1) * 2 + ld.iters.min(most) * 2 + entered_here as usize;
+        }
+        Some(input.pos_to_offset(pos) * plan.slots + slot + key)
+    }
+
+    /// Record that the state at \p ip and \p pos is being explored, if it is
+    /// memoized. Exploring a state either finds a match, ending the search,
+    /// or fails; so a state explored before has failed.
+    /// \return false if the state was explored before.
+    #[inline]
+    fn memoize_state(&mut self, input: &Input, ip: IP, pos: Input::Position) -> bool {
+        match self.memo_entry(input, ip, pos) {
+            Some(entry) => self.memo.insert(entry),
+            None => true,
         }
     }
 
@@ -123,6 +566,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
         pos: Input::Position,
         ip: IP,
     ) -> Option<IP> {
+        if !self.consume_budget() {
+            return None;
+        }
         let loop_data = &mut self.s.loops[loop_fields.loop_id as usize];
         let iteration = loop_data.iters;
 
@@ -389,7 +835,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
     ) -> Option<IP> {
         // For non-greedy loops, we can avoid computing the maximum match eagerly.
         // We'll only compute it when we need to set up backtracking.
-        let (min_pos, max_pos) = if greedy {
+        let (min_pos, max_pos) = if self.memo.active && max == usize::MAX {
+            self.run_memoized_scm_loop(input, dir, *pos, min, ip, greedy)?
+        } else if greedy {
             // For greedy loops, compute both min and max positions
             Self::with_scm_loop_impl(self.re, input, *pos, min, max, dir, ip)?
         } else {
@@ -441,8 +889,49 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
         Some(continuation)
     }
 
-    // Run a lookaround instruction, which is either forwards or backwards
-    // (according to Direction). The half-open range
+    // Drive an unbounded single character loop from \p pos, memoizing its
+    // state at each position past the minimum. The scan stops before the
+    // first position whose state was explored before, as the loop has already
+    // failed from there. A greedy loop explores the positions it scans from
+    // the last, before anything else may reach them, so they are memoized as
+    // they are scanned. A non-greedy loop does not scan, and memoizes each
+    // position as it advances to it.
+    // \return the position (min, max), or None on failure.
+    fn run_memoized_scm_loop<Dir: Direction>(
+        &mut self,
+        input: &Input,
+        dir: Dir,
+        pos: Input::Position,
+        min: usize,
+        ip: IP,
+        greedy: bool,
+    ) -> Option<(Input::Position, Input::Position)> {
+        let (min_pos, _) = Self::with_scm_loop_impl(self.re, input, pos, min, min, dir, ip)?;
+        if !self.memoize_state(input, ip, min_pos) {
+            return None;
+        }
+        if !greedy {
+            // Rather than scan ahead, bound the loop by the end of the input.
+            // Backtracking matches the body as it advances.
+            let end = if Dir::FORWARD {
+                input.right_end()
+            } else {
+                input.left_end()
+            };
+            return Some((min_pos, end));
+        }
+        let mut max_pos = min_pos;
+        while let Some(next) = Self::with_scm_compute_max(self.re, input, max_pos, 1, dir, ip) {
+            if next == max_pos || !self.memoize_state(input, ip, next) {
+                break;
+            }
+            max_pos = next;
+        }
+        Some((min_pos, max_pos))
+    }
+
+    // Run the lookaround instruction at ip, which is either forwards or
+    // backwards (according to Direction). The half-open range
     // start_group..end_group is the range of contained capture groups.
     // \return whether we matched and negate was false, or did not match but negate
     // is true.
@@ -455,23 +944,66 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
         end_group: CaptureGroupID,
         negate: bool,
     ) -> bool {
+        // If there are no groups to report, the result may be memoized.
+        let memo_entry = match &self.re.memo {
+            Some(plan) if self.memo.active => match plan.points[ip] {
+                MemoPoint::Lookaround { slot, has_groups }
+                    if !has_groups || !self.track_captures =>
+                {
+                    Some(input.pos_to_offset(pos) * plan.slots + slot)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(entry) = memo_entry {
+            if self.memo.contains(entry) {
+                return self.memo.contains(entry + 1) != negate;
+            }
+        }
+        let memo_mark = self.memo.enter_lookaround();
+
         // Copy capture groups, because if the match fails (or if we are inverted)
-        // we need to restore these.
-        let range = (start_group as usize)..(end_group as usize);
-        // TODO: consider retaining storage here?
+        // we need to restore these. There is nothing to copy if we are not
+        // tracking captures.
+        let range = if self.track_captures {
+            (start_group as usize)..(end_group as usize)
+        } else {
+            0..0
+        };
         // Temporarily defeat backtracking.
-        let saved_groups = self.s.groups.iat(range.clone()).to_vec();
+        let mut saved_groups = self
+            .spare_groups
+            .pop()
+            .map_or_else(Vec::new, SpareVec::into_vec);
+        saved_groups.extend_from_slice(self.s.groups.iat(range.clone()));
 
         // Start with an "empty" backtrack stack.
-        // TODO: consider using a stack-allocated array.
-        let mut saved_bts = vec![BacktrackInsn::Exhausted];
+        let mut saved_bts = self
+            .spare_bts
+            .pop()
+            .map_or_else(Vec::new, SpareVec::into_vec);
+        saved_bts.push(BacktrackInsn::Exhausted);
         core::mem::swap(&mut self.bts, &mut saved_bts);
 
         // Enter into the lookaround's instruction stream.
-        let matched = self.try_at_pos(*input, ip, pos, Dir::new()).is_some();
+        let matched = self.try_at_pos(*input, ip + 1, pos, Dir::new()).is_some();
+
+        // The states explored on the way to a match did not fail, so forget
+        // them. Record the result if it may be memoized.
+        self.memo.exit_lookaround(memo_mark, matched);
+        if let Some(entry) = memo_entry {
+            if self.abandoned.is_none() {
+                self.memo.insert(entry);
+                if matched {
+                    self.memo.insert(entry + 1);
+                }
+            }
+        }
 
         // Put back our bts.
         core::mem::swap(&mut self.bts, &mut saved_bts);
+        self.spare_bts.push(SpareVec::new(saved_bts));
 
         // If we are a positive lookahead that successfully matched, retain the
         // capture groups (but we need to set up backtracking). Otherwise restore
@@ -485,8 +1017,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                 });
             }
         } else {
-            self.s.groups.splice(range, saved_groups);
+            self.s.groups.mat(range).copy_from_slice(&saved_groups);
         }
+        self.spare_groups.push(SpareVec::new(saved_groups));
         matched != negate
     }
 
@@ -497,8 +1030,12 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
         input: &Input,
         ip: &mut IP,
         pos: &mut Input::Position,
-        _dir: Dir,
+        dir: Dir,
     ) -> bool {
+        if !self.consume_budget() {
+            self.abandon();
+            return false;
+        }
         loop {
             // We always have a single Exhausted instruction backstopping our stack,
             // so we do not need to check for empty bts.
@@ -605,6 +1142,30 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                         self.bts.pop();
                         continue;
                     }
+                    if self.memo.active {
+                        // The max of a memoized loop may be the end of the
+                        // input, so match the body to advance; and the loop
+                        // may have been explored from there.
+                        let loop_ip = *continuation - 2;
+                        let newmin =
+                            Self::with_scm_compute_max(self.re, input, *min, 1, dir, loop_ip);
+                        match newmin {
+                            Some(newmin) if newmin != *min => {
+                                *pos = newmin;
+                                *min = newmin;
+                                *ip = *continuation;
+                            }
+                            _ => {
+                                self.bts.pop();
+                                continue;
+                            }
+                        }
+                        if !self.memoize_state(input, loop_ip, *pos) {
+                            self.bts.pop();
+                            continue;
+                        }
+                        return true;
+                    }
                     // Move in the direction of the cursor.
                     let newmin = if Dir::FORWARD {
                         input.next_right_pos(*min)
@@ -624,6 +1185,61 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
         }
     }
 
+    /// Attempt to match the program at \p pos, running its machine code if
+    /// it has any.
+    /// \return the end of the match, or None on failure.
+    fn try_at_start(&mut self, input: Input, pos: Input::Position) -> Option<Input::Position> {
+        #[cfg(feature = "backend-jit")]
+        if let Some(end) = self.try_jit(&input, pos) {
+            return end;
+        }
+        self.try_at_pos(input, 0, pos, Forward::new())
+    }
+
+    /// Attempt to match exactly at \p pos with the machine code of our regex,
+    /// setting the capture groups on success.
+    /// \return the end of the match or None on failure, or None if the regex
+    /// was not compiled to machine code, \p input is not UTF-8 or the match
+    /// gave up.
+    #[cfg(feature = "backend-jit")]
+    fn try_jit(&mut self, input: &Input, pos: Input::Position) -> Option<Option<Input::Position>> {
+        let re = self.re;
+        let jit = re.jit.as_deref()?;
+        let text = input.utf8_text()?;
+        let to_pos = |offset| input.try_move_right(input.left_end(), offset);
+        let start = input.pos_to_offset(pos);
+        let Some(end) = jit.find_at(&re.brackets, text, start, &mut self.jit_buf)? else {
+            return Some(None);
+        };
+        if self.track_captures {
+            for (idx, gd) in self.s.groups.iter_mut().enumerate() {
+                let (start, end) = jit.group(&self.jit_buf, idx);
+                gd.start = start.and_then(to_pos);
+                gd.end = end.and_then(to_pos);
+            }
+        }
+        Some(to_pos(end))
+    }
+
+    /// Attempt to match exactly at \p pos, with the one-pass matcher
+    /// \p onepass for our regex.
+    /// \return the end of the match, or None on failure.
+    fn try_onepass(
+        &mut self,
+        onepass: &OnePass,
+        input: &Input,
+        pos: Input::Position,
+    ) -> Option<Input::Position> {
+        let mut loops = core::mem::take(&mut self.s.loops);
+        let mut groups = core::mem::take(&mut self.s.groups);
+        let end = onepass.find_at(self.re, input, pos, &mut groups, &mut loops, || {
+            self.consume_budget()
+        });
+        self.s.loops = loops;
+        self.s.groups = groups;
+        end
+    }
+
     /// Attempt to match at a given IP and position.
     fn try_at_pos<Dir: Direction>(
         &mut self,
@@ -801,6 +1417,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     &Insn::BeginCaptureGroup(cg_idx) => {
+                        if !self.track_captures {
+                            next_or_bt!(true)
+                        }
                         let cg = self.s.groups.mat(cg_idx as usize);
                         self.bts.push(BacktrackInsn::SetCaptureGroup {
                             id: cg_idx,
@@ -823,6 +1442,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     &Insn::EndCaptureGroup(cg_idx) => {
+                        if !self.track_captures {
+                            next_or_bt!(true)
+                        }
                         let cg = self.s.groups.mat(cg_idx as usize);
                         if Dir::FORWARD {
                             debug_assert!(
@@ -841,6 +1463,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     &Insn::ResetCaptureGroup(cg_idx) => {
+                        if !self.track_captures {
+                            next_or_bt!(true)
+                        }
                         let cg = self.s.groups.mat(cg_idx as usize);
                         self.bts.push(BacktrackInsn::SetCaptureGroup {
                             id: cg_idx,
@@ -879,14 +1504,21 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                         end_group,
                         continuation,
                     } => {
-                        if self.run_lookaround::<Forward>(
+                        let matched = self.run_lookaround::<Forward>(
                             input,
-                            ip + 1,
+                            ip,
                             pos,
                             start_group,
                             end_group,
                             negate,
-                        ) {
+                        );
+                        // A lookaround which was abandoned neither matched nor
+                        // failed, so the attempt cannot continue.
+                        if self.abandoned.is_some() {
+                            self.abandon();
+                            return None;
+                        }
+                        if matched {
                             ip = continuation as IP;
                             continue 'nextinsn;
                         } else {
@@ -900,14 +1532,21 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                         end_group,
                         continuation,
                     } => {
-                        if self.run_lookaround::<Backward>(
+                        let matched = self.run_lookaround::<Backward>(
                             input,
-                            ip + 1,
+                            ip,
                             pos,
                             start_group,
                             end_group,
                             negate,
-                        ) {
+                        );
+                        // A lookaround which was abandoned neither matched nor
+                        // failed, so the attempt cannot continue.
+                        if self.abandoned.is_some() {
+                            self.abandon();
+                            return None;
+                        }
+                        if matched {
                             ip = continuation as IP;
                             continue 'nextinsn;
                         } else {
@@ -916,6 +1555,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     &Insn::Alt { secondary } => {
+                        if self.memo.active && !self.memoize_state(input, ip, pos) {
+                            break 'backtrack;
+                        }
                         self.push_backtrack(BacktrackInsn::SetPosition {
                             ip: secondary as IP,
                             pos,
@@ -924,8 +1566,21 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     Insn::EnterLoop(fields) => {
-                        // Entering a loop, not re-entering it.
-                        self.s.loops.mat(fields.loop_id as usize).iters = 0;
+                        if self.memo.active && !self.memoize_state(input, ip, pos) {
+                            break 'backtrack;
+                        }
+                        // Entering a loop, not re-entering it. The loop may be
+                        // nested in another, whose earlier iterations we may
+                        // backtrack into; they expect to see their own count.
+                        let loop_data = self.s.loops.mat(fields.loop_id as usize);
+                        if loop_data.iters != 0 {
+                            let data = *loop_data;
+                            loop_data.iters = 0;
+                            self.push_backtrack(BacktrackInsn::SetLoopData {
+                                id: fields.loop_id,
+                                data,
+                            });
+                        }
                         match self.run_loop(fields, pos, ip) {
                             Some(next_ip) => {
                                 ip = next_ip;
@@ -938,6 +1593,9 @@ impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
                     }
 
                     &Insn::LoopAgain { begin } => {
+                        if self.memo.active && !self.memoize_state(input, ip, pos) {
+                            break 'backtrack;
+                        }
                         let act = match re.insns.iat(begin as IP) {
                             Insn::EnterLoop(fields) => self.run_loop(fields, pos, begin as IP),
                             _ => rs_unreachable!("EnterLoop should always refer to loop field"),
@@ -1005,11 +1663,15 @@ pub struct BacktrackExecutor<'r, Input: InputIndexer> {
     matcher: MatchAttempter<'r, Input>,
 }
 
-#[cfg(feature = "utf16")]
 impl<'r, Input: InputIndexer> BacktrackExecutor<'r, Input> {
     pub(crate) fn new(input: Input, matcher: MatchAttempter<'r, Input>) -> Self {
         Self { input, matcher }
     }
+
+    /// Give up the matcher's buffers so that they may be reused.
+    pub(crate) fn into_scratch(self) -> Scratch {
+        self.matcher.into_scratch()
+    }
 }
 
 impl<Input: InputIndexer> BacktrackExecutor<'_, Input> {
@@ -1037,39 +1699,48 @@ impl<Input: InputIndexer> BacktrackExecutor<'_, Input> {
         }
     }
 
-    /// \return the next match for an anchored regex that only matches at the start.
-    /// This avoids any string searching and only tries matching at the given position.
-    #[cfg(not(feature = "utf16"))]
-    fn next_match_anchored(
+    /// Convert a matched range to a Match, and set `next_start` to the
+    /// position to start looking for the next match.
+    fn complete_match(
         &mut self,
-        pos: Input::Position,
+        (start, end): (Input::Position, Input::Position),
         next_start: &mut Option<Input::Position>,
-    ) -> Option<Match> {
-        let inp = self.input;
-        // For anchored regexes, only try matching at the current position
-        if let Some(end) = self.matcher.try_at_pos(inp, 0, pos, Forward::new()) {
-            // If we matched the empty string, we have to increment.
-            if end != pos {
-                *next_start = Some(end)
-            } else {
-                *next_start = inp.next_right_pos(end);
-            }
-            Some(self.successful_match(pos, end))
+    ) -> Match {
+        // If we matched the empty string, we have to increment.
+        if end != start {
+            *next_start = Some(end)
         } else {
-            // Anchored regex failed to match at this position, no more matches
-            None
+            *next_start = self.input.next_right_pos(end);
         }
+        self.successful_match(start, end)
     }
 
-    /// \return the next match, searching the remaining bytes using the given
-    /// prefix searcher to quickly find the first potential match location.
-    fn next_match_with_prefix_search<PrefixSearch: bytesearch::ByteSearcher>(
+    /// \return the start and end of a match exactly at the given position.
+    /// This avoids any string searching.
+    fn find_range_anchored(
+        &mut self,
+        pos: Input::Position,
+    ) -> Option<(Input::Position, Input::Position)> {
+        self.matcher.reset_budget(&self.input);
+        // The one-pass matcher needs no backtracking at all, so prefer it even
+        // to machine code.
+        let end = match self.matcher.re.onepass.as_deref() {
+            Some(onepass) => self.matcher.try_onepass(onepass, &self.input, pos),
+            None => self.matcher.try_at_start(self.input, pos),
+        }?;
+        Some((pos, end))
+    }
+
+    /// \return the start and end of the next match, searching the remaining
+    /// bytes using the given prefix searcher to quickly find the first
+    /// potential match location.
+    fn find_range_with_prefix_search<PrefixSearch: bytesearch::ByteSearcher>(
         &mut self,
         mut pos: Input::Position,
-        next_start: &mut Option<Input::Position>,
         prefix_search: &PrefixSearch,
-    ) -> Option<Match> {
+    ) -> Option<(Input::Position, Input::Position)> {
         let inp = self.input;
+        self.matcher.reset_budget(&self.input);
         loop {
             // Find the next start location, or None if none.
             // Don't try this unless CODE_UNITS_ARE_BYTES - i.e. don't do byte searches
@@ -1077,19 +1748,88 @@ impl<Input: InputIndexer> BacktrackExecutor<'_, Input> {
             if Input::CODE_UNITS_ARE_BYTES {
                 pos = inp.find_bytes(pos, prefix_search)?;
             }
-            if let Some(end) = self.matcher.try_at_pos(inp, 0, pos, Forward::new()) {
-                // If we matched the empty string, we have to increment.
-                if end != pos {
-                    *next_start = Some(end)
-                } else {
-                    *next_start = inp.next_right_pos(end);
-                }
-                return Some(self.successful_match(pos, end));
+            if let Some(end) = self.matcher.try_at_start(inp, pos) {
+                return Some((pos, end));
+            }
+            if self.matcher.abandoned.is_some() {
+                return None;
             }
             // Didn't find it at this position, try the next one.
             pos = inp.next_right_pos(pos)?;
         }
     }
+
+    /// \return the start and end of the next match at or after `pos`.
+    fn find_range(&mut self, pos: Input::Position) -> Option<(Input::Position, Input::Position)> {
+        if self.matcher.re.flags.sticky {
+            return self.find_range_anchored(pos);
+        }
+
+        // When UTF-16 support is active prefix search is not used due to the different encoding.
+        #[cfg(feature = "utf16")]
+        return match self.matcher.re.start_pred {
+            StartPredicate::StartAnchored => self.find_range_anchored(pos),
+            _ => self.find_range_with_prefix_search(pos, &bytesearch::EmptyString {}),
+        };
+
+        #[cfg(not(feature = "utf16"))]
+        match &self.matcher.re.start_pred {
+            StartPredicate::Arbitrary => {
+                self.find_range_with_prefix_search(pos, &bytesearch::EmptyString {})
+            }
+            StartPredicate::StartAnchored => self.find_range_anchored(pos),
+            StartPredicate::ByteSet1(bytes) => self.find_range_with_prefix_search(pos, bytes),
+            StartPredicate::ByteSet2(bytes) => self.find_range_with_prefix_search(pos, bytes),
+            StartPredicate::ByteSet3(bytes) => self.find_range_with_prefix_search(pos, bytes),
+            StartPredicate::ByteSeq(bytes) => {
+                self.find_range_with_prefix_search(pos, bytes.as_ref())
+            }
+            StartPredicate::ByteBracket(bitmap) => self.find_range_with_prefix_search(pos, bitmap),
+            #[cfg(feature = "perf-literal")]
+            StartPredicate::LiteralSet(set) => {
+                self.find_range_with_prefix_search(pos, set.as_ref())
+            }
+            StartPredicate::InnerLiteral(literal) => {
+                self.find_range_with_prefix_search(pos, &literal.searcher())
+            }
+        }
+    }
+
+    /// Search for a match at or after `pos`, writing the offsets of the match
+    /// to `slots[0]` and of each capture group to the following slots.
+    /// If there is no match, every slot is set to None.
+    /// \return whether we matched.
+    pub(crate) fn read_captures_from(
+        &mut self,
+        pos: Input::Position,
+        slots: &mut [Option<Range<usize>>],
+    ) -> bool {
+        debug_assert!(slots.len() == self.matcher.re.groups as usize + 1);
+        let Some((start, end)) = self.find_range(pos) else {
+            slots.fill(None);
+            return false;
+        };
+        let input = self.input;
+        slots[0] = Some(input.pos_to_offset(start)..input.pos_to_offset(end));
+        if !self.matcher.track_captures {
+            slots[1..].fill(None);
+            return true;
+        }
+        for (slot, gd) in slots[1..].iter_mut().zip(self.matcher.s.groups.iter_mut()) {
+            *slot = gd
+                .as_range()
+                .map(|r| input.pos_to_offset(r.start)..input.pos_to_offset(r.end));
+            gd.reset();
+        }
+        true
+    }
+
+    /// \return whether there is a match at or after `pos`.
+    /// No Match is constructed. If the matcher was created via
+    /// `MatchAttempter::match_only_with_scratch`, capture groups are not tracked either.
+    pub(crate) fn is_match_from(&mut self, pos: Input::Position) -> bool {
+        self.find_range(pos).is_some()
+    }
 }
 
 impl<Input: InputIndexer> exec::MatchProducer for BacktrackExecutor<'_, Input> {
@@ -1104,32 +1844,21 @@ impl<Input: InputIndexer> exec::MatchProducer for BacktrackExecutor<'_, Input> {
         pos: Input::Position,
         next_start: &mut Option<Input::Position>,
     ) -> Option<Match> {
-        // When UTF-16 support is active prefix search is not used due to the different encoding.
-        #[cfg(feature = "utf16")]
-        return self.next_match_with_prefix_search(pos, next_start, &bytesearch::EmptyString {});
+        let range = self.find_range(pos)?;
+        Some(self.complete_match(range, next_start))
+    }
 
-        #[cfg(not(feature = "utf16"))]
-        match &self.matcher.re.start_pred {
-            StartPredicate::Arbitrary => {
-                self.next_match_with_prefix_search(pos, next_start, &bytesearch::EmptyString {})
-            }
-            StartPredicate::StartAnchored => self.next_match_anchored(pos, next_start),
-            StartPredicate::ByteSet1(bytes) => {
-                self.next_match_with_prefix_search(pos, next_start, bytes)
-            }
-            StartPredicate::ByteSet2(bytes) => {
-                self.next_match_with_prefix_search(pos, next_start, bytes)
-            }
-            StartPredicate::ByteSet3(bytes) => {
-                self.next_match_with_prefix_search(pos, next_start, bytes)
-            }
-            StartPredicate::ByteSeq(bytes) => {
-                self.next_match_with_prefix_search(pos, next_start, bytes.as_ref())
-            }
-            StartPredicate::ByteBracket(bitmap) => {
-                self.next_match_with_prefix_search(pos, next_start, bitmap)
-            }
-        }
+    fn next_match_anchored(
+        &mut self,
+        pos: Input::Position,
+        next_start: &mut Option<Input::Position>,
+    ) -> Option<Match> {
+        let range = self.find_range_anchored(pos)?;
+        Some(self.complete_match(range, next_start))
+    }
+
+    fn abandoned(&self) -> Option<MatchError> {
+        self.matcher.abandoned
     }
 }
 
@@ -1,15 +1,20 @@
 //! Regex compiler back-end: transforms IR into a CompiledRegex
 
 use crate::bytesearch::{AsciiBitmap, ByteArraySet};
+use crate::engine::Engine;
 use crate::insn::{CompiledRegex, Insn, LoopFields, MAX_BYTE_SEQ_LENGTH, MAX_CHAR_SET_LENGTH};
 use crate::ir;
 use crate::ir::Node;
+use crate::lazydfa;
 #[cfg(not(feature = "utf16"))]
 use crate::literal::lower_code_point_sequence;
+use crate::onepass::OnePass;
+use crate::requiredliterals;
 use crate::startpredicate;
 use crate::types::{BracketContents, CaptureGroupID, LoopID};
 #[cfg(feature = "utf16")]
 use crate::unicode;
+use crate::util::Pool;
 #[cfg(not(feature = "std"))]
 use alloc::{boxed::Box, vec::Vec};
 use core::convert::TryInto;
@@ -397,6 +402,7 @@ impl Emitter {
                     &Node::BackRef { group, icase } => {
                         debug_assert!(group >= 1, "Group should not be zero");
                         // -1 because \1 matches the first capture group, which has index 0.
+                        self.result.has_backrefs = true;
                         self.emit_insn(Insn::BackRef {
                             group: group - 1,
                             icase,
@@ -452,6 +458,18 @@ pub fn emit(n: &ir::Regex) -> CompiledRegex {
             loops: 0,
             groups: 0,
             group_names: Box::new([]),
+            has_backrefs: false,
+            match_len: n.match_len(),
+            literals: requiredliterals::required_literals(n),
+            nfa: lazydfa::Nfa::new(n).map(Box::new),
+            backtrack_limit: None,
+            interrupt: None,
+            engine: Engine::Backtrack,
+            memo: None,
+            onepass: None,
+            #[cfg(feature = "backend-jit")]
+            jit: None,
+            pool: Pool::default(),
             flags: n.flags,
             start_pred: startpredicate::predicate_for_re(n),
         },
@@ -469,5 +487,6 @@ pub fn emit(n: &ir::Regex) -> CompiledRegex {
     debug_assert!(
         result.group_names.is_empty() || result.group_names.len() == result.groups as usize
     );
+    result.onepass = OnePass::new(&result).map(Box::new);
     result
 }
@@ -0,0 +1,214 @@
+//! Selection of the engine which resolves matches.
+
+use crate::api::{Match, MatchError};
+use crate::cache::Cache;
+use crate::classicalbacktrack::{BacktrackExecutor, MatchAttempter};
+use crate::exec::{self, MatchProducer};
+use crate::indexing::{AsciiInput, InputIndexer, Utf8Input};
+use crate::insn::CompiledRegex;
+#[cfg(feature = "backend-pikevm")]
+use crate::pikevm::PikeVMExecutor;
+use core::ops::Range;
+
+/// The engine used to search with a [`Regex`](crate::Regex), chosen with
+/// [`RegexBuilder::engine`](crate::RegexBuilder::engine).
+///
+/// Every engine finds the same matches. Where the regex permits, the bounds
+/// of a match are found by a lazy DFA whichever engine is chosen; the engine
+/// searches where the DFA cannot, and resolves the capture groups.
+///
+/// This is non-exhaustive: some engines exist only with a crate feature, and
+/// more may be added.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Engine {
+    /// The backtracking engine. This is fast for most patterns, but may take
+    /// time exponential in the length of the input for patterns prone to
+    /// catastrophic backtracking, such as `(a+)+$`.
+    #[default]
+    Backtrack,
+
+    /// The backtracking engine, memoizing the states which failed to match
+    /// so that none is explored twice. For patterns without backreferences
+    /// this bounds the work of a search by the product of the lengths of the
+    /// pattern and the input, at the cost of a table of that size. Patterns
+    /// with backreferences are backtracked without memoization, as are loops
+    /// with very large bounds; lookarounds containing capture groups are
+    /// rerun wherever they are reached.
+    MemoizedBacktrack,
+
+    /// The PikeVM, which runs the alternatives of a match in lockstep. It
+    /// takes time linear in the length of the input for patterns without
+    /// backreferences or lookarounds, at a constant factor over the
+    /// backtracker. It ignores the backtrack limit.
+    #[cfg(feature = "backend-pikevm")]
+    PikeVM,
+
+    /// The PikeVM if [`analysis::redos_risk`](crate::analysis::redos_risk)
+    /// finds the pattern prone to catastrophic backtracking, or the memoizing
+    /// backtracker without the `backend-pikevm` feature. Otherwise the
+    /// backtracker, which starts memoizing if a search takes as many steps
+    /// as the memoizing backtracker could need.
+    Auto,
+
+    /// The backtracking engine, compiled to machine code with Cranelift. It
+    /// finds the same matches as [`Engine::Backtrack`], though the regex
+    /// takes much longer to build. It can be faster for patterns which
+    /// backtrack through short matches, such as over each line of a text, and
+    /// is otherwise about as fast as the interpreter; see `benches/jit.rs`.
+    /// Patterns with backreferences or lookarounds are interpreted, as are
+    /// searches of ASCII, UTF-16 or UCS-2 text, those which backtrack deeply,
+    /// and those of a regex with a backtrack limit or an interrupt. Anchored
+    /// searches of a one-pass pattern use the one-pass matcher instead.
+    ///
+    /// This exists only with the `backend-jit` feature. Since `Engine` is
+    /// non-exhaustive, code which matches on it is unaffected by the feature.
+    #[cfg(feature = "backend-jit")]
+    Jit,
+}
+
+/// An executor which searches with the engine chosen for a regex.
+#[derive(Debug)]
+pub enum EngineExecutor<'r, Input: InputIndexer> {
+    Backtrack(BacktrackExecutor<'r, Input>),
+    #[cfg(feature = "backend-pikevm")]
+    PikeVM(PikeVMExecutor<'r, Input>),
+}
+
+impl<'r, Input: InputIndexer> EngineExecutor<'r, Input> {
+    /// Construct an executor for \p re searching \p input.
+    pub(crate) fn new(re: &'r CompiledRegex, input: Input) -> Self {
+        Self::with_cache(re, input, &mut Cache::default())
+    }
+
+    /// Construct an executor for \p re searching \p input, taking the buffers
+    /// of its engine from \p cache.
+    pub(crate) fn with_cache(re: &'r CompiledRegex, input: Input, cache: &mut Cache) -> Self {
+        match re.engine {
+            #[cfg(feature = "backend-pikevm")]
+            Engine::PikeVM => {
+                let scratch = core::mem::take(&mut cache.pikevm);
+                Self::PikeVM(PikeVMExecutor::with_scratch(re, input, scratch))
+            }
+            _ => {
+                let scratch = core::mem::take(&mut cache.backtrack);
+                let matcher = MatchAttempter::with_scratch(re, input.left_end(), scratch);
+                Self::Backtrack(BacktrackExecutor::new(input, matcher))
+            }
+        }
+    }
+
+    /// Construct an executor for \p re searching \p input, which only
+    /// determines whether a match exists, and not the capture groups, taking
+    /// the buffers of its engine from \p cache.
+    pub(crate) fn match_only_with_cache(
+        re: &'r CompiledRegex,
+        input: Input,
+        cache: &mut Cache,
+    ) -> Self {
+        match re.engine {
+            // The PikeVM tracks captures regardless.
+            #[cfg(feature = "backend-pikevm")]
+            Engine::PikeVM => Self::with_cache(re, input, cache),
+            _ => {
+                let scratch = core::mem::take(&mut cache.backtrack);
+                let matcher =
+                    MatchAttempter::match_only_with_scratch(re, input.left_end(), scratch);
+                Self::Backtrack(BacktrackExecutor::new(input, matcher))
+            }
+        }
+    }
+
+    /// Return the buffers of our engine to \p cache.
+    pub(crate) fn into_cache(self, cache: &mut Cache) {
+        match self {
+            Self::Backtrack(executor) => cache.backtrack = executor.into_scratch(),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => cache.pikevm = executor.into_scratch(),
+        }
+    }
+
+    /// Search for a match at or after `pos`, writing the offsets of the match
+    /// to `slots[0]` and of each capture group to the following slots.
+    /// \return whether we matched.
+    pub(crate) fn read_captures_from(
+        &mut self,
+        pos: Input::Position,
+        slots: &mut [Option<Range<usize>>],
+    ) -> bool {
+        match self {
+            Self::Backtrack(executor) => executor.read_captures_from(pos, slots),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.read_captures_from(pos, slots),
+        }
+    }
+
+    /// \return whether there is a match at or after `pos`.
+    pub(crate) fn is_match_from(&mut self, pos: Input::Position) -> bool {
+        match self {
+            Self::Backtrack(executor) => executor.is_match_from(pos),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.is_match_from(pos),
+        }
+    }
+}
+
+impl<Input: InputIndexer> MatchProducer for EngineExecutor<'_, Input> {
+    type Position = Input::Position;
+
+    fn initial_position(&self, offset: usize) -> Option<Self::Position> {
+        match self {
+            Self::Backtrack(executor) => executor.initial_position(offset),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.initial_position(offset),
+        }
+    }
+
+    fn next_match(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match> {
+        match self {
+            Self::Backtrack(executor) => executor.next_match(pos, next_start),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.next_match(pos, next_start),
+        }
+    }
+
+    fn next_match_anchored(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match> {
+        match self {
+            Self::Backtrack(executor) => executor.next_match_anchored(pos, next_start),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.next_match_anchored(pos, next_start),
+        }
+    }
+
+    fn abandoned(&self) -> Option<MatchError> {
+        match self {
+            Self::Backtrack(executor) => executor.abandoned(),
+            #[cfg(feature = "backend-pikevm")]
+            Self::PikeVM(executor) => executor.abandoned(),
+        }
+    }
+}
+
+impl<'r, 't> exec::Executor<'r, 't> for EngineExecutor<'r, Utf8Input<'t>> {
+    type AsAscii = EngineExecutor<'r, AsciiInput<'t>>;
+
+    fn new(re: &'r CompiledRegex, text: &'t str) -> Self {
+        Self::new(re, Utf8Input::new(text, re.flags.unicode))
+    }
+}
+
+impl<'r, 't> exec::Executor<'r, 't> for EngineExecutor<'r, AsciiInput<'t>> {
+    type AsAscii = EngineExecutor<'r, AsciiInput<'t>>;
+
+    fn new(re: &'r CompiledRegex, text: &'t str) -> Self {
+        Self::new(re, AsciiInput::new(text, re.flags.unicode))
+    }
+}
@@ -1,8 +1,37 @@
 //! Execution engine bits.
 
-use crate::api::Match;
+use crate::api::{Match, MatchError};
 use crate::insn::CompiledRegex;
 use crate::position::PositionType;
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+/// The number of steps between polls of an interrupt.
+/// For the backtracker a step is a backtrack or loop iteration; for the
+/// PikeVM it is a position of the input, including positions skipped by
+/// prefix search.
+pub const INTERRUPT_POLL_INTERVAL: usize = 1024;
+
+/// A hook which is polled during searches, and returns true to cancel.
+#[derive(Clone)]
+pub struct Interrupt(pub Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl Interrupt {
+    /// \return whether the search should be cancelled.
+    #[inline]
+    pub fn is_set(&self) -> bool {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Interrupt")
+    }
+}
 
 /// A trait for finding the next match in a regex.
 /// This is broken out from Executor to avoid needing to thread lifetimes
@@ -22,6 +51,35 @@ pub trait MatchProducer: core::fmt::Debug {
         pos: Self::Position,
         next_start: &mut Option<Self::Position>,
     ) -> Option<Match>;
+
+    /// Attempt to match exactly at the given location, without searching
+    /// forwards. This implements "sticky" matching.
+    /// \return either the Match and the position to start looking for the next
+    /// match, or None on failure.
+    fn next_match_anchored(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match>;
+
+    /// \return why the most recent search was abandoned, if it was. If so,
+    /// its failure to match is not meaningful.
+    fn abandoned(&self) -> Option<MatchError>;
+}
+
+/// Attempt a match exactly at the offset `start`, without searching.
+/// \return the match, or an error if the search was abandoned.
+pub fn try_find_anchored<Producer: MatchProducer>(
+    mut mp: Producer,
+    start: usize,
+) -> Result<Option<Match>, MatchError> {
+    let Some(pos) = mp.initial_position(start) else {
+        return Ok(None);
+    };
+    match mp.next_match_anchored(pos, &mut None) {
+        None => mp.abandoned().map_or(Ok(None), Err),
+        m => Ok(m),
+    }
 }
 
 /// A trait for executing a regex.
@@ -54,3 +112,34 @@ impl<Producer: MatchProducer> Iterator for Matches<Producer> {
         self.mp.next_match(pos, &mut self.position)
     }
 }
+
+/// A struct which enables iteration over matches, reporting searches which
+/// were abandoned. Iteration stops after the first error.
+#[derive(Debug)]
+pub struct TryMatches<Producer: MatchProducer> {
+    mp: Producer,
+    position: Option<Producer::Position>,
+}
+
+impl<Producer: MatchProducer> TryMatches<Producer> {
+    pub fn new(mp: Producer, start: usize) -> Self {
+        let position = mp.initial_position(start);
+        TryMatches { mp, position }
+    }
+}
+
+impl<Producer: MatchProducer> Iterator for TryMatches<Producer> {
+    type Item = Result<Match, MatchError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.position?;
+        match self.mp.next_match(pos, &mut self.position) {
+            Some(m) => Some(Ok(m)),
+            None => {
+                self.position = None;
+                self.mp.abandoned().map(Err)
+            }
+        }
+    }
+}
+
+impl<Producer: MatchProducer> core::iter::FusedIterator for TryMatches<Producer> {}
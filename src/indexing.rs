@@ -98,6 +98,12 @@ where
     /// \return a position at the left end of this input.
     fn left_end(&self) -> Self::Position;
 
+    /// \return the text of this input, if it is decoded as UTF-8.
+    #[cfg(feature = "backend-jit")]
+    fn utf8_text(&self) -> Option<&str> {
+        None
+    }
+
     /// \return a position at the right end of this input.
     fn right_end(&self) -> Self::Position;
 
@@ -280,6 +286,11 @@ impl<'a> InputIndexer for Utf8Input<'a> {
         self.unicode
     }
 
+    #[cfg(feature = "backend-jit")]
+    fn utf8_text(&self) -> Option<&str> {
+        Some(self.input)
+    }
+
     #[inline(always)]
     fn subinput(&self, range: ops::Range<Self::Position>) -> Self {
         Self::new(self.str_slice(range), self.unicode)
@@ -4,8 +4,22 @@
 use alloc::{boxed::Box, vec::Vec};
 
 use crate::api;
+#[cfg(feature = "perf-literal")]
+use crate::bytesearch::LiteralSet;
 use crate::bytesearch::{AsciiBitmap, ByteArraySet, ByteBitmap};
+use crate::cache::Cache;
+use crate::classicalbacktrack::MemoPlan;
+use crate::engine::Engine;
+use crate::exec::Interrupt;
+use crate::ir;
+#[cfg(feature = "backend-jit")]
+use crate::jit::JitProgram;
+use crate::lazydfa::Nfa;
+use crate::onepass::OnePass;
+use crate::requiredliterals::RequiredLiterals;
+use crate::startpredicate::InnerLiteral;
 use crate::types::{BracketContents, CaptureGroupID, LoopID};
+use crate::util::Pool;
 extern crate memchr;
 use memchr::memmem;
 
@@ -181,6 +195,14 @@ pub enum StartPredicate {
     /// Look for a byte which matches the bitmap.
     ByteBracket(ByteBitmap),
 
+    /// Look for any of a set of byte sequences.
+    #[cfg(feature = "perf-literal")]
+    LiteralSet(Box<LiteralSet>),
+
+    /// Look for a literal which every match contains, which may be preceded
+    /// by a bounded or unbounded number of bytes.
+    InnerLiteral(Box<InnerLiteral>),
+
     /// The regex is anchored to the start of the line/string.
     /// This avoids string searching entirely and only tries matching at the beginning.
     StartAnchored,
@@ -209,6 +231,47 @@ pub struct CompiledRegex {
     //     group names in order. Groups without names have an empty string.
     pub group_names: Box<[Box<str>]>,
 
+    // Whether any backreference exists. If not, capture groups do not affect
+    // whether we match, only what we report.
+    pub has_backrefs: bool,
+
+    // Bounds on the length of a match, or None if the regex never matches.
+    pub match_len: Option<ir::MatchLen>,
+
+    // Literals which every match must contain.
+    pub literals: RequiredLiterals,
+
+    // The NFA from which a lazy DFA finds the bounds of matches, or None if
+    // the regex is not supported by it.
+    pub nfa: Option<Box<Nfa>>,
+
+    // The maximum number of backtracks and loop iterations permitted in a
+    // search for a single match, or None if unlimited.
+    pub backtrack_limit: Option<usize>,
+
+    // A hook polled during searches, which may cancel them.
+    pub interrupt: Option<Interrupt>,
+
+    // The engine which searches where the lazy DFA cannot, and resolves
+    // capture groups. This is never Engine::Auto.
+    pub engine: Engine,
+
+    // How the backtracker memoizes failed states, or None if it does not.
+    pub memo: Option<Box<MemoPlan>>,
+
+    // The branches of the program, if it is one-pass, by which anchored
+    // searches are matched without backtracking.
+    pub onepass: Option<Box<OnePass>>,
+
+    // The program compiled to machine code, if the engine is Engine::Jit and
+    // the program is supported.
+    #[cfg(feature = "backend-jit")]
+    pub jit: Option<std::sync::Arc<JitProgram>>,
+
+    // The buffers of the engine, kept for searches which are not given a
+    // Cache.
+    pub pool: Pool<Cache>,
+
     // Flags controlling matching.
     pub flags: api::Flags,
 }
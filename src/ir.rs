@@ -1,7 +1,9 @@
 //! Intermediate representation for a regex
 
 use crate::api;
+use crate::codepointset::{CODE_POINT_MAX, CodePointSet};
 use crate::types::{BracketContents, CaptureGroupID, CaptureGroupName};
+use crate::unicode;
 #[cfg(not(feature = "std"))]
 use alloc::{boxed::Box, vec::Vec};
 use core::fmt;
@@ -473,13 +475,188 @@ where
     walker.process(n);
 }
 
+/// Bounds on the length in bytes of the UTF-8 text which a node may match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MatchLen {
+    /// The minimum length.
+    pub min: usize,
+
+    /// The maximum length, or None if unbounded.
+    pub max: Option<usize>,
+}
+
+impl MatchLen {
+    /// The bounds of a zero-width match.
+    const ZERO: MatchLen = MatchLen {
+        min: 0,
+        max: Some(0),
+    };
+
+    /// \return the bounds of matching any code point in \p first..=last.
+    fn code_points(first: u32, last: u32) -> MatchLen {
+        MatchLen {
+            min: utf8_len(first),
+            max: Some(utf8_len(last)),
+        }
+    }
+
+    /// \return the bounds of matching \p self then \p rhs.
+    fn then(self, rhs: MatchLen) -> MatchLen {
+        MatchLen {
+            min: self.min.saturating_add(rhs.min),
+            max: self.max.zip(rhs.max).and_then(|(a, b)| a.checked_add(b)),
+        }
+    }
+
+    /// \return the bounds of matching either \p self or \p rhs.
+    fn or(self, rhs: MatchLen) -> MatchLen {
+        MatchLen {
+            min: self.min.min(rhs.min),
+            max: self.max.zip(rhs.max).map(|(a, b)| a.max(b)),
+        }
+    }
+
+    /// \return the bounds of matching \p self repeated per \p quant.
+    fn repeat(self, quant: Quantifier) -> MatchLen {
+        MatchLen {
+            min: self.min.saturating_mul(quant.min),
+            max: match (self.max, quant.max) {
+                (Some(0), _) => Some(0),
+                (Some(a), Some(b)) => a.checked_mul(b),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// \return the length of a code point encoded as UTF-8. Surrogates are given
+/// the length they would have if they could be encoded.
+fn utf8_len(cp: u32) -> usize {
+    match cp {
+        0..0x80 => 1,
+        0x80..0x800 => 2,
+        0x800..0x10000 => 3,
+        _ => 4,
+    }
+}
+
+/// \return the bounds on the length of text matched by \p node, or None if
+/// it never matches. \p group_max is the maximum length of text captured by
+/// each group, or None if unbounded; missing groups are taken as unbounded.
+pub fn match_len(node: &Node, group_max: &[Option<usize>]) -> Option<MatchLen> {
+    let of_set = |cps: &[u32]| {
+        let first = cps.iter().copied().min()?;
+        let last = cps.iter().copied().max()?;
+        Some(MatchLen::code_points(first, last))
+    };
+    match node {
+        Node::Empty | Node::Goal | Node::Anchor { .. } | Node::WordBoundary { .. } => {
+            Some(MatchLen::ZERO)
+        }
+        &Node::Char { c } => Some(MatchLen::code_points(c, c)),
+        Node::ByteSequence(bytes) => Some(MatchLen {
+            min: bytes.len(),
+            max: Some(bytes.len()),
+        }),
+        Node::ByteSet(bytes) => (!bytes.is_empty()).then_some(MatchLen {
+            min: 1,
+            max: Some(1),
+        }),
+        Node::CharSet(chars) => of_set(chars),
+        Node::Bracket(contents) => {
+            let inverted;
+            let cps = if contents.invert {
+                inverted = contents.cps.inverted();
+                &inverted
+            } else {
+                &contents.cps
+            };
+            let first = cps.intervals().first()?.first;
+            let last = cps.intervals().last()?.last;
+            Some(MatchLen::code_points(first, last))
+        }
+        Node::MatchAny | Node::MatchAnyExceptLineTerminator => {
+            Some(MatchLen::code_points(0, CODE_POINT_MAX))
+        }
+        Node::StringSet {
+            alternatives,
+            icase,
+        } => alternatives
+            .iter()
+            .map(|alt| {
+                alt.iter().fold(MatchLen::ZERO, |len, &c| {
+                    // Case folding may change the encoded length.
+                    let cp_len = if *icase {
+                        let mut cps = CodePointSet::new();
+                        cps.add_one(c);
+                        let folded = unicode::add_icase_code_points(cps);
+                        let ivs = folded.intervals();
+                        MatchLen::code_points(ivs[0].first, ivs[ivs.len() - 1].last)
+                    } else {
+                        MatchLen::code_points(c, c)
+                    };
+                    len.then(cp_len)
+                })
+            })
+            .reduce(MatchLen::or),
+        Node::Cat(nodes) => nodes.iter().try_fold(MatchLen::ZERO, |len, node| {
+            Some(len.then(match_len(node, group_max)?))
+        }),
+        Node::Alt(left, right) => match (match_len(left, group_max), match_len(right, group_max)) {
+            (Some(l), Some(r)) => Some(l.or(r)),
+            (l, r) => l.or(r),
+        },
+        Node::CaptureGroup { contents, .. } => match_len(contents, group_max),
+        &Node::BackRef { group, icase } => {
+            // A backreference matches nothing if its group did not participate.
+            // Case folding may change the encoded length.
+            let max = match icase {
+                true => None,
+                false => group_max.get(group as usize - 1).copied().flatten(),
+            };
+            Some(MatchLen { min: 0, max })
+        }
+        Node::LookaroundAssertion {
+            negate, contents, ..
+        } => match negate {
+            false => match_len(contents, group_max).map(|_| MatchLen::ZERO),
+            true => Some(MatchLen::ZERO),
+        },
+        Node::Loop { loopee, quant, .. } | Node::Loop1CharBody { loopee, quant } => {
+            match match_len(loopee, group_max) {
+                Some(len) => Some(len.repeat(*quant)),
+                None if quant.min == 0 => Some(MatchLen::ZERO),
+                None => None,
+            }
+        }
+    }
+}
+
 /// A regex in IR form.
 pub struct Regex {
     pub node: Node,
     pub flags: api::Flags,
 }
 
-impl Regex {}
+impl Regex {
+    /// \return the bounds on the length of text matched by the regex, or None
+    /// if it never matches.
+    pub fn match_len(&self) -> Option<MatchLen> {
+        // Backreferences are bounded by their groups. Find the bounds of the
+        // groups, taking any backreferences within them as unbounded.
+        let mut group_max = Vec::new();
+        walk(false, self.flags.unicode, &self.node, &mut |node, _| {
+            if let Node::CaptureGroup { id, contents, .. } = node {
+                let id = *id as usize;
+                if group_max.len() <= id {
+                    group_max.resize(id + 1, None);
+                }
+                group_max[id] = match_len(contents, &[]).map_or(Some(0), |len| len.max);
+            }
+        });
+        match_len(&self.node, &group_max)
+    }
+}
 
 fn display_node(node: &Node, depth: usize, f: &mut fmt::Formatter) -> fmt::Result {
     const MAX_DEPTH_DOTS: usize = 120;
@@ -0,0 +1,1373 @@
+//! Compilation of the backtracker to machine code.
+//!
+//! A program is compiled with Cranelift to a function which matches it at a
+//! position of UTF-8 text exactly as the backtracker interprets it. Each
+//! instruction becomes a block of code, with the values of its operands
+//! folded in. The backtrack stack is an array of fixed-size entries in a
+//! buffer owned by the caller, which also holds the capture groups and the
+//! loop data. Programs with backreferences or lookarounds are not compiled;
+//! they are interpreted instead.
+
+use crate::bytesearch::ByteSet;
+use crate::insn::{CompiledRegex, Insn, LoopFields};
+use crate::matchers::{CharProperties, UTF8CharProperties};
+use crate::types::{BracketContents, IP};
+use core::fmt;
+use core::mem::offset_of;
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::types::{I8, I64};
+use cranelift_codegen::ir::{AbiParam, Block, InstBuilder, MemFlags, SigRef, Signature, Value};
+use cranelift_codegen::isa::CallConv;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::Module;
+
+/// The most instructions of a program which is compiled. Larger programs are
+/// interpreted, as they would take too long to compile.
+const MAX_JIT_INSNS: usize = 1 << 12;
+
+/// The number of words in an entry of the backtrack stack. The first word
+/// holds the kind of the entry in its low bits, and an IP or an ID in the
+/// rest; the meaning of the others depends on the kind.
+const ENTRY_WORDS: i64 = 4;
+
+/// The number of bits of the first word of an entry holding its kind.
+const TAG_BITS: i64 = 4;
+
+// The kinds of entries of the backtrack stack, which mirror BacktrackInsn.
+// Nothing more to backtrack.
+const TAG_EXHAUSTED: i64 = 0;
+// Resume at an IP with the position in the second word.
+const TAG_SET_POSITION: i64 = 1;
+// Restore a capture group to the start and end in the second and third words.
+const TAG_SET_CAPTURE_GROUP: i64 = 2;
+// Restore a loop to the iterations and entry in the second and third words.
+const TAG_SET_LOOP_DATA: i64 = 3;
+// Enter a non-greedy loop at an IP, from the original entry, iterations and
+// entry in the second, third and fourth words.
+const TAG_ENTER_NON_GREEDY_LOOP: i64 = 4;
+// Retry the continuation at an IP, with the min and max in the second and
+// third words.
+const TAG_GREEDY_LOOP_1CHAR: i64 = 5;
+const TAG_NON_GREEDY_LOOP_1CHAR: i64 = 6;
+
+/// The value of an unset capture group bound, and the result of a failed
+/// match.
+const NONE: u64 = u64::MAX;
+
+/// The result of a match which gave up because its backtrack stack outgrew
+/// MAX_JIT_STACK_WORDS.
+const DECLINED: u64 = u64::MAX - 1;
+
+/// The most words the buffer grows to. The entries of the stack are larger
+/// than those of the interpreter, so deeper stacks are left to it, which is
+/// faster once they no longer fit in the cache.
+const MAX_JIT_STACK_WORDS: usize = 1 << 16;
+
+/// The state shared between the compiled code and the functions it calls.
+#[repr(C)]
+struct Frame {
+    text: *const u8,
+    len: u64,
+    // The buffer of capture groups, loop data and the backtrack stack, and
+    // its length in words.
+    buf: *mut u64,
+    cap: u64,
+    brackets: *const BracketContents,
+    // The vector owning the buffer, which is grown when the stack fills.
+    owner: *mut Vec<u64>,
+}
+
+/// The signature of a compiled program, which takes the frame and the start
+/// of the match and returns its end, NONE or DECLINED.
+type MatchFn = unsafe extern "C" fn(*mut Frame, u64) -> u64;
+
+/// A program compiled to machine code.
+pub struct JitProgram {
+    // The module which owns the code; it is freed when the program is
+    // dropped.
+    module: Option<JITModule>,
+    entry: MatchFn,
+    groups: usize,
+    // The offset in words of the backtrack stack within the buffer, after
+    // the capture groups and the loop data.
+    stack_base: usize,
+}
+
+// The code is immutable once finalized, and the module is only touched
+// again to free it.
+unsafe impl Send for JitProgram {}
+unsafe impl Sync for JitProgram {}
+
+impl fmt::Debug for JitProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JitProgram")
+            .field("groups", &self.groups)
+            .field("stack_base", &self.stack_base)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for JitProgram {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // Safety: the code is unreachable once the program is gone.
+            unsafe { module.free_memory() }
+        }
+    }
+}
+
+impl JitProgram {
+    /// Compile the program of \p re.
+    /// \return the compiled program, or None if it contains instructions
+    /// which are not supported, or machine code cannot be generated for the
+    /// host.
+    pub(crate) fn new(re: &CompiledRegex) -> Option<JitProgram> {
+        if cfg!(feature = "prohibit-unsafe")
+            || re.insns.len() > MAX_JIT_INSNS
+            || re.insns.iter().any(|insn| {
+                matches!(
+                    insn,
+                    Insn::BackRef { .. } | Insn::Lookahead { .. } | Insn::Lookbehind { .. }
+                )
+            })
+        {
+            return None;
+        }
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").ok()?;
+        let isa = cranelift_native::builder()
+            .ok()?
+            .finish(settings::Flags::new(flags))
+            .ok()?;
+        if isa.pointer_type() != I64 {
+            return None;
+        }
+        let mut module = JITModule::new(JITBuilder::with_isa(
+            isa,
+            cranelift_module::default_libcall_names(),
+        ));
+        match Self::define(re, &mut module) {
+            Some(code) => Some(JitProgram {
+                module: Some(module),
+                // Safety: the code was compiled with this signature.
+                entry: unsafe { core::mem::transmute::<*const u8, MatchFn>(code) },
+                groups: re.groups as usize,
+                stack_base: 2 * (re.groups + re.loops) as usize,
+            }),
+            None => {
+                // Safety: nothing refers to the code.
+                unsafe { module.free_memory() };
+                None
+            }
+        }
+    }
+
+    /// Compile the program of \p re into \p module.
+    /// \return the address of the code, or None on failure.
+    fn define(re: &CompiledRegex, module: &mut JITModule) -> Option<*const u8> {
+        let call_conv = module.isa().default_call_conv();
+        let mut ctx = module.make_context();
+        ctx.func.signature = signature(call_conv, 2, 1);
+        let mut func_ctx = FunctionBuilderContext::new();
+        Compiler::new(
+            re,
+            FunctionBuilder::new(&mut ctx.func, &mut func_ctx),
+            call_conv,
+        )
+        .compile();
+        let id = module
+            .declare_anonymous_function(&ctx.func.signature)
+            .ok()?;
+        module.define_function(id, &mut ctx).ok()?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().ok()?;
+        Some(module.get_finalized_function(id))
+    }
+
+    /// Match the program at byte offset \p start of \p text, using \p buf for
+    /// the capture groups, loop data and backtrack stack. \p brackets are the
+    /// bracket contents of the regex which was compiled.
+    /// \return the end of the match, or Some(None) if there is none, or None
+    /// if the match needed too deep a stack and should be interpreted
+    /// instead. On success, the capture groups may be read with `group`.
+    pub(crate) fn find_at(
+        &self,
+        brackets: &[BracketContents],
+        text: &str,
+        start: usize,
+        buf: &mut Vec<u64>,
+    ) -> Option<Option<usize>> {
+        let min_len = self.stack_base + 16 * ENTRY_WORDS as usize;
+        if buf.len() < min_len {
+            buf.resize(min_len, 0);
+        }
+        buf[..2 * self.groups].fill(NONE);
+        let owner: *mut Vec<u64> = buf;
+        let mut frame = Frame {
+            text: text.as_ptr(),
+            len: text.len() as u64,
+            // Safety: the buffer is only accessed through the frame until
+            // the match returns.
+            buf: unsafe { (*owner).as_mut_ptr() },
+            cap: unsafe { (*owner).len() } as u64,
+            brackets: brackets.as_ptr(),
+            owner,
+        };
+        // Safety: the frame describes valid UTF-8 text, a buffer large
+        // enough for the groups, loops and first entry of the stack, and the
+        // brackets of the program.
+        let end = unsafe { (self.entry)(&mut frame, start as u64) };
+        match end {
+            DECLINED => None,
+            NONE => Some(None),
+            end => Some(Some(end as usize)),
+        }
+    }
+
+    /// \return the byte offsets of the start and end of capture group \p idx
+    /// (counting from 0) in \p buf, after a successful `find_at`.
+    pub(crate) fn group(&self, buf: &[u64], idx: usize) -> (Option<usize>, Option<usize>) {
+        let bound = |v: u64| (v != NONE).then_some(v as usize);
+        (bound(buf[2 * idx]), bound(buf[2 * idx + 1]))
+    }
+}
+
+/// \return a signature of \p params and \p returns words.
+fn signature(call_conv: CallConv, params: usize, returns: usize) -> Signature {
+    let mut sig = Signature::new(call_conv);
+    sig.params.extend((0..params).map(|_| AbiParam::new(I64)));
+    sig.returns.extend((0..returns).map(|_| AbiParam::new(I64)));
+    sig
+}
+
+/// \return a 256 bit bitmap of the bytes for which \p f is true.
+fn byte_table(f: impl Fn(u8) -> bool) -> [u64; 4] {
+    let mut words = [0; 4];
+    for b in 0..=255u8 {
+        if f(b) {
+            words[b as usize / 64] |= 1 << (b % 64);
+        }
+    }
+    words
+}
+
+/// Grow the buffer of \p frame, when the backtrack stack is full.
+/// \return the new length of the buffer, or 0 if it may not grow further.
+unsafe extern "C" fn grow(frame: *mut Frame) -> u64 {
+    // Safety: the frame and its buffer belong to the running match.
+    let frame = unsafe { &mut *frame };
+    let buf = unsafe { &mut *frame.owner };
+    if buf.len() >= MAX_JIT_STACK_WORDS {
+        return 0;
+    }
+    buf.resize(buf.len() * 2, 0);
+    frame.buf = buf.as_mut_ptr();
+    frame.cap = buf.len() as u64;
+    frame.cap
+}
+
+/// Decode the non-ASCII character at \p p, with \p remaining bytes of text.
+/// \return the character in the low 32 bits, and its length above them.
+unsafe extern "C" fn decode(p: *const u8, remaining: u64) -> u64 {
+    // Safety: there is at least one byte of text at p.
+    let b = unsafe { *p };
+    let len = match b {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        _ => 2,
+    }
+    .min(remaining as usize);
+    let bytes = unsafe { core::slice::from_raw_parts(p, len) };
+    match core::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+    {
+        Some(c) => c as u64 | (len as u64) << 32,
+        // Not at a character boundary; step over the byte.
+        None => u32::MAX as u64 | 1 << 32,
+    }
+}
+
+/// \return whether the non-ASCII character \p c matches the bracket at
+/// \p idx of \p brackets.
+unsafe extern "C" fn bracket(brackets: *const BracketContents, idx: u64, c: u64) -> u64 {
+    // Safety: the program only refers to brackets of its regex.
+    let bc = unsafe { &*brackets.add(idx as usize) };
+    (bc.cps.contains(c as u32) != bc.invert) as u64
+}
+
+/// \return whether \p pos in the \p len bytes of \p text is a word boundary
+/// in Unicode case-insensitive mode.
+unsafe extern "C" fn word_boundary_unicode_icase(text: *const u8, len: u64, pos: u64) -> u64 {
+    // Safety: the text is a valid str.
+    let text =
+        unsafe { core::str::from_utf8_unchecked(core::slice::from_raw_parts(text, len as usize)) };
+    let pos = pos as usize;
+    let is_word = |c: Option<char>| c.is_some_and(UTF8CharProperties::is_word_char_unicode_icase);
+    let prev = is_word(text.get(..pos).and_then(|s| s.chars().next_back()));
+    let curr = is_word(text.get(pos..).and_then(|s| s.chars().next()));
+    (prev != curr) as u64
+}
+
+/// The variables of the compiled function.
+#[derive(Clone, Copy)]
+struct Vars {
+    // The current position.
+    pos: Variable,
+    // The offset in words of the top of the backtrack stack.
+    sp: Variable,
+    // The buffer and its length in words, which change as it grows.
+    buf: Variable,
+    cap: Variable,
+    // The IP to which to dispatch.
+    target: Variable,
+    // The second, third and fourth words of the entry being backtracked.
+    a: Variable,
+    b: Variable,
+    c: Variable,
+    // The iterations of a single character loop.
+    count: Variable,
+}
+
+/// The signatures of the functions called by the compiled code.
+struct Sigs {
+    grow: SigRef,
+    decode: SigRef,
+    bracket: SigRef,
+    word_boundary: SigRef,
+}
+
+/// Builds the function for a program.
+struct Compiler<'a> {
+    re: &'a CompiledRegex,
+    b: FunctionBuilder<'a>,
+    vars: Vars,
+    sigs: Sigs,
+    // The block of each instruction.
+    blocks: Vec<Block>,
+    // The block deciding whether to enter each loop, by the IP of its
+    // EnterLoop.
+    loop_blocks: Vec<Option<Block>>,
+    // The blocks reentering the non-greedy loops from the backtrack stack,
+    // by the IP of their EnterLoop.
+    reentry_blocks: Vec<(IP, Block)>,
+    // Pops the backtrack stack and acts on the entry.
+    backtrack: Block,
+    // Jumps to the block of the IP in `target`.
+    dispatch: Block,
+    // Returns a failed match.
+    exhausted: Block,
+    // Returns a match which gave up.
+    declined: Block,
+    frame: Value,
+    text: Value,
+    len: Value,
+    brackets: Value,
+    // The offset in words of the loop data within the buffer.
+    loops_base: i64,
+}
+
+/// Flags for loads from the text, which need not be aligned.
+fn text_flags() -> MemFlags {
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_readonly();
+    flags
+}
+
+impl<'a> Compiler<'a> {
+    fn new(re: &'a CompiledRegex, mut b: FunctionBuilder<'a>, call_conv: CallConv) -> Self {
+        let sigs = Sigs {
+            grow: b.import_signature(signature(call_conv, 1, 1)),
+            decode: b.import_signature(signature(call_conv, 2, 1)),
+            bracket: b.import_signature(signature(call_conv, 3, 1)),
+            word_boundary: b.import_signature(signature(call_conv, 3, 1)),
+        };
+        let mut next_var = 0;
+        let mut var = |ty| {
+            let v = Variable::new(next_var);
+            next_var += 1;
+            b.declare_var(v, ty);
+            v
+        };
+        let vars = Vars {
+            pos: var(I64),
+            sp: var(I64),
+            buf: var(I64),
+            cap: var(I64),
+            target: var(I64),
+            a: var(I64),
+            b: var(I64),
+            c: var(I64),
+            count: var(I64),
+        };
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let (frame, start) = (b.block_params(entry)[0], b.block_params(entry)[1]);
+        let load_field = |b: &mut FunctionBuilder, offset: usize| {
+            b.ins().load(I64, MemFlags::trusted(), frame, offset as i32)
+        };
+        let text = load_field(&mut b, offset_of!(Frame, text));
+        let len = load_field(&mut b, offset_of!(Frame, len));
+        let brackets = load_field(&mut b, offset_of!(Frame, brackets));
+        let buf = load_field(&mut b, offset_of!(Frame, buf));
+        let cap = load_field(&mut b, offset_of!(Frame, cap));
+        b.def_var(vars.buf, buf);
+        b.def_var(vars.cap, cap);
+        b.def_var(vars.pos, start);
+
+        let blocks = (0..re.insns.len()).map(|_| b.create_block()).collect();
+        let loop_blocks = re
+            .insns
+            .iter()
+            .map(|insn| matches!(insn, Insn::EnterLoop(_)).then(|| b.create_block()))
+            .collect();
+        let backtrack = b.create_block();
+        let dispatch = b.create_block();
+        let exhausted = b.create_block();
+        let declined = b.create_block();
+        let mut compiler = Compiler {
+            re,
+            b,
+            vars,
+            sigs,
+            blocks,
+            loop_blocks,
+            reentry_blocks: Vec::new(),
+            backtrack,
+            dispatch,
+            exhausted,
+            declined,
+            frame,
+            text,
+            len,
+            brackets,
+            loops_base: 2 * re.groups as i64,
+        };
+        // The stack starts with the entry which backstops it.
+        let sp = compiler.iconst(2 * (re.groups + re.loops) as i64);
+        compiler.b.def_var(vars.sp, sp);
+        let zero = compiler.iconst(0);
+        compiler.push([zero; 4]);
+        let first = compiler.blocks[0];
+        compiler.b.ins().jump(first, &[]);
+        compiler
+    }
+
+    /// Emit the code of every instruction, and of backtracking, and finish
+    /// the function.
+    fn compile(mut self) {
+        for ip in 0..self.re.insns.len() {
+            self.b.switch_to_block(self.blocks[ip]);
+            self.emit_insn(ip);
+        }
+        self.emit_backtrack();
+        self.emit_dispatch();
+        self.b.switch_to_block(self.exhausted);
+        let none = self.iconst(NONE as i64);
+        self.b.ins().return_(&[none]);
+        self.b.switch_to_block(self.declined);
+        let declined = self.iconst(DECLINED as i64);
+        self.b.ins().return_(&[declined]);
+        self.b.seal_all_blocks();
+        self.b.finalize();
+    }
+
+    fn iconst(&mut self, v: i64) -> Value {
+        self.b.ins().iconst(I64, v)
+    }
+
+    fn bool_const(&mut self, v: bool) -> Value {
+        self.b.ins().iconst(I8, v as i64)
+    }
+
+    fn pos(&mut self) -> Value {
+        self.b.use_var(self.vars.pos)
+    }
+
+    /// \return the address of word \p idx of the buffer.
+    fn word_addr(&mut self, idx: Value) -> Value {
+        let buf = self.b.use_var(self.vars.buf);
+        let offset = self.b.ins().ishl_imm(idx, 3);
+        self.b.ins().iadd(buf, offset)
+    }
+
+    /// \return word \p idx + \p k of the buffer.
+    fn load_word(&mut self, idx: Value, k: i64) -> Value {
+        let addr = self.word_addr(idx);
+        self.b
+            .ins()
+            .load(I64, MemFlags::trusted(), addr, (k * 8) as i32)
+    }
+
+    /// Set word \p idx + \p k of the buffer to \p v.
+    fn store_word(&mut self, idx: Value, k: i64, v: Value) {
+        let addr = self.word_addr(idx);
+        self.b
+            .ins()
+            .store(MemFlags::trusted(), v, addr, (k * 8) as i32);
+    }
+
+    /// \return the first word of an entry of kind \p tag for \p payload.
+    fn entry_header(&mut self, tag: i64, payload: usize) -> Value {
+        self.iconst(tag | (payload as i64) << TAG_BITS)
+    }
+
+    /// Push an entry of \p words onto the backtrack stack, growing the
+    /// buffer if it is full, or giving up if it may not grow.
+    fn push(&mut self, words: [Value; ENTRY_WORDS as usize]) {
+        let sp = self.b.use_var(self.vars.sp);
+        let cap = self.b.use_var(self.vars.cap);
+        let new_sp = self.b.ins().iadd_imm(sp, ENTRY_WORDS);
+        let full = self.b.ins().icmp(IntCC::UnsignedGreaterThan, new_sp, cap);
+        let grow_block = self.b.create_block();
+        let cont = self.b.create_block();
+        self.b.set_cold_block(grow_block);
+        self.b.ins().brif(full, grow_block, &[], cont, &[]);
+
+        self.b.switch_to_block(grow_block);
+        let callee = self.iconst(grow as *const () as i64);
+        let call = self
+            .b
+            .ins()
+            .call_indirect(self.sigs.grow, callee, &[self.frame]);
+        let grown = self.b.inst_results(call)[0];
+        self.check(grown, self.declined);
+        let buf = self.b.ins().load(
+            I64,
+            MemFlags::trusted(),
+            self.frame,
+            offset_of!(Frame, buf) as i32,
+        );
+        let cap = self.b.ins().load(
+            I64,
+            MemFlags::trusted(),
+            self.frame,
+            offset_of!(Frame, cap) as i32,
+        );
+        self.b.def_var(self.vars.buf, buf);
+        self.b.def_var(self.vars.cap, cap);
+        self.b.ins().jump(cont, &[]);
+
+        self.b.switch_to_block(cont);
+        for (k, word) in words.into_iter().enumerate() {
+            self.store_word(sp, k as i64, word);
+        }
+        self.b.def_var(self.vars.sp, new_sp);
+    }
+
+    /// Continue if \p ok is true, or jump to \p fail.
+    fn check(&mut self, ok: Value, fail: Block) {
+        let cont = self.b.create_block();
+        self.b.ins().brif(ok, cont, &[], fail, &[]);
+        self.b.switch_to_block(cont);
+    }
+
+    /// Jump to \p target, continuing in an unreachable block.
+    fn jump_away(&mut self, target: Block) {
+        self.b.ins().jump(target, &[]);
+        let dead = self.b.create_block();
+        self.b.switch_to_block(dead);
+    }
+
+    /// \return the byte at offset \p p + \p k of the text.
+    fn byte(&mut self, p: Value, k: i32) -> Value {
+        let addr = self.b.ins().iadd(self.text, p);
+        self.b.ins().uload8(I64, text_flags(), addr, k)
+    }
+
+    /// Continue if there are at least \p n bytes of text at \p p, or jump to
+    /// \p fail.
+    fn ensure(&mut self, p: Value, n: i64, fail: Block) {
+        let end = self.b.ins().iadd_imm(p, n);
+        let ok = self
+            .b
+            .ins()
+            .icmp(IntCC::UnsignedLessThanOrEqual, end, self.len);
+        self.check(ok, fail);
+    }
+
+    /// \return whether \p val is one of \p values.
+    fn any_eq(&mut self, val: Value, values: impl IntoIterator<Item = u32>) -> Value {
+        let mut result = self.bool_const(false);
+        for v in values {
+            let eq = self.b.ins().icmp_imm(IntCC::Equal, val, v as i64);
+            result = self.b.ins().bor(result, eq);
+        }
+        result
+    }
+
+    /// \return whether the byte \p val is set in the bitmap \p table.
+    fn test_byte(&mut self, val: Value, table: [u64; 4]) -> Value {
+        let word = if table[2] == 0 && table[3] == 0 {
+            let lo = self.iconst(table[0] as i64);
+            let hi = self.iconst(table[1] as i64);
+            let is_lo = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, val, 64);
+            let word = self.b.ins().select(is_lo, lo, hi);
+            // Non-ASCII values are not in the table.
+            let is_ascii = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, val, 128);
+            let zero = self.iconst(0);
+            self.b.ins().select(is_ascii, word, zero)
+        } else {
+            let idx = self.b.ins().ushr_imm(val, 6);
+            let mut word = self.iconst(table[3] as i64);
+            for i in (0..3).rev() {
+                let is_i = self.b.ins().icmp_imm(IntCC::Equal, idx, i as i64);
+                let w = self.iconst(table[i] as i64);
+                word = self.b.ins().select(is_i, w, word);
+            }
+            word
+        };
+        let shift = self.b.ins().band_imm(val, 63);
+        let bit = self.b.ins().ushr(word, shift);
+        let bit = self.b.ins().band_imm(bit, 1);
+        self.b.ins().icmp_imm(IntCC::NotEqual, bit, 0)
+    }
+
+    /// \return the length of the UTF-8 sequence starting with byte \p b.
+    fn seq_len(&mut self, b: Value) -> Value {
+        let hi = self.b.ins().band_imm(b, 0xF0);
+        let is_3 = self.b.ins().icmp_imm(IntCC::Equal, hi, 0xE0);
+        let is_4 = self.b.ins().icmp_imm(IntCC::Equal, hi, 0xF0);
+        let is_1 = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, b, 0x80);
+        let (one, two, three, four) = (
+            self.iconst(1),
+            self.iconst(2),
+            self.iconst(3),
+            self.iconst(4),
+        );
+        let n = self.b.ins().select(is_4, four, two);
+        let n = self.b.ins().select(is_3, three, n);
+        self.b.ins().select(is_1, one, n)
+    }
+
+    /// Decode the character at \p p, or jump to \p fail at the end of the
+    /// text.
+    /// \return the character and its length in bytes.
+    fn decode(&mut self, p: Value, fail: Block) -> (Value, Value) {
+        self.ensure(p, 1, fail);
+        let b0 = self.byte(p, 0);
+        let join = self.b.create_block();
+        let c = self.b.append_block_param(join, I64);
+        let n = self.b.append_block_param(join, I64);
+        let slow = self.b.create_block();
+        self.b.set_cold_block(slow);
+        let is_ascii = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, b0, 0x80);
+        let one = self.iconst(1);
+        self.b.ins().brif(is_ascii, join, &[b0, one], slow, &[]);
+
+        self.b.switch_to_block(slow);
+        let addr = self.b.ins().iadd(self.text, p);
+        let remaining = self.b.ins().isub(self.len, p);
+        let callee = self.iconst(decode as *const () as i64);
+        let call = self
+            .b
+            .ins()
+            .call_indirect(self.sigs.decode, callee, &[addr, remaining]);
+        let r = self.b.inst_results(call)[0];
+        let slow_c = self.b.ins().band_imm(r, 0xFFFF_FFFF);
+        let slow_n = self.b.ins().ushr_imm(r, 32);
+        self.b.ins().jump(join, &[slow_c, slow_n]);
+
+        self.b.switch_to_block(join);
+        (c, n)
+    }
+
+    /// \return the offset of the character before \p p, which is not at the
+    /// start of the text.
+    fn prev_char_boundary(&mut self, p: Value) -> Value {
+        let step = self.b.create_block();
+        let q = self.b.append_block_param(step, I64);
+        let done = self.b.create_block();
+        let result = self.b.append_block_param(done, I64);
+        self.b.ins().jump(step, &[p]);
+
+        self.b.switch_to_block(step);
+        let q = self.b.ins().iadd_imm(q, -1);
+        let b = self.byte(q, 0);
+        let top = self.b.ins().band_imm(b, 0xC0);
+        let is_continuation = self.b.ins().icmp_imm(IntCC::Equal, top, 0x80);
+        self.b.ins().brif(is_continuation, step, &[q], done, &[q]);
+
+        self.b.switch_to_block(done);
+        result
+    }
+
+    /// Match the literal \p bytes at \p p, or jump to \p fail.
+    /// \return the position after them.
+    fn match_lit(&mut self, p: Value, bytes: &[u8], fail: Block) -> Value {
+        self.ensure(p, bytes.len() as i64, fail);
+        let addr = self.b.ins().iadd(self.text, p);
+        let mut ok = self.bool_const(true);
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let rest = &bytes[offset..];
+            let (width, loaded) = match rest.len() {
+                8.. => (8, self.b.ins().load(I64, text_flags(), addr, offset as i32)),
+                4.. => (4, self.b.ins().uload32(text_flags(), addr, offset as i32)),
+                2.. => (
+                    2,
+                    self.b.ins().uload16(I64, text_flags(), addr, offset as i32),
+                ),
+                _ => (
+                    1,
+                    self.b.ins().uload8(I64, text_flags(), addr, offset as i32),
+                ),
+            };
+            let mut expected = [0; 8];
+            expected[..width].copy_from_slice(&rest[..width]);
+            let expected = u64::from_ne_bytes(expected);
+            #[cfg(target_endian = "big")]
+            let expected = expected >> (64 - 8 * width);
+            let eq = self.b.ins().icmp_imm(IntCC::Equal, loaded, expected as i64);
+            ok = self.b.ins().band(ok, eq);
+            offset += width;
+        }
+        self.check(ok, fail);
+        self.b.ins().iadd_imm(p, bytes.len() as i64)
+    }
+
+    /// Match the byte at \p p against the bitmap \p table, or jump to
+    /// \p fail.
+    /// \return the position after it.
+    fn match_byte(&mut self, p: Value, table: [u64; 4], fail: Block) -> Value {
+        self.ensure(p, 1, fail);
+        let b = self.byte(p, 0);
+        let ok = self.test_byte(b, table);
+        self.check(ok, fail);
+        self.b.ins().iadd_imm(p, 1)
+    }
+
+    /// \return whether the character \p c matches the bracket at \p idx.
+    fn test_bracket(&mut self, idx: usize, c: Value) -> Value {
+        let bc = &self.re.brackets[idx];
+        let table = byte_table(|b| b < 128 && bc.cps.contains(b as u32) != bc.invert);
+        let join = self.b.create_block();
+        let result = self.b.append_block_param(join, I8);
+        let fast = self.b.create_block();
+        let slow = self.b.create_block();
+        let is_ascii = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, c, 128);
+        self.b.ins().brif(is_ascii, fast, &[], slow, &[]);
+
+        self.b.switch_to_block(fast);
+        let m = self.test_byte(c, table);
+        self.b.ins().jump(join, &[m]);
+
+        self.b.switch_to_block(slow);
+        let idx = self.iconst(idx as i64);
+        let callee = self.iconst(bracket as *const () as i64);
+        let call = self
+            .b
+            .ins()
+            .call_indirect(self.sigs.bracket, callee, &[self.brackets, idx, c]);
+        let r = self.b.inst_results(call)[0];
+        let m = self.b.ins().icmp_imm(IntCC::NotEqual, r, 0);
+        self.b.ins().jump(join, &[m]);
+
+        self.b.switch_to_block(join);
+        result
+    }
+
+    /// Match the single character instruction \p insn at the current
+    /// position, advancing it, or jump to \p fail leaving it unchanged.
+    fn emit_match(&mut self, insn: &Insn, fail: Block) {
+        let p = self.pos();
+        let new_pos = match insn {
+            &Insn::Char(c) => match char::from_u32(c) {
+                Some(c) => {
+                    let mut bytes = [0; 4];
+                    self.match_lit(p, c.encode_utf8(&mut bytes).as_bytes(), fail)
+                }
+                None => return self.jump_away(fail),
+            },
+            Insn::CharSet(chars) => {
+                let (c, n) = self.decode(p, fail);
+                let ok = self.any_eq(c, chars.iter().copied());
+                self.check(ok, fail);
+                self.b.ins().iadd(p, n)
+            }
+            &Insn::ByteSet2(bytes) => self.match_byte(p, byte_table(|b| bytes.contains(b)), fail),
+            &Insn::ByteSet3(bytes) => self.match_byte(p, byte_table(|b| bytes.contains(b)), fail),
+            &Insn::ByteSet4(bytes) => self.match_byte(p, byte_table(|b| bytes.contains(b)), fail),
+            Insn::AsciiBracket(bitmap) => {
+                self.match_byte(p, byte_table(|b| bitmap.contains(b)), fail)
+            }
+            Insn::ByteSeq1(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq2(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq3(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq4(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq5(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq6(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq7(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq8(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq9(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq10(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq11(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq12(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq13(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq14(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq15(v) => self.match_lit(p, v, fail),
+            Insn::ByteSeq16(v) => self.match_lit(p, v, fail),
+            &Insn::Bracket(idx) => {
+                let (c, n) = self.decode(p, fail);
+                let ok = self.test_bracket(idx, c);
+                self.check(ok, fail);
+                self.b.ins().iadd(p, n)
+            }
+            Insn::MatchAny => {
+                self.ensure(p, 1, fail);
+                let b0 = self.byte(p, 0);
+                let n = self.seq_len(b0);
+                self.b.ins().iadd(p, n)
+            }
+            Insn::MatchAnyExceptLineTerminator => {
+                let (c, n) = self.decode(p, fail);
+                let is_lt = self.any_eq(c, [0x0A, 0x0D, 0x2028, 0x2029]);
+                let cont = self.b.create_block();
+                self.b.ins().brif(is_lt, fail, &[], cont, &[]);
+                self.b.switch_to_block(cont);
+                self.b.ins().iadd(p, n)
+            }
+            _ => unreachable!("Not a single character instruction"),
+        };
+        self.b.def_var(self.vars.pos, new_pos);
+    }
+
+    /// \return whether the line terminator U+2028 or U+2029 is encoded at
+    /// offset \p p, whose first byte is \p b0, knowing that the text extends
+    /// at least three bytes past it.
+    fn is_unicode_line_terminator(&mut self, p: Value, b0: Value) -> Value {
+        let b1 = self.byte(p, 1);
+        let b2 = self.byte(p, 2);
+        let first = self.b.ins().icmp_imm(IntCC::Equal, b0, 0xE2);
+        let second = self.b.ins().icmp_imm(IntCC::Equal, b1, 0x80);
+        let third = self.any_eq(b2, [0xA8, 0xA9]);
+        let ok = self.b.ins().band(first, second);
+        self.b.ins().band(ok, third)
+    }
+
+    /// \return whether the line terminator ends just before \p p, which
+    /// is not at the start of the text.
+    fn line_terminator_before(&mut self, p: Value) -> Value {
+        let join = self.b.create_block();
+        let result = self.b.append_block_param(join, I8);
+        let b = self.byte(p, -1);
+        let is_lt = self.any_eq(b, [0x0A, 0x0D]);
+        let wide = self.b.create_block();
+        let yes = self.bool_const(true);
+        self.b.ins().brif(is_lt, join, &[yes], wide, &[]);
+
+        self.b.switch_to_block(wide);
+        let long_enough = self
+            .b
+            .ins()
+            .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, p, 3);
+        let check = self.b.create_block();
+        let no = self.bool_const(false);
+        self.b.ins().brif(long_enough, check, &[], join, &[no]);
+
+        self.b.switch_to_block(check);
+        let start = self.b.ins().iadd_imm(p, -3);
+        let b0 = self.byte(start, 0);
+        let m = self.is_unicode_line_terminator(start, b0);
+        self.b.ins().jump(join, &[m]);
+
+        self.b.switch_to_block(join);
+        result
+    }
+
+    /// \return whether a line terminator starts at \p p, which is not at the
+    /// end of the text.
+    fn line_terminator_at(&mut self, p: Value) -> Value {
+        let join = self.b.create_block();
+        let result = self.b.append_block_param(join, I8);
+        let b0 = self.byte(p, 0);
+        let is_lt = self.any_eq(b0, [0x0A, 0x0D]);
+        let wide = self.b.create_block();
+        let yes = self.bool_const(true);
+        self.b.ins().brif(is_lt, join, &[yes], wide, &[]);
+
+        self.b.switch_to_block(wide);
+        let end = self.b.ins().iadd_imm(p, 3);
+        let long_enough = self
+            .b
+            .ins()
+            .icmp(IntCC::UnsignedLessThanOrEqual, end, self.len);
+        let check = self.b.create_block();
+        let no = self.bool_const(false);
+        self.b.ins().brif(long_enough, check, &[], join, &[no]);
+
+        self.b.switch_to_block(check);
+        let m = self.is_unicode_line_terminator(p, b0);
+        self.b.ins().jump(join, &[m]);
+
+        self.b.switch_to_block(join);
+        result
+    }
+
+    /// Continue at \p next if \p at_edge, or otherwise if \p multiline and
+    /// \p lt computes that a line terminator is adjacent; else backtrack.
+    fn emit_line_anchor(
+        &mut self,
+        at_edge: Value,
+        multiline: bool,
+        next: Block,
+        lt: impl FnOnce(&mut Self) -> Value,
+    ) {
+        if !multiline {
+            self.b.ins().brif(at_edge, next, &[], self.backtrack, &[]);
+            return;
+        }
+        let inner = self.b.create_block();
+        self.b.ins().brif(at_edge, next, &[], inner, &[]);
+        self.b.switch_to_block(inner);
+        let ok = lt(self);
+        self.b.ins().brif(ok, next, &[], self.backtrack, &[]);
+    }
+
+    /// \return whether the byte at \p p + \p k is an ASCII word character,
+    /// or false if \p at_edge.
+    fn word_byte(&mut self, p: Value, k: i32, at_edge: Value) -> Value {
+        let join = self.b.create_block();
+        let result = self.b.append_block_param(join, I8);
+        let inner = self.b.create_block();
+        let no = self.bool_const(false);
+        self.b.ins().brif(at_edge, join, &[no], inner, &[]);
+
+        self.b.switch_to_block(inner);
+        let b = self.byte(p, k);
+        let table = byte_table(|b| UTF8CharProperties::is_word_char(b as char));
+        let m = self.test_byte(b, table);
+        self.b.ins().jump(join, &[m]);
+
+        self.b.switch_to_block(join);
+        result
+    }
+
+    /// Emit the code of instruction \p ip.
+    fn emit_insn(&mut self, ip: IP) {
+        let next = self.blocks.get(ip + 1).copied().unwrap_or(self.exhausted);
+        let backtrack = self.backtrack;
+        match &self.re.insns[ip] {
+            Insn::Goal => {
+                let pos = self.pos();
+                self.b.ins().return_(&[pos]);
+            }
+            Insn::JustFail
+            | Insn::BackRef { .. }
+            | Insn::Lookahead { .. }
+            | Insn::Lookbehind { .. } => {
+                self.b.ins().jump(backtrack, &[]);
+            }
+            &Insn::StartOfLine { multiline } => {
+                let p = self.pos();
+                let at_start = self.b.ins().icmp_imm(IntCC::Equal, p, 0);
+                self.emit_line_anchor(at_start, multiline, next, |s| s.line_terminator_before(p));
+            }
+            &Insn::EndOfLine { multiline } => {
+                let p = self.pos();
+                let at_end = self.b.ins().icmp(IntCC::Equal, p, self.len);
+                self.emit_line_anchor(at_end, multiline, next, |s| s.line_terminator_at(p));
+            }
+            &Insn::WordBoundary { invert } => {
+                let p = self.pos();
+                let at_start = self.b.ins().icmp_imm(IntCC::Equal, p, 0);
+                let prev = self.word_byte(p, -1, at_start);
+                let at_end = self.b.ins().icmp(IntCC::Equal, p, self.len);
+                let curr = self.word_byte(p, 0, at_end);
+                let cc = if invert {
+                    IntCC::Equal
+                } else {
+                    IntCC::NotEqual
+                };
+                let ok = self.b.ins().icmp(cc, prev, curr);
+                self.b.ins().brif(ok, next, &[], backtrack, &[]);
+            }
+            &Insn::WordBoundaryUnicodeICase { invert } => {
+                let p = self.pos();
+                let callee = self.iconst(word_boundary_unicode_icase as *const () as i64);
+                let call = self.b.ins().call_indirect(
+                    self.sigs.word_boundary,
+                    callee,
+                    &[self.text, self.len, p],
+                );
+                let r = self.b.inst_results(call)[0];
+                let cc = if invert {
+                    IntCC::Equal
+                } else {
+                    IntCC::NotEqual
+                };
+                let ok = self.b.ins().icmp_imm(cc, r, 0);
+                self.b.ins().brif(ok, next, &[], backtrack, &[]);
+            }
+            &Insn::Jump { target } => {
+                let target = self.blocks[target as IP];
+                self.b.ins().jump(target, &[]);
+            }
+            &Insn::Alt { secondary } => {
+                let header = self.entry_header(TAG_SET_POSITION, secondary as IP);
+                let pos = self.pos();
+                let zero = self.iconst(0);
+                self.push([header, pos, zero, zero]);
+                self.b.ins().jump(next, &[]);
+            }
+            &Insn::BeginCaptureGroup(id) => {
+                self.save_group(id as usize);
+                let idx = self.iconst(2 * id as i64);
+                let pos = self.pos();
+                self.store_word(idx, 0, pos);
+                self.b.ins().jump(next, &[]);
+            }
+            &Insn::EndCaptureGroup(id) => {
+                let idx = self.iconst(2 * id as i64);
+                let pos = self.pos();
+                self.store_word(idx, 1, pos);
+                self.b.ins().jump(next, &[]);
+            }
+            &Insn::ResetCaptureGroup(id) => {
+                self.save_group(id as usize);
+                let idx = self.iconst(2 * id as i64);
+                let none = self.iconst(NONE as i64);
+                self.store_word(idx, 0, none);
+                self.store_word(idx, 1, none);
+                self.b.ins().jump(next, &[]);
+            }
+            Insn::EnterLoop(fields) => self.emit_enter_loop(ip, fields),
+            &Insn::LoopAgain { begin } => {
+                let Some(loop_block) = self.loop_blocks[begin as IP] else {
+                    unreachable!("LoopAgain should always refer to EnterLoop")
+                };
+                self.b.ins().jump(loop_block, &[]);
+            }
+            &Insn::Loop1CharBody {
+                min_iters,
+                max_iters,
+                greedy,
+            } => self.emit_scm_loop(ip, min_iters, max_iters, greedy),
+            insn => {
+                self.emit_match(insn, backtrack);
+                self.b.ins().jump(next, &[]);
+            }
+        }
+    }
+
+    /// Push an entry restoring capture group \p id.
+    fn save_group(&mut self, id: usize) {
+        let idx = self.iconst(2 * id as i64);
+        let start = self.load_word(idx, 0);
+        let end = self.load_word(idx, 1);
+        let header = self.entry_header(TAG_SET_CAPTURE_GROUP, id);
+        let zero = self.iconst(0);
+        self.push([header, start, end, zero]);
+    }
+
+    /// \return the index in the buffer of the data of the loop \p fields.
+    fn loop_idx(&mut self, fields: &LoopFields) -> Value {
+        self.iconst(self.loops_base + 2 * fields.loop_id as i64)
+    }
+
+    /// Push an entry restoring the loop \p fields to \p iters and \p entry,
+    /// then count an iteration entered at the current position.
+    fn prepare_to_enter_loop(&mut self, fields: &LoopFields, iters: Value, entry: Value) {
+        let header = self.entry_header(TAG_SET_LOOP_DATA, fields.loop_id as usize);
+        let zero = self.iconst(0);
+        self.push([header, iters, entry, zero]);
+        let idx = self.loop_idx(fields);
+        let iters = self.b.ins().iadd_imm(iters, 1);
+        let pos = self.pos();
+        self.store_word(idx, 0, iters);
+        self.store_word(idx, 1, pos);
+    }
+
+    /// Emit the EnterLoop at \p ip, which saves and clears the iterations of
+    /// its loop before jumping to the loop block; and emit the loop block, which
+    /// decides whether to enter the loop, mirroring `run_loop`.
+    fn emit_enter_loop(&mut self, ip: IP, fields: &LoopFields) {
+        let Some(loop_block) = self.loop_blocks[ip] else {
+            unreachable!("EnterLoop should have a loop block")
+        };
+        // Save the loop's data for the earlier iterations of any loop
+        // enclosing it, which we may backtrack into.
+        let idx = self.loop_idx(fields);
+        let iters = self.load_word(idx, 0);
+        let entry = self.load_word(idx, 1);
+        let header = self.entry_header(TAG_SET_LOOP_DATA, fields.loop_id as usize);
+        let zero = self.iconst(0);
+        self.push([header, iters, entry, zero]);
+        self.store_word(idx, 0, zero);
+        self.b.ins().jump(loop_block, &[]);
+
+        self.b.switch_to_block(loop_block);
+        let taken = self.blocks[ip + 1];
+        let not_taken = self.blocks[fields.exit as IP];
+        let idx = self.loop_idx(fields);
+        let iters = self.load_word(idx, 0);
+        let entry = self.load_word(idx, 1);
+        let pos = self.pos();
+
+        // If we have looped more than the minimum number of iterations,
+        // reject empty matches.
+        let empty = self.b.ins().icmp(IntCC::Equal, entry, pos);
+        let past_min =
+            self.b
+                .ins()
+                .icmp_imm(IntCC::UnsignedGreaterThan, iters, fields.min_iters as i64);
+        let reject = self.b.ins().band(empty, past_min);
+        self.check_not(reject, self.backtrack);
+
+        let do_taken =
+            self.b
+                .ins()
+                .icmp_imm(IntCC::UnsignedLessThan, iters, fields.max_iters as i64);
+        let do_not_taken = self.b.ins().icmp_imm(
+            IntCC::UnsignedGreaterThanOrEqual,
+            iters,
+            fields.min_iters as i64,
+        );
+        let only_not_taken = self.b.create_block();
+        let can_take = self.b.create_block();
+        self.b
+            .ins()
+            .brif(do_taken, can_take, &[], only_not_taken, &[]);
+
+        self.b.switch_to_block(only_not_taken);
+        self.b
+            .ins()
+            .brif(do_not_taken, not_taken, &[], self.backtrack, &[]);
+
+        self.b.switch_to_block(can_take);
+        let only_taken = self.b.create_block();
+        let both = self.b.create_block();
+        self.b.ins().brif(do_not_taken, both, &[], only_taken, &[]);
+
+        self.b.switch_to_block(only_taken);
+        self.prepare_to_enter_loop(fields, iters, entry);
+        self.b.ins().jump(taken, &[]);
+
+        self.b.switch_to_block(both);
+        if fields.greedy {
+            // Backtrack out of the loop.
+            let header = self.entry_header(TAG_SET_POSITION, fields.exit as IP);
+            let zero = self.iconst(0);
+            self.push([header, pos, zero, zero]);
+            self.prepare_to_enter_loop(fields, iters, entry);
+            self.b.ins().jump(taken, &[]);
+        } else {
+            // Backtrack into the loop.
+            let idx = self.loop_idx(fields);
+            self.store_word(idx, 1, pos);
+            let header = self.entry_header(TAG_ENTER_NON_GREEDY_LOOP, ip);
+            self.push([header, entry, iters, pos]);
+            self.b.ins().jump(not_taken, &[]);
+            let reentry = self.b.create_block();
+            self.reentry_blocks.push((ip, reentry));
+        }
+    }
+
+    /// Continue if \p fails is false, or jump to \p fail.
+    fn check_not(&mut self, fails: Value, fail: Block) {
+        let cont = self.b.create_block();
+        self.b.ins().brif(fails, fail, &[], cont, &[]);
+        self.b.switch_to_block(cont);
+    }
+
+    /// Emit a loop matching the single character instruction after \p ip
+    /// between \p min and \p max times, mirroring `run_scm_loop`.
+    fn emit_scm_loop(&mut self, ip: IP, min: usize, max: usize, greedy: bool) {
+        let body = &self.re.insns[ip + 1];
+        let continuation = ip + 2;
+        if min > 0 {
+            self.emit_counted_loop(body, min, self.backtrack);
+        }
+        let min_pos = self.pos();
+        if max > min {
+            let done = self.b.create_block();
+            self.emit_counted_loop(body, max - min, done);
+            self.b.ins().jump(done, &[]);
+            self.b.switch_to_block(done);
+
+            let max_pos = self.pos();
+            let differ = self.b.ins().icmp(IntCC::NotEqual, min_pos, max_pos);
+            let save = self.b.create_block();
+            let cont = self.b.create_block();
+            self.b.ins().brif(differ, save, &[], cont, &[]);
+
+            self.b.switch_to_block(save);
+            let tag = if greedy {
+                TAG_GREEDY_LOOP_1CHAR
+            } else {
+                TAG_NON_GREEDY_LOOP_1CHAR
+            };
+            let header = self.entry_header(tag, continuation);
+            let zero = self.iconst(0);
+            self.push([header, min_pos, max_pos, zero]);
+            self.b.ins().jump(cont, &[]);
+
+            self.b.switch_to_block(cont);
+            // Start at the max (min) if greedy (nongreedy).
+            let pos = if greedy { max_pos } else { min_pos };
+            self.b.def_var(self.vars.pos, pos);
+        }
+        let next = self.blocks[continuation];
+        self.b.ins().jump(next, &[]);
+    }
+
+    /// Match \p body up to \p n times, jumping to \p fail if it fails to
+    /// match.
+    fn emit_counted_loop(&mut self, body: &Insn, n: usize, fail: Block) {
+        let head = self.b.create_block();
+        let iterate = self.b.create_block();
+        let done = self.b.create_block();
+        let zero = self.iconst(0);
+        self.b.def_var(self.vars.count, zero);
+        self.b.ins().jump(head, &[]);
+
+        self.b.switch_to_block(head);
+        let count = self.b.use_var(self.vars.count);
+        let more = self
+            .b
+            .ins()
+            .icmp_imm(IntCC::UnsignedLessThan, count, n as i64);
+        self.b.ins().brif(more, iterate, &[], done, &[]);
+
+        self.b.switch_to_block(iterate);
+        self.emit_match(body, fail);
+        let count = self.b.use_var(self.vars.count);
+        let count = self.b.ins().iadd_imm(count, 1);
+        self.b.def_var(self.vars.count, count);
+        self.b.ins().jump(head, &[]);
+
+        self.b.switch_to_block(done);
+    }
+
+    /// Emit the backtrack block, which pops the top entry of the stack and
+    /// acts on it, mirroring `try_backtrack`.
+    fn emit_backtrack(&mut self) {
+        self.b.switch_to_block(self.backtrack);
+        let sp = self.b.use_var(self.vars.sp);
+        let sp = self.b.ins().iadd_imm(sp, -ENTRY_WORDS);
+        self.b.def_var(self.vars.sp, sp);
+        let header = self.load_word(sp, 0);
+        let a = self.load_word(sp, 1);
+        let b = self.load_word(sp, 2);
+        let c = self.load_word(sp, 3);
+        self.b.def_var(self.vars.a, a);
+        self.b.def_var(self.vars.b, b);
+        self.b.def_var(self.vars.c, c);
+        let payload = self.b.ins().ushr_imm(header, TAG_BITS);
+        self.b.def_var(self.vars.target, payload);
+        let tag = self.b.ins().band_imm(header, (1 << TAG_BITS) - 1);
+
+        let set_position = self.b.create_block();
+        let set_capture_group = self.b.create_block();
+        let set_loop_data = self.b.create_block();
+        let enter_non_greedy_loop = self.b.create_block();
+        let greedy_loop = self.b.create_block();
+        let non_greedy_loop = self.b.create_block();
+        let mut switch = Switch::new();
+        switch.set_entry(TAG_EXHAUSTED as u128, self.exhausted);
+        switch.set_entry(TAG_SET_POSITION as u128, set_position);
+        switch.set_entry(TAG_SET_CAPTURE_GROUP as u128, set_capture_group);
+        switch.set_entry(TAG_SET_LOOP_DATA as u128, set_loop_data);
+        switch.set_entry(TAG_ENTER_NON_GREEDY_LOOP as u128, enter_non_greedy_loop);
+        switch.set_entry(TAG_GREEDY_LOOP_1CHAR as u128, greedy_loop);
+        switch.set_entry(TAG_NON_GREEDY_LOOP_1CHAR as u128, non_greedy_loop);
+        switch.emit(&mut self.b, tag, self.exhausted);
+
+        self.b.switch_to_block(set_position);
+        let a = self.b.use_var(self.vars.a);
+        self.b.def_var(self.vars.pos, a);
+        self.b.ins().jump(self.dispatch, &[]);
+
+        // Restore a capture group or loop, and keep backtracking.
+        for (block, base) in [(set_capture_group, 0), (set_loop_data, self.loops_base)] {
+            self.b.switch_to_block(block);
+            let id = self.b.use_var(self.vars.target);
+            let idx = self.b.ins().ishl_imm(id, 1);
+            let idx = self.b.ins().iadd_imm(idx, base);
+            let a = self.b.use_var(self.vars.a);
+            let b = self.b.use_var(self.vars.b);
+            self.store_word(idx, 0, a);
+            self.store_word(idx, 1, b);
+            self.b.ins().jump(self.backtrack, &[]);
+        }
+
+        self.b.switch_to_block(enter_non_greedy_loop);
+        let target = self.b.use_var(self.vars.target);
+        let mut switch = Switch::new();
+        for &(ip, block) in &self.reentry_blocks {
+            switch.set_entry(ip as u128, block);
+        }
+        switch.emit(&mut self.b, target, self.exhausted);
+        for (ip, block) in core::mem::take(&mut self.reentry_blocks) {
+            self.emit_reentry(ip, block);
+        }
+
+        // Single character loops. If min is equal to max, there is no more
+        // backtracking to be done; otherwise move max left (min right), keep
+        // the entry, and retry the continuation.
+        for (block, greedy) in [(greedy_loop, true), (non_greedy_loop, false)] {
+            self.b.switch_to_block(block);
+            let min = self.b.use_var(self.vars.a);
+            let max = self.b.use_var(self.vars.b);
+            let exhausted = self.b.ins().icmp(IntCC::Equal, min, max);
+            self.check_not(exhausted, self.backtrack);
+            let sp = self.b.use_var(self.vars.sp);
+            let pos = if greedy {
+                let new_max = self.prev_char_boundary(max);
+                self.store_word(sp, 2, new_max);
+                new_max
+            } else {
+                let b0 = self.byte(min, 0);
+                let n = self.seq_len(b0);
+                let new_min = self.b.ins().iadd(min, n);
+                self.store_word(sp, 1, new_min);
+                new_min
+            };
+            let sp = self.b.ins().iadd_imm(sp, ENTRY_WORDS);
+            self.b.def_var(self.vars.sp, sp);
+            self.b.def_var(self.vars.pos, pos);
+            self.b.ins().jump(self.dispatch, &[]);
+        }
+    }
+
+    /// Emit \p block, which backtracks into the non-greedy loop at \p ip.
+    fn emit_reentry(&mut self, ip: IP, block: Block) {
+        let Insn::EnterLoop(fields) = &self.re.insns[ip] else {
+            unreachable!("Reentry must point at a loop instruction")
+        };
+        self.b.switch_to_block(block);
+        let orig_pos = self.b.use_var(self.vars.a);
+        let iters = self.b.use_var(self.vars.b);
+        let entry = self.b.use_var(self.vars.c);
+        // Replace the entry with one restoring the position before the loop,
+        // should we backtrack out of it.
+        let sp = self.b.use_var(self.vars.sp);
+        let header = self.entry_header(TAG_SET_LOOP_DATA, fields.loop_id as usize);
+        self.store_word(sp, 0, header);
+        self.store_word(sp, 1, iters);
+        self.store_word(sp, 2, orig_pos);
+        let sp = self.b.ins().iadd_imm(sp, ENTRY_WORDS);
+        self.b.def_var(self.vars.sp, sp);
+        self.b.def_var(self.vars.pos, entry);
+        self.prepare_to_enter_loop(fields, iters, entry);
+        let body = self.blocks[ip + 1];
+        self.b.ins().jump(body, &[]);
+    }
+
+    /// Emit the dispatch block, which jumps to the block of the IP in
+    /// `target`.
+    fn emit_dispatch(&mut self) {
+        self.b.switch_to_block(self.dispatch);
+        let target = self.b.use_var(self.vars.target);
+        let mut switch = Switch::new();
+        for (ip, &block) in self.blocks.iter().enumerate() {
+            switch.set_entry(ip as u128, block);
+        }
+        switch.emit(&mut self.b, target, self.exhausted);
+    }
+}
@@ -0,0 +1,1195 @@
+//! A lazily built DFA, for regexes without backreferences or lookarounds.
+//!
+//! The IR is compiled to a Thompson NFA over UTF-8 bytes, once forwards and
+//! once reversed. A search runs the forward DFA to find the end of the
+//! leftmost match, preferring alternatives in the same order as the
+//! backtracker, and then runs the reverse DFA back from that end to find the
+//! start. DFA states are built from the NFA as the text requires them, and
+//! cached, so that a search takes time linear in the length of the text. The
+//! regex's engine is then needed only to find capture groups within the span.
+
+use crate::api::Match;
+use crate::bytesearch::ByteSearcher;
+use crate::codepointset::{CODE_POINT_MAX, CodePointSet, Interval};
+use crate::engine::EngineExecutor;
+use crate::exec::{self, MatchProducer};
+use crate::indexing::{AsciiInput, InputIndexer, Utf8Input};
+use crate::insn::{CompiledRegex, StartPredicate};
+use crate::ir::{self, AnchorType, Node, Quantifier};
+use crate::unicode;
+use crate::util::Pool;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use hashbrown::HashMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// The most NFA states we will build. Larger regexes use the engine.
+const MAX_NFA_STATES: usize = 20_000;
+
+/// The most memory, in bytes, which the cached states of one DFA may use
+/// before they are cleared.
+const CACHE_CAPACITY: usize = 2 << 20;
+
+/// The most times a search may clear a DFA's cache before giving up on the
+/// DFA, and using the engine instead.
+const MAX_CACHE_CLEARS: usize = 8;
+
+/// The approximate memory used by a DFA state, besides its transitions and
+/// NFA states.
+const STATE_OVERHEAD: usize = 64;
+
+/// An index of an NFA state.
+type StateID = u32;
+
+/// What is known of the text on one side of a position: whether there is a
+/// byte there, and if so whether it is a word char.
+type Context = u32;
+const CTX_BOUNDARY: Context = 0;
+const CTX_WORD: Context = 1;
+const CTX_OTHER: Context = 2;
+
+/// \return the context given by the byte \p b.
+/// Word chars are all ASCII, so no byte of a multibyte char is one.
+#[inline(always)]
+fn byte_context(b: u8) -> Context {
+    if b.is_ascii_alphanumeric() || b == b'_' {
+        CTX_WORD
+    } else {
+        CTX_OTHER
+    }
+}
+
+/// A zero-width assertion about the text around a position.
+#[derive(Debug, Copy, Clone)]
+enum Look {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+impl Look {
+    /// \return whether we hold between text of contexts \p before and
+    /// \p after, in text order.
+    fn holds(self, before: Context, after: Context) -> bool {
+        match self {
+            Look::Start => before == CTX_BOUNDARY,
+            Look::End => after == CTX_BOUNDARY,
+            Look::WordBoundary => (before == CTX_WORD) != (after == CTX_WORD),
+            Look::NotWordBoundary => (before == CTX_WORD) == (after == CTX_WORD),
+        }
+    }
+}
+
+/// A state of the NFA.
+#[derive(Debug, Clone)]
+enum State {
+    /// Consume a byte in the inclusive range, then continue at `next`.
+    Range { lo: u8, hi: u8, next: StateID },
+
+    /// Continue at each of the states, in priority order.
+    /// This never matches if there are none.
+    Union(Vec<StateID>),
+
+    /// Continue at `next` if the assertion holds.
+    Look { look: Look, next: StateID },
+
+    /// The regex has matched.
+    Match,
+}
+
+/// An NFA which matches in one direction.
+#[derive(Debug, Clone)]
+struct Prog {
+    states: Vec<State>,
+
+    /// Where an anchored search begins.
+    start: StateID,
+
+    /// Where an unanchored search begins: a lazy loop over any char, which
+    /// tries `start` at every char boundary.
+    unanchored: StateID,
+
+    /// Whether we match the text from its end towards its start.
+    reverse: bool,
+}
+
+/// Append to \p out sequences of UTF-8 byte ranges which together match
+/// exactly the code points of \p iv, other than surrogates.
+fn utf8_sequences(iv: Interval, out: &mut Vec<Vec<(u8, u8)>>) {
+    let mut pending = vec![(iv.first, iv.last.min(CODE_POINT_MAX))];
+    'next: while let Some((first, last)) = pending.pop() {
+        if first > last {
+            continue;
+        }
+        // Surrogates have no UTF-8 encoding.
+        if first <= 0xDFFF && last >= 0xD800 {
+            if last > 0xDFFF {
+                pending.push((0xE000, last));
+            }
+            if first < 0xD800 {
+                pending.push((first, 0xD7FF));
+            }
+            continue;
+        }
+        // Split where the length of the encoding changes.
+        for max in [0x7F, 0x7FF, 0xFFFF] {
+            if first <= max && max < last {
+                pending.push((max + 1, last));
+                pending.push((first, max));
+                continue 'next;
+            }
+        }
+        if last <= 0x7F {
+            out.push(vec![(first as u8, last as u8)]);
+            continue;
+        }
+        // Split until every continuation byte after the first which differs
+        // between the ends spans its full range.
+        for i in 1..4 {
+            let m = (1 << (6 * i)) - 1;
+            if first & !m != last & !m {
+                if first & m != 0 {
+                    pending.push(((first | m) + 1, last));
+                    pending.push((first, first | m));
+                    continue 'next;
+                }
+                if last & m != m {
+                    pending.push((last & !m, last));
+                    pending.push((first, (last & !m) - 1));
+                    continue 'next;
+                }
+            }
+        }
+        let (mut lo, mut hi) = ([0; 4], [0; 4]);
+        let lo = char::from_u32(first)
+            .unwrap()
+            .encode_utf8(&mut lo)
+            .as_bytes();
+        let hi = char::from_u32(last)
+            .unwrap()
+            .encode_utf8(&mut hi)
+            .as_bytes();
+        debug_assert!(lo.len() == hi.len());
+        out.push(lo.iter().copied().zip(hi.iter().copied()).collect());
+    }
+}
+
+/// Compiles IR to an NFA.
+struct Builder {
+    states: Vec<State>,
+
+    // Whether we are building an NFA which matches backwards.
+    reverse: bool,
+
+    // Whether the regex is in unicode mode, which affects case folding.
+    unicode: bool,
+}
+
+impl Builder {
+    /// Add a state, \return its ID, or None if there are too many.
+    fn add(&mut self, state: State) -> Option<StateID> {
+        if self.states.len() >= MAX_NFA_STATES {
+            return None;
+        }
+        self.states.push(state);
+        Some((self.states.len() - 1) as StateID)
+    }
+
+    /// \return a state which continues at any of \p alternatives, in order.
+    fn union(&mut self, alternatives: Vec<StateID>) -> Option<StateID> {
+        match alternatives.as_slice() {
+            [only] => Some(*only),
+            _ => self.add(State::Union(alternatives)),
+        }
+    }
+
+    /// Compile a sequence of byte ranges in text order, continuing at
+    /// \p next.
+    fn byte_ranges(&mut self, ranges: &[(u8, u8)], mut next: StateID) -> Option<StateID> {
+        // States are built from the last to be matched to the first.
+        for i in 0..ranges.len() {
+            let (lo, hi) = match self.reverse {
+                true => ranges[i],
+                false => ranges[ranges.len() - 1 - i],
+            };
+            next = self.add(State::Range { lo, hi, next })?;
+        }
+        Some(next)
+    }
+
+    /// Compile a match of any code point within \p ivs, continuing at
+    /// \p next.
+    fn code_points(&mut self, ivs: &[Interval], next: StateID) -> Option<StateID> {
+        let mut sequences = Vec::new();
+        for iv in ivs {
+            utf8_sequences(*iv, &mut sequences);
+        }
+        let mut alternatives = Vec::with_capacity(sequences.len());
+        for seq in &sequences {
+            alternatives.push(self.byte_ranges(seq, next)?);
+        }
+        self.union(alternatives)
+    }
+
+    /// Compile a match of the code point \p cp, or any code point which it
+    /// matches case-insensitively if \p icase is set.
+    fn code_point(&mut self, cp: u32, icase: bool, next: StateID) -> Option<StateID> {
+        let ivs: Vec<Interval> = unicode::expand_code_point(cp, icase, self.unicode)
+            .into_iter()
+            .map(|c| Interval { first: c, last: c })
+            .collect();
+        self.code_points(&ivs, next)
+    }
+
+    /// Compile \p quant repetitions of \p loopee, continuing at \p next.
+    /// \return None if an optional iteration may match the empty string:
+    /// such iterations are rejected by the backtracker, in a way that an
+    /// NFA cannot follow.
+    fn repeat(&mut self, loopee: &Node, quant: &Quantifier, next: StateID) -> Option<StateID> {
+        let optional = quant.max != Some(quant.min);
+        if optional && ir::match_len(loopee, &[]).is_some_and(|len| len.min == 0) {
+            return None;
+        }
+        let ordered = |body: StateID| match quant.greedy {
+            true => vec![body, next],
+            false => vec![next, body],
+        };
+        let mut cur = next;
+        match quant.max {
+            None => {
+                // A star; its body is patched in once it is built.
+                cur = self.add(State::Union(Vec::new()))?;
+                let body = self.compile(loopee, cur)?;
+                self.states[cur as usize] = State::Union(ordered(body));
+            }
+            Some(max) => {
+                // Nest the optional iterations, so that each is only tried
+                // after the previous one.
+                for _ in quant.min..max {
+                    let body = self.compile(loopee, cur)?;
+                    cur = self.add(State::Union(ordered(body)))?;
+                }
+            }
+        }
+        for _ in 0..quant.min {
+            cur = self.compile(loopee, cur)?;
+        }
+        Some(cur)
+    }
+
+    /// Compile \p node, continuing at \p next.
+    /// \return the state which begins it, or None if it is not supported.
+    fn compile(&mut self, node: &Node, next: StateID) -> Option<StateID> {
+        match node {
+            Node::Empty | Node::Goal => Some(next),
+            &Node::Char { c } => self.code_point(c, false, next),
+            Node::ByteSequence(bytes) => {
+                let ranges: Vec<(u8, u8)> = bytes.iter().map(|&b| (b, b)).collect();
+                self.byte_ranges(&ranges, next)
+            }
+            Node::ByteSet(bytes) => {
+                let mut alternatives = Vec::with_capacity(bytes.len());
+                for &b in bytes {
+                    alternatives.push(self.add(State::Range { lo: b, hi: b, next })?);
+                }
+                self.union(alternatives)
+            }
+            Node::CharSet(chars) => {
+                let ivs: Vec<Interval> = chars
+                    .iter()
+                    .map(|&c| Interval { first: c, last: c })
+                    .collect();
+                self.code_points(&ivs, next)
+            }
+            Node::Cat(nodes) => {
+                let mut cur = next;
+                if self.reverse {
+                    for n in nodes {
+                        cur = self.compile(n, cur)?;
+                    }
+                } else {
+                    for n in nodes.iter().rev() {
+                        cur = self.compile(n, cur)?;
+                    }
+                }
+                Some(cur)
+            }
+            Node::Alt(left, right) => {
+                let left = self.compile(left, next)?;
+                let right = self.compile(right, next)?;
+                self.union(vec![left, right])
+            }
+            Node::MatchAny => self.code_points(
+                &[Interval {
+                    first: 0,
+                    last: CODE_POINT_MAX,
+                }],
+                next,
+            ),
+            Node::MatchAnyExceptLineTerminator => {
+                let mut terminators = CodePointSet::new();
+                for c in ['\n', '\r', '\u{2028}', '\u{2029}'] {
+                    terminators.add_one(c as u32);
+                }
+                self.code_points(terminators.inverted().intervals(), next)
+            }
+            // Line terminators may be several bytes, which is more than the
+            // context we keep.
+            Node::Anchor {
+                multiline: true, ..
+            } => None,
+            Node::Anchor { anchor_type, .. } => {
+                let look = match anchor_type {
+                    AnchorType::StartOfLine => Look::Start,
+                    AnchorType::EndOfLine => Look::End,
+                };
+                self.add(State::Look { look, next })
+            }
+            // Some non-ASCII chars are word chars here.
+            Node::WordBoundary {
+                unicode_icase: true,
+                ..
+            } => None,
+            &Node::WordBoundary { invert, .. } => {
+                let look = match invert {
+                    false => Look::WordBoundary,
+                    true => Look::NotWordBoundary,
+                };
+                self.add(State::Look { look, next })
+            }
+            Node::CaptureGroup { contents, .. } => self.compile(contents, next),
+            Node::BackRef { .. } | Node::LookaroundAssertion { .. } => None,
+            Node::Bracket(bc) => match bc.invert {
+                false => self.code_points(bc.cps.intervals(), next),
+                true => self.code_points(bc.cps.inverted().intervals(), next),
+            },
+            Node::StringSet {
+                alternatives,
+                icase,
+            } => {
+                let mut starts = Vec::with_capacity(alternatives.len());
+                for cps in alternatives {
+                    let mut cur = next;
+                    for i in 0..cps.len() {
+                        let cp = match self.reverse {
+                            true => cps[i],
+                            false => cps[cps.len() - 1 - i],
+                        };
+                        cur = self.code_point(cp, *icase, cur)?;
+                    }
+                    starts.push(cur);
+                }
+                self.union(starts)
+            }
+            Node::Loop { loopee, quant, .. } | Node::Loop1CharBody { loopee, quant } => {
+                self.repeat(loopee, quant, next)
+            }
+        }
+    }
+
+    /// Compile the regex \p re to an NFA matching in the given direction.
+    fn build(re: &ir::Regex, reverse: bool) -> Option<Prog> {
+        let mut builder = Builder {
+            states: Vec::new(),
+            reverse,
+            unicode: re.flags.unicode,
+        };
+        let matched = builder.add(State::Match)?;
+        let start = builder.compile(&re.node, matched)?;
+        let unanchored = builder.add(State::Union(Vec::new()))?;
+        let any = builder.code_points(
+            &[Interval {
+                first: 0,
+                last: CODE_POINT_MAX,
+            }],
+            unanchored,
+        )?;
+        builder.states[unanchored as usize] = State::Union(vec![start, any]);
+        Some(Prog {
+            states: builder.states,
+            start,
+            unanchored,
+            reverse,
+        })
+    }
+}
+
+/// A partition of bytes into classes, which no NFA state distinguishes.
+#[derive(Debug, Clone)]
+struct ByteClasses {
+    /// The class of each byte.
+    classes: [u8; 256],
+
+    /// A byte of each class.
+    reps: Vec<u8>,
+}
+
+impl ByteClasses {
+    fn new(progs: &[&Prog]) -> ByteClasses {
+        let mut starts_class = [false; 256];
+        let mut split = |lo: u8, hi: u8| {
+            starts_class[lo as usize] = true;
+            if hi < u8::MAX {
+                starts_class[hi as usize + 1] = true;
+            }
+        };
+        for prog in progs {
+            for state in &prog.states {
+                if let State::Range { lo, hi, .. } = *state {
+                    split(lo, hi);
+                }
+            }
+        }
+        // Word boundaries distinguish word chars.
+        for (lo, hi) in [(b'0', b'9'), (b'A', b'Z'), (b'_', b'_'), (b'a', b'z')] {
+            split(lo, hi);
+        }
+        let mut classes = [0; 256];
+        let mut reps = Vec::new();
+        for b in 0..=u8::MAX {
+            if b == 0 || starts_class[b as usize] {
+                reps.push(b);
+            }
+            classes[b as usize] = (reps.len() - 1) as u8;
+        }
+        ByteClasses { classes, reps }
+    }
+
+    /// \return the class of the byte \p b.
+    #[inline(always)]
+    fn get(&self, b: u8) -> usize {
+        self.classes[b as usize] as usize
+    }
+
+    /// \return the class which stands for the end of the text.
+    #[inline(always)]
+    fn eoi(&self) -> usize {
+        self.reps.len()
+    }
+
+    /// \return the number of transitions from each DFA state.
+    #[inline(always)]
+    fn stride(&self) -> usize {
+        self.reps.len() + 1
+    }
+}
+
+/// Distinguishes the Nfas of different regexes, so that a Cache built for
+/// one is not used with another.
+static NEXT_NFA_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The NFAs of a regex, from which DFA states are built lazily.
+#[derive(Debug, Clone)]
+pub struct Nfa {
+    id: usize,
+    forward: Prog,
+    reverse: Prog,
+    classes: ByteClasses,
+    pool: CachePool,
+}
+
+/// A search which exceeded the cache too often to be worth continuing with
+/// the DFA.
+#[derive(Debug)]
+struct GaveUp;
+
+impl Nfa {
+    /// \return the NFAs for \p re, or None if it is not supported: if it has
+    /// backreferences, lookarounds, multiline anchors or word boundaries
+    /// which are Unicode case-insensitive, or is too large.
+    pub fn new(re: &ir::Regex) -> Option<Nfa> {
+        let forward = Builder::build(re, false)?;
+        let reverse = Builder::build(re, true)?;
+        let classes = ByteClasses::new(&[&forward, &reverse]);
+        Some(Nfa {
+            id: NEXT_NFA_ID.fetch_add(1, Ordering::Relaxed),
+            forward,
+            reverse,
+            classes,
+            pool: CachePool::default(),
+        })
+    }
+
+    /// \return the end of the leftmost match at or after the offset \p start
+    /// of \p text, or None if there is none.
+    /// If \p anchored is set, the match must begin at \p start.
+    /// If \p earliest is set, return the end of the first match seen instead,
+    /// which suffices to know that there is a match.
+    fn find_end(
+        &self,
+        cache: &mut Cache,
+        text: &[u8],
+        start: usize,
+        anchored: bool,
+        earliest: bool,
+        pred: Option<&StartPredicate>,
+    ) -> Result<Option<usize>, GaveUp> {
+        let (prog, classes) = (&self.forward, &self.classes);
+        let Cache {
+            forward: dfa,
+            closure,
+            ..
+        } = cache;
+        dfa.clears = 0;
+        let ctx = match start {
+            0 => CTX_BOUNDARY,
+            _ => byte_context(text[start - 1]),
+        };
+        let mut state = dfa.start(prog, classes, closure, anchored, ctx)?;
+        let mut end = None;
+        let mut at = start;
+        // An inner literal's searcher remembers where the literal was found.
+        let inner = match pred {
+            Some(StartPredicate::InnerLiteral(literal)) => Some(literal.searcher()),
+            _ => None,
+        };
+        while at < text.len() {
+            // If no match is underway, skip to where the next may begin.
+            if let Some(pred) = pred.filter(|_| !anchored && dfa.is_unanchored_start(state)) {
+                let next = match &inner {
+                    Some(inner) => inner.find_in(&text[at..]),
+                    None => next_candidate(pred, &text[at..]),
+                };
+                match next {
+                    None => return Ok(None),
+                    Some(0) => {}
+                    Some(skip) => {
+                        at += skip;
+                        let ctx = byte_context(text[at - 1]);
+                        state = dfa.start(prog, classes, closure, false, ctx)?;
+                    }
+                }
+            }
+            let entry = dfa.next(prog, classes, closure, state, classes.get(text[at]))?;
+            if entry & 1 != 0 {
+                end = Some(at);
+                if earliest {
+                    return Ok(end);
+                }
+            }
+            state = entry >> 1;
+            if state == DEAD {
+                return Ok(end);
+            }
+            at += 1;
+        }
+        let entry = dfa.next(prog, classes, closure, state, classes.eoi())?;
+        if entry & 1 != 0 {
+            end = Some(text.len());
+        }
+        Ok(end)
+    }
+
+    /// \return the least offset, no less than \p start, from which the regex
+    /// matches \p text exactly up to the offset \p end.
+    fn find_start(
+        &self,
+        cache: &mut Cache,
+        text: &[u8],
+        start: usize,
+        end: usize,
+    ) -> Result<Option<usize>, GaveUp> {
+        let (prog, classes) = (&self.reverse, &self.classes);
+        let Cache {
+            reverse: dfa,
+            closure,
+            ..
+        } = cache;
+        dfa.clears = 0;
+        let ctx = match text.get(end) {
+            None => CTX_BOUNDARY,
+            Some(&b) => byte_context(b),
+        };
+        let mut state = dfa.start(prog, classes, closure, true, ctx)?;
+        let mut found = None;
+        let mut at = end;
+        loop {
+            // Whether we match at `at` depends on the byte before it, even if
+            // we will not consume it.
+            let class = match at {
+                0 => classes.eoi(),
+                _ => classes.get(text[at - 1]),
+            };
+            let entry = dfa.next(prog, classes, closure, state, class)?;
+            if entry & 1 != 0 {
+                found = Some(at);
+            }
+            state = entry >> 1;
+            if state == DEAD || at == start {
+                return Ok(found);
+            }
+            at -= 1;
+        }
+    }
+}
+
+/// \return the offset in \p haystack of the first byte at which \p pred
+/// permits a match to begin, or None if there is none.
+fn next_candidate(pred: &StartPredicate, haystack: &[u8]) -> Option<usize> {
+    match pred {
+        StartPredicate::Arbitrary | StartPredicate::StartAnchored => Some(0),
+        StartPredicate::ByteSet1(bytes) => bytes.find_in(haystack),
+        StartPredicate::ByteSet2(bytes) => bytes.find_in(haystack),
+        StartPredicate::ByteSet3(bytes) => bytes.find_in(haystack),
+        StartPredicate::ByteSeq(bytes) => bytes.find_in(haystack),
+        StartPredicate::ByteBracket(bitmap) => bitmap.find_in(haystack),
+        #[cfg(feature = "perf-literal")]
+        StartPredicate::LiteralSet(set) => set.find_in(haystack),
+        StartPredicate::InnerLiteral(literal) => literal.searcher().find_in(haystack),
+    }
+}
+
+/// The DFA state which matches nothing, and never will.
+const DEAD: u32 = 0;
+
+/// A transition which has not been computed yet.
+const UNKNOWN: u32 = u32::MAX;
+
+/// The cached states of the DFA for one direction.
+#[derive(Debug, Default)]
+struct Dfa {
+    /// Transitions, indexed by state * stride + class. Each holds the next
+    /// state shifted left by one, with the low bit set if the state matches
+    /// before the byte is consumed.
+    trans: Vec<u32>,
+
+    /// The NFA states of each DFA state, in priority order and before their
+    /// closure is taken, followed by the context of the byte before.
+    states: Vec<Box<[StateID]>>,
+
+    /// Maps the contents of each state to its index.
+    map: HashMap<Box<[StateID]>, u32>,
+
+    /// Start states, indexed by whether they are anchored and the context.
+    starts: [u32; 6],
+
+    /// The approximate memory used by the states.
+    memory: usize,
+
+    /// How many times the current search has cleared the cache.
+    clears: usize,
+}
+
+impl Dfa {
+    /// Discard all states, leaving only the dead state.
+    fn reset(&mut self, stride: usize) {
+        self.trans.clear();
+        self.states.clear();
+        self.map.clear();
+        self.starts = [UNKNOWN; 6];
+        self.memory = 0;
+        self.trans.resize(stride, DEAD << 1);
+        self.states.push(Box::new([]));
+    }
+
+    /// \return whether \p state is where an unanchored search begins.
+    #[inline(always)]
+    fn is_unanchored_start(&self, state: u32) -> bool {
+        self.starts[..3].contains(&state)
+    }
+
+    /// \return the state which begins a search, where the byte before has
+    /// context \p ctx.
+    fn start(
+        &mut self,
+        prog: &Prog,
+        classes: &ByteClasses,
+        closure: &mut Closure,
+        anchored: bool,
+        ctx: Context,
+    ) -> Result<u32, GaveUp> {
+        let slot = anchored as usize * 3 + ctx as usize;
+        if self.starts[slot] == UNKNOWN {
+            let root = match anchored {
+                true => prog.start,
+                false => prog.unanchored,
+            };
+            closure.next.clear();
+            closure.next.extend([root, ctx]);
+            self.starts[slot] = self.add_state(&closure.next, classes.stride())?;
+        }
+        Ok(self.starts[slot])
+    }
+
+    /// \return the transition from \p state for the byte class \p class.
+    #[inline(always)]
+    fn next(
+        &mut self,
+        prog: &Prog,
+        classes: &ByteClasses,
+        closure: &mut Closure,
+        state: u32,
+        class: usize,
+    ) -> Result<u32, GaveUp> {
+        match self.trans[state as usize * classes.stride() + class] {
+            UNKNOWN => self.compute(prog, classes, closure, state, class),
+            entry => Ok(entry),
+        }
+    }
+
+    /// Compute, cache and \return the transition from \p from for the byte
+    /// class \p class.
+    #[cold]
+    fn compute(
+        &mut self,
+        prog: &Prog,
+        classes: &ByteClasses,
+        closure: &mut Closure,
+        from: u32,
+        class: usize,
+    ) -> Result<u32, GaveUp> {
+        let key = &self.states[from as usize];
+        let (remembered, set) = key.split_last().expect("Dead state should be precomputed");
+        let byte = classes.reps.get(class).copied();
+        let upcoming = byte.map_or(CTX_BOUNDARY, byte_context);
+        let (before, after) = match prog.reverse {
+            false => (*remembered, upcoming),
+            true => (upcoming, *remembered),
+        };
+        // The reverse DFA must find every start, not just the preferred one.
+        let matched = closure.compute(prog, set, before, after, !prog.reverse);
+        let mut next = DEAD;
+        let clears = self.clears;
+        if let Some(byte) = byte {
+            closure.step(prog, byte);
+            next = self.add_state(&closure.next, classes.stride())?;
+        }
+        let entry = (next << 1) | matched as u32;
+        // If the cache was cleared, `from` is gone.
+        if self.clears == clears {
+            self.trans[from as usize * classes.stride() + class] = entry;
+        }
+        Ok(entry)
+    }
+
+    /// \return the index of the state with contents \p key, adding it if
+    /// needed.
+    fn add_state(&mut self, key: &[StateID], stride: usize) -> Result<u32, GaveUp> {
+        if key.len() == 1 {
+            return Ok(DEAD);
+        }
+        if let Some(&idx) = self.map.get(key) {
+            return Ok(idx);
+        }
+        let cost = stride * 4 + key.len() * 8 + STATE_OVERHEAD;
+        if self.memory + cost > CACHE_CAPACITY {
+            self.clears += 1;
+            if self.clears > MAX_CACHE_CLEARS {
+                return Err(GaveUp);
+            }
+            self.reset(stride);
+        }
+        let idx = self.states.len() as u32;
+        let key: Box<[StateID]> = key.into();
+        self.states.push(key.clone());
+        self.map.insert(key, idx);
+        self.trans.resize(self.trans.len() + stride, UNKNOWN);
+        self.memory += cost;
+        Ok(idx)
+    }
+}
+
+/// Buffers for computing DFA states from NFA states.
+#[derive(Debug, Default)]
+struct Closure {
+    stack: Vec<StateID>,
+
+    /// The generation in which each NFA state was last seen.
+    seen: Vec<u32>,
+    generation: u32,
+
+    /// The Range states of the closure, in priority order.
+    ranges: Vec<StateID>,
+
+    /// The contents of the next DFA state.
+    next: Vec<StateID>,
+}
+
+impl Closure {
+    /// Begin a new generation, so that no NFA state is seen.
+    fn begin(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.seen.fill(0);
+            self.generation = 1;
+        }
+    }
+
+    /// \return whether \p id had already been seen in this generation, and
+    /// mark it as seen.
+    #[inline(always)]
+    fn check_seen(&mut self, id: StateID) -> bool {
+        let seen = &mut self.seen[id as usize];
+        core::mem::replace(seen, self.generation) == self.generation
+    }
+
+    /// Collect the Range states reachable from \p set without consuming a
+    /// byte, in priority order, where the text has contexts \p before and
+    /// \p after. If \p leftmost_first is set, stop at the first match, as
+    /// lower priority states can no longer matter.
+    /// \return whether the regex matched.
+    fn compute(
+        &mut self,
+        prog: &Prog,
+        set: &[StateID],
+        before: Context,
+        after: Context,
+        leftmost_first: bool,
+    ) -> bool {
+        self.begin();
+        self.ranges.clear();
+        self.stack.clear();
+        let mut matched = false;
+        for &root in set {
+            self.stack.push(root);
+            while let Some(id) = self.stack.pop() {
+                if self.check_seen(id) {
+                    continue;
+                }
+                match &prog.states[id as usize] {
+                    State::Range { .. } => self.ranges.push(id),
+                    State::Union(alternatives) => self.stack.extend(alternatives.iter().rev()),
+                    State::Look { look, next } => {
+                        if look.holds(before, after) {
+                            self.stack.push(*next)
+                        }
+                    }
+                    State::Match => {
+                        matched = true;
+                        if leftmost_first {
+                            return true;
+                        }
+                    }
+                }
+            }
+        }
+        matched
+    }
+
+    /// Consume \p byte from the Range states of the closure, setting `next`
+    /// to the contents of the next DFA state.
+    fn step(&mut self, prog: &Prog, byte: u8) {
+        self.begin();
+        self.next.clear();
+        for i in 0..self.ranges.len() {
+            if let State::Range { lo, hi, next } = prog.states[self.ranges[i] as usize] {
+                if lo <= byte && byte <= hi && !self.check_seen(next) {
+                    self.next.push(next);
+                }
+            }
+        }
+        self.next.push(byte_context(byte));
+    }
+}
+
+/// The DFA states built by searches with a regex, which may be reused by
+/// later searches with it.
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    /// The ID of the Nfa whose states we hold, or 0 if none.
+    id: usize,
+    forward: Dfa,
+    reverse: Dfa,
+    closure: Closure,
+}
+
+impl Cache {
+    /// Prepare to search with \p nfa, discarding the states of any other.
+    fn prepare(&mut self, nfa: &Nfa) {
+        if self.id != nfa.id {
+            let stride = nfa.classes.stride();
+            self.forward.reset(stride);
+            self.reverse.reset(stride);
+            let nfa_states = nfa.forward.states.len().max(nfa.reverse.states.len());
+            self.closure.seen.clear();
+            self.closure.seen.resize(nfa_states, 0);
+            self.closure.generation = 0;
+            self.id = nfa.id;
+        }
+    }
+}
+
+/// The Cache kept by an Nfa for searches which are not given one, so that
+/// successive searches need not rebuild the same states.
+type CachePool = Pool<Cache>;
+
+/// A Cache which is returned to the pool it came from, if any, when dropped.
+#[derive(Debug)]
+struct PooledCache<'r> {
+    cache: Cache,
+    pool: Option<&'r CachePool>,
+}
+
+impl PooledCache<'_> {
+    /// Give up the Cache, without returning it to the pool.
+    fn into_inner(mut self) -> Cache {
+        self.pool = None;
+        core::mem::take(&mut self.cache)
+    }
+}
+
+impl core::ops::Deref for PooledCache<'_> {
+    type Target = Cache;
+    fn deref(&self) -> &Cache {
+        &self.cache
+    }
+}
+
+impl core::ops::DerefMut for PooledCache<'_> {
+    fn deref_mut(&mut self) -> &mut Cache {
+        &mut self.cache
+    }
+}
+
+impl Drop for PooledCache<'_> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool {
+            pool.put(core::mem::take(&mut self.cache));
+        }
+    }
+}
+
+/// \return the Nfa to search \p re with, or None if it must be searched by
+/// its engine. A backtrack limit or interrupt selects the engine, whose
+/// searches they bound.
+fn usable_nfa(re: &CompiledRegex) -> Option<&Nfa> {
+    match (&re.backtrack_limit, &re.interrupt) {
+        (None, None) => re.nfa.as_deref(),
+        _ => None,
+    }
+}
+
+/// \return whether the match of \p re must begin where the search does.
+fn is_anchored(re: &CompiledRegex) -> bool {
+    re.flags.sticky || matches!(re.start_pred, StartPredicate::StartAnchored)
+}
+
+/// \return the start predicate of \p re, if it may be used to skip ahead.
+fn skip_predicate(re: &CompiledRegex) -> Option<&StartPredicate> {
+    // Prefix search is not used when UTF-16 support is active, as with the
+    // backtracker.
+    if cfg!(feature = "utf16") {
+        return None;
+    }
+    match &re.start_pred {
+        StartPredicate::Arbitrary | StartPredicate::StartAnchored => None,
+        pred => Some(pred),
+    }
+}
+
+/// \return whether \p re matches \p text, or None if the DFA cannot say.
+pub(crate) fn is_match(re: &CompiledRegex, text: &str, cache: &mut Cache) -> Option<bool> {
+    let nfa = usable_nfa(re)?;
+    cache.prepare(nfa);
+    let end = nfa.find_end(
+        cache,
+        text.as_bytes(),
+        0,
+        is_anchored(re),
+        true,
+        skip_predicate(re),
+    );
+    end.ok().map(|end| end.is_some())
+}
+
+/// \return whether \p re matches \p text, or None if the DFA cannot say,
+/// using the Cache pooled by the regex.
+pub(crate) fn is_match_pooled(re: &CompiledRegex, text: &str) -> Option<bool> {
+    let pool = &usable_nfa(re)?.pool;
+    let mut cache = PooledCache {
+        cache: pool.get(),
+        pool: Some(pool),
+    };
+    is_match(re, text, &mut cache)
+}
+
+/// An Executor which finds the bounds of matches with a lazy DFA, where the
+/// regex permits, and otherwise searches with the regex's engine.
+#[derive(Debug)]
+pub struct LazyDfaExecutor<'r, 't> {
+    text: &'t str,
+    input: Utf8Input<'t>,
+    re: &'r CompiledRegex,
+    nfa: Option<&'r Nfa>,
+    cache: PooledCache<'r>,
+    fallback: EngineExecutor<'r, Utf8Input<'t>>,
+}
+
+impl<'r, 't> LazyDfaExecutor<'r, 't> {
+    /// Construct an executor for \p re searching \p text, taking the buffers
+    /// of the DFA and the engine from \p cache.
+    pub(crate) fn with_cache(
+        re: &'r CompiledRegex,
+        text: &'t str,
+        cache: &mut crate::cache::Cache,
+    ) -> Self {
+        let dfa_cache = core::mem::take(&mut cache.lazydfa);
+        let input = Utf8Input::new(text, re.flags.unicode);
+        let fallback = EngineExecutor::with_cache(re, input, cache);
+        Self::with_pooled_cache(
+            re,
+            text,
+            fallback,
+            PooledCache {
+                cache: dfa_cache,
+                pool: None,
+            },
+        )
+    }
+
+    /// Construct an executor for \p re searching \p text with \p cache.
+    fn with_pooled_cache(
+        re: &'r CompiledRegex,
+        text: &'t str,
+        fallback: EngineExecutor<'r, Utf8Input<'t>>,
+        cache: PooledCache<'r>,
+    ) -> Self {
+        Self {
+            text,
+            input: Utf8Input::new(text, re.flags.unicode),
+            re,
+            nfa: usable_nfa(re),
+            cache,
+            fallback,
+        }
+    }
+
+    /// Return our buffers to \p cache so that they may be reused.
+    pub(crate) fn into_cache(self, cache: &mut crate::cache::Cache) {
+        cache.lazydfa = self.cache.into_inner();
+        self.fallback.into_cache(cache);
+    }
+
+    /// \return the position at byte offset \p offset.
+    fn position(&self, offset: usize) -> <Utf8Input<'t> as InputIndexer>::Position {
+        self.input
+            .try_move_right(self.input.left_end(), offset)
+            .expect("Offset should be within the text")
+    }
+
+    /// \return the bounds of the next match at or after \p pos, found by
+    /// the DFA, or GaveUp if the DFA was not worth continuing with.
+    fn find_range(
+        &mut self,
+        nfa: &Nfa,
+        pos: <Utf8Input<'t> as InputIndexer>::Position,
+        anchored: bool,
+    ) -> Result<Option<(usize, usize)>, GaveUp> {
+        self.cache.prepare(nfa);
+        let text = self.text.as_bytes();
+        let start = self.input.pos_to_offset(pos);
+        let anchored = anchored || is_anchored(self.re);
+        let pred = skip_predicate(self.re);
+        let Some(end) = nfa.find_end(&mut self.cache, text, start, anchored, false, pred)? else {
+            return Ok(None);
+        };
+        if anchored {
+            return Ok(Some((start, end)));
+        }
+        let found = nfa.find_start(&mut self.cache, text, start, end)?;
+        debug_assert!(found.is_some(), "Reverse DFA should find the match");
+        Ok(found.map(|start| (start, end)))
+    }
+
+    /// Search for the next match at or after \p pos with the engine.
+    fn next_match_fallback(
+        &mut self,
+        pos: <Utf8Input<'t> as InputIndexer>::Position,
+        anchored: bool,
+        next_start: &mut Option<<Utf8Input<'t> as InputIndexer>::Position>,
+    ) -> Option<Match> {
+        match anchored {
+            true => self.fallback.next_match_anchored(pos, next_start),
+            false => self.fallback.next_match(pos, next_start),
+        }
+    }
+
+    /// Search for the next match at or after \p pos with the DFA, falling
+    /// back to the engine if it gives up.
+    fn next_match_dfa(
+        &mut self,
+        nfa: &Nfa,
+        pos: <Utf8Input<'t> as InputIndexer>::Position,
+        anchored: bool,
+        next_start: &mut Option<<Utf8Input<'t> as InputIndexer>::Position>,
+    ) -> Option<Match> {
+        let Ok(found) = self.find_range(nfa, pos, anchored) else {
+            return self.next_match_fallback(pos, anchored, next_start);
+        };
+        let (start, end) = found?;
+        if self.re.groups == 0 {
+            // If we matched the empty string, we have to increment.
+            let end_pos = self.position(end);
+            *next_start = match start == end {
+                false => Some(end_pos),
+                true => self.input.next_right_pos(end_pos),
+            };
+            return Some(Match {
+                range: start..end,
+                captures: Vec::new(),
+                group_names: self.re.group_names.clone(),
+            });
+        }
+        // Resolve the capture groups with the engine, from the start. The
+        // engine is authoritative, should it find a different match.
+        match self
+            .fallback
+            .next_match_anchored(self.position(start), next_start)
+        {
+            Some(m) if m.range == (start..end) => Some(m),
+            _ => self.next_match_fallback(pos, anchored, next_start),
+        }
+    }
+}
+
+impl<'t> MatchProducer for LazyDfaExecutor<'_, 't> {
+    type Position = <Utf8Input<'t> as InputIndexer>::Position;
+
+    fn initial_position(&self, offset: usize) -> Option<Self::Position> {
+        self.fallback.initial_position(offset)
+    }
+
+    fn next_match(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match> {
+        match self.nfa {
+            Some(nfa) => self.next_match_dfa(nfa, pos, false, next_start),
+            None => self.next_match_fallback(pos, false, next_start),
+        }
+    }
+
+    fn next_match_anchored(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match> {
+        match self.nfa {
+            Some(nfa) => self.next_match_dfa(nfa, pos, true, next_start),
+            None => self.next_match_fallback(pos, true, next_start),
+        }
+    }
+
+    fn abandoned(&self) -> Option<crate::api::MatchError> {
+        self.fallback.abandoned()
+    }
+}
+
+impl<'r, 't> exec::Executor<'r, 't> for LazyDfaExecutor<'r, 't> {
+    type AsAscii = EngineExecutor<'r, AsciiInput<'t>>;
+
+    fn new(re: &'r CompiledRegex, text: &'t str) -> Self {
+        let pool = usable_nfa(re).map(|nfa| &nfa.pool);
+        let cache = PooledCache {
+            cache: pool.map_or_else(Cache::default, CachePool::get),
+            pool,
+        };
+        let fallback = EngineExecutor::new(re, Utf8Input::new(text, re.flags.unicode));
+        Self::with_pooled_cache(re, text, fallback, cache)
+    }
+}
@@ -125,18 +125,29 @@ assert!(re.find("abcd").is_some());
 regress supports features (required by the EcmaScript spec) that regex does not, including backreferences and zero-width lookaround assertions.
 However the regex crate provides linear-time matching guarantees, while regress does not. This difference is due
 to the architecture: regex uses finite automata while regress uses "classical backtracking."
+Patterns without backreferences or lookarounds are searched with a lazy DFA, which takes time linear in the length of the text to find the bounds of a match, though capture groups within it are still found by backtracking, unless the linear-time PikeVM engine is chosen.
 
 
 # Architecture
 
 regress has a parser, intermediate representation, optimizer which acts on the IR, bytecode emitter, and two bytecode interpreters, referred to as "backends".
 
-The major interpreter is the "classical backtracking" which uses an explicit backtracking stack, similar to JS implementations. There is also the "PikeVM" pseudo-toy backend which is mainly used for testing and verification.
+The major interpreter is the "classical backtracking" which uses an explicit backtracking stack, similar to JS implementations. There is also the "PikeVM", which runs the alternatives of a match in lockstep and so takes time linear in the length of the text for patterns without backreferences or lookarounds. It is chosen with [`RegexBuilder::engine`], where [`Engine::Auto`] picks it for patterns prone to catastrophic backtracking. The backtracker may instead memoize the states which failed to match, bounding its work for patterns without backreferences by the product of the lengths of the pattern and the text; [`Engine::Auto`] starts memoizing when a search runs long.
+
+Where the pattern permits, the default backend first finds the bounds of a match with a lazy DFA, built over UTF-8 bytes from the IR, and only runs the chosen engine within that span to resolve capture groups. The DFA's states are cached with the regex, or in a [`Cache`] when one is given.
+
+The backtracker runs anchored searches, including those resolving the capture groups within a span found by the DFA, in a single forward scan if the program is "one-pass": wherever it may continue in two ways, the next character decides which. Such patterns, like `^(\d{4})-(\d{2})-(\d{2})$`, are matched without a backtrack stack.
+
+With the `backend-jit` feature, `Engine::Jit` compiles the backtracker's program to machine code with Cranelift. Each instruction becomes a block of native code, and the backtrack stack an array of fixed-size entries; UTF-8 searches run the compiled code in place of `try_at_pos`, with the same semantics, after the one-pass matcher where that applies. A match whose stack outgrows a fixed size gives up and is interpreted, as the interpreter's smaller entries are faster once the stack leaves the cache. Programs with backreferences or lookarounds, and searches with a backtrack limit or interrupt, are interpreted.
 
 # Crate features
 
 - **utf16**. When enabled, additional APIs are made available that allow matching text formatted in UTF-16 and UCS-2 (`&[u16]`) without going through a conversion to and from UTF-8 (`&str`) first. This is particularly useful when interacting with and/or (re)implementing existing systems that use those encodings, such as JavaScript, Windows, and the JVM.
 
+- **backend-jit**. When enabled, `Engine::Jit` compiles the backtracker to machine code with [Cranelift](https://cranelift.dev), which can speed up patterns that backtrack through many short matches; `cargo bench --features backend-jit --bench jit` compares it with the interpreter. It adds Cranelift as a dependency, and requires `std`.
+
+- **perf-literal**. Enabled by default. Searches for the start of a match among several literals, as for `foo|bar`, with [aho-corasick](https://docs.rs/aho-corasick). When disabled, that dependency is dropped and only the first bytes of the literals are searched for.
+
 - **pattern**. When enabled (nightly only), implements the `std::str::pattern::Pattern` trait for `Regex`, allowing it to be used with standard string methods like `str::find`, `str::contains`, `str::split`, etc.
 
 */
@@ -163,29 +174,50 @@ compile_error!(
 extern crate alloc;
 
 pub use crate::api::*;
+pub use crate::cache::Cache;
+pub use crate::engine::Engine;
+#[cfg(feature = "backend-pikevm")]
+pub use crate::regexset::{RegexSet, SetMatches, SetMatchesIter};
+#[cfg(feature = "utf16")]
+pub use crate::replace::ReplacerUtf16;
+pub use crate::replace::{ReplacementTemplate, Replacer, TemplateError};
+pub use crate::requiredliterals::{RequiredLiteral, RequiredLiterals};
+
+pub mod analysis;
+pub mod ast;
+pub mod syntax;
 
 #[macro_use]
 mod util;
 
 mod api;
 mod bytesearch;
+mod cache;
 mod charclasses;
 mod classicalbacktrack;
 mod codepointset;
 mod cursor;
 mod emit;
+mod engine;
 mod exec;
 mod indexing;
 mod insn;
 mod ir;
+#[cfg(feature = "backend-jit")]
+mod jit;
+mod lazydfa;
 // UTF-16 never matches against bytes, so the byte-oriented literal lowering is
 // UTF-8 only. See `emit_code_point_sequence` for the UTF-16 path.
 #[cfg(not(feature = "utf16"))]
 mod literal;
 mod matchers;
+mod onepass;
 mod optimizer;
 mod parse;
 mod position;
+mod printer;
+mod replace;
+mod requiredliterals;
 mod scm;
 mod startpredicate;
 mod types;
@@ -194,3 +226,5 @@ mod unicodetables;
 
 #[cfg(feature = "backend-pikevm")]
 mod pikevm;
+#[cfg(feature = "backend-pikevm")]
+mod regexset;
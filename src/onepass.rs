@@ -0,0 +1,538 @@
+//! One-pass matching of unambiguous programs.
+//!
+//! A program is one-pass if, wherever it may continue in two ways, the next
+//! character (or the end of the input) determines which of them could lead
+//! to a match. Such a program is run in a single forward scan, choosing its
+//! way at each branch by peeking at the next character, so it never
+//! backtracks and needs no backtrack stack. It finds the same match as the
+//! backtracker, because any other way would have failed.
+
+use crate::bytesearch::ByteSet;
+use crate::codepointset::{CODE_POINT_MAX, CodePointSet, Interval};
+use crate::cursor::{self, Forward};
+use crate::indexing::{ElementType, InputIndexer};
+use crate::insn::{CompiledRegex, Insn, LoopFields};
+use crate::matchers::CharProperties;
+use crate::scm::{self, SingleCharMatcher};
+use crate::types::{GroupData, IP, LoopData};
+use crate::util::DebugCheckIndex;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec::Vec};
+
+/// The most instructions of a program which is analyzed. Larger programs
+/// are not matched in one pass.
+const MAX_ONEPASS_INSNS: usize = 1024;
+
+/// The characters with which a match may continue from some instruction,
+/// and whether it may continue at the end of the input.
+#[derive(Debug, Clone, Default)]
+struct NextSet {
+    cps: CodePointSet,
+    end: bool,
+}
+
+impl NextSet {
+    /// \return a set of every character and the end of the input.
+    fn any() -> NextSet {
+        NextSet {
+            cps: all_code_points(),
+            end: true,
+        }
+    }
+
+    /// \return a set of the characters \p cps, not including the end of the
+    /// input.
+    fn chars(cps: CodePointSet) -> NextSet {
+        NextSet { cps, end: false }
+    }
+
+    /// Add the contents of \p rhs to the set.
+    fn union(&mut self, rhs: NextSet) {
+        self.cps.add_set(rhs.cps);
+        self.end |= rhs.end;
+    }
+
+    /// \return whether the set shares a character, or the end of the input,
+    /// with \p rhs.
+    fn overlaps(&self, rhs: &NextSet) -> bool {
+        if self.end && rhs.end {
+            return true;
+        }
+        let (mut lhs, mut rhs) = (self.cps.intervals(), rhs.cps.intervals());
+        while let (Some(l), Some(r)) = (lhs.first(), rhs.first()) {
+            if l.overlaps(*r) {
+                return true;
+            }
+            if l.last < r.last {
+                lhs = &lhs[1..];
+            } else {
+                rhs = &rhs[1..];
+            }
+        }
+        false
+    }
+
+    /// \return whether the set contains \p next, where None is the end of the
+    /// input.
+    #[inline]
+    fn contains(&self, next: Option<u32>) -> bool {
+        match next {
+            Some(c) => self.cps.contains(c),
+            None => self.end,
+        }
+    }
+}
+
+/// \return a set of every code point.
+fn all_code_points() -> CodePointSet {
+    CodePointSet::from_sorted_disjoint_intervals(vec![Interval::new(0, CODE_POINT_MAX)])
+}
+
+/// \return a set of the line terminators, ES9 11.3.
+fn line_terminators() -> CodePointSet {
+    CodePointSet::from_sorted_disjoint_intervals(vec![
+        Interval::new(0x0A, 0x0A),
+        Interval::new(0x0D, 0x0D),
+        Interval::new(0x2028, 0x2029),
+    ])
+}
+
+/// The progress of computing the NextSet of an instruction.
+#[derive(Debug, Clone)]
+enum Progress {
+    Unvisited,
+    Visiting,
+    Done(NextSet),
+}
+
+/// Computes the NextSets of the instructions of a program.
+struct Analyzer<'a> {
+    re: &'a CompiledRegex,
+    progress: Vec<Progress>,
+}
+
+impl Analyzer<'_> {
+    /// \return the characters which the single-character (or literal)
+    /// instruction at \p ip may consume first, or None if it does not consume
+    /// characters.
+    fn consumed(&self, ip: IP) -> Option<CodePointSet> {
+        let mut cps = CodePointSet::new();
+        match self.re.insns.iat(ip) {
+            &Insn::Char(c) => cps.add_one(c),
+            Insn::CharSet(chars) => chars.iter().for_each(|&c| cps.add_one(c)),
+            Insn::ByteSet2(bytes) => bytes.0.iter().for_each(|&b| cps.add_one(b.into())),
+            Insn::ByteSet3(bytes) => bytes.0.iter().for_each(|&b| cps.add_one(b.into())),
+            Insn::ByteSet4(bytes) => bytes.0.iter().for_each(|&b| cps.add_one(b.into())),
+            Insn::ByteSeq1(v) => cps = leading_char(v),
+            Insn::ByteSeq2(v) => cps = leading_char(v),
+            Insn::ByteSeq3(v) => cps = leading_char(v),
+            Insn::ByteSeq4(v) => cps = leading_char(v),
+            Insn::ByteSeq5(v) => cps = leading_char(v),
+            Insn::ByteSeq6(v) => cps = leading_char(v),
+            Insn::ByteSeq7(v) => cps = leading_char(v),
+            Insn::ByteSeq8(v) => cps = leading_char(v),
+            Insn::ByteSeq9(v) => cps = leading_char(v),
+            Insn::ByteSeq10(v) => cps = leading_char(v),
+            Insn::ByteSeq11(v) => cps = leading_char(v),
+            Insn::ByteSeq12(v) => cps = leading_char(v),
+            Insn::ByteSeq13(v) => cps = leading_char(v),
+            Insn::ByteSeq14(v) => cps = leading_char(v),
+            Insn::ByteSeq15(v) => cps = leading_char(v),
+            Insn::ByteSeq16(v) => cps = leading_char(v),
+            Insn::AsciiBracket(bitmap) => (0..=0x7F)
+                .filter(|&b| bitmap.contains(b))
+                .for_each(|b| cps.add_one(b.into())),
+            &Insn::Bracket(idx) => {
+                let bc = &self.re.brackets[idx];
+                cps = match bc.invert {
+                    false => bc.cps.clone(),
+                    true => bc.cps.inverted(),
+                };
+            }
+            Insn::MatchAny => cps = all_code_points(),
+            Insn::MatchAnyExceptLineTerminator => cps = line_terminators().inverted(),
+            _ => return None,
+        }
+        Some(cps)
+    }
+
+    /// \return the NextSet of the instruction at \p ip: the characters with
+    /// which a match may continue from it, ignoring the iteration counts of
+    /// loops. \return None if the program is not supported, or if an empty
+    /// path leads from \p ip back to itself.
+    fn next_set(&mut self, ip: IP) -> Option<NextSet> {
+        match self.progress.iat(ip) {
+            Progress::Done(set) => return Some(set.clone()),
+            Progress::Visiting => return None,
+            Progress::Unvisited => *self.progress.mat(ip) = Progress::Visiting,
+        }
+        let set = if let Some(cps) = self.consumed(ip) {
+            NextSet::chars(cps)
+        } else {
+            match self.re.insns.iat(ip) {
+                Insn::Goal => NextSet::any(),
+                Insn::JustFail => NextSet::default(),
+                Insn::StartOfLine { .. }
+                | Insn::WordBoundary { .. }
+                | Insn::WordBoundaryUnicodeICase { .. }
+                | Insn::BeginCaptureGroup(_)
+                | Insn::EndCaptureGroup(_)
+                | Insn::ResetCaptureGroup(_) => self.next_set(ip + 1)?,
+                &Insn::EndOfLine { multiline } => {
+                    let mut set = self.next_set(ip + 1)?;
+                    let allowed = match multiline {
+                        true => line_terminators(),
+                        false => CodePointSet::new(),
+                    };
+                    set.cps.intersect(allowed.intervals());
+                    set
+                }
+                &Insn::Jump { target } => self.next_set(target as IP)?,
+                &Insn::Alt { secondary } => {
+                    let mut set = self.next_set(ip + 1)?;
+                    set.union(self.next_set(secondary as IP)?);
+                    set
+                }
+                Insn::EnterLoop(fields) => {
+                    let mut set = self.next_set(fields.exit as IP)?;
+                    if fields.max_iters > 0 {
+                        set.union(self.next_set(ip + 1)?);
+                    }
+                    set
+                }
+                &Insn::LoopAgain { begin } => self.next_set(begin as IP)?,
+                &Insn::Loop1CharBody {
+                    min_iters,
+                    max_iters,
+                    ..
+                } => {
+                    let mut set = match min_iters {
+                        0 => self.next_set(ip + 2)?,
+                        _ => NextSet::default(),
+                    };
+                    if max_iters > 0 {
+                        set.union(NextSet::chars(self.consumed(ip + 1)?));
+                    }
+                    set
+                }
+                // Backreferences and lookarounds are not supported.
+                _ => return None,
+            }
+        };
+        *self.progress.mat(ip) = Progress::Done(set.clone());
+        Some(set)
+    }
+
+    /// \return the NextSet of the primary way to continue from the
+    /// instruction at \p ip, if it may continue in two ways, and Some(None)
+    /// if it may not. \return None if the ways overlap.
+    fn choice(&mut self, ip: IP) -> Option<Option<NextSet>> {
+        let (primary, secondary) = match self.re.insns.iat(ip) {
+            &Insn::Alt { secondary } => (self.next_set(ip + 1)?, self.next_set(secondary as IP)?),
+            Insn::EnterLoop(fields) if fields.min_iters < fields.max_iters => {
+                (self.next_set(ip + 1)?, self.next_set(fields.exit as IP)?)
+            }
+            // The body of a single character loop is matched directly.
+            &Insn::Loop1CharBody {
+                min_iters,
+                max_iters,
+                ..
+            } if min_iters < max_iters => {
+                let body = NextSet::chars(self.consumed(ip + 1)?);
+                return (!body.overlaps(&self.next_set(ip + 2)?)).then_some(None);
+            }
+            _ => return Some(None),
+        };
+        (!primary.overlaps(&secondary)).then_some(Some(primary))
+    }
+}
+
+/// \return the characters whose UTF-8 encoding may begin with the literal
+/// bytes \p v, or whose first byte may, as ASCII input matches bytes.
+fn leading_char(v: &[u8]) -> CodePointSet {
+    let mut cps = CodePointSet::new();
+    cps.add_one(v[0].into());
+    if v[0].is_ascii() {
+        return cps;
+    }
+    let decoded = (2..=v.len().min(4))
+        .find_map(|len| core::str::from_utf8(&v[..len]).ok())
+        .and_then(|s| s.chars().next());
+    match decoded {
+        Some(c) => cps.add_one(c.into()),
+        // The literal begins partway through a character.
+        None => cps = all_code_points(),
+    }
+    cps
+}
+
+/// A one-pass program, with the set of characters which select the primary
+/// way at each of its branches.
+#[derive(Debug, Clone)]
+pub struct OnePass {
+    // For each instruction which may continue in two ways, the NextSet of
+    // its primary way. A primary way is chosen if the next character is in
+    // this set, and the secondary way otherwise.
+    choices: Box<[Option<NextSet>]>,
+}
+
+impl OnePass {
+    /// \return the OnePass for \p re, or None if it is not one-pass.
+    pub(crate) fn new(re: &CompiledRegex) -> Option<OnePass> {
+        let unsupported = |insn: &Insn| {
+            matches!(
+                insn,
+                Insn::BackRef { .. } | Insn::Lookahead { .. } | Insn::Lookbehind { .. }
+            )
+        };
+        if re.insns.len() > MAX_ONEPASS_INSNS || re.insns.iter().any(unsupported) {
+            return None;
+        }
+        let mut analyzer = Analyzer {
+            re,
+            progress: vec![Progress::Unvisited; re.insns.len()],
+        };
+        let choices = (0..re.insns.len())
+            .map(|ip| analyzer.choice(ip))
+            .collect::<Option<Box<[_]>>>()?;
+        Some(OnePass { choices })
+    }
+
+    /// \return whether the primary way is chosen at the branch at \p ip,
+    /// given the character \p next.
+    #[inline]
+    fn choose_primary<Input: InputIndexer>(&self, ip: IP, next: Option<Input::Element>) -> bool {
+        match self.choices.iat(ip) {
+            Some(set) => set.contains(next.map(ElementType::as_u32)),
+            None => rs_unreachable!("Instruction should be a branch"),
+        }
+    }
+
+    /// Attempt to match \p re exactly at \p pos in \p input, recording the
+    /// capture groups into \p groups if it is not empty. \p step is called
+    /// for each loop iteration, and abandons the match if it returns false.
+    /// \return the end of the match, or None on failure, in which case the
+    /// groups are reset.
+    pub(crate) fn find_at<Input: InputIndexer>(
+        &self,
+        re: &CompiledRegex,
+        input: &Input,
+        pos: Input::Position,
+        groups: &mut [GroupData<Input::Position>],
+        loops: &mut [LoopData<Input::Position>],
+        step: impl FnMut() -> bool,
+    ) -> Option<Input::Position> {
+        let end = self.run(re, input, pos, groups, loops, step);
+        if end.is_none() {
+            groups.iter_mut().for_each(GroupData::reset);
+        }
+        end
+    }
+
+    /// Run a loop at \p ip, either entering or re-entering it, at \p pos.
+    /// \return the instruction to continue with, or None on failure.
+    fn run_loop<Input: InputIndexer>(
+        &self,
+        input: &Input,
+        fields: &LoopFields,
+        loop_data: &mut LoopData<Input::Position>,
+        pos: Input::Position,
+        ip: IP,
+    ) -> Option<IP> {
+        let iteration = loop_data.iters;
+        // Reject empty iterations past the minimum, as the backtracker does.
+        if loop_data.entry == pos && iteration > fields.min_iters {
+            return None;
+        }
+        let enter = match (iteration < fields.max_iters, iteration >= fields.min_iters) {
+            (false, false) => return None,
+            (false, true) => false,
+            (true, false) => true,
+            (true, true) => self.choose_primary::<Input>(ip, input.peek_right(pos)),
+        };
+        if !enter {
+            return Some(fields.exit as IP);
+        }
+        loop_data.iters += 1;
+        loop_data.entry = pos;
+        Some(ip + 1)
+    }
+
+    fn run<Input: InputIndexer>(
+        &self,
+        re: &CompiledRegex,
+        input: &Input,
+        mut pos: Input::Position,
+        groups: &mut [GroupData<Input::Position>],
+        loops: &mut [LoopData<Input::Position>],
+        mut step: impl FnMut() -> bool,
+    ) -> Option<Input::Position> {
+        let mut ip = 0;
+        loop {
+            match re.insns.iat(ip) {
+                Insn::Goal => return Some(pos),
+                &Insn::StartOfLine { multiline } => {
+                    let matches = match input.peek_left(pos) {
+                        None => true,
+                        Some(c) => multiline && Input::CharProps::is_line_terminator(c),
+                    };
+                    if !matches {
+                        return None;
+                    }
+                }
+                &Insn::EndOfLine { multiline } => {
+                    let matches = match input.peek_right(pos) {
+                        None => true,
+                        Some(c) => multiline && Input::CharProps::is_line_terminator(c),
+                    };
+                    if !matches {
+                        return None;
+                    }
+                }
+                &Insn::WordBoundary { invert } => {
+                    let prev = input
+                        .peek_left(pos)
+                        .is_some_and(Input::CharProps::is_word_char);
+                    let curr = input
+                        .peek_right(pos)
+                        .is_some_and(Input::CharProps::is_word_char);
+                    if (prev != curr) == invert {
+                        return None;
+                    }
+                }
+                &Insn::WordBoundaryUnicodeICase { invert } => {
+                    let prev = input
+                        .peek_left(pos)
+                        .is_some_and(Input::CharProps::is_word_char_unicode_icase);
+                    let curr = input
+                        .peek_right(pos)
+                        .is_some_and(Input::CharProps::is_word_char_unicode_icase);
+                    if (prev != curr) == invert {
+                        return None;
+                    }
+                }
+                &Insn::Jump { target } => {
+                    ip = target as IP;
+                    continue;
+                }
+                &Insn::Alt { secondary } => {
+                    if !self.choose_primary::<Input>(ip, input.peek_right(pos)) {
+                        ip = secondary as IP;
+                        continue;
+                    }
+                }
+                &Insn::BeginCaptureGroup(cg) => {
+                    if let Some(gd) = groups.get_mut(cg as usize) {
+                        gd.start = Some(pos);
+                    }
+                }
+                &Insn::EndCaptureGroup(cg) => {
+                    if let Some(gd) = groups.get_mut(cg as usize) {
+                        gd.end = Some(pos);
+                    }
+                }
+                &Insn::ResetCaptureGroup(cg) => {
+                    if let Some(gd) = groups.get_mut(cg as usize) {
+                        gd.reset();
+                    }
+                }
+                Insn::EnterLoop(fields) => {
+                    if !step() {
+                        return None;
+                    }
+                    let loop_data = loops.mat(fields.loop_id as usize);
+                    loop_data.iters = 0;
+                    ip = self.run_loop(input, fields, loop_data, pos, ip)?;
+                    continue;
+                }
+                &Insn::LoopAgain { begin } => {
+                    if !step() {
+                        return None;
+                    }
+                    let Insn::EnterLoop(fields) = re.insns.iat(begin as IP) else {
+                        rs_unreachable!("LoopAgain should always refer to an EnterLoop");
+                    };
+                    let loop_data = loops.mat(fields.loop_id as usize);
+                    ip = self.run_loop(input, fields, loop_data, pos, begin as IP)?;
+                    continue;
+                }
+                &Insn::Loop1CharBody {
+                    min_iters,
+                    max_iters,
+                    ..
+                } => {
+                    // The body and what follows share no characters, so
+                    // iterate for as long as the body matches.
+                    for iteration in 0..max_iters {
+                        let saved = pos;
+                        if !match_insn(re, input, ip + 1, &mut pos) {
+                            if iteration < min_iters {
+                                return None;
+                            }
+                            pos = saved;
+                            break;
+                        }
+                    }
+                    ip += 2;
+                    continue;
+                }
+                _ => {
+                    if !match_insn(re, input, ip, &mut pos) {
+                        return None;
+                    }
+                }
+            }
+            ip += 1;
+        }
+    }
+}
+
+/// Match the instruction at \p ip, which consumes characters, at \p pos,
+/// advancing it. \return whether it matched. On failure the position is
+/// unspecified.
+#[inline]
+fn match_insn<Input: InputIndexer>(
+    re: &CompiledRegex,
+    input: &Input,
+    ip: IP,
+    pos: &mut Input::Position,
+) -> bool {
+    let dir = Forward;
+    match re.insns.iat(ip) {
+        &Insn::Char(c) => match <Input::Element as ElementType>::try_from(c) {
+            Some(c) => scm::Char { c }.matches(input, dir, pos),
+            None => false,
+        },
+        Insn::CharSet(chars) => scm::CharSet { chars }.matches(input, dir, pos),
+        &Insn::ByteSet2(bytes) => scm::MatchByteArraySet(bytes).matches(input, dir, pos),
+        &Insn::ByteSet3(bytes) => scm::MatchByteArraySet(bytes).matches(input, dir, pos),
+        &Insn::ByteSet4(bytes) => scm::MatchByteArraySet(bytes).matches(input, dir, pos),
+        Insn::ByteSeq1(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq2(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq3(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq4(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq5(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq6(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq7(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq8(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq9(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq10(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq11(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq12(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq13(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq14(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq15(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::ByteSeq16(v) => cursor::try_match_lit(input, dir, pos, v),
+        Insn::AsciiBracket(bitmap) => scm::MatchByteSet { bytes: bitmap }.matches(input, dir, pos),
+        &Insn::Bracket(idx) => scm::Bracket {
+            bc: &re.brackets[idx],
+        }
+        .matches(input, dir, pos),
+        Insn::MatchAny => scm::MatchAny::new().matches(input, dir, pos),
+        Insn::MatchAnyExceptLineTerminator => {
+            scm::MatchAnyExceptLineTerminator::new().matches(input, dir, pos)
+        }
+        Insn::JustFail => false,
+        _ => rs_unreachable!("Instruction should consume characters"),
+    }
+}
@@ -28,18 +28,309 @@ use {
     hashbrown::HashMap,
 };
 
+/// The kind of an [`Error`] encountered during regex compilation.
+///
+/// This is non-exhaustive: new kinds may be added as the syntax grows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A group was not closed, or a `)` had no matching group.
+    UnbalancedParenthesis,
+    /// A character was left over after parsing the pattern.
+    UnexpectedCharacter,
+    /// Groups or classes were nested too deeply.
+    TooDeeplyNested,
+    /// A `\` was at the end of the pattern.
+    IncompleteEscape,
+    /// The pattern has too many capture groups.
+    CaptureGroupLimitExceeded,
+    /// The name of a named capture group was missing or invalid.
+    InvalidGroupName,
+    /// A braced quantifier like `{2}` had nothing to quantify.
+    InvalidBracedQuantifier,
+    /// A character which must be escaped appeared unescaped.
+    InvalidAtomCharacter,
+    /// A quantifier followed something which may not be quantified.
+    QuantifierNotAllowed,
+    /// A quantifier was malformed, or its minimum exceeded its maximum.
+    InvalidQuantifier,
+    /// The pattern has too many quantifiers.
+    LoopLimitExceeded,
+    /// The flags of a modifier group like `(?i:...)` were invalid.
+    InvalidGroupModifier,
+    /// A character class was not closed.
+    UnbalancedBracket,
+    /// A character class range had its start after its end.
+    RangeOutOfOrder,
+    /// A character class range had a class escape like `\d` as an endpoint.
+    InvalidCharacterRange,
+    /// A `\` was at the end of a character class.
+    UnterminatedEscape,
+    /// A property of strings was used where only code points are allowed.
+    InvalidPropertyEscape,
+    /// A class set in `v` mode was not closed.
+    UnbalancedClassSetBracket,
+    /// A class set range was malformed or out of order.
+    InvalidClassSetRange,
+    /// A class set intersection contained an unexpected character.
+    InvalidClassSetIntersection,
+    /// A class set subtraction contained an unexpected character.
+    InvalidClassSetSubtraction,
+    /// A class set operand was missing.
+    EmptyClassSetOperand,
+    /// A `\` was at the end of a class set.
+    IncompleteClassSetEscape,
+    /// A `\q` was not followed by `{`.
+    InvalidClassSetEscape,
+    /// A `\q{...}` string disjunction was not closed.
+    UnbalancedClassStringDisjunction,
+    /// A character escape was invalid.
+    InvalidCharacterEscape,
+    /// A class set character was missing.
+    IncompleteClassSetCharacter,
+    /// A class set contained a character which must be escaped.
+    InvalidClassSetCharacter,
+    /// A `\u` escape was malformed.
+    InvalidUnicodeEscape,
+    /// A `\k` was not followed by a group name.
+    InvalidNamedBackreference,
+    /// A `\k<name>` referred to a group which does not exist.
+    UnknownGroupName,
+    /// Two capture groups which may both participate have the same name.
+    DuplicateGroupName,
+    /// A `\p` or `\P` was not followed by `{`.
+    InvalidPropertyEscapeStart,
+    /// A property name or value was not recognized.
+    InvalidPropertyName,
+}
+
+impl ErrorKind {
+    /// \return the message for errors of this kind.
+    fn message(self) -> &'static str {
+        use ErrorKind::*;
+        match self {
+            UnbalancedParenthesis => "Unbalanced parenthesis",
+            UnexpectedCharacter => "Unexpected char",
+            TooDeeplyNested => "Regular expression is too deeply nested",
+            IncompleteEscape => "Incomplete escape",
+            CaptureGroupLimitExceeded => "Capture group count limit exceeded",
+            InvalidGroupName => "Invalid token at named capture group identifier",
+            InvalidBracedQuantifier => "Invalid braced quantifier",
+            InvalidAtomCharacter => "Invalid atom character",
+            QuantifierNotAllowed => "Quantifier not allowed here",
+            InvalidQuantifier => "Invalid quantifier",
+            LoopLimitExceeded => "Loop count limit exceeded",
+            InvalidGroupModifier => "Invalid group modifier",
+            UnbalancedBracket => "Unbalanced bracket",
+            RangeOutOfOrder => {
+                "Range values reversed, start char code is greater than end char code."
+            }
+            InvalidCharacterRange => "Invalid character range",
+            UnterminatedEscape => "Unterminated escape",
+            InvalidPropertyEscape => "Invalid property escape",
+            UnbalancedClassSetBracket => "Unbalanced class set bracket",
+            InvalidClassSetRange => "Invalid class set range",
+            InvalidClassSetIntersection => "Unexpected character in class set intersection",
+            InvalidClassSetSubtraction => "Unexpected character in class set subtraction",
+            EmptyClassSetOperand => "Empty class set operand",
+            IncompleteClassSetEscape => "Incomplete class set escape",
+            InvalidClassSetEscape => "Invalid class set escape: expected {",
+            UnbalancedClassStringDisjunction => "Unbalanced class set string disjunction",
+            InvalidCharacterEscape => "Invalid character escape",
+            IncompleteClassSetCharacter => "Incomplete class set character",
+            InvalidClassSetCharacter => "Invalid class set character",
+            InvalidUnicodeEscape => "Invalid unicode escape",
+            InvalidNamedBackreference => "Invalid named backreference syntax",
+            UnknownGroupName => "Backreference to invalid named capture group",
+            DuplicateGroupName => "Duplicate capture group name",
+            InvalidPropertyEscapeStart => "Invalid character at property escape start",
+            InvalidPropertyName => "Invalid property name",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
 /// Represents an error encountered during regex compilation.
 ///
-/// The text contains a human-readable error message.
+/// Its [`text`](Error::text) is a human-readable message. Errors from parsing
+/// a pattern also carry the span of the pattern at fault. The alternate form
+/// of Display (`{:#}`) renders the pattern with a caret under that span:
+///
+/// ```rust
+/// let err = regress::Regex::new("ab(c").unwrap_err();
+/// assert_eq!(err.kind(), regress::ErrorKind::UnbalancedParenthesis);
+/// assert_eq!(err.span(), Some(2..3));
+/// assert_eq!(format!("{:#}", err), "Unbalanced parenthesis\nab(c\n  ^");
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Error {
-    pub text: String,
+    /// A human-readable message.
+    text: String,
+
+    /// The kind of error.
+    kind: ErrorKind,
+
+    /// Where in the pattern the error occurred, if known.
+    /// This is boxed to keep parse results small, as the parser is recursive.
+    location: Option<Box<ErrorLocation>>,
+
+    /// The index of the pattern at fault among those of a RegexSet.
+    pattern_index: Option<usize>,
+}
+
+/// The location of an error within a pattern.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct ErrorLocation {
+    /// The span in code units of the pattern, as a half-open interval.
+    span: (usize, usize),
+
+    /// The span in chars of `pattern`, for rendering.
+    char_span: (usize, usize),
+
+    /// The pattern, with unpaired surrogates replaced, for rendering.
+    pattern: Box<str>,
+}
+
+impl Error {
+    /// Construct an error of \p kind with the given text, without a span.
+    pub(crate) fn new<S: ToString>(kind: ErrorKind, text: S) -> Error {
+        Error {
+            text: text.to_string(),
+            kind,
+            location: None,
+            pattern_index: None,
+        }
+    }
+
+    /// \return the human-readable message, without the index of the
+    /// pattern at fault which Display adds.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// \return the kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// \return the span of the pattern at fault, if known.
+    ///
+    /// For patterns given as `&str` the span is in bytes. For patterns given
+    /// to [`Regex::from_unicode`](crate::Regex::from_unicode) it is in
+    /// elements of the iterator, i.e. code points.
+    pub fn span(&self) -> Option<core::ops::Range<usize>> {
+        let (start, end) = self.location.as_ref()?.span;
+        Some(start..end)
+    }
+
+    /// \return the index of the pattern at fault, for an error constructing a
+    /// [`RegexSet`](crate::RegexSet).
+    ///
+    /// ```rust
+    /// let err = regress::RegexSet::new(["a", "b(", "c"]).unwrap_err();
+    /// assert_eq!(err.pattern_index(), Some(1));
+    /// assert_eq!(err.span(), Some(1..2));
+    /// assert_eq!(err.text(), "Unbalanced parenthesis");
+    /// assert_eq!(err.to_string(), "Unbalanced parenthesis in pattern 1");
+    /// ```
+    pub fn pattern_index(&self) -> Option<usize> {
+        self.pattern_index
+    }
+
+    /// Attribute this error to the pattern at \p idx of a set.
+    pub(crate) fn with_pattern_index(mut self, idx: usize) -> Error {
+        self.pattern_index = Some(idx);
+        self
+    }
+
+    /// Attribute this error to the whole of \p pattern, where no narrower
+    /// span is known.
+    pub(crate) fn spanning(mut self, pattern: &str) -> Error {
+        self.location = Some(Box::new(ErrorLocation {
+            span: (0, pattern.len()),
+            char_span: (0, pattern.chars().count()),
+            pattern: pattern.into(),
+        }));
+        self
+    }
+
+    /// Attach the \p pattern which caused this error, for rendering.
+    fn with_pattern<I>(mut self, pattern: I) -> Error
+    where
+        I: Iterator<Item = u32>,
+    {
+        if let Some(location) = &mut self.location {
+            let pattern: String = pattern
+                .map(|c| char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+            location.pattern = pattern.into_boxed_str();
+        }
+        self
+    }
+
+    /// Convert our span from code points to bytes of \p pattern, which is
+    /// the pattern that was parsed.
+    pub(crate) fn with_utf8_span(mut self, pattern: &str) -> Error {
+        if let Some(location) = &mut self.location {
+            let byte_offset = |idx: usize| {
+                pattern
+                    .char_indices()
+                    .nth(idx)
+                    .map_or(pattern.len(), |(offset, _)| offset)
+            };
+            let (start, end) = location.char_span;
+            location.span = (byte_offset(start), byte_offset(end));
+        }
+        self
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(&self.text)
+        f.write_str(&self.text)?;
+        if let Some(idx) = self.pattern_index {
+            write!(f, " in pattern {idx}")?;
+        }
+        if !f.alternate() {
+            return Ok(());
+        }
+        let Some(location) = &self.location else {
+            return Ok(());
+        };
+        let (start, end) = location.char_span;
+        write_caret(f, &location.pattern, start, end)
+    }
+}
+
+/// Write \p text on a line of its own, and a caret under its chars
+/// \p start..end on the line after.
+pub(crate) fn write_caret(
+    f: &mut fmt::Formatter,
+    text: &str,
+    start: usize,
+    end: usize,
+) -> fmt::Result {
+    // Render line breaks and tabs as spaces so the caret stays aligned.
+    f.write_str("\n")?;
+    for c in text.chars() {
+        match c {
+            '\n' | '\r' | '\t' | '\u{2028}' | '\u{2029}' => f.write_str(" ")?,
+            c => fmt::Write::write_char(f, c)?,
+        }
     }
+    f.write_str("\n")?;
+    for _ in 0..start {
+        f.write_str(" ")?;
+    }
+    for _ in 0..(end - start).max(1) {
+        f.write_str("^")?;
+    }
+    Ok(())
 }
 
 #[cfg(feature = "std")]
@@ -281,13 +572,33 @@ impl IntoIterator for ClassSetAlternativeStrings {
     }
 }
 
-fn error<S, T>(text: S) -> Result<T, Error>
-where
-    S: ToString,
-{
-    Err(Error {
-        text: text.to_string(),
-    })
+/// The remaining input of a pattern, tracking how many code points have
+/// been consumed so that errors may report where they occurred.
+#[derive(Clone)]
+struct PatternInput<I: Iterator<Item = u32>> {
+    iter: Peekable<I>,
+    offset: usize,
+}
+
+impl<I: Iterator<Item = u32>> PatternInput<I> {
+    fn peek(&mut self) -> Option<&u32> {
+        self.iter.peek()
+    }
+
+    fn next_if_eq(&mut self, expected: &u32) -> Option<u32> {
+        let c = self.iter.next_if_eq(expected)?;
+        self.offset += 1;
+        Some(c)
+    }
+}
+
+impl<I: Iterator<Item = u32>> Iterator for PatternInput<I> {
+    type Item = u32;
+    fn next(&mut self) -> Option<u32> {
+        let c = self.iter.next()?;
+        self.offset += 1;
+        Some(c)
+    }
 }
 
 fn make_cat(nodes: ir::NodeList) -> ir::Node {
@@ -405,7 +716,7 @@ where
     I: Iterator<Item = u32>,
 {
     /// The remaining input.
-    input: Peekable<I>,
+    input: PatternInput<I>,
 
     /// Flags used.
     flags: api::Flags,
@@ -429,12 +740,38 @@ where
 
     /// Current recursion nesting depth.
     depth: u32,
+
+    /// Errors recovered from so far, if recovering from errors.
+    /// If this is None, parsing stops at the first error.
+    diagnostics: Option<Vec<Error>>,
+
+    /// The span in code points of each loop parsed so far, if recording.
+    /// A loop's span covers its quantifier and the term it quantifies.
+    loop_spans: Option<Vec<core::ops::Range<usize>>>,
 }
 
 impl<I> Parser<I>
 where
     I: Iterator<Item = u32> + Clone,
 {
+    fn new(pattern: I, flags: api::Flags) -> Self {
+        Parser {
+            input: PatternInput {
+                iter: pattern.peekable(),
+                offset: 0,
+            },
+            flags,
+            loop_count: 0,
+            group_count: 0,
+            named_group_indices: HashMap::new(),
+            group_count_max: 0,
+            has_lookbehind: false,
+            depth: 0,
+            diagnostics: None,
+            loop_spans: None,
+        }
+    }
+
     /// Consume a character, returning it.
     fn consume<C: Into<u32>>(&mut self, c: C) -> u32 {
         let nc = self.input.next();
@@ -484,20 +821,136 @@ where
         self.input.next()
     }
 
+    /// Record \p err and continue parsing, if recovering from errors.
+    /// Otherwise return it.
+    fn recover(&mut self, err: Error) -> Result<(), Error> {
+        match &mut self.diagnostics {
+            Some(diagnostics) => {
+                diagnostics.push(err);
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Skip the remainder of an alternative, up to the next `|` or `)`
+    /// outside of any group or class, or the end of input.
+    /// This does not parse; it only balances groups and classes, and counts
+    /// the capture groups skipped so that those after keep their numbers.
+    fn skip_alternative(&mut self) {
+        let mut paren_depth = 0usize;
+        while let Some(c) = self.peek() {
+            match to_char_sat(c) {
+                ')' | '|' if paren_depth == 0 => return,
+                '(' => {
+                    self.consume(c);
+                    paren_depth += 1;
+                    let capturing = !self.try_consume('?')
+                        || (self.try_consume('<')
+                            && !matches!(self.peek().map(to_char_sat), Some('=' | '!')));
+                    if capturing && (self.group_count as usize) < MAX_CAPTURE_GROUPS {
+                        self.group_count += 1;
+                    }
+                    continue;
+                }
+                ')' => paren_depth -= 1,
+                '\\' => {
+                    self.consume(c);
+                }
+                '[' => {
+                    self.skip_class();
+                    continue;
+                }
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    /// Skip a class starting at `[`, through its closing `]` or the end of input.
+    /// Classes may nest in unicode sets mode.
+    fn skip_class(&mut self) {
+        let mut bracket_depth = 0usize;
+        while let Some(c) = self.next() {
+            match to_char_sat(c) {
+                '\\' => {
+                    self.next();
+                }
+                '[' if bracket_depth == 0 || self.flags.unicode_sets => bracket_depth += 1,
+                ']' => {
+                    bracket_depth -= 1;
+                    if bracket_depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// \return the number of code points consumed so far.
+    fn offset(&self) -> usize {
+        self.input.offset
+    }
+
+    /// \return an error of \p kind covering the code points \p start..end.
+    #[cold]
+    #[inline(never)]
+    fn error_at<T>(&self, kind: ErrorKind, start: usize, end: usize) -> Result<T, Error> {
+        self.error_text_at(kind, kind.message(), start, end)
+    }
+
+    /// \return an error of \p kind with the given text, covering the code
+    /// points \p start..end.
+    #[cold]
+    #[inline(never)]
+    fn error_text_at<S: ToString, T>(
+        &self,
+        kind: ErrorKind,
+        text: S,
+        start: usize,
+        end: usize,
+    ) -> Result<T, Error> {
+        let mut err = Error::new(kind, text);
+        err.location = Some(Box::new(ErrorLocation {
+            span: (start, end),
+            char_span: (start, end),
+            pattern: Box::default(),
+        }));
+        Err(err)
+    }
+
+    /// \return an error of \p kind covering the code points from \p start to
+    /// the current position. An empty span is widened to the next code point.
+    #[cold]
+    #[inline(never)]
+    fn error_since<T>(&mut self, kind: ErrorKind, start: usize) -> Result<T, Error> {
+        let mut end = self.offset().max(start);
+        if end == start && self.peek().is_some() {
+            end += 1;
+        }
+        self.error_at(kind, start, end)
+    }
+
     fn try_parse(&mut self) -> Result<ir::Regex, Error> {
-        self.parse_capture_groups()?;
+        if let Err(err) = self.parse_capture_groups() {
+            self.recover(err)?;
+        }
 
         // Parse a catenation. If we consume everything, it's success. If there's
         // something left, it's an error (for example, an excess closing paren).
-        let body = self.consume_disjunction()?;
-        match self.input.peek().copied() {
-            Some(c) if c == ')' as u32 => error("Unbalanced parenthesis"),
-            Some(c) => error(format!(
-                "Unexpected char: {}",
-                char::from_u32(c)
-                    .map(String::from)
-                    .unwrap_or_else(|| format!("\\u{c:04X}"))
-            )),
+        let mut body = self.consume_disjunction()?;
+        while let Some(c) = self.peek()
+            && self.diagnostics.is_some()
+        {
+            // Report the leftover character, then carry on after it.
+            self.leftover_error::<()>(c)
+                .or_else(|err| self.recover(err))?;
+            self.consume(c);
+            body = self.consume_disjunction()?;
+        }
+        match self.peek() {
+            Some(c) => self.leftover_error(c),
             None => self.finalize(ir::Regex {
                 node: make_cat(vec![body, ir::Node::Goal]),
                 flags: self.flags,
@@ -505,15 +958,66 @@ where
         }
     }
 
+    /// \return the error for a character \p c left over after parsing a disjunction.
+    fn leftover_error<T>(&self, c: u32) -> Result<T, Error> {
+        let start = self.offset();
+        if c == ')' as u32 {
+            self.error_at(ErrorKind::UnbalancedParenthesis, start, start + 1)
+        } else {
+            self.error_text_at(
+                ErrorKind::UnexpectedCharacter,
+                format!(
+                    "Unexpected char: {}",
+                    char::from_u32(c)
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("\\u{c:04X}"))
+                ),
+                start,
+                start + 1,
+            )
+        }
+    }
+
     /// ES6 21.2.2.3 Disjunction.
     fn consume_disjunction(&mut self) -> Result<ir::Node, Error> {
         self.depth += 1;
         if self.depth > MAX_NESTING_DEPTH {
-            return error("Regular expression is too deeply nested");
+            let start = self.offset();
+            return self.error_since(ErrorKind::TooDeeplyNested, start);
         }
-        let mut terms = vec![self.consume_term()?];
-        while self.try_consume('|') {
-            terms.push(self.consume_term()?)
+        let mut terms = Vec::new();
+        loop {
+            // When recovering from errors, remember where this alternative
+            // began so a failed one may be skipped. The groups and loops it
+            // counted are forgotten with it.
+            let restart = self.diagnostics.is_some().then(|| {
+                (
+                    self.input.clone(),
+                    self.depth,
+                    self.group_count,
+                    self.loop_count,
+                )
+            });
+            match self.consume_term() {
+                Ok(term) => terms.push(term),
+                Err(err) => {
+                    self.recover(err)?;
+                    if let Some((input, depth, group_count, loop_count)) = restart {
+                        self.input = input;
+                        self.depth = depth;
+                        self.group_count = group_count;
+                        self.loop_count = loop_count;
+                        if let Some(loop_spans) = &mut self.loop_spans {
+                            loop_spans.truncate(loop_count as usize);
+                        }
+                    }
+                    self.skip_alternative();
+                    terms.push(ir::Node::Empty);
+                }
+            }
+            if !self.try_consume('|') {
+                break;
+            }
         }
         self.depth -= 1;
         Ok(make_alt(terms))
@@ -526,6 +1030,7 @@ where
             let start_group = self.group_count;
             let mut start_offset = result.len();
             let mut quantifier_allowed = true;
+            let term_start = self.offset();
 
             let nc = self.peek();
             if nc.is_none() {
@@ -557,7 +1062,7 @@ where
                 '\\' => {
                     self.consume('\\');
                     let Some(c) = self.peek() else {
-                        return error("Incomplete escape");
+                        return self.error_since(ErrorKind::IncompleteEscape, term_start);
                     };
                     match to_char_sat(c) {
                         // Term :: Assertion :: \b
@@ -652,7 +1157,8 @@ where
                         self.consume('(');
                         let group = self.group_count;
                         if self.group_count as usize >= MAX_CAPTURE_GROUPS {
-                            return error("Capture group count limit exceeded");
+                            return self
+                                .error_since(ErrorKind::CaptureGroupLimitExceeded, term_start);
                         }
                         self.group_count += 1;
 
@@ -661,7 +1167,7 @@ where
                         if self.try_consume_str("?") {
                             group_name = self.try_consume_named_capture_group_name();
                             if group_name.is_none() {
-                                return error("Invalid token at named capture group identifier");
+                                return self.error_since(ErrorKind::InvalidGroupName, term_start);
                             };
                         }
                         let contents = Box::new(self.consume_disjunction()?);
@@ -672,7 +1178,11 @@ where
                         })
                     }
                     if !self.try_consume(')') {
-                        return error("Unbalanced parenthesis");
+                        return self.error_at(
+                            ErrorKind::UnbalancedParenthesis,
+                            term_start,
+                            term_start + 1,
+                        );
                     }
                 }
 
@@ -681,7 +1191,7 @@ where
                     self.consume('[');
                     let negate_set = self.try_consume('^');
                     result.push(
-                        self.consume_class_set_expression(negate_set)?
+                        self.consume_class_set_expression(negate_set, term_start)?
                             .node(self.flags.icase, negate_set),
                     );
                 }
@@ -693,7 +1203,7 @@ where
                 // Term :: ExtendedAtom :: InvalidBracedQuantifier
                 '{' if !self.flags.unicode => {
                     if self.try_consume_braced_quantifier().is_some() {
-                        return error("Invalid braced quantifier");
+                        return self.error_since(ErrorKind::InvalidBracedQuantifier, term_start);
                     }
 
                     // Term :: ExtendedAtom :: ExtendedPatternCharacter
@@ -703,12 +1213,12 @@ where
 
                 // Term :: Atom :: PatternCharacter :: SourceCharacter but not ^ $ \ . * + ? ( ) [ ] { } |
                 '*' | '+' | '?' | ']' | '{' | '}' if self.flags.unicode => {
-                    return error("Invalid atom character");
+                    return self.error_since(ErrorKind::InvalidAtomCharacter, term_start);
                 }
 
                 // Term :: ExtendedAtom :: SourceCharacter but not ^ $ \ . * + ? ( ) [ |
                 '*' | '+' | '?' => {
-                    return error("Invalid atom character");
+                    return self.error_since(ErrorKind::InvalidAtomCharacter, term_start);
                 }
 
                 // Term :: Atom :: PatternCharacter
@@ -720,22 +1230,27 @@ where
             }
 
             // We just parsed a term; try parsing a quantifier.
+            let quant_start = self.offset();
             if let Some(quant) = self.try_consume_quantifier()? {
                 if !quantifier_allowed {
-                    return error("Quantifier not allowed here");
+                    return self.error_since(ErrorKind::QuantifierNotAllowed, quant_start);
                 }
                 // Validate the quantifier.
                 // Note we don't want to do this as part of parsing the quantiifer in some cases
                 // an incomplete quantifier is not recognized as a quantifier, e.g. `/{3/` is
                 // valid.
                 if matches!(quant.max, Some(max) if quant.min > max) {
-                    return error("Invalid quantifier");
+                    return self.error_since(ErrorKind::InvalidQuantifier, quant_start);
                 }
                 let quantifee = result.split_off(start_offset);
                 if self.loop_count as usize >= MAX_LOOPS {
-                    return error("Loop count limit exceeded");
+                    return self.error_since(ErrorKind::LoopLimitExceeded, quant_start);
                 }
                 self.loop_count += 1;
+                let term_end = self.offset();
+                if let Some(loop_spans) = &mut self.loop_spans {
+                    loop_spans.push(term_start..term_end);
+                }
                 result.push(ir::Node::Loop {
                     loopee: Box::new(make_cat(quantifee)),
                     quant,
@@ -780,20 +1295,32 @@ where
                         _ => unreachable!(),
                     };
                     if target.is_some() {
-                        return error("Invalid group modifier");
+                        return self.error_at(
+                            ErrorKind::InvalidGroupModifier,
+                            self.offset(),
+                            cursor.offset,
+                        );
                     }
                     *target = Some(value);
                     saw_flag = true;
                 }
                 '-' => {
                     if seen_hyphen {
-                        return error("Invalid group modifier");
+                        return self.error_at(
+                            ErrorKind::InvalidGroupModifier,
+                            self.offset(),
+                            cursor.offset,
+                        );
                     }
                     seen_hyphen = true;
                 }
                 ':' => {
                     if !saw_flag {
-                        return error("Invalid group modifier");
+                        return self.error_at(
+                            ErrorKind::InvalidGroupModifier,
+                            self.offset(),
+                            cursor.offset,
+                        );
                     }
                     self.input = cursor;
                     let mut new_flags = self.flags;
@@ -819,19 +1346,30 @@ where
                     return Ok(Some(contents));
                 }
                 _ => {
-                    return error("Invalid group modifier");
+                    return self.error_at(
+                        ErrorKind::InvalidGroupModifier,
+                        self.offset(),
+                        cursor.offset,
+                    );
                 }
             }
 
             current = match cursor.next() {
                 Some(next) => next,
-                None => return error("Invalid group modifier"),
+                None => {
+                    return self.error_at(
+                        ErrorKind::InvalidGroupModifier,
+                        self.offset(),
+                        cursor.offset,
+                    );
+                }
             };
         }
     }
 
     /// ES6 21.2.2.13 CharacterClass.
     fn consume_bracket(&mut self) -> Result<ir::Node, Error> {
+        let start = self.offset();
         self.consume('[');
         let invert = self.try_consume('^');
         let mut result = BracketContents {
@@ -842,7 +1380,7 @@ where
         loop {
             match self.peek().map(to_char_sat) {
                 None => {
-                    return error("Unbalanced bracket");
+                    return self.error_at(ErrorKind::UnbalancedBracket, start, start + 1);
                 }
                 Some(']') => {
                     self.consume(']');
@@ -855,6 +1393,7 @@ where
             }
 
             // Parse a code point or character class.
+            let range_start = self.offset();
             let Some(first) = self.try_consume_bracket_class_atom()? else {
                 continue;
             };
@@ -876,9 +1415,7 @@ where
             // ES6 21.2.2.15.1 "If i > j, throw a SyntaxError exception"
             if let (ClassAtom::CodePoint(c1), ClassAtom::CodePoint(c2)) = (&first, &second) {
                 if c1 > c2 {
-                    return error(
-                        "Range values reversed, start char code is greater than end char code.",
-                    );
+                    return self.error_since(ErrorKind::RangeOutOfOrder, range_start);
                 }
                 result.cps.add(Interval {
                     first: *c1,
@@ -889,7 +1426,7 @@ where
             }
 
             if self.flags.unicode {
-                return error("Invalid character range");
+                return self.error_since(ErrorKind::InvalidCharacterRange, range_start);
             }
 
             // If it does not match a range treat as any match single characters.
@@ -905,6 +1442,7 @@ where
             return Ok(None);
         }
         let c = c.unwrap();
+        let start = self.offset();
         match to_char_sat(c) {
             // End of bracket.
             ']' => Ok(None),
@@ -915,7 +1453,7 @@ where
                 let ec = if let Some(ec) = self.peek() {
                     ec
                 } else {
-                    return error("Unterminated escape");
+                    return self.error_since(ErrorKind::UnterminatedEscape, start);
                 };
                 match to_char_sat(ec) {
                     // ClassEscape :: b
@@ -1007,7 +1545,9 @@ where
                                 iv: CodePointSet::from_sorted_disjoint_intervals(s.to_vec()),
                                 negate,
                             })),
-                            PropertyEscapeKind::StringSet(_) => error("Invalid property escape"),
+                            PropertyEscapeKind::StringSet(_) => {
+                                self.error_since(ErrorKind::InvalidPropertyEscape, start)
+                            }
                         }
                     }
                     // ClassEscape :: CharacterEscape
@@ -1024,9 +1564,15 @@ where
 
     // CharacterClass :: ClassContents :: ClassSetExpression
     // `in_negated_class` forbids string operands. It does not invert the result.
-    fn consume_class_set_expression(&mut self, in_negated_class: bool) -> Result<ClassSet, Error> {
+    // `open` is the offset of the opening bracket.
+    fn consume_class_set_expression(
+        &mut self,
+        in_negated_class: bool,
+        open: usize,
+    ) -> Result<ClassSet, Error> {
         let mut result = ClassSet::new();
 
+        let first_start = self.offset();
         let first = match self.peek() {
             Some(0x5D /* ] */) => {
                 self.consume(']');
@@ -1034,7 +1580,7 @@ where
             }
             Some(_) => self.consume_class_set_operand(in_negated_class)?,
             None => {
-                return error("Unbalanced class set bracket");
+                return self.error_at(ErrorKind::UnbalancedClassSetBracket, open, open + 1);
             }
         };
 
@@ -1073,15 +1619,17 @@ where
                             let ClassSetOperand::ClassSetCharacter(last) =
                                 self.consume_class_set_operand(in_negated_class)?
                             else {
-                                return error("Invalid class set range");
+                                return self
+                                    .error_since(ErrorKind::InvalidClassSetRange, first_start);
                             };
                             if first > last {
-                                return error("Invalid class set range");
+                                return self
+                                    .error_since(ErrorKind::InvalidClassSetRange, first_start);
                             }
                             result.codepoints.add(Interval { first, last });
                         }
                         _ => {
-                            return error("Invalid class set range");
+                            return self.error_since(ErrorKind::InvalidClassSetRange, first_start);
                         }
                     };
                     ClassSetOperator::Union
@@ -1092,20 +1640,27 @@ where
                 ClassSetOperator::Union
             }
             None => {
-                return error("Unbalanced class set bracket");
+                return self.error_at(ErrorKind::UnbalancedClassSetBracket, open, open + 1);
             }
         };
 
         match op {
             ClassSetOperator::Union => {
                 loop {
+                    let operand_start = self.offset();
                     let operand = match self.peek() {
                         Some(0x5D /* ] */) => {
                             self.consume(']');
                             return Ok(result);
                         }
                         Some(_) => self.consume_class_set_operand(in_negated_class)?,
-                        None => return error("Unbalanced class set bracket"),
+                        None => {
+                            return self.error_at(
+                                ErrorKind::UnbalancedClassSetBracket,
+                                open,
+                                open + 1,
+                            );
+                        }
                     };
                     if self.peek() == Some(0x2D /* - */) {
                         self.consume('-');
@@ -1114,15 +1669,22 @@ where
                                 let ClassSetOperand::ClassSetCharacter(last) =
                                     self.consume_class_set_operand(in_negated_class)?
                                 else {
-                                    return error("Invalid class set range");
+                                    return self.error_since(
+                                        ErrorKind::InvalidClassSetRange,
+                                        operand_start,
+                                    );
                                 };
                                 if first > last {
-                                    return error("Invalid class set range");
+                                    return self.error_since(
+                                        ErrorKind::InvalidClassSetRange,
+                                        operand_start,
+                                    );
                                 }
                                 result.codepoints.add(Interval { first, last });
                             }
                             _ => {
-                                return error("Invalid class set range");
+                                return self
+                                    .error_since(ErrorKind::InvalidClassSetRange, operand_start);
                             }
                         };
                     } else {
@@ -1135,14 +1697,23 @@ where
                 loop {
                     let operand = self.consume_class_set_operand(in_negated_class)?;
                     result.intersect_operand(operand);
+                    let at = self.offset();
                     match self.next() {
                         Some(0x5D /* ] */) => return Ok(result),
                         Some(0x26 /* & */) => {}
-                        Some(_) => return error("Unexpected character in class set intersection"),
-                        _ => return error("Unbalanced class set bracket"),
+                        Some(_) => {
+                            return self.error_since(ErrorKind::InvalidClassSetIntersection, at);
+                        }
+                        _ => {
+                            return self.error_at(
+                                ErrorKind::UnbalancedClassSetBracket,
+                                open,
+                                open + 1,
+                            );
+                        }
                     }
                     if self.next() != Some(0x26 /* & */) {
-                        return error("Unbalanced class set bracket");
+                        return self.error_at(ErrorKind::UnbalancedClassSetBracket, open, open + 1);
                     }
                 }
             }
@@ -1151,14 +1722,23 @@ where
                 loop {
                     let operand = self.consume_class_set_operand(in_negated_class)?;
                     result.subtract_operand(operand);
+                    let at = self.offset();
                     match self.next() {
                         Some(0x5D /* ] */) => return Ok(result),
                         Some(0x2D /* - */) => {}
-                        Some(_) => return error("Unexpected character in class set subtraction"),
-                        _ => return error("Unbalanced class set bracket"),
+                        Some(_) => {
+                            return self.error_since(ErrorKind::InvalidClassSetSubtraction, at);
+                        }
+                        _ => {
+                            return self.error_at(
+                                ErrorKind::UnbalancedClassSetBracket,
+                                open,
+                                open + 1,
+                            );
+                        }
                     }
                     if self.next() != Some(0x2D /* - */) {
-                        return error("Unbalanced class set bracket");
+                        return self.error_at(ErrorKind::UnbalancedClassSetBracket, open, open + 1);
                     }
                 }
             }
@@ -1170,8 +1750,9 @@ where
         in_negated_class: bool,
     ) -> Result<ClassSetOperand, Error> {
         use ClassSetOperand::*;
+        let start = self.offset();
         let Some(cp) = self.peek() else {
-            return error("Empty class set operand");
+            return self.error_since(ErrorKind::EmptyClassSetOperand, start);
         };
         match cp {
             // ClassSetOperand :: NestedClass :: [ [lookahead ≠ ^] ClassContents[+UnicodeMode, +UnicodeSetsMode] ]
@@ -1179,11 +1760,12 @@ where
             0x5B /* [ */ => {
                 self.depth += 1;
                 if self.depth > MAX_NESTING_DEPTH {
-                    return error("Regular expression is too deeply nested");
+                    return self.error_since(ErrorKind::TooDeeplyNested, start);
                 }
+                let open = self.offset();
                 self.consume('[');
                 let negate_set = self.try_consume('^');
-                let mut result = self.consume_class_set_expression(negate_set)?;
+                let mut result = self.consume_class_set_expression(negate_set, open)?;
                 if negate_set {
                     result.codepoints = result.codepoints.inverted();
                 }
@@ -1197,14 +1779,14 @@ where
             0x5C /* \ */ => {
                 self.consume('\\');
                 let Some(cp) = self.peek() else {
-                    return error("Incomplete class set escape");
+                    return self.error_since(ErrorKind::IncompleteClassSetEscape, start);
                 };
                 match cp {
                     // ClassStringDisjunction  \q{ ClassStringDisjunctionContents }
                     0x71 /* q */ => {
                         self.consume('q');
                         if !self.try_consume('{') {
-                            return error("Invalid class set escape: expected {");
+                            return self.error_since(ErrorKind::InvalidClassSetEscape, start);
                         }
                         let mut alternatives = Vec::new();
                         let mut alternative = Vec::new();
@@ -1229,7 +1811,7 @@ where
                                     alternative.push(self.consume_class_set_character()?);
                                 }
                                 None => {
-                                    return error("Unbalanced class set string disjunction");
+                                    return self.error_since(ErrorKind::UnbalancedClassStringDisjunction, start);
                                 }
                             }
                         }
@@ -1274,7 +1856,7 @@ where
                                     intervals.to_vec(),
                                 )))
                             }
-                            PropertyEscapeKind::StringSet(_) if in_negated_class => error("Invalid character escape"),
+                            PropertyEscapeKind::StringSet(_) if in_negated_class => self.error_since(ErrorKind::InvalidCharacterEscape, start),
                             PropertyEscapeKind::StringSet(strings) => {
                                 Ok(ClassStringDisjunction(ClassSetAlternativeStrings(strings.iter().map(|s| Box::from(*s)).collect())))
                             }
@@ -1289,7 +1871,7 @@ where
                                     s.to_vec(),
                                 ).inverted()))
                             }
-                            PropertyEscapeKind::StringSet(_) => error("Invalid character escape"),
+                            PropertyEscapeKind::StringSet(_) => self.error_since(ErrorKind::InvalidCharacterEscape, start),
                         }
                     }
                     // ClassSetCharacter:: \b
@@ -1310,13 +1892,14 @@ where
 
     // ClassSetCharacter
     fn consume_class_set_character(&mut self) -> Result<u32, Error> {
+        let start = self.offset();
         let Some(cp) = self.next() else {
-            return error("Incomplete class set character");
+            return self.error_since(ErrorKind::IncompleteClassSetCharacter, start);
         };
         match cp {
             0x5C /* \ */ => {
                 let Some(cp) = self.peek() else {
-                    return error("Incomplete class set escape");
+                    return self.error_since(ErrorKind::IncompleteClassSetEscape, start);
                 };
                 match cp {
                     // \b
@@ -1331,12 +1914,12 @@ where
             }
             // [lookahead ∉ ClassSetReservedDoublePunctuator] SourceCharacter but not ClassSetSyntaxCharacter
             0x28 /* ( */ | 0x29 /* ) */ | 0x7B /* { */ | 0x7D /* } */ | 0x2F /* / */
-            | 0x2D /* - */ | 0x7C /* | */ => error("Invalid class set character"),
+            | 0x2D /* - */ | 0x7C /* | */ => self.error_since(ErrorKind::InvalidClassSetCharacter, start),
             _ => {
                 if Self::is_class_set_reserved_double_punctuator(cp)
                     && let Some(cp) = self.peek()
                         && Self::is_class_set_reserved_double_punctuator(cp) {
-                            return error("Invalid class set character");
+                            return self.error_since(ErrorKind::InvalidClassSetCharacter, start);
                         }
                 Ok(cp)
             }
@@ -1409,7 +1992,8 @@ where
                 } else if self.flags.unicode {
                     // if there was a brace '{' that doesn't parse into a valid quantifier,
                     // it's not valid with the unicode flag
-                    error("Invalid quantifier")
+                    let start = self.offset();
+                    self.error_since(ErrorKind::InvalidQuantifier, start)
                 } else {
                     Ok(None)
                 }
@@ -1484,7 +2068,9 @@ where
         })
     }
 
+    // This is called after consuming the backslash.
     fn consume_character_escape(&mut self) -> Result<u32, Error> {
+        let start = self.offset().saturating_sub(1);
         let c = self.next().expect("Should have a character");
         let ch = to_char_sat(c);
         match ch {
@@ -1505,7 +2091,7 @@ where
                 {
                     return Ok((nc as u32) % 32);
                 }
-                error("Invalid character escape")
+                self.error_since(ErrorKind::InvalidCharacterEscape, start)
             }
             // CharacterEscape :: 0 [lookahead ∉ DecimalDigit]
             '0' if self
@@ -1530,7 +2116,7 @@ where
                         self.input = orig_input;
                         Ok(c)
                     }
-                    _ => error("Invalid character escape"),
+                    _ => self.error_since(ErrorKind::InvalidCharacterEscape, start),
                 }
             }
             // CharacterEscape :: RegExpUnicodeEscapeSequence
@@ -1541,7 +2127,7 @@ where
                     // CharacterEscape :: IdentityEscape :: SourceCharacterIdentityEscape
                     Ok(c)
                 } else {
-                    error("Invalid unicode escape")
+                    self.error_since(ErrorKind::InvalidUnicodeEscape, start)
                 }
             }
             // CharacterEscape :: [~UnicodeMode] LegacyOctalEscapeSequence
@@ -1583,14 +2169,16 @@ where
             | '/' => Ok(c),
             // CharacterEscape :: IdentityEscape :: SourceCharacterIdentityEscape
             _ if !self.flags.unicode => Ok(c),
-            _ => error("Invalid character escape"),
+            _ => self.error_since(ErrorKind::InvalidCharacterEscape, start),
         }
     }
 
     // AtomEscape
+    // This is called after consuming the backslash.
     fn consume_atom_escape(&mut self) -> Result<ir::Node, Error> {
+        let start = self.offset().saturating_sub(1);
         let Some(c) = self.peek() else {
-            return error("Incomplete escape");
+            return self.error_since(ErrorKind::IncompleteEscape, start);
         };
         match to_char_sat(c) {
             'd' | 'D' => {
@@ -1646,7 +2234,9 @@ where
                             }))
                         }
                     }
-                    PropertyEscapeKind::StringSet(_) if negate => error("Invalid character escape"),
+                    PropertyEscapeKind::StringSet(_) if negate => {
+                        self.error_since(ErrorKind::InvalidCharacterEscape, start)
+                    }
                     PropertyEscapeKind::StringSet(strings) => Ok(ir::Node::StringSet {
                         alternatives: strings.iter().map(|s| Box::from(*s)).collect(),
                         icase: self.flags.icase,
@@ -1664,7 +2254,7 @@ where
                         icase: self.flags.icase,
                     })
                 } else {
-                    error("Invalid character escape")
+                    self.error_since(ErrorKind::InvalidCharacterEscape, start)
                 }
             }
 
@@ -1694,14 +2284,19 @@ where
                 self.consume('k');
                 // Must have a valid group name.
                 let Some(group_name) = self.try_consume_named_capture_group_name() else {
-                    return error("Invalid named backreference syntax");
+                    return self.error_since(ErrorKind::InvalidNamedBackreference, start);
                 };
                 // The group name must be the name of a previously defined capture group.
                 let Some(group_indices) = self.named_group_indices.get(&group_name) else {
-                    return error(format!(
-                        "Backreference to invalid named capture group: {}",
-                        group_name
-                    ));
+                    return self.error_text_at(
+                        ErrorKind::UnknownGroupName,
+                        format!(
+                            "Backreference to invalid named capture group: {}",
+                            group_name
+                        ),
+                        start,
+                        self.offset(),
+                    );
                 };
                 // Note backreferences are 1-based.
                 let node = match group_indices.len() {
@@ -1915,10 +2510,11 @@ where
         Ok(())
     }
 
-    /// Pass 1: Collect all named capture groups and record which alternative path each appears in.
+    /// Pass 1: Collect all named capture groups and record which alternative path each appears in,
+    /// along with the offset of its opening parenthesis.
     fn collect_named_group_locations(
         &mut self,
-    ) -> Result<HashMap<String, Vec<AlternativePath>>, Error> {
+    ) -> Result<HashMap<String, Vec<(AlternativePath, usize)>>, Error> {
         // Track parenthesis depth and alternative index at each depth
         let mut paren_depth: usize = 0;
         // Map from depth to current alternative index at that depth
@@ -1926,7 +2522,8 @@ where
         alt_indices.insert(0, 0);
 
         // Map from group name to all alternative paths where it appears
-        let mut named_group_locations: HashMap<String, Vec<AlternativePath>> = HashMap::new();
+        let mut named_group_locations: HashMap<String, Vec<(AlternativePath, usize)>> =
+            HashMap::new();
 
         loop {
             match self.next().map(to_char_sat) {
@@ -1969,6 +2566,7 @@ where
                     }
                 },
                 Some('(') => {
+                    let group_start = self.offset() - 1;
                     // Determine whether we're a capturing group, and optionally the name.
                     let is_capturing;
                     let group_name;
@@ -1991,7 +2589,7 @@ where
                         named_group_locations
                             .entry(name.clone())
                             .or_default()
-                            .push(AlternativePath { segments });
+                            .push((AlternativePath { segments }, group_start));
 
                         // Store all occurrences in named_group_indices.
                         self.named_group_indices
@@ -2038,14 +2636,16 @@ where
     /// but `/(?<a>x)(?<a>y)/` is invalid (same alternative).
     fn check_duplicate_conflicts(
         &self,
-        named_group_locations: &HashMap<String, Vec<AlternativePath>>,
+        named_group_locations: &HashMap<String, Vec<(AlternativePath, usize)>>,
     ) -> Result<(), Error> {
         for paths in named_group_locations.values() {
             // Check each pair of paths for this group name
             for i in 0..paths.len() {
                 for j in (i + 1)..paths.len() {
-                    if paths[i].conflicts_with(&paths[j]) {
-                        return error("Duplicate capture group name");
+                    let (path, _) = &paths[i];
+                    let (other, start) = &paths[j];
+                    if path.conflicts_with(other) {
+                        return self.error_at(ErrorKind::DuplicateGroupName, *start, *start + 1);
                     }
                 }
             }
@@ -2053,9 +2653,11 @@ where
         Ok(())
     }
 
+    // This is called after consuming the backslash and p or P.
     fn try_consume_unicode_property_escape(&mut self) -> Result<PropertyEscapeKind, Error> {
+        let start = self.offset().saturating_sub(2);
         if !self.try_consume('{') {
-            return error("Invalid character at property escape start");
+            return self.error_since(ErrorKind::InvalidPropertyEscapeStart, start);
         }
 
         let mut buffer = String::new();
@@ -2088,7 +2690,7 @@ where
             }
         }
 
-        error("Invalid property name")
+        self.error_since(ErrorKind::InvalidPropertyName, start)
     }
 
     fn finalize(&self, mut re: ir::Regex) -> Result<ir::Regex, Error> {
@@ -2112,15 +2714,54 @@ pub fn try_parse<I>(pattern: I, flags: api::Flags) -> Result<ir::Regex, Error>
 where
     I: Iterator<Item = u32> + Clone,
 {
-    let mut p = Parser {
-        input: pattern.peekable(),
-        flags,
-        loop_count: 0,
-        group_count: 0,
-        named_group_indices: HashMap::new(),
-        group_count_max: 0,
-        has_lookbehind: false,
-        depth: 0,
-    };
-    p.try_parse()
+    let source = pattern.clone();
+    let mut p = Parser::new(pattern, flags);
+    p.try_parse().map_err(|err| err.with_pattern(source))
+}
+
+/// Try parsing a given pattern, recording the span in code points of each
+/// loop. Loops are recorded in the order they are parsed, which is a post-order
+/// walk of the IR in pattern order: a loop follows the loops it encloses.
+/// Note this order is not preserved by the reversal of lookbehinds.
+pub fn try_parse_with_loop_spans<I>(
+    pattern: I,
+    flags: api::Flags,
+) -> Result<(ir::Regex, Vec<core::ops::Range<usize>>), Error>
+where
+    I: Iterator<Item = u32> + Clone,
+{
+    let source = pattern.clone();
+    let mut p = Parser::new(pattern, flags);
+    p.loop_spans = Some(Vec::new());
+    let re = p.try_parse().map_err(|err| err.with_pattern(source))?;
+    Ok((re, p.loop_spans.take().unwrap_or_default()))
+}
+
+/// Parse a given pattern, recovering from errors.
+/// Return every error found, in the order found.
+pub fn check_syntax<I>(pattern: I, flags: api::Flags) -> Vec<Error>
+where
+    I: Iterator<Item = u32> + Clone,
+{
+    let source = pattern.clone();
+    let mut p = Parser::new(pattern, flags);
+    p.diagnostics = Some(Vec::new());
+    let result = p.try_parse();
+    let mut diagnostics = p.diagnostics.take().unwrap_or_default();
+    diagnostics.extend(result.err());
+    diagnostics
+        .into_iter()
+        .map(|err| err.with_pattern(source.clone()))
+        .collect()
+}
+
+/// Quickly scan a pattern for its capture groups, as is done before parsing.
+/// \return the number of capture groups, and whether any of them are named.
+pub(crate) fn scan_capture_groups<I>(pattern: I, flags: api::Flags) -> (u32, bool)
+where
+    I: Iterator<Item = u32> + Clone,
+{
+    let mut p = Parser::new(pattern, flags);
+    let _ = p.collect_named_group_locations();
+    (p.group_count_max, !p.named_group_indices.is_empty())
 }
@@ -1,41 +1,77 @@
-//! PikeVM regex execution engine
+//! PikeVM regex execution engine.
+//!
+//! The PikeVM runs the threads of a match in lockstep over the input, one
+//! character at a time. Threads are kept in order of priority, which is the
+//! order in which a backtracker would try them, so the match found is the
+//! one the backtracker would find. A thread is dropped when a thread of
+//! higher priority has reached the same instruction, in the same state, at
+//! the same position: from there they would behave alike. So the number of
+//! threads is bounded by the size of the regex, and a search takes time
+//! linear in the length of the input, provided the regex has no
+//! backreferences (which distinguish threads by their captures too) or
+//! lookarounds (which are searched anew at each position where they are
+//! tried).
 
-use crate::api::Match;
+use crate::api::{Match, MatchError};
+use crate::bytesearch;
 use crate::bytesearch::charset_contains;
 use crate::cursor;
 use crate::cursor::{Backward, Direction, Forward};
-use crate::exec;
+use crate::exec::{self, INTERRUPT_POLL_INTERVAL};
 use crate::indexing::{AsciiInput, ElementType, InputIndexer, Utf8Input};
-use crate::insn::{CompiledRegex, Insn, LoopFields, StartPredicate};
+#[cfg(not(feature = "utf16"))]
+use crate::insn::StartPredicate;
+use crate::insn::{CompiledRegex, Insn, LoopFields};
 use crate::matchers;
 use crate::matchers::CharProperties;
 use crate::position::PositionType;
 use crate::scm;
 use crate::scm::SingleCharMatcher;
-use crate::types::{GroupData, LoopData};
-use crate::util::DebugCheckIndex;
+use crate::types::GroupData;
+use crate::util::{DebugCheckIndex, SpareVec};
 #[cfg(not(feature = "std"))]
 use alloc::{boxed::Box, vec::Vec};
 use core::ops::Range;
 
-#[derive(Debug, Clone)]
+/// The data of a loop in a thread.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct LoopState<Position: PositionType> {
+    /// The number of complete iterations. For a loop without a maximum, this
+    /// saturates one past the minimum, as the loop behaves alike thereafter.
+    iters: usize,
+
+    /// The position at which the current iteration began, or None if the
+    /// loop is not being iterated.
+    entry: Option<Position>,
+}
+
+impl<Position: PositionType> LoopState<Position> {
+    const EXITED: Self = LoopState {
+        iters: 0,
+        entry: None,
+    };
+}
+
+#[derive(Debug)]
 struct State<Position: PositionType> {
     /// Position in the input string.
     pos: Position,
 
+    /// Position at which the match began.
+    start: Position,
+
     /// Offset in the bytecode.
     ip: usize,
 
     /// Iteration count of the `Loop1CharBody` currently being executed.
     /// This is 0 except while actively iterating such a loop: the loop's exit
     /// branch resets it to 0, so a fresh loop entry always observes 0.
-    /// This is basically as hack to support the Loop1CharBody optimization
-    /// used by the classical backtracker.
-    /// Only the `Insn::Loop1CharBody` arm reads or writes this field.
+    /// Like the iterations of other loops, it saturates at the minimum for a
+    /// loop without a maximum.
     loop1_iters: usize,
 
     /// Loop datas.
-    loops: Box<[LoopData<Position>]>,
+    loops: Box<[LoopState<Position>]>,
 
     /// Group datas.
     groups: Box<[GroupData<Position>]>,
@@ -48,435 +84,1085 @@ enum StateMatch<Position: PositionType> {
     Complete,
 }
 
-fn run_loop<Position: PositionType>(
-    s: &mut State<Position>,
-    lf: &LoopFields,
-    is_initial_entry: bool,
-) -> StateMatch<Position> {
-    debug_assert!(lf.max_iters >= lf.min_iters);
-    let ld = &mut s.loops[lf.loop_id as usize];
-    let exit = lf.exit as usize;
-    let skip_ok;
-    let enter_ok;
-    if is_initial_entry {
-        // Entering the loop for the "first" time.
-        ld.iters = 0;
-        enter_ok = lf.max_iters > 0;
-        skip_ok = lf.min_iters == 0;
-    } else {
-        // Note that iters is the number of complete iterations.
-        ld.iters += 1;
-        // We can enter the loop if we have iterated less than the maximum number of
-        // times.
-        enter_ok = ld.iters < lf.max_iters;
-
-        // We can skip the loop if we have iterated at least the minimum number of
-        // times.
-        skip_ok = ld.iters >= lf.min_iters;
-
-        // Check if this iteration was beyond the minimum number of times, and our entry
-        // position is the same as last time (ES6 21.2.2.5.1 note 4).
-        // If so, we matched the empty string and we stop.
-        if ld.iters > lf.min_iters && ld.entry == s.pos {
-            return StateMatch::Fail;
+fn successful_match<Input: InputIndexer>(
+    input: Input,
+    state: &State<Input::Position>,
+    group_names: Box<[Box<str>]>,
+) -> Match {
+    let group_to_offset = |mr: &GroupData<Input::Position>| -> Option<Range<usize>> {
+        mr.as_range().map(|r| Range {
+            start: input.pos_to_offset(r.start),
+            end: input.pos_to_offset(r.end),
+        })
+    };
+    let captures = state.groups.iter().map(group_to_offset).collect();
+    Match {
+        range: input.pos_to_offset(state.start)..input.pos_to_offset(state.pos),
+        captures,
+        group_names,
+    }
+}
+
+/// The buffers of the PikeVM, which may be reused across searches. Those
+/// which hold positions are bound to the lifetime of their input, so they
+/// are stored without their element type: the thread lists empty, and the
+/// loop and group datas of threads one allocation apiece.
+#[derive(Debug, Default)]
+pub(crate) struct Scratch {
+    current: SpareVec,
+    next: SpareVec,
+    stack: SpareVec,
+    seen_ips: Vec<(u32, usize)>,
+    generation: u32,
+    seen_states: Vec<SeenState>,
+    seen_keys: Vec<usize>,
+    backref_groups: Vec<usize>,
+    spare_loops: SpareVec,
+    spare_groups: SpareVec,
+    stored_loops: Vec<SpareVec>,
+    stored_groups: Vec<SpareVec>,
+    nested: SpareVec,
+    nested_scratch: Option<Box<Scratch>>,
+}
+
+/// A state run at the current position.
+#[derive(Debug)]
+struct SeenState {
+    /// The range of its key in `MatchAttempter::seen_keys`.
+    start: usize,
+    end: usize,
+
+    /// The next state run at the same instruction, or NO_STATE.
+    next: usize,
+}
+
+const NO_STATE: usize = usize::MAX;
+
+/// Set \p groups to the groups of \p re read by backreferences, in order.
+fn backref_groups(re: &CompiledRegex, groups: &mut Vec<usize>) {
+    groups.clear();
+    groups.extend(re.insns.iter().filter_map(|insn| match *insn {
+        Insn::BackRef { group, .. } => Some(group as usize),
+        _ => None,
+    }));
+    groups.sort_unstable();
+    groups.dedup();
+}
+
+/// \return the buffer of \p spare as a boxed slice of \p len copies of
+/// \p value.
+fn respare<T: Copy>(spare: SpareVec, len: usize, value: T) -> Box<[T]> {
+    let mut v = spare.into_vec();
+    v.resize(len, value);
+    v.into_boxed_slice()
+}
+
+#[derive(Debug)]
+struct MatchAttempter<'a, Input: InputIndexer> {
+    re: &'a CompiledRegex,
+
+    /// The threads at the current position, in order of priority.
+    current: Vec<State<Input::Position>>,
+
+    /// The threads at the next position, in order of priority. A thread
+    /// which consumed more than one character waits here until the current
+    /// position catches up with it.
+    next: Vec<State<Input::Position>>,
+
+    /// Threads at the current position of lower priority than the one being
+    /// run, in reverse order of priority.
+    stack: Vec<State<Input::Position>>,
+
+    /// For each instruction, the generation at which it was last run, and
+    /// the first of the states it was run in then, or NO_STATE.
+    seen_ips: Vec<(u32, usize)>,
+    generation: u32,
+
+    /// The states run at the current position, chained by instruction. The
+    /// key of each state is stored in `seen_keys`.
+    seen_states: Vec<SeenState>,
+    seen_keys: Vec<usize>,
+
+    /// The groups read by backreferences, which distinguish threads.
+    backref_groups: Vec<usize>,
+
+    /// Spare loop and group datas, to avoid allocating for each thread.
+    spare_loops: Vec<Box<[LoopState<Input::Position>]>>,
+    spare_groups: Vec<Box<[GroupData<Input::Position>]>>,
+
+    /// The lists in which the spare datas are stored between searches,
+    /// emptied into `spare_loops` and `spare_groups`.
+    stored_loops: Vec<SpareVec>,
+    stored_groups: Vec<SpareVec>,
+
+    /// The attempter for lookarounds, created when first needed. This holds
+    /// at most one, and is a Vec so that its allocation may be reused.
+    nested: Vec<Self>,
+
+    /// The buffers of the attempter for lookarounds between searches.
+    nested_scratch: Option<Box<Scratch>>,
+
+    /// Set if the most recent search was cancelled by the interrupt.
+    cancelled: bool,
+}
+
+impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
+    fn with_scratch(re: &'a CompiledRegex, scratch: Scratch) -> Self {
+        // Entries left from an earlier search are of an earlier generation,
+        // and so are ignored.
+        let mut seen_ips = scratch.seen_ips;
+        seen_ips.resize(re.insns.len(), (0, NO_STATE));
+        let mut backref_groups = scratch.backref_groups;
+        self::backref_groups(re, &mut backref_groups);
+        let (mut stored_loops, mut stored_groups) = (scratch.stored_loops, scratch.stored_groups);
+        let mut spare_loops = scratch.spare_loops.into_vec();
+        if re.loops > 0 {
+            spare_loops.extend(
+                stored_loops
+                    .drain(..)
+                    .map(|spare| respare(spare, re.loops as usize, LoopState::EXITED)),
+            );
+        }
+        let mut spare_groups = scratch.spare_groups.into_vec();
+        if re.groups > 0 {
+            spare_groups.extend(
+                stored_groups
+                    .drain(..)
+                    .map(|spare| respare(spare, re.groups as usize, GroupData::new())),
+            );
+        }
+        Self {
+            re,
+            current: scratch.current.into_vec(),
+            next: scratch.next.into_vec(),
+            stack: scratch.stack.into_vec(),
+            seen_ips,
+            generation: scratch.generation,
+            seen_states: scratch.seen_states,
+            seen_keys: scratch.seen_keys,
+            backref_groups,
+            spare_loops,
+            spare_groups,
+            stored_loops,
+            stored_groups,
+            nested: scratch.nested.into_vec(),
+            nested_scratch: scratch.nested_scratch,
+            cancelled: false,
         }
     }
-    // Set up our fields as if we are going to enter the loop.
-    ld.entry = s.pos;
-    s.ip += 1;
 
-    if !enter_ok && !skip_ok {
-        StateMatch::Fail
-    } else if !enter_ok {
-        s.ip = exit;
-        StateMatch::Continue
-    } else if !skip_ok {
-        StateMatch::Continue
-    } else {
-        debug_assert!(enter_ok && skip_ok);
-        // We need to split our state.
-        let mut newstate = s.clone();
-        let exit_state = if lf.greedy { s } else { &mut newstate };
-        exit_state.ip = exit;
-        StateMatch::Split(newstate)
+    fn into_scratch(mut self) -> Scratch {
+        self.free_threads();
+        let mut stored_loops = self.stored_loops;
+        stored_loops.extend(
+            self.spare_loops
+                .drain(..)
+                .map(|l| SpareVec::new(l.into_vec())),
+        );
+        let mut stored_groups = self.stored_groups;
+        stored_groups.extend(
+            self.spare_groups
+                .drain(..)
+                .map(|g| SpareVec::new(g.into_vec())),
+        );
+        let mut nested_scratch = self.nested_scratch;
+        if let Some(nested) = self.nested.pop() {
+            let scratch = nested.into_scratch();
+            match &mut nested_scratch {
+                Some(boxed) => **boxed = scratch,
+                None => nested_scratch = Some(Box::new(scratch)),
+            }
+        }
+        Scratch {
+            current: SpareVec::new(self.current),
+            next: SpareVec::new(self.next),
+            stack: SpareVec::new(self.stack),
+            seen_ips: self.seen_ips,
+            generation: self.generation,
+            seen_states: self.seen_states,
+            seen_keys: self.seen_keys,
+            backref_groups: self.backref_groups,
+            spare_loops: SpareVec::new(self.spare_loops),
+            spare_groups: SpareVec::new(self.spare_groups),
+            stored_loops,
+            stored_groups,
+            nested: SpareVec::new(self.nested),
+            nested_scratch,
+        }
     }
-}
 
-fn try_match_state<Input: InputIndexer, Dir: Direction>(
-    re: &CompiledRegex,
-    input: &Input,
-    s: &mut State<Input::Position>,
-    dir: Dir,
-) -> StateMatch<Input::Position> {
-    macro_rules! nextinsn_or_fail {
-        ($e:expr) => {
-            if $e {
-                s.ip += 1;
-                StateMatch::Continue
-            } else {
-                StateMatch::Fail
+    /// \return a state at \p pos at the given \p ip, with no loops iterated
+    /// and no groups matched.
+    fn initial_state(&mut self, pos: Input::Position, ip: usize) -> State<Input::Position> {
+        let loops = match self.spare_loops.pop() {
+            Some(mut loops) => {
+                loops.fill(LoopState::EXITED);
+                loops
+            }
+            None => vec![LoopState::EXITED; self.re.loops as usize].into(),
+        };
+        let groups = match self.spare_groups.pop() {
+            Some(mut groups) => {
+                groups.fill(GroupData::new());
+                groups
             }
+            None => vec![GroupData::new(); self.re.groups as usize].into(),
         };
+        State {
+            pos,
+            start: pos,
+            ip,
+            loop1_iters: 0,
+            loops,
+            groups,
+        }
     }
-    match &re.insns[s.ip] {
-        Insn::Goal => StateMatch::Complete,
-        Insn::JustFail => StateMatch::Fail,
-        &Insn::Char(c) => match cursor::next(input, dir, &mut s.pos) {
-            Some(c2) => nextinsn_or_fail!(c == c2.as_u32()),
-            _ => StateMatch::Fail,
-        },
-
-        Insn::CharSet(v) => match cursor::next(input, dir, &mut s.pos) {
-            Some(c) => nextinsn_or_fail!(charset_contains(v, c.as_u32())),
-            _ => StateMatch::Fail,
-        },
-
-        Insn::ByteSeq1(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq2(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq3(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq4(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq5(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq6(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq7(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq8(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq9(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq10(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq11(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq12(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq13(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq14(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq15(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-        Insn::ByteSeq16(v) => nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v)),
-
-        Insn::StartOfLine { multiline } => {
-            let multiline = *multiline;
-            let matches = match input.peek_left(s.pos) {
-                None => true,
-                Some(c) if multiline && Input::CharProps::is_line_terminator(c) => true,
-                _ => false,
-            };
-            nextinsn_or_fail!(matches)
+
+    /// \return a copy of \p s, reusing spare datas.
+    fn fork(&mut self, s: &State<Input::Position>) -> State<Input::Position> {
+        let loops = match self.spare_loops.pop() {
+            Some(mut loops) => {
+                loops.copy_from_slice(&s.loops);
+                loops
+            }
+            None => s.loops.clone(),
+        };
+        let groups = match self.spare_groups.pop() {
+            Some(mut groups) => {
+                groups.copy_from_slice(&s.groups);
+                groups
+            }
+            None => s.groups.clone(),
+        };
+        State {
+            pos: s.pos,
+            start: s.start,
+            ip: s.ip,
+            loop1_iters: s.loop1_iters,
+            loops,
+            groups,
         }
+    }
 
-        Insn::EndOfLine { multiline } => {
-            let multiline = *multiline;
-            let matches = match input.peek_right(s.pos) {
-                None => true, // we're at the right of the string
-                Some(c) if multiline && Input::CharProps::is_line_terminator(c) => true,
-                _ => false,
-            };
-            nextinsn_or_fail!(matches)
+    /// Discard \p s, keeping its datas for reuse.
+    fn free(&mut self, s: State<Input::Position>) {
+        if !s.loops.is_empty() {
+            self.spare_loops.push(s.loops);
         }
+        if !s.groups.is_empty() {
+            self.spare_groups.push(s.groups);
+        }
+    }
 
-        Insn::MatchAny => match cursor::next(input, dir, &mut s.pos) {
-            Some(_) => nextinsn_or_fail!(true),
-            _ => StateMatch::Fail,
-        },
+    /// Discard every thread, keeping their datas for reuse.
+    fn free_threads(&mut self) {
+        while let Some(s) = self.current.pop() {
+            self.free(s);
+        }
+        while let Some(s) = self.next.pop() {
+            self.free(s);
+        }
+        while let Some(s) = self.stack.pop() {
+            self.free(s);
+        }
+    }
 
-        Insn::MatchAnyExceptLineTerminator => match cursor::next(input, dir, &mut s.pos) {
-            Some(c2) => nextinsn_or_fail!(!Input::CharProps::is_line_terminator(c2)),
-            _ => StateMatch::Fail,
-        },
+    /// Prepare to run threads at a new position.
+    fn begin_position(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.seen_ips.fill((0, NO_STATE));
+            self.generation = 1;
+        }
+        self.seen_states.clear();
+        self.seen_keys.clear();
+    }
 
-        &Insn::Jump { target } => {
-            s.ip = target as usize;
-            StateMatch::Continue
+    /// Mark the state of \p s as run at the current position, which is that
+    /// of \p s.
+    /// \return whether it was not already run, by a thread of higher
+    /// priority.
+    fn first_visit(&mut self, input: &Input, s: &State<Input::Position>) -> bool {
+        // The key of the state is everything but its instruction which may
+        // affect what it goes on to match. Loops which are not being iterated
+        // are alike, and the entry of a loop matters only in whether it is the
+        // current position.
+        let start = self.seen_keys.len();
+        self.seen_keys.push(s.loop1_iters);
+        for (loop_id, ld) in s.loops.iter().enumerate() {
+            if ld.entry.is_some() {
+                let entered_here = ld.entry == Some(s.pos);
+                self.seen_keys
+                    .extend([loop_id, ld.iters, entered_here as usize]);
+            }
+        }
+        let offset =
+            |pos: Option<Input::Position>| pos.map_or(usize::MAX, |p| input.pos_to_offset(p));
+        for &group in self.backref_groups.iter() {
+            let gd = &s.groups[group];
+            self.seen_keys.extend([offset(gd.start), offset(gd.end)]);
         }
+        let end = self.seen_keys.len();
 
-        &Insn::Alt { secondary } => {
-            let mut left = s.clone();
-            left.ip += 1;
-            s.ip = secondary as usize;
-            StateMatch::Split(left)
+        let (generation, head) = &mut self.seen_ips[s.ip];
+        if *generation != self.generation {
+            *generation = self.generation;
+            *head = NO_STATE;
         }
+        let mut idx = *head;
+        while idx != NO_STATE {
+            let seen = &self.seen_states[idx];
+            if self.seen_keys[seen.start..seen.end] == self.seen_keys[start..end] {
+                self.seen_keys.truncate(start);
+                return false;
+            }
+            idx = seen.next;
+        }
+        self.seen_states.push(SeenState {
+            start,
+            end,
+            next: *head,
+        });
+        *head = self.seen_states.len() - 1;
+        true
+    }
 
-        &Insn::BeginCaptureGroup(group_idx) => {
-            let group = &mut s.groups[group_idx as usize];
-            if Dir::FORWARD {
-                core::debug_assert!(!group.start_matched(), "Group should not have been entered");
-                group.start = Some(s.pos);
-            } else {
-                core::debug_assert!(!group.end_matched(), "Group should not have been entered");
-                group.end = Some(s.pos);
+    /// Run the threads of the current position, in order of priority,
+    /// moving those which consume input to the next list.
+    /// \return the first thread to reach the goal. Threads of lower priority
+    /// than it are discarded.
+    fn run_position<Dir: Direction>(
+        &mut self,
+        input: &Input,
+        pos: Input::Position,
+        dir: Dir,
+    ) -> Option<State<Input::Position>> {
+        let mut found = None;
+        self.run_position_with(input, pos, dir, |_, s| {
+            found = Some(s);
+            true
+        });
+        found
+    }
+
+    /// Run the threads of the current position, in order of priority,
+    /// moving those which consume input to the next list. Each thread to
+    /// reach the goal is passed to \p goal, which returns whether to stop;
+    /// if so, the threads of lower priority are discarded.
+    fn run_position_with<Dir: Direction>(
+        &mut self,
+        input: &Input,
+        pos: Input::Position,
+        dir: Dir,
+        mut goal: impl FnMut(&mut Self, State<Input::Position>) -> bool,
+    ) {
+        self.begin_position();
+        let mut current = core::mem::take(&mut self.current);
+        let mut threads = current.drain(..);
+        'threads: for thread in threads.by_ref() {
+            debug_assert!(self.stack.is_empty(), "Stack should be empty");
+            let mut s = thread;
+            loop {
+                // A literal may end within a character, so a thread may wake
+                // up behind the current position. Such a thread goes on to
+                // match the rest of its literal, so it need not be deduplicated.
+                let ahead = match Dir::FORWARD {
+                    true => s.pos > pos,
+                    false => s.pos < pos,
+                };
+                if ahead {
+                    // This thread has consumed input.
+                    self.next.push(s);
+                } else if s.pos == pos && !self.first_visit(input, &s) {
+                    self.free(s);
+                } else {
+                    match self.try_match_state(input, &mut s, dir) {
+                        StateMatch::Fail => self.free(s),
+                        StateMatch::Continue => continue,
+                        StateMatch::Split(newstate) => {
+                            // Explore the new state first.
+                            self.stack.push(s);
+                            s = newstate;
+                            continue;
+                        }
+                        StateMatch::Complete => {
+                            if goal(self, s) {
+                                break 'threads;
+                            }
+                        }
+                    }
+                }
+                match self.stack.pop() {
+                    Some(lower) => s = lower,
+                    None => break,
+                }
             }
-            nextinsn_or_fail!(true)
         }
+        // Discard the threads of lower priority than the match, if any.
+        for s in threads {
+            self.free(s);
+        }
+        while let Some(s) = self.stack.pop() {
+            self.free(s);
+        }
+        self.current = current;
+    }
 
-        &Insn::EndCaptureGroup(group_idx) => {
-            let group = &mut s.groups[group_idx as usize];
-            if Dir::FORWARD {
-                core::debug_assert!(group.start_matched(), "Group should have been entered");
-                group.end = Some(s.pos);
-            } else {
-                core::debug_assert!(group.end_matched(), "Group should have been exited");
-                group.start = Some(s.pos);
+    /// Run from \p init, in the direction \p dir.
+    /// If \p skip is None, the match must begin at the position of \p init;
+    /// otherwise a thread is started at each later position too, with \p skip
+    /// used to find the next position where a match may begin, when no
+    /// thread is running.
+    /// If \p earliest is set, return the first thread to reach the goal;
+    /// otherwise the thread of highest priority to do so.
+    /// \return the thread which matched, or None.
+    fn run<Dir: Direction, Skip: bytesearch::ByteSearcher>(
+        &mut self,
+        input: Input,
+        init: State<Input::Position>,
+        dir: Dir,
+        skip: Option<&Skip>,
+        earliest: bool,
+    ) -> Option<State<Input::Position>> {
+        debug_assert!(self.current.is_empty() && self.next.is_empty());
+        self.cancelled = false;
+        let mut pos = init.pos;
+        let mut init = Some(init);
+        let mut best: Option<State<Input::Position>> = None;
+        let mut polled = 0;
+        loop {
+            if best.is_none() {
+                if let Some(skip) = skip {
+                    if self.current.is_empty() && Input::CODE_UNITS_ARE_BYTES {
+                        // Positions skipped count as steps for the interrupt.
+                        match input.find_bytes(pos, skip) {
+                            Some(p) => {
+                                polled += input.pos_to_offset(p) - input.pos_to_offset(pos);
+                                pos = p
+                            }
+                            None => break,
+                        }
+                    }
+                    let template = init.as_ref().expect("Should have an initial state");
+                    let mut s = self.fork(template);
+                    s.pos = pos;
+                    s.start = pos;
+                    self.current.push(s);
+                } else if let Some(s) = init.take() {
+                    self.current.push(s);
+                }
             }
-            core::debug_assert!(
-                group.end >= group.start,
-                "Exit pos should be after start pos"
-            );
-            nextinsn_or_fail!(true)
+            if self.current.is_empty() {
+                break;
+            }
+
+            polled += 1;
+            if polled >= INTERRUPT_POLL_INTERVAL {
+                polled = 0;
+                if self.re.interrupt.as_ref().is_some_and(|i| i.is_set()) {
+                    self.cancelled = true;
+                }
+            }
+            if let Some(s) = self.run_position(&input, pos, dir) {
+                if let Some(prev) = best.replace(s) {
+                    self.free(prev);
+                }
+                if earliest {
+                    break;
+                }
+            }
+            if self.cancelled {
+                break;
+            }
+            core::mem::swap(&mut self.current, &mut self.next);
+            if cursor::next(&input, dir, &mut pos).is_none() {
+                break;
+            }
+        }
+        self.free_threads();
+        if let Some(s) = init {
+            self.free(s);
         }
+        if self.cancelled {
+            if let Some(s) = best.take() {
+                self.free(s);
+            }
+        }
+        best
+    }
 
-        &Insn::ResetCaptureGroup(group_idx) => {
-            s.groups[group_idx as usize].reset();
-            nextinsn_or_fail!(true)
+    /// Run a lookaround from \p s, whose ip is that of its first
+    /// instruction.
+    /// \return the state in which it matched, or None.
+    fn run_lookaround<Dir: Direction>(
+        &mut self,
+        input: &Input,
+        s: &State<Input::Position>,
+        earliest: bool,
+    ) -> Option<State<Input::Position>> {
+        let re = self.re;
+        let init = self.fork(s);
+        if self.nested.is_empty() {
+            let scratch = self.nested_scratch.as_deref_mut().map(core::mem::take);
+            self.nested
+                .push(Self::with_scratch(re, scratch.unwrap_or_default()));
         }
+        // The states of the lookaround come back to us, so it shares our
+        // spare datas.
+        let nested = &mut self.nested[0];
+        core::mem::swap(&mut self.spare_loops, &mut nested.spare_loops);
+        core::mem::swap(&mut self.spare_groups, &mut nested.spare_groups);
+        let result = nested.run(
+            *input,
+            init,
+            Dir::new(),
+            None::<&bytesearch::EmptyString>,
+            earliest,
+        );
+        core::mem::swap(&mut self.spare_loops, &mut nested.spare_loops);
+        core::mem::swap(&mut self.spare_groups, &mut nested.spare_groups);
+        self.cancelled |= nested.cancelled;
+        result
+    }
+
+    fn run_loop(
+        &mut self,
+        s: &mut State<Input::Position>,
+        lf: &LoopFields,
+        is_initial_entry: bool,
+    ) -> StateMatch<Input::Position> {
+        debug_assert!(lf.max_iters >= lf.min_iters);
+        let ld = &mut s.loops[lf.loop_id as usize];
+        let exit = lf.exit as usize;
+        let skip_ok;
+        let enter_ok;
+        if is_initial_entry {
+            // Entering the loop for the "first" time.
+            ld.iters = 0;
+            enter_ok = lf.max_iters > 0;
+            skip_ok = lf.min_iters == 0;
+        } else {
+            // Note that iters is the number of complete iterations.
+            ld.iters += 1;
+            if lf.max_iters == usize::MAX {
+                ld.iters = ld.iters.min(lf.min_iters + 1);
+            }
+            // We can enter the loop if we have iterated less than the maximum number of
+            // times.
+            enter_ok = ld.iters < lf.max_iters;
 
-        &Insn::BackRef {
-            group: group_idx,
-            icase,
-        } => {
-            let matched;
-            let group = &mut s.groups[group_idx as usize];
-            if let Some(orig_range) = group.as_range() {
-                if icase {
-                    matched = matchers::backref_icase(input, dir, orig_range, &mut s.pos);
+            // We can skip the loop if we have iterated at least the minimum number of
+            // times.
+            skip_ok = ld.iters >= lf.min_iters;
+
+            // Check if this iteration was beyond the minimum number of times, and our entry
+            // position is the same as last time (ES6 21.2.2.5.1 note 4).
+            // If so, we matched the empty string and we stop.
+            if ld.iters > lf.min_iters && ld.entry == Some(s.pos) {
+                return StateMatch::Fail;
+            }
+        }
+        // Set up our fields as if we are going to enter the loop.
+        ld.entry = Some(s.pos);
+        s.ip += 1;
+
+        if !enter_ok && !skip_ok {
+            StateMatch::Fail
+        } else if !enter_ok {
+            s.ip = exit;
+            s.loops[lf.loop_id as usize] = LoopState::EXITED;
+            StateMatch::Continue
+        } else if !skip_ok {
+            StateMatch::Continue
+        } else {
+            debug_assert!(enter_ok && skip_ok);
+            // We need to split our state.
+            let mut newstate = self.fork(s);
+            let exit_state = if lf.greedy { s } else { &mut newstate };
+            exit_state.ip = exit;
+            exit_state.loops[lf.loop_id as usize] = LoopState::EXITED;
+            StateMatch::Split(newstate)
+        }
+    }
+
+    fn try_match_state<Dir: Direction>(
+        &mut self,
+        input: &Input,
+        s: &mut State<Input::Position>,
+        dir: Dir,
+    ) -> StateMatch<Input::Position> {
+        macro_rules! nextinsn_or_fail {
+            ($e:expr) => {
+                if $e {
+                    s.ip += 1;
+                    StateMatch::Continue
                 } else {
-                    matched = matchers::backref(input, dir, orig_range, &mut s.pos)
+                    StateMatch::Fail
                 }
-            } else {
-                // This group has not been exited, and therefore the match succeeds
-                // (ES6 21.2.2.9).
-                matched = true;
-            }
-            nextinsn_or_fail!(matched)
-        }
-
-        &Insn::Lookahead {
-            negate,
-            start_group: _,
-            end_group: _,
-            continuation,
-        } => {
-            // Enter into the lookaround's instruction stream.
-            s.ip += 1;
-            let saved_pos = s.pos;
-            let attempt_succeeded =
-                MatchAttempter::<Input>::new(re).try_at_pos(*input, s, Forward::new());
-            let matched = attempt_succeeded != negate;
-            if matched {
-                s.ip = continuation as usize;
-                s.pos = saved_pos;
-                StateMatch::Continue
-            } else {
-                StateMatch::Fail
-            }
-        }
-
-        &Insn::Lookbehind {
-            negate,
-            start_group: _,
-            end_group: _,
-            continuation,
-        } => {
-            // Enter into the lookaround's instruction stream.
-            s.ip += 1;
-            let saved_pos = s.pos;
-            let attempt_succeeded = MatchAttempter::new(re).try_at_pos(*input, s, Backward::new());
-            let matched = attempt_succeeded != negate;
-            if matched {
-                s.ip = continuation as usize;
-                s.pos = saved_pos;
-                StateMatch::Continue
-            } else {
-                StateMatch::Fail
-            }
-        }
-        Insn::EnterLoop(lf) => run_loop(s, lf, true),
-        &Insn::LoopAgain { begin } => {
-            s.ip = begin as usize;
-            match re.insns.iat(s.ip) {
-                Insn::EnterLoop(lf) => run_loop(s, lf, false),
-                _ => panic!("LoopAgain does not point at EnterLoop"),
-            }
-        }
-        &Insn::Loop1CharBody {
-            min_iters,
-            max_iters,
-            greedy,
-        } => {
-            let loop_ip = s.ip;
-            let continuation = loop_ip + 2;
-
-            // Try to iterate on the loop. We may "fail" if we're already at the max, or if
-            // the loop body fails to match.
-            let iters = s.loop1_iters;
-            let mut taken_pos = None;
-            if iters < max_iters {
-                // Try matching the next instruction, which matches exactly one character,
-                // and does not modify loops or groups.
-                let saved_pos = s.pos;
-                s.ip = loop_ip + 1;
-                taken_pos = match try_match_state(re, input, s, dir) {
-                    StateMatch::Continue => Some(s.pos),
-                    StateMatch::Fail => None,
-                    _ => unreachable!("Loop1CharBody body must match exactly one character"),
-                };
-                s.ip = loop_ip;
-                s.pos = saved_pos;
             };
+        }
+        let re = self.re;
+        match &re.insns[s.ip] {
+            Insn::Goal => StateMatch::Complete,
+            Insn::JustFail => StateMatch::Fail,
+            &Insn::Char(c) => match cursor::next(input, dir, &mut s.pos) {
+                Some(c2) => nextinsn_or_fail!(c == c2.as_u32()),
+                _ => StateMatch::Fail,
+            },
 
-            match (taken_pos, iters >= min_iters) {
-                // Cannot not iterate and the minimum isn't met: dead end.
-                (None, false) => StateMatch::Fail,
+            Insn::CharSet(v) => match cursor::next(input, dir, &mut s.pos) {
+                Some(c) => nextinsn_or_fail!(charset_contains(v, c.as_u32())),
+                _ => StateMatch::Fail,
+            },
 
-                // Cannot (or did not) iterate but the minimum is met: exit the loop.
-                (None, true) => {
-                    s.ip = continuation;
-                    s.loop1_iters = 0;
-                    StateMatch::Continue
+            Insn::ByteSeq1(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq2(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq3(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq4(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq5(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq6(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq7(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq8(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq9(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq10(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq11(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq12(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq13(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq14(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq15(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+            Insn::ByteSeq16(v) => {
+                nextinsn_or_fail!(cursor::try_match_lit(input, dir, &mut s.pos, v))
+            }
+
+            Insn::StartOfLine { multiline } => {
+                let multiline = *multiline;
+                let matches = match input.peek_left(s.pos) {
+                    None => true,
+                    Some(c) if multiline && Input::CharProps::is_line_terminator(c) => true,
+                    _ => false,
+                };
+                nextinsn_or_fail!(matches)
+            }
+
+            Insn::EndOfLine { multiline } => {
+                let multiline = *multiline;
+                let matches = match input.peek_right(s.pos) {
+                    None => true, // we're at the right of the string
+                    Some(c) if multiline && Input::CharProps::is_line_terminator(c) => true,
+                    _ => false,
+                };
+                nextinsn_or_fail!(matches)
+            }
+
+            Insn::MatchAny => match cursor::next(input, dir, &mut s.pos) {
+                Some(_) => nextinsn_or_fail!(true),
+                _ => StateMatch::Fail,
+            },
+
+            Insn::MatchAnyExceptLineTerminator => match cursor::next(input, dir, &mut s.pos) {
+                Some(c2) => nextinsn_or_fail!(!Input::CharProps::is_line_terminator(c2)),
+                _ => StateMatch::Fail,
+            },
+
+            &Insn::Jump { target } => {
+                s.ip = target as usize;
+                StateMatch::Continue
+            }
+
+            &Insn::Alt { secondary } => {
+                let mut left = self.fork(s);
+                left.ip += 1;
+                s.ip = secondary as usize;
+                StateMatch::Split(left)
+            }
+
+            &Insn::BeginCaptureGroup(group_idx) => {
+                let group = &mut s.groups[group_idx as usize];
+                if Dir::FORWARD {
+                    group.start = Some(s.pos);
+                } else {
+                    group.end = Some(s.pos);
                 }
+                nextinsn_or_fail!(true)
+            }
 
-                // Below the minimum: another iteration is mandatory.
-                (Some(taken_pos), false) => {
-                    s.pos = taken_pos;
-                    s.loop1_iters = iters + 1;
-                    StateMatch::Continue
+            &Insn::EndCaptureGroup(group_idx) => {
+                let group = &mut s.groups[group_idx as usize];
+                if Dir::FORWARD {
+                    core::debug_assert!(group.start_matched(), "Group should have been entered");
+                    group.end = Some(s.pos);
+                } else {
+                    core::debug_assert!(group.end_matched(), "Group should have been exited");
+                    group.start = Some(s.pos);
                 }
+                core::debug_assert!(
+                    group.end >= group.start,
+                    "Exit pos should be after start pos"
+                );
+                nextinsn_or_fail!(true)
+            }
 
-                // Both iterating and exiting are viable: split, ordered by greed.
-                // Split(new) explores `new` first, so push the preferred branch.
-                (Some(taken_pos), true) => {
-                    if greedy {
-                        // Prefer iterating. The clone keeps ip == loop_ip.
-                        let mut iterate = s.clone();
-                        iterate.pos = taken_pos;
-                        iterate.loop1_iters = iters + 1;
+            &Insn::ResetCaptureGroup(group_idx) => {
+                s.groups[group_idx as usize].reset();
+                nextinsn_or_fail!(true)
+            }
+
+            &Insn::BackRef {
+                group: group_idx,
+                icase,
+            } => {
+                let matched;
+                let group = &mut s.groups[group_idx as usize];
+                if let Some(orig_range) = group.as_range() {
+                    if icase {
+                        matched = matchers::backref_icase(input, dir, orig_range, &mut s.pos);
+                    } else {
+                        matched = matchers::backref(input, dir, orig_range, &mut s.pos)
+                    }
+                } else {
+                    // This group has not been exited, and therefore the match succeeds
+                    // (ES6 21.2.2.9).
+                    matched = true;
+                }
+                nextinsn_or_fail!(matched)
+            }
+
+            &Insn::Lookahead {
+                negate,
+                start_group,
+                end_group,
+                continuation,
+            } => {
+                // Enter into the lookaround's instruction stream. Only a
+                // positive lookaround with groups needs the match of highest
+                // priority.
+                s.ip += 1;
+                let earliest = negate || start_group == end_group;
+                let found = self.run_lookaround::<Forward>(input, s, earliest);
+                self.finish_lookaround(s, found, negate, continuation as usize)
+            }
+
+            &Insn::Lookbehind {
+                negate,
+                start_group,
+                end_group,
+                continuation,
+            } => {
+                s.ip += 1;
+                let earliest = negate || start_group == end_group;
+                let found = self.run_lookaround::<Backward>(input, s, earliest);
+                self.finish_lookaround(s, found, negate, continuation as usize)
+            }
+            Insn::EnterLoop(lf) => self.run_loop(s, lf, true),
+            &Insn::LoopAgain { begin } => {
+                s.ip = begin as usize;
+                match re.insns.iat(s.ip) {
+                    Insn::EnterLoop(lf) => self.run_loop(s, lf, false),
+                    _ => panic!("LoopAgain does not point at EnterLoop"),
+                }
+            }
+            &Insn::Loop1CharBody {
+                min_iters,
+                max_iters,
+                greedy,
+            } => {
+                let loop_ip = s.ip;
+                let continuation = loop_ip + 2;
+
+                // Try to iterate on the loop. We may "fail" if we're already at the max, or if
+                // the loop body fails to match.
+                let iters = s.loop1_iters;
+                let mut taken_pos = None;
+                if iters < max_iters {
+                    // Try matching the next instruction, which matches exactly one character,
+                    // and does not modify loops or groups.
+                    let saved_pos = s.pos;
+                    s.ip = loop_ip + 1;
+                    taken_pos = match self.try_match_state(input, s, dir) {
+                        StateMatch::Continue => Some(s.pos),
+                        StateMatch::Fail => None,
+                        _ => unreachable!("Loop1CharBody body must match exactly one character"),
+                    };
+                    s.ip = loop_ip;
+                    s.pos = saved_pos;
+                };
+                let next_iters = match max_iters {
+                    usize::MAX => (iters + 1).min(min_iters),
+                    _ => iters + 1,
+                };
+
+                match (taken_pos, iters >= min_iters) {
+                    // Cannot not iterate and the minimum isn't met: dead end.
+                    (None, false) => StateMatch::Fail,
+
+                    // Cannot (or did not) iterate but the minimum is met: exit the loop.
+                    (None, true) => {
                         s.ip = continuation;
                         s.loop1_iters = 0;
-                        StateMatch::Split(iterate)
-                    } else {
-                        // Prefer exiting.
-                        let mut exit = s.clone();
+                        StateMatch::Continue
+                    }
+
+                    // Below the minimum: another iteration is mandatory.
+                    (Some(taken_pos), false) => {
+                        s.pos = taken_pos;
+                        s.loop1_iters = next_iters;
+                        StateMatch::Continue
+                    }
+
+                    // Both iterating and exiting are viable: split, ordered by greed.
+                    // Split(new) explores `new` first, so return the preferred branch.
+                    (Some(taken_pos), true) => {
+                        let mut new = self.fork(s);
+                        let (iterate, exit) = if greedy { (&mut new, s) } else { (s, &mut new) };
+                        iterate.pos = taken_pos;
+                        iterate.loop1_iters = next_iters;
                         exit.ip = continuation;
                         exit.loop1_iters = 0;
-                        s.pos = taken_pos;
-                        s.loop1_iters = iters + 1;
-                        StateMatch::Split(exit)
+                        StateMatch::Split(new)
                     }
                 }
             }
-        }
-        &Insn::Bracket(idx) => match cursor::next(input, dir, &mut s.pos) {
-            Some(c) => nextinsn_or_fail!(Input::CharProps::bracket(&re.brackets[idx], c)),
-            _ => StateMatch::Fail,
-        },
+            &Insn::Bracket(idx) => match cursor::next(input, dir, &mut s.pos) {
+                Some(c) => nextinsn_or_fail!(Input::CharProps::bracket(&re.brackets[idx], c)),
+                _ => StateMatch::Fail,
+            },
+
+            Insn::AsciiBracket(bytes) => {
+                nextinsn_or_fail!(scm::MatchByteSet { bytes }.matches(input, dir, &mut s.pos))
+            }
+            &Insn::ByteSet2(bytes) => {
+                nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+            }
+            &Insn::ByteSet3(bytes) => {
+                nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+            }
+            &Insn::ByteSet4(bytes) => {
+                nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+            }
+
+            &Insn::WordBoundary { invert } => {
+                let prev_wordchar = input
+                    .peek_left(s.pos)
+                    .is_some_and(Input::CharProps::is_word_char);
+                let curr_wordchar = input
+                    .peek_right(s.pos)
+                    .is_some_and(Input::CharProps::is_word_char);
+                let is_boundary = prev_wordchar != curr_wordchar;
+                nextinsn_or_fail!(is_boundary != invert)
+            }
 
-        Insn::AsciiBracket(bytes) => {
-            nextinsn_or_fail!(scm::MatchByteSet { bytes }.matches(input, dir, &mut s.pos))
+            &Insn::WordBoundaryUnicodeICase { invert } => {
+                let prev_wordchar = input
+                    .peek_left(s.pos)
+                    .is_some_and(Input::CharProps::is_word_char_unicode_icase);
+                let curr_wordchar = input
+                    .peek_right(s.pos)
+                    .is_some_and(Input::CharProps::is_word_char_unicode_icase);
+                let is_boundary = prev_wordchar != curr_wordchar;
+                nextinsn_or_fail!(is_boundary != invert)
+            }
         }
-        &Insn::ByteSet2(bytes) => {
-            nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+    }
+
+    /// Continue \p s after a lookaround, which matched in the state \p found
+    /// if any. A positive lookaround which matched keeps the groups it set.
+    fn finish_lookaround(
+        &mut self,
+        s: &mut State<Input::Position>,
+        found: Option<State<Input::Position>>,
+        negate: bool,
+        continuation: usize,
+    ) -> StateMatch<Input::Position> {
+        if found.is_some() == negate {
+            if let Some(found) = found {
+                self.free(found);
+            }
+            return StateMatch::Fail;
         }
-        &Insn::ByteSet3(bytes) => {
-            nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+        if let Some(mut found) = found {
+            found.pos = s.pos;
+            found.start = s.start;
+            self.free(core::mem::replace(s, found));
         }
-        &Insn::ByteSet4(bytes) => {
-            nextinsn_or_fail!(scm::MatchByteArraySet(bytes).matches(input, dir, &mut s.pos))
+        s.ip = continuation;
+        StateMatch::Continue
+    }
+}
+
+#[derive(Debug)]
+pub struct PikeVMExecutor<'r, Input: InputIndexer> {
+    input: Input,
+    matcher: MatchAttempter<'r, Input>,
+}
+
+impl<'r, Input: InputIndexer> PikeVMExecutor<'r, Input> {
+    /// Construct an executor for \p re searching \p input.
+    pub(crate) fn new(re: &'r CompiledRegex, input: Input) -> Self {
+        Self::with_scratch(re, input, Scratch::default())
+    }
+
+    /// Construct an executor for \p re searching \p input, using the thread
+    /// lists of \p scratch.
+    pub(crate) fn with_scratch(re: &'r CompiledRegex, input: Input, scratch: Scratch) -> Self {
+        Self {
+            input,
+            matcher: MatchAttempter::with_scratch(re, scratch),
         }
+    }
+
+    /// Give up the thread lists so that they may be reused.
+    pub(crate) fn into_scratch(self) -> Scratch {
+        self.matcher.into_scratch()
+    }
 
-        &Insn::WordBoundary { invert } => {
-            let prev_wordchar = input
-                .peek_left(s.pos)
-                .is_some_and(Input::CharProps::is_word_char);
-            let curr_wordchar = input
-                .peek_right(s.pos)
-                .is_some_and(Input::CharProps::is_word_char);
-            let is_boundary = prev_wordchar != curr_wordchar;
-            nextinsn_or_fail!(is_boundary != invert)
+    /// \return the thread which matched first at or after \p pos, or of
+    /// highest priority unless \p earliest is set.
+    fn find_state(
+        &mut self,
+        pos: Input::Position,
+        earliest: bool,
+    ) -> Option<State<Input::Position>> {
+        let re = self.matcher.re;
+        let init = self.matcher.initial_state(pos, 0);
+        // Anchored and sticky regexes only try matching at the current position.
+        if re.flags.sticky {
+            return self.run_anchored(init, earliest);
         }
 
-        &Insn::WordBoundaryUnicodeICase { invert } => {
-            let prev_wordchar = input
-                .peek_left(s.pos)
-                .is_some_and(Input::CharProps::is_word_char_unicode_icase);
-            let curr_wordchar = input
-                .peek_right(s.pos)
-                .is_some_and(Input::CharProps::is_word_char_unicode_icase);
-            let is_boundary = prev_wordchar != curr_wordchar;
-            nextinsn_or_fail!(is_boundary != invert)
+        // When UTF-16 support is active prefix search is not used due to the different encoding.
+        #[cfg(feature = "utf16")]
+        return self.run(init, &bytesearch::EmptyString {}, earliest);
+
+        #[cfg(not(feature = "utf16"))]
+        match &re.start_pred {
+            StartPredicate::Arbitrary => self.run(init, &bytesearch::EmptyString {}, earliest),
+            StartPredicate::StartAnchored => self.run_anchored(init, earliest),
+            StartPredicate::ByteSet1(bytes) => self.run(init, bytes, earliest),
+            StartPredicate::ByteSet2(bytes) => self.run(init, bytes, earliest),
+            StartPredicate::ByteSet3(bytes) => self.run(init, bytes, earliest),
+            StartPredicate::ByteSeq(bytes) => self.run(init, bytes.as_ref(), earliest),
+            StartPredicate::ByteBracket(bitmap) => self.run(init, bitmap, earliest),
+            #[cfg(feature = "perf-literal")]
+            StartPredicate::LiteralSet(set) => self.run(init, set.as_ref(), earliest),
+            StartPredicate::InnerLiteral(literal) => self.run(init, &literal.searcher(), earliest),
         }
     }
-}
 
-fn successful_match<Input: InputIndexer>(
-    input: Input,
-    start: Input::Position,
-    state: &State<Input::Position>,
-    group_names: Box<[Box<str>]>,
-) -> Match {
-    let group_to_offset = |mr: &GroupData<Input::Position>| -> Option<Range<usize>> {
-        mr.as_range().map(|r| Range {
-            start: input.pos_to_offset(r.start),
-            end: input.pos_to_offset(r.end),
-        })
-    };
-    let captures = state.groups.iter().map(group_to_offset).collect();
-    Match {
-        range: input.pos_to_offset(start)..input.pos_to_offset(state.pos),
-        captures,
-        group_names,
+    fn run<Skip: bytesearch::ByteSearcher>(
+        &mut self,
+        init: State<Input::Position>,
+        skip: &Skip,
+        earliest: bool,
+    ) -> Option<State<Input::Position>> {
+        self.matcher
+            .run(self.input, init, Forward::new(), Some(skip), earliest)
     }
-}
 
-#[derive(Debug)]
-struct MatchAttempter<'a, Input: InputIndexer> {
-    states: Vec<State<Input::Position>>,
-    re: &'a CompiledRegex,
-}
+    fn run_anchored(
+        &mut self,
+        init: State<Input::Position>,
+        earliest: bool,
+    ) -> Option<State<Input::Position>> {
+        self.matcher.run(
+            self.input,
+            init,
+            Forward::new(),
+            None::<&bytesearch::EmptyString>,
+            earliest,
+        )
+    }
 
-impl<'a, Input: InputIndexer> MatchAttempter<'a, Input> {
-    fn new(re: &'a CompiledRegex) -> Self {
-        Self {
-            states: Vec::new(),
-            re,
+    /// Convert a successful state to a Match, and set `next_start` to the
+    /// position to start looking for the next match.
+    fn complete_match(
+        &mut self,
+        state: State<Input::Position>,
+        next_start: &mut Option<Input::Position>,
+    ) -> Match {
+        // If we matched the empty string, we have to increment.
+        if state.pos != state.start {
+            *next_start = Some(state.pos)
+        } else {
+            *next_start = self.input.next_right_pos(state.pos)
         }
+        let result = successful_match(self.input, &state, self.matcher.re.group_names.clone());
+        self.matcher.free(state);
+        result
     }
 
-    fn try_at_pos<Dir: Direction>(
+    /// Search for a match at or after `pos`, writing the offsets of the match
+    /// to `slots[0]` and of each capture group to the following slots.
+    /// If there is no match, every slot is set to None.
+    /// \return whether we matched.
+    pub(crate) fn read_captures_from(
         &mut self,
-        input: Input,
-        init_state: &mut State<Input::Position>,
-        dir: Dir,
+        pos: Input::Position,
+        slots: &mut [Option<Range<usize>>],
     ) -> bool {
-        debug_assert!(self.states.is_empty(), "Should be no states");
-        self.states.push(init_state.clone());
-        while !self.states.is_empty() {
-            let s = self.states.last_mut().unwrap();
-            match try_match_state(self.re, &input, s, dir) {
-                StateMatch::Fail => {
-                    self.states.pop();
-                }
-                StateMatch::Continue => {}
-                StateMatch::Complete => {
-                    // Give the successful state to the caller.
-                    core::mem::swap(init_state, s);
-                    self.states.clear();
-                    return true;
-                }
-                StateMatch::Split(newstate) => self.states.push(newstate),
-            }
+        debug_assert!(slots.len() == self.matcher.re.groups as usize + 1);
+        let Some(state) = self.find_state(pos, false) else {
+            slots.fill(None);
+            return false;
+        };
+        let input = self.input;
+        slots[0] = Some(input.pos_to_offset(state.start)..input.pos_to_offset(state.pos));
+        for (slot, gd) in slots[1..].iter_mut().zip(state.groups.iter()) {
+            *slot = gd
+                .as_range()
+                .map(|r| input.pos_to_offset(r.start)..input.pos_to_offset(r.end));
         }
-        false
+        self.matcher.free(state);
+        true
     }
-}
 
-#[derive(Debug)]
-pub struct PikeVMExecutor<'r, Input: InputIndexer> {
-    input: Input,
-    matcher: MatchAttempter<'r, Input>,
+    /// \return whether there is a match at or after `pos`.
+    /// No Match is constructed.
+    pub(crate) fn is_match_from(&mut self, pos: Input::Position) -> bool {
+        match self.find_state(pos, true) {
+            Some(state) => {
+                self.matcher.free(state);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl<'r, 't> exec::Executor<'r, 't> for PikeVMExecutor<'r, Utf8Input<'t>> {
     type AsAscii = PikeVMExecutor<'r, AsciiInput<'t>>;
 
     fn new(re: &'r CompiledRegex, text: &'t str) -> Self {
-        let input = Utf8Input::new(text, re.flags.unicode);
-        Self {
-            input,
-            matcher: MatchAttempter::new(re),
-        }
+        Self::new(re, Utf8Input::new(text, re.flags.unicode))
     }
 }
 
@@ -484,11 +1170,7 @@ impl<'r, 't> exec::Executor<'r, 't> for PikeVMExecutor<'r, AsciiInput<'t>> {
     type AsAscii = PikeVMExecutor<'r, AsciiInput<'t>>;
 
     fn new(re: &'r CompiledRegex, text: &'t str) -> Self {
-        let input = AsciiInput::new(text, re.flags.unicode);
-        Self {
-            input,
-            matcher: MatchAttempter::new(re),
-        }
+        Self::new(re, AsciiInput::new(text, re.flags.unicode))
     }
 }
 
@@ -504,71 +1186,190 @@ impl<Input: InputIndexer> exec::MatchProducer for PikeVMExecutor<'_, Input> {
         pos: Self::Position,
         next_start: &mut Option<Self::Position>,
     ) -> Option<Match> {
+        let state = self.find_state(pos, false)?;
+        Some(self.complete_match(state, next_start))
+    }
+
+    fn next_match_anchored(
+        &mut self,
+        pos: Self::Position,
+        next_start: &mut Option<Self::Position>,
+    ) -> Option<Match> {
+        let init = self.matcher.initial_state(pos, 0);
+        let state = self.run_anchored(init, false)?;
+        Some(self.complete_match(state, next_start))
+    }
+
+    fn abandoned(&self) -> Option<MatchError> {
+        // The PikeVM does not enforce a backtrack limit.
+        self.matcher.cancelled.then_some(MatchError::Cancelled)
+    }
+}
+
+/// Drives a compiled RegexSet. Each member of the set is a separate entry
+/// point into the same program, and the threads of every member are run
+/// together in a single pass over the input.
+#[derive(Debug)]
+pub(crate) struct SetExecutor<'r, Input: InputIndexer> {
+    input: Input,
+    matcher: MatchAttempter<'r, Input>,
+    entries: &'r [usize],
+}
+
+impl<'r, Input: InputIndexer> SetExecutor<'r, Input> {
+    /// Construct an executor using the thread lists of `scratch`.
+    pub(crate) fn with_scratch(
+        re: &'r CompiledRegex,
+        entries: &'r [usize],
+        input: Input,
+        scratch: Scratch,
+    ) -> Self {
+        Self {
+            input,
+            matcher: MatchAttempter::with_scratch(re, scratch),
+            entries,
+        }
+    }
+
+    /// Give up the thread lists so that they may be reused.
+    pub(crate) fn into_scratch(self) -> Scratch {
+        self.matcher.into_scratch()
+    }
+
+    /// Set `matched[i]` for each member `i` which matches somewhere in the
+    /// input. If `stop_at_first` is set, return as soon as any member matches.
+    pub(crate) fn run(&mut self, matched: &mut [bool], stop_at_first: bool) {
+        debug_assert!(matched.len() == self.entries.len());
         let re = self.matcher.re;
+        if re.flags.sticky {
+            return self.run_members(matched, stop_at_first, None::<&bytesearch::EmptyString>);
+        }
 
-        // Check if this is an anchored regex - if so, only try matching at the current position
-        if matches!(re.start_pred, StartPredicate::StartAnchored) {
-            let mut state = State {
-                pos,
-                ip: 0,
-                loop1_iters: 0,
-                loops: vec![LoopData::new(pos); re.loops as usize].into(),
-                groups: vec![GroupData::new(); re.groups as usize].into(),
-            };
-            if self
-                .matcher
-                .try_at_pos(self.input, &mut state, Forward::new())
-            {
-                let end = state.pos;
-                if end != pos {
-                    *next_start = Some(end)
-                } else {
-                    *next_start = self.input.next_right_pos(end)
-                }
-                return Some(successful_match(
-                    self.input,
-                    pos,
-                    &state,
-                    re.group_names.clone(),
-                ));
+        // When UTF-16 support is active prefix search is not used due to the different encoding.
+        #[cfg(feature = "utf16")]
+        return self.run_members(matched, stop_at_first, Some(&bytesearch::EmptyString {}));
+
+        #[cfg(not(feature = "utf16"))]
+        match &re.start_pred {
+            StartPredicate::Arbitrary => {
+                self.run_members(matched, stop_at_first, Some(&bytesearch::EmptyString {}))
+            }
+            StartPredicate::StartAnchored => {
+                self.run_members(matched, stop_at_first, None::<&bytesearch::EmptyString>)
+            }
+            StartPredicate::ByteSet1(bytes) => {
+                self.run_members(matched, stop_at_first, Some(bytes))
+            }
+            StartPredicate::ByteSet2(bytes) => {
+                self.run_members(matched, stop_at_first, Some(bytes))
+            }
+            StartPredicate::ByteSet3(bytes) => {
+                self.run_members(matched, stop_at_first, Some(bytes))
+            }
+            StartPredicate::ByteSeq(bytes) => {
+                self.run_members(matched, stop_at_first, Some(bytes.as_ref()))
+            }
+            StartPredicate::ByteBracket(bitmap) => {
+                self.run_members(matched, stop_at_first, Some(bitmap))
+            }
+            #[cfg(feature = "perf-literal")]
+            StartPredicate::LiteralSet(set) => {
+                self.run_members(matched, stop_at_first, Some(set.as_ref()))
+            }
+            StartPredicate::InnerLiteral(literal) => {
+                self.run_members(matched, stop_at_first, Some(&literal.searcher()))
             }
-            // Anchored regex failed to match at this position
-            return None;
         }
+    }
 
-        // Standard matching - try at each position
-        // Note the "initial" loop position is ignored. Use whatever is most convenient.
-        let mut state = State {
-            pos,
-            ip: 0,
-            loop1_iters: 0,
-            loops: vec![LoopData::new(pos); re.loops as usize].into(),
-            groups: vec![GroupData::new(); re.groups as usize].into(),
-        };
-        loop {
-            let start = state.pos;
-            if self
-                .matcher
-                .try_at_pos(self.input, &mut state, Forward::new())
-            {
-                let end = state.pos;
-                if end != start {
-                    *next_start = Some(end)
-                } else {
-                    *next_start = self.input.next_right_pos(end)
+    /// Search for every unmatched member at once, from the start of the
+    /// input. A thread is started for each at every position where \p skip
+    /// finds that a match may begin, or only at the start if \p skip is None.
+    /// A member matches once any of its threads reaches the goal.
+    fn run_members<Skip: bytesearch::ByteSearcher>(
+        &mut self,
+        matched: &mut [bool],
+        stop_at_first: bool,
+        skip: Option<&Skip>,
+    ) {
+        // The code of each member runs from its entry to that of the next.
+        let entries = self.entries;
+        debug_assert!(entries.windows(2).all(|w| w[0] < w[1]));
+        let member = |ip: usize| entries.partition_point(|&entry| entry <= ip) - 1;
+        let input = self.input;
+        let m = &mut self.matcher;
+        debug_assert!(m.current.is_empty() && m.next.is_empty());
+        m.cancelled = false;
+        let mut remaining = matched.iter().filter(|&&matched| !matched).count();
+        let mut pos = input.left_end();
+        let mut polled = 0;
+        while remaining > 0 {
+            if skip.is_some() || pos == input.left_end() {
+                if let Some(skip) = skip {
+                    if m.current.is_empty() && Input::CODE_UNITS_ARE_BYTES {
+                        // Positions skipped count as steps for the interrupt.
+                        match input.find_bytes(pos, skip) {
+                            Some(p) => {
+                                polled += input.pos_to_offset(p) - input.pos_to_offset(pos);
+                                pos = p
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                for (idx, &entry) in entries.iter().enumerate() {
+                    if !matched[idx] {
+                        let s = m.initial_state(pos, entry);
+                        m.current.push(s);
+                    }
+                }
+            }
+            if m.current.is_empty() {
+                break;
+            }
+
+            polled += 1;
+            if polled >= INTERRUPT_POLL_INTERVAL {
+                polled = 0;
+                if m.re.interrupt.as_ref().is_some_and(|i| i.is_set()) {
+                    m.cancelled = true;
+                    break;
                 }
-                return Some(successful_match(
-                    self.input,
-                    start,
-                    &state,
-                    re.group_names.clone(),
-                ));
             }
-            match self.input.next_right_pos(start) {
-                Some(nextpos) => state.pos = nextpos,
-                None => break,
+            let before = remaining;
+            m.run_position_with(&input, pos, Forward::new(), |m, s| {
+                let idx = member(s.ip);
+                m.free(s);
+                if !matched[idx] {
+                    matched[idx] = true;
+                    remaining -= 1;
+                }
+                stop_at_first || remaining == 0
+            });
+            if stop_at_first && remaining < before {
+                break;
             }
+            core::mem::swap(&mut m.current, &mut m.next);
+            if remaining < before {
+                // Stop running the threads of members which have matched.
+                let threads = core::mem::take(&mut m.current);
+                for s in threads {
+                    if matched[member(s.ip)] {
+                        m.free(s);
+                    } else {
+                        m.current.push(s);
+                    }
+                }
+            }
+            if cursor::next(&input, Forward::new(), &mut pos).is_none() {
+                break;
+            }
+        }
+        for s in core::mem::take(&mut m.current) {
+            m.free(s);
+        }
+        for s in core::mem::take(&mut m.next) {
+            m.free(s);
         }
-        None
     }
 }
@@ -0,0 +1,649 @@
+//! Printing of syntax trees back to pattern source.
+
+use crate::api::Flags;
+use crate::ast::{
+    Assertion, Ast, Backreference, Class, ClassEscape, ClassEscapeKind, ClassItem, ClassItemKind,
+    ClassSetOp, Group, GroupKind, Literal, LiteralKind, Modifiers, Node, NodeKind, Quantifier,
+    QuantifierKind,
+};
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+/// Print the pattern. With the alternate flag `{:#}`, print a regex literal
+/// like `/a\/b/u` instead, with `/` and line terminators escaped, and an
+/// empty pattern written as `(?:)`.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut p = Printer {
+            out: String::new(),
+            flags: self.flags,
+            for_literal: f.alternate(),
+            tail: Tail::Other,
+        };
+        p.node(&self.node);
+        p.finish();
+        if !f.alternate() {
+            return f.write_str(&p.out);
+        }
+        let pattern = if p.out.is_empty() { "(?:)" } else { &p.out };
+        write!(f, "/{}/{}", pattern, self.flags)
+    }
+}
+
+/// Where a literal is printed, which determines what must be escaped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Context {
+    /// Outside of a class.
+    Atom,
+
+    /// Within a class, outside of unicode sets mode.
+    Class,
+
+    /// At the start of an operand of a class, in unicode sets mode.
+    ClassSet,
+
+    /// Where only a code point may appear in a class, in unicode sets mode:
+    /// the end of a range, or within a string disjunction.
+    ClassSetCharacter,
+}
+
+/// What the output ends with, as far as it affects the next literal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tail {
+    /// Nothing which a literal could extend.
+    Other,
+
+    /// A null escape `\0`, which a following digit would turn into an octal
+    /// escape (or an error, in unicode mode).
+    Null,
+
+    /// A numbered backreference, which a following digit would extend.
+    Backreference,
+
+    /// A lone backslash at this offset, which is only valid (in annex B)
+    /// when followed by a `c` which does not form a control escape.
+    Backslash(usize),
+
+    /// A lone backslash and its `c`, which a following letter would turn
+    /// into a control escape.
+    LoneC,
+
+    /// A unicode escape of a high surrogate. The parser consumes a `\u`
+    /// after it while looking for a low surrogate, even if there is none.
+    HighSurrogate,
+
+    /// A verbatim `{` at this offset, in annex B, and whether a comma and
+    /// digits have followed it. It must be escaped if it turns out to begin
+    /// a braced quantifier.
+    Brace {
+        pos: usize,
+        comma: bool,
+        digits: bool,
+    },
+}
+
+/// Prints a syntax tree, escaping as needed for it to parse back the same.
+struct Printer {
+    /// The pattern printed so far.
+    out: String,
+
+    /// Flags of the pattern.
+    flags: Flags,
+
+    /// Whether to escape `/` and line terminators, for a regex literal.
+    for_literal: bool,
+
+    /// What the output ends with.
+    tail: Tail,
+}
+
+impl Printer {
+    /// \return whether the pattern is in unicode or unicode sets mode.
+    fn unicode(&self) -> bool {
+        self.flags.unicode || self.flags.unicode_sets
+    }
+
+    /// Append \p s, which is not a literal.
+    fn write(&mut self, s: &str) {
+        self.finish();
+        self.out.push_str(s);
+        self.tail = Tail::Other;
+    }
+
+    /// Settle a pending lone backslash: if it is not followed by a `c`, it
+    /// must be escaped.
+    fn finish(&mut self) {
+        if let Tail::Backslash(pos) = self.tail {
+            self.out.insert(pos, '\\');
+            self.tail = Tail::Other;
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::Empty => {}
+            NodeKind::Alternation(nodes) => {
+                for (idx, node) in nodes.iter().enumerate() {
+                    if idx > 0 {
+                        self.write("|");
+                    }
+                    self.node(node);
+                }
+            }
+            NodeKind::Concatenation(nodes) => {
+                for node in nodes {
+                    if matches!(node.kind, NodeKind::Alternation(_)) {
+                        self.non_capture(node);
+                    } else {
+                        self.node(node);
+                    }
+                }
+            }
+            NodeKind::Literal(lit) => self.literal(lit, Context::Atom, false),
+            NodeKind::Dot => self.write("."),
+            NodeKind::Assertion(assertion) => self.write(match assertion {
+                Assertion::StartOfLine => "^",
+                Assertion::EndOfLine => "$",
+                Assertion::WordBoundary => "\\b",
+                Assertion::NotWordBoundary => "\\B",
+            }),
+            NodeKind::ClassEscape(escape) => self.class_escape(escape),
+            NodeKind::Class(class) => self.class(class),
+            NodeKind::Group(group) => self.group(group),
+            NodeKind::Repetition(rep) => {
+                let quantifiable = matches!(
+                    rep.node.kind,
+                    NodeKind::Literal(_)
+                        | NodeKind::Dot
+                        | NodeKind::Assertion(_)
+                        | NodeKind::ClassEscape(_)
+                        | NodeKind::Class(_)
+                        | NodeKind::Group(_)
+                        | NodeKind::Backreference(_)
+                );
+                if quantifiable {
+                    self.node(&rep.node);
+                } else {
+                    self.non_capture(&rep.node);
+                }
+                self.quantifier(&rep.quantifier);
+            }
+            NodeKind::Backreference(Backreference::Number(group)) => {
+                self.write(&format!("\\{}", group));
+                self.tail = Tail::Backreference;
+            }
+            NodeKind::Backreference(Backreference::Name(name)) => {
+                self.write(&format!("\\k<{}>", name));
+            }
+        }
+    }
+
+    /// Print \p node in a non-capturing group, for nodes which cannot
+    /// otherwise appear where they are.
+    fn non_capture(&mut self, node: &Node) {
+        self.write("(?:");
+        self.node(node);
+        self.write(")");
+    }
+
+    fn quantifier(&mut self, quantifier: &Quantifier) {
+        let text = match (quantifier.kind, quantifier.min, quantifier.max) {
+            (QuantifierKind::ZeroOrMore, 0, None) => "*".into(),
+            (QuantifierKind::OneOrMore, 1, None) => "+".into(),
+            (QuantifierKind::ZeroOrOne, 0, Some(1)) => "?".into(),
+            (QuantifierKind::Exactly, min, Some(max)) if min == max => format!("{{{}}}", min),
+            (QuantifierKind::AtLeast, min, None) => format!("{{{},}}", min),
+            (QuantifierKind::Between, min, Some(max)) => format!("{{{},{}}}", min, max),
+            // The kind disagrees with the bounds, so print the bounds.
+            (_, min, None) => format!("{{{},}}", min),
+            (_, min, Some(max)) if min == max => format!("{{{}}}", min),
+            (_, min, Some(max)) => format!("{{{},{}}}", min, max),
+        };
+        self.write(&text);
+        if !quantifier.greedy {
+            self.write("?");
+        }
+    }
+
+    fn group(&mut self, group: &Group) {
+        match &group.kind {
+            GroupKind::Capture { name: None, .. } => self.write("("),
+            GroupKind::Capture {
+                name: Some(name), ..
+            } => self.write(&format!("(?<{}>", name)),
+            GroupKind::NonCapture => self.write("(?:"),
+            GroupKind::Modifiers { enable, disable } => {
+                let mut text = String::from("(?");
+                push_modifiers(&mut text, enable);
+                if *disable != Modifiers::default() {
+                    text.push('-');
+                    push_modifiers(&mut text, disable);
+                }
+                text.push(':');
+                self.write(&text);
+            }
+            GroupKind::Lookahead { negated } => self.write(if *negated { "(?!" } else { "(?=" }),
+            GroupKind::Lookbehind { negated } => self.write(if *negated { "(?<!" } else { "(?<=" }),
+        }
+        self.node(&group.node);
+        self.write(")");
+    }
+
+    fn class_escape(&mut self, escape: &ClassEscape) {
+        let letter = match &escape.kind {
+            ClassEscapeKind::Digit => 'd',
+            ClassEscapeKind::Space => 's',
+            ClassEscapeKind::Word => 'w',
+            ClassEscapeKind::Property { name, value } => {
+                let p = if escape.negated { 'P' } else { 'p' };
+                let text = match name {
+                    Some(name) => format!("\\{}{{{}={}}}", p, name, value),
+                    None => format!("\\{}{{{}}}", p, value),
+                };
+                return self.write(&text);
+            }
+        };
+        let letter = if escape.negated {
+            letter.to_ascii_uppercase()
+        } else {
+            letter
+        };
+        self.write(&format!("\\{}", letter));
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.write(if class.negated { "[^" } else { "[" });
+        if self.flags.unicode_sets {
+            let separator = match class.op {
+                ClassSetOp::Union => "",
+                ClassSetOp::Intersection => "&&",
+                ClassSetOp::Subtraction => "--",
+            };
+            for idx in 0..class.items.len() {
+                if idx > 0 && !separator.is_empty() {
+                    self.write(separator);
+                }
+                self.class_set_operand(class, idx);
+            }
+        } else {
+            self.class_items(class);
+        }
+        self.write("]");
+    }
+
+    /// Print the items of a class outside of unicode sets mode. This follows
+    /// the parser, so that a dash only makes a range where one is intended.
+    fn class_items(&mut self, class: &Class) {
+        // After a lone atom, whether that atom is a code point: a dash would
+        // then start a range with it.
+        let mut after_atom: Option<bool> = None;
+        let mut idx = 0;
+        while idx < class.items.len() {
+            let item = &class.items[idx];
+            let next = class.items.get(idx + 1);
+            // A caret at the start would negate the class.
+            let is_caret =
+                |lit: &Literal| idx == 0 && !class.negated && lit.value == u32::from('^');
+            let is_dash =
+                |lit: &Literal| lit.kind == LiteralKind::Verbatim && lit.value == u32::from('-');
+            after_atom = match &item.kind {
+                ClassItemKind::Literal(lit) if is_dash(lit) && after_atom.is_some() => {
+                    let joins_range = next.is_some_and(|next| match next.kind {
+                        ClassItemKind::Range { .. } => true,
+                        ClassItemKind::Literal(_) => after_atom == Some(true),
+                        _ => false,
+                    });
+                    self.literal(lit, Context::Class, joins_range);
+                    if let Some(next) = next.filter(|_| !joins_range) {
+                        // The dash and the next atom are parsed with the
+                        // previous atom as a range, which is then split.
+                        match &next.kind {
+                            ClassItemKind::Literal(lit) => self.literal(lit, Context::Class, false),
+                            _ => self.class_set_item(next),
+                        }
+                        idx += 1;
+                        None
+                    } else {
+                        Some(true)
+                    }
+                }
+                ClassItemKind::Literal(lit) => {
+                    self.literal(lit, Context::Class, is_caret(lit));
+                    Some(true)
+                }
+                ClassItemKind::Range { first, last } => {
+                    let escape = is_caret(first) || (is_dash(first) && after_atom.is_some());
+                    self.literal(first, Context::Class, escape);
+                    self.write("-");
+                    self.literal(last, Context::Class, false);
+                    None
+                }
+                _ => {
+                    self.class_set_item(item);
+                    Some(false)
+                }
+            };
+            idx += 1;
+        }
+    }
+
+    /// Print the operand at \p idx of \p class, in unicode sets mode.
+    fn class_set_operand(&mut self, class: &Class, idx: usize) {
+        let item = &class.items[idx];
+        let next = class.items.get(idx + 1);
+        // In unicode sets mode, reserved punctuators may not be doubled,
+        // including with the && separator.
+        let next_char = match class.op {
+            ClassSetOp::Union => next.and_then(leading_literal).and_then(verbatim_value),
+            ClassSetOp::Intersection => next.map(|_| u32::from('&')),
+            ClassSetOp::Subtraction => next.map(|_| u32::from('-')),
+        };
+        // A caret at the start would negate the class.
+        let escape_first =
+            |lit: &Literal| idx == 0 && !class.negated && lit.value == u32::from('^');
+        // Like the parser, a lone & after the first operand is taken as is.
+        let is_second_amp = |lit: &Literal| {
+            class.op == ClassSetOp::Union && idx == 1 && lit.value == u32::from('&')
+        };
+        match &item.kind {
+            ClassItemKind::Literal(lit) => {
+                let doubled = if is_second_amp(lit) {
+                    next_char == Some(lit.value)
+                } else {
+                    is_doubled_punctuator(lit, next_char)
+                };
+                let escape = escape_first(lit) || doubled;
+                // As in the parser, an operand after && or -- may be a
+                // verbatim closing bracket.
+                let after_separator = class.op != ClassSetOp::Union && idx > 0;
+                let context = if after_separator && lit.value == u32::from(']') {
+                    Context::ClassSetCharacter
+                } else {
+                    Context::ClassSet
+                };
+                self.literal(lit, context, escape);
+            }
+            ClassItemKind::Range { first, last } => {
+                let escape = escape_first(first) || is_second_amp(first);
+                self.literal(first, Context::ClassSet, escape);
+                self.write("-");
+                // The end of a range is parsed as an operand, so may not
+                // begin a nested class.
+                let escape = is_doubled_punctuator(last, next_char) || last.value == u32::from('[');
+                self.literal(last, Context::ClassSetCharacter, escape);
+            }
+            _ => self.class_set_item(item),
+        }
+    }
+
+    /// Print a class item which is not a code point or range.
+    fn class_set_item(&mut self, item: &ClassItem) {
+        match &item.kind {
+            ClassItemKind::Literal(_) | ClassItemKind::Range { .. } => {
+                unreachable!("code points are printed by the caller")
+            }
+            ClassItemKind::ClassEscape(escape) => self.class_escape(escape),
+            ClassItemKind::Class(class) => self.class(class),
+            ClassItemKind::Strings(strings) => {
+                self.write("\\q{");
+                for (idx, string) in strings.iter().enumerate() {
+                    if idx > 0 {
+                        self.write("|");
+                    }
+                    for (idx, lit) in string.literals.iter().enumerate() {
+                        let next = string.literals.get(idx + 1).and_then(verbatim_value);
+                        let escape = is_doubled_punctuator(lit, next);
+                        self.literal(lit, Context::ClassSetCharacter, escape);
+                    }
+                }
+                self.write("}");
+            }
+        }
+    }
+
+    /// Print a literal in \p context. If \p escape is set, a verbatim code
+    /// point is escaped even if it could be written as is.
+    fn literal(&mut self, lit: &Literal, context: Context, escape: bool) {
+        if let Tail::Backslash(_) = self.tail {
+            if lit.kind == LiteralKind::Verbatim && lit.value == u32::from('c') {
+                self.out.push('c');
+                self.tail = Tail::LoneC;
+                return;
+            }
+        }
+        self.finish();
+
+        // Continue or settle a pending brace.
+        let mut brace = None;
+        if let Tail::Brace { pos, comma, digits } = self.tail {
+            self.tail = Tail::Other;
+            let c = char::from_u32(lit.value).filter(|_| lit.kind == LiteralKind::Verbatim);
+            match c {
+                Some('0'..='9') => brace = Some((pos, comma, true)),
+                Some(',') if digits && !comma => brace = Some((pos, true, false)),
+                Some('}') if digits || comma => self.out.insert(pos, '\\'),
+                _ => {}
+            }
+        }
+
+        let after_high_surrogate = self.tail == Tail::HighSurrogate;
+        let start = self.out.len();
+        self.write_literal(lit, context, escape);
+        let written = &self.out[start..];
+        if let Some((pos, comma, digits)) = brace {
+            self.tail = Tail::Brace { pos, comma, digits };
+        } else if written == "{" && context == Context::Atom {
+            self.tail = Tail::Brace {
+                pos: start,
+                comma: false,
+                digits: false,
+            };
+        }
+        let is_high_surrogate = written.len() == 6
+            && written.starts_with("\\u")
+            && u16::from_str_radix(&written[2..], 16).is_ok_and(|u| (0xD800..=0xDBFF).contains(&u));
+        if after_high_surrogate && written.starts_with("\\u") {
+            // Give the parser a \u to consume, so this escape is kept.
+            self.out.insert_str(start, "\\u");
+        }
+        if is_high_surrogate && self.tail == Tail::Other {
+            self.tail = Tail::HighSurrogate;
+        }
+    }
+
+    /// Write a literal, after any pending lone backslash is settled.
+    fn write_literal(&mut self, lit: &Literal, context: Context, escape: bool) {
+        let value = lit.value;
+        let c = char::from_u32(value);
+        let verbatim = lit.kind == LiteralKind::Verbatim;
+
+        // Verbatim digits may not follow some escapes, and letters may not
+        // follow a lone \c; write these as hex escapes instead.
+        let extends_tail = verbatim
+            && c.is_some_and(|c| match self.tail {
+                Tail::Other | Tail::Backslash(_) | Tail::HighSurrogate | Tail::Brace { .. } => {
+                    false
+                }
+                Tail::Null if self.flags.unicode => c.is_ascii_digit(),
+                Tail::Null => ('0'..='7').contains(&c),
+                Tail::Backreference => c.is_ascii_digit(),
+                Tail::LoneC if context == Context::Atom => c.is_ascii_alphabetic(),
+                Tail::LoneC => c.is_ascii_alphanumeric() || c == '_',
+            });
+        self.tail = Tail::Other;
+        if extends_tail {
+            return self.code_point_escape(value);
+        }
+
+        match lit.kind {
+            LiteralKind::Verbatim => {
+                let Some(c) = c else {
+                    return self.code_point_escape(value);
+                };
+                if c == '\\'
+                    && matches!(context, Context::Atom | Context::Class)
+                    && !self.flags.unicode
+                {
+                    // Only a lone backslash is parsed as a verbatim one.
+                    self.tail = Tail::Backslash(self.out.len());
+                    self.out.push('\\');
+                } else if escape || self.must_escape(c, context) {
+                    self.identity_escape(c);
+                } else {
+                    self.out.push(c);
+                }
+            }
+            LiteralKind::Identity => match c {
+                Some(c) => self.identity_escape(c),
+                None => self.code_point_escape(value),
+            },
+            LiteralKind::Control => match c {
+                Some('\x0C') => self.out.push_str("\\f"),
+                Some('\n') => self.out.push_str("\\n"),
+                Some('\r') => self.out.push_str("\\r"),
+                Some('\t') => self.out.push_str("\\t"),
+                Some('\x0B') => self.out.push_str("\\v"),
+                _ => self.code_point_escape(value),
+            },
+            LiteralKind::ControlLetter if (1..=26).contains(&value) => {
+                self.out.push_str("\\c");
+                self.out.push(char::from(b'A' + value as u8 - 1));
+            }
+            // Annex B allows an underscore within a class.
+            LiteralKind::ControlLetter
+                if value == 0x1F && context == Context::Class && !self.flags.unicode =>
+            {
+                self.out.push_str("\\c_")
+            }
+            LiteralKind::Null if value == 0 => {
+                self.out.push_str("\\0");
+                self.tail = Tail::Null;
+            }
+            // Always use three digits, so that a following digit is not
+            // absorbed, and a leading digit is never a backreference.
+            LiteralKind::Octal if value <= 0o377 && !self.flags.unicode => {
+                self.out.push_str(&format!("\\{:03o}", value))
+            }
+            LiteralKind::Hex if value <= 0xFF => self.out.push_str(&format!("\\x{:02X}", value)),
+            LiteralKind::Unicode if value <= 0xFFFF => {
+                self.out.push_str(&format!("\\u{:04X}", value))
+            }
+            LiteralKind::Unicode => self.surrogate_pair_escape(value),
+            LiteralKind::UnicodeBraced if value <= 0x10_FFFF => {
+                self.out.push_str(&format!("\\u{{{:X}}}", value))
+            }
+            LiteralKind::Backspace if context != Context::Atom && value == 0x08 => {
+                self.out.push_str("\\b")
+            }
+            _ => self.code_point_escape(value),
+        }
+    }
+
+    /// \return whether the code point \p c must be escaped in \p context.
+    fn must_escape(&self, c: char, context: Context) -> bool {
+        if self.for_literal && (c == '/' || is_line_terminator(c)) {
+            return true;
+        }
+        match context {
+            Context::Atom => match c {
+                '^' | '$' | '\\' | '.' | '*' | '+' | '?' | '(' | ')' | '[' | '|' => true,
+                // As in the parser, only the u flag makes these syntax.
+                ']' | '{' | '}' => self.flags.unicode,
+                _ => false,
+            },
+            Context::Class => matches!(c, '\\' | ']'),
+            Context::ClassSet => matches!(
+                c,
+                '(' | ')' | '[' | ']' | '{' | '}' | '/' | '-' | '\\' | '|'
+            ),
+            // As in the parser, brackets are only syntax at the start of an
+            // operand.
+            Context::ClassSetCharacter => {
+                matches!(c, '(' | ')' | '{' | '}' | '/' | '-' | '\\' | '|')
+            }
+        }
+    }
+
+    /// Write \p c with a backslash. Line terminators cannot be escaped this
+    /// way in a regex literal or in unicode mode, and are written as control
+    /// or unicode escapes instead.
+    fn identity_escape(&mut self, c: char) {
+        let literal_escape = self.for_literal || self.flags.unicode;
+        match c {
+            '\n' if literal_escape => self.out.push_str("\\n"),
+            '\r' if literal_escape => self.out.push_str("\\r"),
+            c if literal_escape && is_line_terminator(c) => self.code_point_escape(u32::from(c)),
+            c => {
+                self.out.push('\\');
+                self.out.push(c);
+            }
+        }
+    }
+
+    /// Write the code point \p value as a hex or unicode escape, which is
+    /// valid in every mode and context.
+    fn code_point_escape(&mut self, value: u32) {
+        if value <= 0xFF {
+            self.out.push_str(&format!("\\x{:02X}", value));
+        } else if value <= 0xFFFF {
+            self.out.push_str(&format!("\\u{:04X}", value));
+        } else if self.unicode() {
+            self.out.push_str(&format!("\\u{{{:X}}}", value));
+        } else {
+            // Outside of unicode mode this is two code units anyway.
+            self.surrogate_pair_escape(value);
+        }
+    }
+
+    /// Write the code point \p value as a pair of unicode escapes, which the
+    /// parser combines in every mode.
+    fn surrogate_pair_escape(&mut self, value: u32) {
+        let mut units = [0; 2];
+        let c = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+        for unit in c.encode_utf16(&mut units) {
+            let _ = write!(self.out, "\\u{:04X}", unit);
+        }
+    }
+}
+
+/// \return the code point an item begins with, if it is a code point or a
+/// range.
+fn leading_literal(item: &ClassItem) -> Option<&Literal> {
+    match &item.kind {
+        ClassItemKind::Literal(lit) | ClassItemKind::Range { first: lit, .. } => Some(lit),
+        _ => None,
+    }
+}
+
+/// \return the code point of \p lit, if it is written verbatim.
+fn verbatim_value(lit: &Literal) -> Option<u32> {
+    (lit.kind == LiteralKind::Verbatim).then_some(lit.value)
+}
+
+/// \return whether \p lit followed by the verbatim code point \p next would
+/// be rejected as a ClassSetReservedDoublePunctuator. Like the parser, this
+/// rejects any pair of double punctuators, not only like ones.
+fn is_doubled_punctuator(lit: &Literal, next: Option<u32>) -> bool {
+    let is_double_punctuator =
+        |cp: u32| char::from_u32(cp).is_some_and(|c| "&!#$%*+,.:;<=>?@^`~".contains(c));
+    is_double_punctuator(lit.value) && next.is_some_and(is_double_punctuator)
+}
+
+/// \return whether \p c is a LineTerminator.
+fn is_line_terminator(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// Append the flags set in \p modifiers.
+fn push_modifiers(text: &mut String, modifiers: &Modifiers) {
+    if modifiers.icase {
+        text.push('i');
+    }
+    if modifiers.multiline {
+        text.push('m');
+    }
+    if modifiers.dot_all {
+        text.push('s');
+    }
+}
@@ -0,0 +1,383 @@
+//! Matching many regexes against one input.
+
+use crate::api::{Error, ErrorKind, Flags};
+use crate::cache::Cache;
+use crate::emit;
+use crate::indexing::{AsciiInput, InputIndexer, Utf8Input};
+use crate::insn::{CompiledRegex, Insn};
+use crate::ir::{self, Node};
+use crate::optimizer;
+use crate::parse;
+use crate::pikevm::SetExecutor;
+use crate::types::{CaptureGroupID, IP, MAX_CAPTURE_GROUPS, MAX_LOOPS};
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use core::iter::FusedIterator;
+
+#[cfg(feature = "utf16")]
+use crate::indexing::{Ucs2Input, Utf16Input};
+
+/// A RegexSet is a collection of patterns compiled into a single program,
+/// whose patterns are all searched for in one pass over the input.
+///
+/// Matching reports which patterns matched anywhere in the input, but not
+/// where. To locate the match, use the corresponding [`Regex`](crate::Regex).
+///
+/// Example:
+///
+/// ```rust
+///   use regress::RegexSet;
+///   let set = RegexSet::new([r"\d+", r"[a-z]+", r"^\s"]).unwrap();
+///   let matches = set.matches("abc 123");
+///   assert!(matches.matched(0));
+///   assert!(matches.matched(1));
+///   assert!(!matches.matched(2));
+///   assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 1]);
+/// ```
+///
+/// The set is executed by the PikeVM backend, and so requires the
+/// `backend-pikevm` feature.
+#[derive(Debug, Clone)]
+pub struct RegexSet {
+    cr: CompiledRegex,
+
+    // The IP at which each member begins.
+    entries: Box<[IP]>,
+}
+
+impl RegexSet {
+    /// Construct a set by parsing each of `patterns` using the default flags.
+    /// An Error is returned for the first pattern whose syntax is invalid,
+    /// or which exceeds a limit of the set, naming its index.
+    #[inline]
+    pub fn new<I, S>(patterns: I) -> Result<RegexSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::with_flags(patterns, Flags::default())
+    }
+
+    /// Construct a set by parsing each of `patterns` with `flags`.
+    /// An Error is returned for the first pattern whose syntax is invalid,
+    /// or which exceeds a limit of the set, naming its index.
+    pub fn with_flags<I, S, F>(patterns: I, flags: F) -> Result<RegexSet, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        F: Into<Flags>,
+    {
+        let flags = flags.into();
+        let mut members = Vec::new();
+        let mut groups = 0;
+        let mut loops = 0;
+        for (idx, pattern) in patterns.into_iter().enumerate() {
+            let pattern = pattern.as_ref();
+            let mut ire = parse::try_parse(pattern.chars().map(u32::from), flags)
+                .map_err(|err| err.with_utf8_span(pattern).with_pattern_index(idx))?;
+            if !flags.no_opt {
+                optimizer::optimize(&mut ire);
+            }
+            // A member within the limits may yet take the set past them; the
+            // whole of its pattern is at fault.
+            let (group_count, loop_count) = count_groups_and_loops(&ire.node, flags.unicode);
+            groups += group_count;
+            loops += loop_count;
+            if groups > MAX_CAPTURE_GROUPS {
+                return Err(Error::new(
+                    ErrorKind::CaptureGroupLimitExceeded,
+                    "Capture group count limit exceeded",
+                )
+                .spanning(pattern)
+                .with_pattern_index(idx));
+            }
+            if loops > MAX_LOOPS {
+                return Err(
+                    Error::new(ErrorKind::LoopLimitExceeded, "Loop count limit exceeded")
+                        .spanning(pattern)
+                        .with_pattern_index(idx),
+                );
+            }
+            members.push((ire.node, group_count));
+        }
+        Ok(Self::from_members(members, flags))
+    }
+
+    /// Combine the optimized IR of each member, with the number of its
+    /// capture groups, into a single program, as a balanced tree of
+    /// alternations whose shape gives the entry point of each member. Member
+    /// capture groups are renumbered to follow those of the members before.
+    fn from_members(members: Vec<(Node, usize)>, flags: Flags) -> RegexSet {
+        let count = members.len();
+        let mut next_group = 0;
+        let mut renumbered = Vec::with_capacity(count);
+        for (mut node, group_count) in members {
+            offset_groups(&mut node, next_group as CaptureGroupID, flags.unicode);
+            next_group += group_count;
+            renumbered.push(node);
+        }
+
+        // Each member already ends in a Goal. The trailing Goal is reached
+        // only by the (empty) set, which never matches.
+        let node = Node::Cat(vec![
+            balanced_alt(renumbered).unwrap_or_else(Node::make_always_fails),
+            Node::Goal,
+        ]);
+        let cr = emit::emit(&ir::Regex { node, flags });
+
+        let mut entries = Vec::with_capacity(count);
+        find_entries(&cr.insns, 0, count, &mut entries);
+        RegexSet {
+            cr,
+            entries: entries.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the number of patterns in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the set contains no patterns.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns whether any pattern in the set matches `text`.
+    #[inline]
+    pub fn is_match(&self, text: &str) -> bool {
+        self.is_match_with_cache(text, &mut Cache::new())
+    }
+
+    /// Returns whether any pattern in the set matches `text`, using the
+    /// buffers of `cache`.
+    #[inline]
+    pub fn is_match_with_cache(&self, text: &str, cache: &mut Cache) -> bool {
+        self.run(Utf8Input::new(text, self.cr.flags.unicode), true, cache)
+            .matched_any()
+    }
+
+    /// Searches `text`, returning which patterns match.
+    #[inline]
+    pub fn matches(&self, text: &str) -> SetMatches {
+        self.matches_with_cache(text, &mut Cache::new())
+    }
+
+    /// Searches `text`, returning which patterns match, using the buffers of
+    /// `cache`.
+    #[inline]
+    pub fn matches_with_cache(&self, text: &str, cache: &mut Cache) -> SetMatches {
+        self.run(Utf8Input::new(text, self.cr.flags.unicode), false, cache)
+    }
+
+    /// Searches `text`, returning which patterns match.
+    /// The input text is expected to be ascii-only: only ASCII case-folding is
+    /// supported.
+    #[inline]
+    pub fn matches_ascii(&self, text: &str) -> SetMatches {
+        self.run(
+            AsciiInput::new(text, self.cr.flags.unicode),
+            false,
+            &mut Cache::new(),
+        )
+    }
+
+    /// Searches UTF-16 `text`, returning which patterns match.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn matches_utf16(&self, text: &[u16]) -> SetMatches {
+        self.run(
+            Utf16Input::new(text, self.cr.flags.unicode),
+            false,
+            &mut Cache::new(),
+        )
+    }
+
+    /// Searches UCS-2 `text`, returning which patterns match.
+    #[cfg(feature = "utf16")]
+    #[inline]
+    pub fn matches_ucs2(&self, text: &[u16]) -> SetMatches {
+        self.run(
+            Ucs2Input::new(text, self.cr.flags.unicode),
+            false,
+            &mut Cache::new(),
+        )
+    }
+
+    fn run<Input: InputIndexer>(
+        &self,
+        input: Input,
+        stop_at_first: bool,
+        cache: &mut Cache,
+    ) -> SetMatches {
+        let mut matched = vec![false; self.entries.len()].into_boxed_slice();
+        let scratch = core::mem::take(&mut cache.pikevm);
+        let mut executor = SetExecutor::with_scratch(&self.cr, &self.entries, input, scratch);
+        executor.run(&mut matched, stop_at_first);
+        cache.pikevm = executor.into_scratch();
+        SetMatches { matched }
+    }
+}
+
+/// \return the number of capture groups, and the number of loops, in a node.
+fn count_groups_and_loops(node: &Node, unicode: bool) -> (usize, usize) {
+    let mut groups = 0;
+    let mut loops = 0;
+    ir::walk(
+        false,
+        unicode,
+        node,
+        &mut |n: &Node, _w: &mut ir::Walk| match n {
+            Node::CaptureGroup { id, .. } => groups = groups.max(*id as usize + 1),
+            Node::Loop { .. } => loops += 1,
+            _ => {}
+        },
+    );
+    (groups, loops)
+}
+
+/// Renumber all capture groups in a node by adding `offset`.
+fn offset_groups(node: &mut Node, offset: CaptureGroupID, unicode: bool) {
+    ir::walk_mut(
+        false,
+        unicode,
+        node,
+        &mut |n: &mut Node, _w: &mut ir::Walk| match n {
+            Node::CaptureGroup { id, .. } => *id += offset,
+            Node::BackRef { group, .. } => *group += offset as u32,
+            Node::LookaroundAssertion {
+                start_group,
+                end_group,
+                ..
+            } => {
+                *start_group += offset;
+                *end_group += offset;
+            }
+            Node::Loop {
+                enclosed_groups, ..
+            } => {
+                enclosed_groups.start += offset;
+                enclosed_groups.end += offset;
+            }
+            _ => {}
+        },
+    );
+}
+
+/// Append to \p entries the IP at which each of \p count members begins, in
+/// the code emitted from their balanced_alt at \p ip.
+fn find_entries(insns: &[Insn], ip: IP, count: usize, entries: &mut Vec<IP>) {
+    match count {
+        0 => {}
+        1 => entries.push(ip),
+        count => {
+            let Insn::Alt { secondary } = insns[ip] else {
+                panic!("An alternation of members should begin with Alt");
+            };
+            find_entries(insns, ip + 1, count / 2, entries);
+            find_entries(insns, secondary as IP, count - count / 2, entries);
+        }
+    }
+}
+
+/// Join nodes as a balanced tree of alternations, to keep the depth
+/// logarithmic in the number of nodes.
+/// \return None if there are no nodes.
+fn balanced_alt(mut nodes: Vec<Node>) -> Option<Node> {
+    match nodes.len() {
+        0 => None,
+        1 => nodes.pop(),
+        len => {
+            let right = nodes.split_off(len / 2);
+            Some(Node::Alt(
+                Box::new(balanced_alt(nodes)?),
+                Box::new(balanced_alt(right)?),
+            ))
+        }
+    }
+}
+
+/// The result of matching a RegexSet: which patterns matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetMatches {
+    matched: Box<[bool]>,
+}
+
+impl SetMatches {
+    /// Returns whether any pattern matched.
+    #[inline]
+    pub fn matched_any(&self) -> bool {
+        self.matched.iter().any(|&m| m)
+    }
+
+    /// Returns whether the pattern at index `idx` matched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than the number of patterns in the set.
+    #[inline]
+    pub fn matched(&self, idx: usize) -> bool {
+        self.matched[idx]
+    }
+
+    /// Returns the number of patterns in the set which produced this result.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.matched.len()
+    }
+
+    /// Returns whether the set which produced this result was empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.matched.is_empty()
+    }
+
+    /// Returns an iterator over the indexes of the patterns which matched,
+    /// in ascending order.
+    #[inline]
+    pub fn iter(&self) -> SetMatchesIter<'_> {
+        SetMatchesIter {
+            inner: self.matched.iter().enumerate(),
+        }
+    }
+}
+
+impl<'s> IntoIterator for &'s SetMatches {
+    type Item = usize;
+    type IntoIter = SetMatchesIter<'s>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the indexes of the patterns in a RegexSet which matched.
+#[derive(Debug, Clone)]
+pub struct SetMatchesIter<'s> {
+    inner: core::iter::Enumerate<core::slice::Iter<'s, bool>>,
+}
+
+impl Iterator for SetMatchesIter<'_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find(|&(_, &m)| m).map(|(idx, _)| idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+impl DoubleEndedIterator for SetMatchesIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.rfind(|&(_, &m)| m).map(|(idx, _)| idx)
+    }
+}
+
+impl FusedIterator for SetMatchesIter<'_> {}
@@ -0,0 +1,533 @@
+//! Replacement strings.
+
+use crate::api::{Match, Range, Regex};
+use crate::parse;
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+/// A Replacer produces the replacement text for each match, for
+/// [`Regex::replace`] and its siblings.
+///
+/// This is implemented for:
+///
+/// - `&str` and `String`, which are expanded as replacement templates on
+///   every match. See [`Regex::replace`] for the syntax.
+/// - [`ReplacementTemplate`], a template which has been parsed in advance.
+/// - Closures `FnMut(&Match) -> T` where `T: AsRef<str>`, whose result is
+///   inserted literally.
+pub trait Replacer {
+    /// Append the replacement for the match `m` in `text` to `dst`.
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String);
+}
+
+impl Replacer for &str {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String) {
+        let template: &str = self;
+        let has_named_groups = !m.group_names.is_empty();
+        parse_template(
+            template.as_bytes(),
+            m.captures.len(),
+            has_named_groups,
+            |piece| match piece {
+                Piece::Literal(r) => dst.push_str(&template[r]),
+                Piece::Match => dst.push_str(&text[m.range()]),
+                Piece::Before => dst.push_str(&text[..m.start()]),
+                Piece::After => dst.push_str(&text[m.end()..]),
+                Piece::Group(idx) => {
+                    if let Some(r) = m.group(idx) {
+                        dst.push_str(&text[r])
+                    }
+                }
+                Piece::NamedGroup(name) => {
+                    let name = &template[name];
+                    if let Some(r) = named_capture(m, |n| n == name) {
+                        dst.push_str(&text[r])
+                    }
+                }
+            },
+        )
+    }
+}
+
+impl Replacer for &String {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String) {
+        self.as_str().replace_append(m, text, dst)
+    }
+}
+
+impl Replacer for String {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String) {
+        self.as_str().replace_append(m, text, dst)
+    }
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Match) -> T,
+    T: AsRef<str>,
+{
+    #[inline]
+    fn replace_append(&mut self, m: &Match, _text: &str, dst: &mut String) {
+        dst.push_str((*self)(m).as_ref())
+    }
+}
+
+/// A replacement template which has been parsed in advance against a
+/// particular regex, for efficient use with [`Regex::replace_all`].
+///
+/// The syntax is the same as for [`Regex::replace`], except that a named
+/// group reference `$<name>` which does not name a group in the regex is an
+/// error, rather than expanding to nothing.
+///
+/// A template should only be used with matches of the regex it was created
+/// for.
+///
+/// # Examples
+///
+/// ```rust
+/// use regress::{Regex, ReplacementTemplate};
+///
+/// let re = Regex::new(r"(?<key>\w+)=(?<value>\w+)").unwrap();
+/// let template = ReplacementTemplate::new(&re, "$<value>:$<key>").unwrap();
+/// assert_eq!(re.replace_all("a=1 b=2", &template), "1:a 2:b");
+///
+/// assert!(ReplacementTemplate::new(&re, "$<val>").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplacementTemplate {
+    template: Box<str>,
+    pieces: Box<[TemplatePiece]>,
+}
+
+/// A resolved element of a ReplacementTemplate.
+#[derive(Debug, Clone)]
+enum TemplatePiece {
+    Literal(Range),
+    Match,
+    Before,
+    After,
+    Group(usize),
+    // The 1-based indexes of the groups with a given name.
+    NamedGroup(Box<[usize]>),
+}
+
+impl ReplacementTemplate {
+    /// Parse `template` for use with matches of `re`.
+    /// A TemplateError is returned if the template refers to a group name
+    /// which does not exist.
+    pub fn new(re: &Regex, template: &str) -> Result<ReplacementTemplate, TemplateError> {
+        let group_count = re.cr.groups as usize;
+        let group_names = &re.cr.group_names;
+        let mut pieces = Vec::new();
+        let mut unknown_span = None;
+        parse_template(
+            template.as_bytes(),
+            group_count,
+            !group_names.is_empty(),
+            |piece| {
+                pieces.push(match piece {
+                    Piece::Literal(r) => TemplatePiece::Literal(r),
+                    Piece::Match => TemplatePiece::Match,
+                    Piece::Before => TemplatePiece::Before,
+                    Piece::After => TemplatePiece::After,
+                    Piece::Group(idx) => TemplatePiece::Group(idx),
+                    Piece::NamedGroup(name_range) => {
+                        let name = &template[name_range.clone()];
+                        let groups: Box<[usize]> = group_names
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, n)| !name.is_empty() && n.as_ref() == name)
+                            .map(|(idx, _)| idx + 1)
+                            .collect();
+                        if groups.is_empty() && unknown_span.is_none() {
+                            // Cover the reference from its $ through its >.
+                            unknown_span = Some(name_range.start - 2..name_range.end + 1);
+                        }
+                        TemplatePiece::NamedGroup(groups)
+                    }
+                })
+            },
+        );
+        if let Some(span) = unknown_span {
+            return Err(TemplateError {
+                template: template.into(),
+                span,
+            });
+        }
+        Ok(ReplacementTemplate {
+            template: template.into(),
+            pieces: pieces.into_boxed_slice(),
+        })
+    }
+
+    /// Returns the template string from which this was parsed.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+}
+
+/// An error parsing a [`ReplacementTemplate`], which referred to a group
+/// name that the regex does not have.
+///
+/// The alternate form of Display (`{:#}`) renders the template with a caret
+/// under the reference at fault, as for a pattern's [`Error`](crate::Error).
+///
+/// ```rust
+/// use regress::{Regex, ReplacementTemplate};
+///
+/// let re = Regex::new(r"(?<key>\w+)").unwrap();
+/// let err = ReplacementTemplate::new(&re, "[$<val>]").unwrap_err();
+/// assert_eq!(err.name(), "val");
+/// assert_eq!(err.span(), 1..7);
+/// assert_eq!(
+///     format!("{:#}", err),
+///     "Unknown capture group name in replacement: val\n[$<val>]\n ^^^^^^"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TemplateError {
+    template: Box<str>,
+    span: Range,
+}
+
+impl TemplateError {
+    /// Returns the group name which the template referred to.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.template[self.span.start + 2..self.span.end - 1]
+    }
+
+    /// Returns the span in bytes of the reference to the group within the
+    /// template, from its `$` through its `>`.
+    #[inline]
+    pub fn span(&self) -> Range {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown capture group name in replacement: {}",
+            self.name()
+        )?;
+        if !f.alternate() {
+            return Ok(());
+        }
+        let start = self.template[..self.span.start].chars().count();
+        let end = start + self.template[self.span.clone()].chars().count();
+        parse::write_caret(f, &self.template, start, end)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TemplateError {}
+
+impl Replacer for &ReplacementTemplate {
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String) {
+        for piece in self.pieces.iter() {
+            match piece {
+                TemplatePiece::Literal(r) => dst.push_str(&self.template[r.clone()]),
+                TemplatePiece::Match => dst.push_str(&text[m.range()]),
+                TemplatePiece::Before => dst.push_str(&text[..m.start()]),
+                TemplatePiece::After => dst.push_str(&text[m.end()..]),
+                &TemplatePiece::Group(idx) => {
+                    if let Some(r) = m.group(idx) {
+                        dst.push_str(&text[r])
+                    }
+                }
+                TemplatePiece::NamedGroup(groups) => {
+                    // Prefer whichever group participated.
+                    if let Some(r) = groups.iter().find_map(|&idx| m.group(idx)) {
+                        dst.push_str(&text[r])
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Replacer for ReplacementTemplate {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &str, dst: &mut String) {
+        Replacer::replace_append(&mut &*self, m, text, dst)
+    }
+}
+
+/// An element of a replacement template, as produced by parse_template.
+/// Ranges are in code units of the template.
+#[derive(Debug)]
+enum Piece {
+    Literal(Range),
+    Match,
+    Before,
+    After,
+    Group(usize),
+    NamedGroup(Range),
+}
+
+/// Parse a replacement template of code units, following ECMAScript's
+/// GetSubstitution, and invoke `f` on each piece in turn.
+/// `group_count` is the number of capture groups, and `has_named_groups`
+/// whether any are named.
+fn parse_template<C, F>(template: &[C], group_count: usize, has_named_groups: bool, mut f: F)
+where
+    C: Copy + Into<u32>,
+    F: FnMut(Piece),
+{
+    let unit = |idx: usize| -> Option<u32> { template.get(idx).map(|&c| c.into()) };
+    let digit = |idx: usize| -> Option<usize> {
+        unit(idx)
+            .and_then(|c| char::from_u32(c)?.to_digit(10))
+            .map(|d| d as usize)
+    };
+
+    // The start of the literal text not yet produced.
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i + 1 < template.len() {
+        if unit(i) != Some('$' as u32) {
+            i += 1;
+            continue;
+        }
+        // The number of code units consumed, and the piece; or None if this $
+        // is literal.
+        let piece: Option<(usize, Piece)> = match char::from_u32(unit(i + 1).unwrap_or(0)) {
+            // Substitute the second $.
+            Some('$') => Some((2, Piece::Literal(i + 1..i + 2))),
+            Some('&') => Some((2, Piece::Match)),
+            Some('`') => Some((2, Piece::Before)),
+            Some('\'') => Some((2, Piece::After)),
+            Some('0'..='9') => {
+                let d1 = digit(i + 1).unwrap_or(0);
+                match digit(i + 2).map(|d2| d1 * 10 + d2) {
+                    // A two digit index which does not exceed the group count.
+                    // Note "$00" is literal.
+                    Some(idx) if idx <= group_count => (idx >= 1).then_some((3, Piece::Group(idx))),
+                    // Otherwise try a one digit index.
+                    _ => (1..=group_count)
+                        .contains(&d1)
+                        .then_some((2, Piece::Group(d1))),
+                }
+            }
+            Some('<') if has_named_groups => template[i + 2..]
+                .iter()
+                .position(|&c| c.into() == '>' as u32)
+                .map(|len| (len + 3, Piece::NamedGroup(i + 2..i + 2 + len))),
+            _ => None,
+        };
+        match piece {
+            Some((consumed, piece)) => {
+                if literal_start < i {
+                    f(Piece::Literal(literal_start..i));
+                }
+                f(piece);
+                i += consumed;
+                literal_start = i;
+            }
+            None => i += 1,
+        }
+    }
+    if literal_start < template.len() {
+        f(Piece::Literal(literal_start..template.len()));
+    }
+}
+
+/// \return the range of the named group in `m` whose name satisfies
+/// `is_name`.
+/// Unlike `Match::named_group`, if multiple groups share the name this
+/// returns the one which participated, as JavaScript does.
+fn named_capture<F: Fn(&str) -> bool>(m: &Match, is_name: F) -> Option<Range> {
+    // Empty strings are used as sentinels to indicate unnamed group.
+    m.group_names
+        .iter()
+        .zip(m.captures.iter())
+        .filter(|(n, _)| !n.is_empty() && is_name(n))
+        .find_map(|(_, cap)| cap.clone())
+}
+
+/// Replace up to `limit` matches (or all, if `limit` is 0) in `text`.
+/// \return `text` unchanged if there were no matches.
+pub(crate) fn replacen<'t, R, I>(
+    text: &'t str,
+    matches: I,
+    limit: usize,
+    mut rep: R,
+) -> Cow<'t, str>
+where
+    R: Replacer,
+    I: Iterator<Item = Match>,
+{
+    let limit = if limit == 0 { usize::MAX } else { limit };
+    let mut matches = matches.take(limit).peekable();
+    if matches.peek().is_none() {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for m in matches {
+        result.push_str(&text[last_end..m.start()]);
+        rep.replace_append(&m, text, &mut result);
+        last_end = m.end();
+    }
+    result.push_str(&text[last_end..]);
+    Cow::Owned(result)
+}
+
+/// A ReplacerUtf16 produces the replacement text for each match in a UTF-16
+/// or UCS-2 haystack, for [`Regex::replace_utf16`] and its siblings.
+///
+/// This is implemented for:
+///
+/// - `&[u16]` and `Vec<u16>`, which are expanded as replacement templates on
+///   every match. See [`Regex::replace`] for the syntax.
+/// - [`ReplacementTemplate`].
+/// - Closures `FnMut(&Match) -> T` where `T: AsRef<[u16]>`, whose result is
+///   inserted literally.
+#[cfg(feature = "utf16")]
+pub trait ReplacerUtf16 {
+    /// Append the replacement for the match `m` in `text` to `dst`.
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>);
+}
+
+#[cfg(feature = "utf16")]
+impl ReplacerUtf16 for &[u16] {
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        let template: &[u16] = self;
+        let has_named_groups = !m.group_names.is_empty();
+        parse_template(
+            template,
+            m.captures.len(),
+            has_named_groups,
+            |piece| match piece {
+                Piece::Literal(r) => dst.extend_from_slice(&template[r]),
+                Piece::Match => dst.extend_from_slice(&text[m.range()]),
+                Piece::Before => dst.extend_from_slice(&text[..m.start()]),
+                Piece::After => dst.extend_from_slice(&text[m.end()..]),
+                Piece::Group(idx) => {
+                    if let Some(r) = m.group(idx) {
+                        dst.extend_from_slice(&text[r])
+                    }
+                }
+                Piece::NamedGroup(name) => {
+                    let name = &template[name];
+                    let is_name = |n: &str| n.encode_utf16().eq(name.iter().copied());
+                    if let Some(r) = named_capture(m, is_name) {
+                        dst.extend_from_slice(&text[r])
+                    }
+                }
+            },
+        )
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl<const N: usize> ReplacerUtf16 for &[u16; N] {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        (&self[..]).replace_append(m, text, dst)
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl ReplacerUtf16 for &Vec<u16> {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        self.as_slice().replace_append(m, text, dst)
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl ReplacerUtf16 for Vec<u16> {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        self.as_slice().replace_append(m, text, dst)
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl<F, T> ReplacerUtf16 for F
+where
+    F: FnMut(&Match) -> T,
+    T: AsRef<[u16]>,
+{
+    #[inline]
+    fn replace_append(&mut self, m: &Match, _text: &[u16], dst: &mut Vec<u16>) {
+        dst.extend_from_slice((*self)(m).as_ref())
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl ReplacerUtf16 for &ReplacementTemplate {
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        for piece in self.pieces.iter() {
+            match piece {
+                TemplatePiece::Literal(r) => dst.extend(self.template[r.clone()].encode_utf16()),
+                TemplatePiece::Match => dst.extend_from_slice(&text[m.range()]),
+                TemplatePiece::Before => dst.extend_from_slice(&text[..m.start()]),
+                TemplatePiece::After => dst.extend_from_slice(&text[m.end()..]),
+                &TemplatePiece::Group(idx) => {
+                    if let Some(r) = m.group(idx) {
+                        dst.extend_from_slice(&text[r])
+                    }
+                }
+                TemplatePiece::NamedGroup(groups) => {
+                    if let Some(r) = groups.iter().find_map(|&idx| m.group(idx)) {
+                        dst.extend_from_slice(&text[r])
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl ReplacerUtf16 for ReplacementTemplate {
+    #[inline]
+    fn replace_append(&mut self, m: &Match, text: &[u16], dst: &mut Vec<u16>) {
+        ReplacerUtf16::replace_append(&mut &*self, m, text, dst)
+    }
+}
+
+/// Replace up to `limit` matches (or all, if `limit` is 0) in the UTF-16 or
+/// UCS-2 `text`.
+/// \return `text` unchanged if there were no matches.
+#[cfg(feature = "utf16")]
+pub(crate) fn replacen_utf16<'t, R, I>(
+    text: &'t [u16],
+    matches: I,
+    limit: usize,
+    mut rep: R,
+) -> Cow<'t, [u16]>
+where
+    R: ReplacerUtf16,
+    I: Iterator<Item = Match>,
+{
+    let limit = if limit == 0 { usize::MAX } else { limit };
+    let mut matches = matches.take(limit).peekable();
+    if matches.peek().is_none() {
+        return Cow::Borrowed(text);
+    }
+    let mut result = Vec::with_capacity(text.len());
+    let mut last_end = 0;
+    for m in matches {
+        result.extend_from_slice(&text[last_end..m.start()]);
+        rep.replace_append(&m, text, &mut result);
+        last_end = m.end();
+    }
+    result.extend_from_slice(&text[last_end..]);
+    Cow::Owned(result)
+}
@@ -0,0 +1,517 @@
+//! Extraction of literal strings which every match of a regex must contain.
+
+use crate::ir::{self, Node, Quantifier};
+use crate::unicode;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+use core::cmp::Reverse;
+
+/// The most literals we will keep in a set.
+const MAX_LITERALS: usize = 32;
+
+/// The longest literal we will keep, in bytes.
+const MAX_LITERAL_LEN: usize = 64;
+
+/// The most copies of a loop body we will concatenate.
+const MAX_LOOP_COPIES: usize = 4;
+
+/// Literals at least this long, in bytes, are selective enough that having
+/// fewer alternatives matters more than length.
+const SELECTIVE_LITERAL_LEN: usize = 3;
+
+/// A literal string, which may be matched case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequiredLiteral {
+    /// The text of the literal. Characters which are matched
+    /// case-insensitively are given in their case folded form, which is
+    /// usually lowercase.
+    pub text: String,
+
+    /// Whether some characters of the text are matched case-insensitively,
+    /// as with the `i` flag, and so may appear in a different case.
+    pub icase: bool,
+}
+
+/// Literals which every match of a regex must contain, for use in filtering
+/// text before searching it.
+///
+/// Each field is a set of alternatives, such that every match contains at
+/// least one of them in the stated position. A set is empty if no useful
+/// literals were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequiredLiterals {
+    /// Every match begins with one of these.
+    pub prefixes: Vec<RequiredLiteral>,
+
+    /// Every match ends with one of these.
+    pub suffixes: Vec<RequiredLiteral>,
+
+    /// Every match contains one of these. This is the most selective set which
+    /// was found, and so may be the same as the prefixes or suffixes.
+    pub inner: Vec<RequiredLiteral>,
+}
+
+/// How to shorten a literal which exceeds MAX_LITERAL_LEN.
+#[derive(Debug, Copy, Clone)]
+enum Trim {
+    /// Do not shorten; give up instead.
+    None,
+
+    /// Keep the start of the literal.
+    End,
+
+    /// Keep the end of the literal.
+    Start,
+}
+
+impl RequiredLiteral {
+    /// \return a literal for the code point \p c, or None if it is a
+    /// surrogate.
+    fn from_code_point(c: u32, icase: bool) -> Option<RequiredLiteral> {
+        Some(RequiredLiteral {
+            text: char::from_u32(c)?.into(),
+            icase,
+        })
+    }
+
+    /// \return the literal of \p self followed by \p rhs, shortened per \p
+    /// trim, or None if it is too long.
+    fn concat(&self, rhs: &RequiredLiteral, trim: Trim) -> Option<RequiredLiteral> {
+        let mut text = String::with_capacity(self.text.len() + rhs.text.len());
+        text.push_str(&self.text);
+        text.push_str(&rhs.text);
+        if text.len() > MAX_LITERAL_LEN {
+            match trim {
+                Trim::None => return None,
+                Trim::End => {
+                    let mut end = MAX_LITERAL_LEN;
+                    while !text.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    text.truncate(end);
+                }
+                Trim::Start => {
+                    let mut start = text.len() - MAX_LITERAL_LEN;
+                    while !text.is_char_boundary(start) {
+                        start += 1;
+                    }
+                    text.drain(..start);
+                }
+            }
+        }
+        Some(RequiredLiteral {
+            text,
+            icase: self.icase || rhs.icase,
+        })
+    }
+}
+
+/// \return a set of literals which is true of any text: it begins with, ends
+/// with and contains the empty literal.
+fn anything() -> Vec<RequiredLiteral> {
+    vec![RequiredLiteral::default()]
+}
+
+/// \return whether \p set says nothing about the text, because it contains
+/// the empty literal.
+fn is_trivial(set: &[RequiredLiteral]) -> bool {
+    set.iter().any(|lit| lit.text.is_empty())
+}
+
+/// Sort and deduplicate \p set.
+fn normalize(mut set: Vec<RequiredLiteral>) -> Vec<RequiredLiteral> {
+    if is_trivial(&set) {
+        return anything();
+    }
+    set.sort_unstable();
+    set.dedup();
+    set
+}
+
+/// \return the union of \p a and \p b, or None if it is too large.
+fn union(a: &[RequiredLiteral], b: &[RequiredLiteral]) -> Option<Vec<RequiredLiteral>> {
+    let mut set = Vec::with_capacity(a.len() + b.len());
+    set.extend_from_slice(a);
+    set.extend_from_slice(b);
+    set.sort_unstable();
+    set.dedup();
+    (set.len() <= MAX_LITERALS).then_some(set)
+}
+
+/// \return every literal of \p a followed by every literal of \p b, shortened
+/// per \p trim, or None if this is too large.
+fn cross(a: &[RequiredLiteral], b: &[RequiredLiteral], trim: Trim) -> Option<Vec<RequiredLiteral>> {
+    if a.len().saturating_mul(b.len()) > MAX_LITERALS {
+        return None;
+    }
+    let mut set = Vec::with_capacity(a.len() * b.len());
+    for x in a {
+        for y in b {
+            set.push(x.concat(y, trim)?);
+        }
+    }
+    set.sort_unstable();
+    set.dedup();
+    Some(set)
+}
+
+/// \return how useful \p set is for filtering text; higher is better.
+fn score(set: &[RequiredLiteral]) -> Option<(usize, Reverse<usize>, usize)> {
+    if is_trivial(set) {
+        return None;
+    }
+    // An empty set never matches, which is as selective as possible.
+    let shortest = set
+        .iter()
+        .map(|lit| lit.text.len())
+        .min()
+        .unwrap_or(usize::MAX);
+    Some((
+        shortest.min(SELECTIVE_LITERAL_LEN),
+        Reverse(set.len()),
+        shortest,
+    ))
+}
+
+/// \return the most useful of \p sets.
+fn best(sets: impl IntoIterator<Item = Vec<RequiredLiteral>>) -> Vec<RequiredLiteral> {
+    sets.into_iter()
+        .max_by_key(|set| score(set))
+        .unwrap_or_else(anything)
+}
+
+/// What is known of the text matched by a node.
+/// The prefixes and suffixes describe only the non-empty matches, so that a
+/// node which may match the empty string still contributes them. Each set is
+/// either trivial or free of empty literals.
+#[derive(Debug, Clone)]
+struct Info {
+    /// The node matches exactly one of these, or None if unknown.
+    exact: Option<Vec<RequiredLiteral>>,
+
+    /// Whether the node may match the empty string.
+    empty: bool,
+
+    /// Every non-empty match begins with one of these.
+    prefixes: Vec<RequiredLiteral>,
+
+    /// Every non-empty match ends with one of these.
+    suffixes: Vec<RequiredLiteral>,
+
+    /// Every match contains one of these.
+    inner: Vec<RequiredLiteral>,
+}
+
+/// \return \p set with the empty literal added if \p empty is set.
+fn with_empty(set: &[RequiredLiteral], empty: bool) -> Vec<RequiredLiteral> {
+    let mut set = set.to_vec();
+    if empty {
+        set.push(RequiredLiteral::default());
+    }
+    set
+}
+
+/// \return \p set without its empty literal, normalized.
+fn without_empty(mut set: Vec<RequiredLiteral>) -> Vec<RequiredLiteral> {
+    set.retain(|lit| !lit.text.is_empty());
+    normalize(set)
+}
+
+impl Info {
+    /// \return the info for a node which matches exactly one of \p set.
+    fn exact(set: Vec<RequiredLiteral>) -> Info {
+        let empty = is_trivial(&set);
+        let nonempty = without_empty(set.clone());
+        Info {
+            exact: Some(set),
+            empty,
+            prefixes: nonempty.clone(),
+            suffixes: nonempty.clone(),
+            inner: if empty { anything() } else { nonempty },
+        }
+    }
+
+    /// \return the info for a node which may match any text.
+    fn anything() -> Info {
+        Info {
+            exact: None,
+            empty: true,
+            prefixes: anything(),
+            suffixes: anything(),
+            inner: anything(),
+        }
+    }
+
+    /// \return the info for a node which matches only the empty string.
+    fn empty() -> Info {
+        Info::exact(anything())
+    }
+
+    /// \return the info for matching \p self followed by \p rhs.
+    fn cat(self, rhs: Info) -> Info {
+        if let (Some(a), Some(b)) = (&self.exact, &rhs.exact)
+            && let Some(exact) = cross(a, b, Trim::None)
+        {
+            return Info::exact(exact);
+        }
+        let empty = self.empty && rhs.empty;
+
+        // A non-empty match begins with a non-empty match of self, or else of
+        // rhs. Extending literals may make them more selective, or may just
+        // multiply the alternatives.
+        let prefixes = match self.empty {
+            true => union(&self.prefixes, &rhs.prefixes).map_or_else(anything, normalize),
+            false => self.prefixes,
+        };
+        let extended = self.exact.as_ref().and_then(|a| {
+            let extended = cross(a, &rhs.prefixes, Trim::End)?;
+            match rhs.empty {
+                true => union(&extended, &without_empty(a.clone())).map(normalize),
+                false => Some(normalize(extended)),
+            }
+        });
+        let prefixes = best(extended.into_iter().chain([prefixes]));
+
+        let suffixes = match rhs.empty {
+            true => union(&self.suffixes, &rhs.suffixes).map_or_else(anything, normalize),
+            false => rhs.suffixes,
+        };
+        let extended = rhs.exact.as_ref().and_then(|b| {
+            let extended = cross(&self.suffixes, b, Trim::Start)?;
+            match self.empty {
+                true => union(&extended, &without_empty(b.clone())).map(normalize),
+                false => Some(normalize(extended)),
+            }
+        });
+        let suffixes = best(extended.into_iter().chain([suffixes]));
+
+        let inner = match empty {
+            true => anything(),
+            false => {
+                // Literals may also span the boundary between the two.
+                let spanning = cross(
+                    &with_empty(&self.suffixes, self.empty),
+                    &with_empty(&rhs.prefixes, rhs.empty),
+                    Trim::End,
+                )
+                .map(normalize);
+                best(
+                    [
+                        Some(self.inner),
+                        Some(rhs.inner),
+                        spanning,
+                        Some(prefixes.clone()),
+                        Some(suffixes.clone()),
+                    ]
+                    .into_iter()
+                    .flatten(),
+                )
+            }
+        };
+        Info {
+            exact: None,
+            empty,
+            prefixes,
+            suffixes,
+            inner,
+        }
+    }
+
+    /// \return the info for matching either \p self or \p rhs.
+    fn alt(self, rhs: Info) -> Info {
+        if let (Some(a), Some(b)) = (&self.exact, &rhs.exact)
+            && let Some(exact) = union(a, b)
+        {
+            return Info::exact(exact);
+        }
+        let either = |a: &[RequiredLiteral], b: &[RequiredLiteral]| {
+            union(a, b).map_or_else(anything, normalize)
+        };
+        Info {
+            exact: None,
+            empty: self.empty || rhs.empty,
+            prefixes: either(&self.prefixes, &rhs.prefixes),
+            suffixes: either(&self.suffixes, &rhs.suffixes),
+            inner: either(&self.inner, &rhs.inner),
+        }
+    }
+
+    /// \return the exact matches of \p self repeated per \p quant, or None if
+    /// unknown.
+    fn repeat_exact(&self, quant: &Quantifier) -> Option<Vec<RequiredLiteral>> {
+        let set = self.exact.as_ref()?;
+        let max = quant.max.filter(|&max| max <= MAX_LITERALS)?;
+        // Form the union of each permitted power of the set.
+        let mut power = anything();
+        let mut powers = Vec::new();
+        for count in 0..=max {
+            if count >= quant.min {
+                powers = union(&powers, &power)?;
+            }
+            if count < max {
+                power = cross(&power, set, Trim::None)?;
+            }
+        }
+        Some(powers)
+    }
+
+    /// \return the info for matching \p self repeated per \p quant.
+    fn repeat(self, quant: &Quantifier) -> Info {
+        if let Some(exact) = self.repeat_exact(quant) {
+            return Info::exact(exact);
+        }
+        if quant.min == 0 {
+            // A non-empty match begins and ends with non-empty iterations.
+            return Info {
+                exact: None,
+                empty: true,
+                inner: anything(),
+                ..self
+            };
+        }
+        // The matched text begins and ends with that of the mandatory
+        // iterations.
+        let mut info = self.clone();
+        for _ in 1..quant.min.min(MAX_LOOP_COPIES) {
+            info = info.cat(self.clone());
+        }
+        info.exact = None;
+        info
+    }
+}
+
+/// \return the info for a node matching any one of the code points \p cps.
+fn code_points(cps: &[u32], unicode: bool) -> Info {
+    if let [first, ..] = cps
+        && cps.len() > 1
+    {
+        // Check if these are the case variants of one character.
+        let mut sorted = cps.to_vec();
+        sorted.sort_unstable();
+        if unicode::expand_code_point(*first, true, unicode) == sorted {
+            let folded = unicode::fold(*first);
+            let c = if sorted.contains(&folded) {
+                folded
+            } else {
+                *first
+            };
+            if let Some(lit) = RequiredLiteral::from_code_point(c, true) {
+                return Info::exact(vec![lit]);
+            }
+        }
+    }
+    if cps.len() > MAX_LITERALS {
+        return Info::anything();
+    }
+    cps.iter()
+        .map(|&c| RequiredLiteral::from_code_point(c, false))
+        .collect::<Option<Vec<_>>>()
+        .map_or_else(Info::anything, |mut set| {
+            set.sort_unstable();
+            set.dedup();
+            Info::exact(set)
+        })
+}
+
+/// \return the info for a node.
+fn info(node: &Node, unicode: bool) -> Info {
+    match node {
+        // Zero-width nodes do not affect the matched text.
+        Node::Empty
+        | Node::Goal
+        | Node::Anchor { .. }
+        | Node::WordBoundary { .. }
+        | Node::LookaroundAssertion { .. } => Info::empty(),
+        &Node::Char { c } => code_points(&[c], unicode),
+        Node::CharSet(chars) => code_points(chars, unicode),
+        Node::ByteSet(bytes) => {
+            let cps = bytes.iter().map(|&b| u32::from(b)).collect::<Vec<_>>();
+            code_points(&cps, unicode)
+        }
+        Node::ByteSequence(bytes) => match core::str::from_utf8(bytes) {
+            Ok(text) => text
+                .chars()
+                .map(|c| code_points(&[u32::from(c)], unicode))
+                .fold(Info::empty(), Info::cat),
+            Err(_) => Info::anything(),
+        },
+        Node::Bracket(contents) if !contents.invert => {
+            let mut cps = Vec::new();
+            for iv in contents.cps.intervals() {
+                if cps.len() + iv.count_codepoints() > MAX_LITERALS {
+                    return Info::anything();
+                }
+                cps.extend(iv.codepoints());
+            }
+            code_points(&cps, unicode)
+        }
+        Node::Bracket(_) => Info::anything(),
+        Node::StringSet {
+            alternatives,
+            icase,
+        } => {
+            if alternatives.len() > MAX_LITERALS {
+                return Info::anything();
+            }
+            let mut set = Vec::with_capacity(alternatives.len());
+            for alt in alternatives.iter() {
+                let mut text = String::new();
+                for &c in alt.iter() {
+                    let c = if *icase { unicode::fold(c) } else { c };
+                    match char::from_u32(c) {
+                        Some(c) => text.push(c),
+                        None => return Info::anything(),
+                    }
+                }
+                if text.len() > MAX_LITERAL_LEN {
+                    return Info::anything();
+                }
+                set.push(RequiredLiteral {
+                    text,
+                    icase: *icase,
+                });
+            }
+            set.sort_unstable();
+            set.dedup();
+            Info::exact(set)
+        }
+        Node::MatchAny | Node::MatchAnyExceptLineTerminator | Node::BackRef { .. } => {
+            Info::anything()
+        }
+        Node::Cat(nodes) => {
+            // Join runs of exactly known nodes first, so that their literals
+            // are considered whole.
+            let mut infos: Vec<Info> = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                let info = info(node, unicode);
+                let info = match infos.pop_if(|last| last.exact.is_some() && info.exact.is_some()) {
+                    Some(last) => last.cat(info),
+                    None => info,
+                };
+                infos.push(info);
+            }
+            infos.into_iter().fold(Info::empty(), Info::cat)
+        }
+        Node::Alt(left, right) => info(left, unicode).alt(info(right, unicode)),
+        Node::CaptureGroup { contents, .. } => info(contents, unicode),
+        Node::Loop { loopee, quant, .. } | Node::Loop1CharBody { loopee, quant } => {
+            info(loopee, unicode).repeat(quant)
+        }
+    }
+}
+
+/// \return the literals which every match of \p re must contain.
+pub fn required_literals(re: &ir::Regex) -> RequiredLiterals {
+    let info = info(&re.node, re.flags.unicode);
+    if info.empty {
+        // The regex may match the empty string, which contains nothing.
+        return RequiredLiterals::default();
+    }
+    let inner = best([info.inner, info.prefixes.clone(), info.suffixes.clone()]);
+    let useful = |set: Vec<RequiredLiteral>| if is_trivial(&set) { Vec::new() } else { set };
+    RequiredLiterals {
+        prefixes: useful(info.prefixes),
+        suffixes: useful(info.suffixes),
+        inner: useful(inner),
+    }
+}
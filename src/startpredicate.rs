@@ -1,14 +1,132 @@
 //! Support for quickly finding potential match locations.
-use crate::bytesearch::ByteBitmap;
+#[cfg(feature = "perf-literal")]
+use crate::bytesearch::LiteralSet;
+use crate::bytesearch::{ByteBitmap, ByteSearcher};
 use crate::codepointset;
 use crate::insn::StartPredicate;
 use crate::ir;
 use crate::ir::Node;
 use crate::util::{add_utf8_first_bytes_to_bitmap, utf8_first_byte};
 #[cfg(not(feature = "std"))]
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::cell::Cell;
 use memchr::memmem;
 
+/// The most alternative sequences a start predicate may look for.
+const MAX_START_LITERALS: usize = 4096;
+
+/// A literal which every match contains, though perhaps not at its start.
+#[derive(Debug, Clone)]
+pub struct InnerLiteral {
+    /// The searcher for the literal.
+    finder: memmem::Finder<'static>,
+
+    /// What may precede the literal in a match.
+    lead: Lead,
+}
+
+/// What may precede an InnerLiteral in a match.
+#[derive(Debug, Clone)]
+enum Lead {
+    /// At most this many bytes.
+    Bounded(usize),
+
+    /// Any number of bytes in this set, so that a match begins after the last
+    /// byte outside of it before the literal.
+    Bytes(Box<ByteBitmap>),
+
+    /// Anything.
+    Unbounded,
+}
+
+impl InnerLiteral {
+    /// \return a searcher for the positions at which a match may begin, for
+    /// use in one search of one text.
+    pub fn searcher(&self) -> InnerLiteralSearcher<'_> {
+        InnerLiteralSearcher {
+            literal: self,
+            found: Cell::new(None),
+            lead: Cell::new(None),
+            absent: Cell::new(self.finder.needle().len() - 1),
+        }
+    }
+
+    /// \return whether searching for the literal skips over bytes at which
+    /// no match may begin, rather than only finding whether there is a match.
+    fn skips(&self) -> bool {
+        !matches!(self.lead, Lead::Unbounded)
+    }
+}
+
+/// Finds the positions at which a match may begin from the occurrences of an
+/// InnerLiteral. A match may begin no earlier than the literal's next
+/// occurrence, less its maximum offset, or than the bytes before it which
+/// may lead up to it, found by scanning backwards from it; if the literal
+/// does not occur at all, there is no match.
+///
+/// Successive searches typically begin at nearby positions. To avoid
+/// searching the same bytes repeatedly, the searcher remembers the last
+/// occurrence found and where its lead begins, by their distance from the end
+/// of the text. Therefore every slice searched must be a suffix of the same
+/// text.
+pub struct InnerLiteralSearcher<'a> {
+    literal: &'a InnerLiteral,
+
+    /// The length of the text from the last occurrence found to the end.
+    found: Cell<Option<usize>>,
+
+    /// The length of the text from the start of the lead of the last
+    /// occurrence found to the end, once scanned.
+    lead: Cell<Option<usize>>,
+
+    /// The length of the longest suffix of the text known to not contain the
+    /// literal.
+    absent: Cell<usize>,
+}
+
+impl ByteSearcher for InnerLiteralSearcher<'_> {
+    fn find_in(&self, rhs: &[u8]) -> Option<usize> {
+        if rhs.len() <= self.absent.get() {
+            return None;
+        }
+        let at = match self.found.get() {
+            Some(remaining) if remaining <= rhs.len() => rhs.len() - remaining,
+            _ => match self.literal.finder.find(rhs) {
+                Some(at) => {
+                    self.found.set(Some(rhs.len() - at));
+                    self.lead.set(None);
+                    at
+                }
+                None => {
+                    self.absent.set(rhs.len());
+                    return None;
+                }
+            },
+        };
+        let mut start = match &self.literal.lead {
+            Lead::Bounded(max_offset) => at.saturating_sub(*max_offset),
+            Lead::Bytes(bytes) => match self.lead.get() {
+                Some(remaining) => rhs.len().saturating_sub(remaining),
+                None => {
+                    let start = rhs[..at]
+                        .iter()
+                        .rposition(|&b| !bytes.contains(b))
+                        .map_or(0, |i| i + 1);
+                    self.lead.set(Some(rhs.len() - start));
+                    start
+                }
+            },
+            Lead::Unbounded => return Some(0),
+        };
+        // Round up to the start of a UTF-8 sequence. The literal itself begins
+        // one, so this stops at the literal at the latest.
+        while rhs[start] & 0xC0 == 0x80 {
+            start += 1;
+        }
+        Some(start)
+    }
+}
+
 /// Check if a node is anchored to the start of the line/string.
 /// Returns true if the node begins with a StartOfLine anchor.
 fn is_start_anchored(n: &Node) -> bool {
@@ -48,6 +166,9 @@ enum AbstractStartPredicate {
     /// Sequence of non-empty bytes.
     Sequence(Vec<u8>),
 
+    /// Any of several sequences of non-empty bytes.
+    Literals(Vec<Vec<u8>>),
+
     /// Set of bytes.
     Set(Box<ByteBitmap>),
 }
@@ -68,11 +189,37 @@ impl AbstractStartPredicate {
                     // Use the shared prefix.
                     Self::Sequence(s1[..shared_len].to_vec())
                 } else {
-                    // Use a set of their first byte.
-                    Self::Set(Box::new(ByteBitmap::new(&[s1[0], s2[0]])))
+                    // Look for either.
+                    Self::Literals(vec![s1, s2])
                 }
             }
 
+            (Self::Literals(mut l1), Self::Literals(l2)) => {
+                l1.extend(l2);
+                Self::literals(l1)
+            }
+
+            (Self::Literals(mut l1), Self::Sequence(s2)) => {
+                l1.push(s2);
+                Self::literals(l1)
+            }
+
+            (Self::Sequence(s1), Self::Literals(mut l2)) => {
+                l2.push(s1);
+                Self::literals(l2)
+            }
+
+            (Self::Literals(l1), Self::Set(mut s2)) => {
+                // Add first bytes to set.
+                l1.iter().for_each(|s| s2.set(s[0]));
+                Self::Set(s2)
+            }
+
+            (Self::Set(mut s1), Self::Literals(l2)) => {
+                l2.iter().for_each(|s| s1.set(s[0]));
+                Self::Set(s1)
+            }
+
             (Self::Set(mut s1), Self::Set(s2)) => {
                 s1.bitor(s2.as_ref());
                 Self::Set(s1)
@@ -91,6 +238,16 @@ impl AbstractStartPredicate {
         }
     }
 
+    /// \return a predicate for any of \p seqs, which are non-empty. If there
+    /// are too many, use a set of their first bytes.
+    fn literals(seqs: Vec<Vec<u8>>) -> Self {
+        if seqs.len() <= MAX_START_LITERALS {
+            return Self::Literals(seqs);
+        }
+        let first_bytes = seqs.iter().map(|s| s[0]).collect::<Vec<_>>();
+        Self::Set(Box::new(ByteBitmap::new(&first_bytes)))
+    }
+
     /// Resolve ourselves to a concrete start predicate.
     fn resolve_to_insn(self) -> StartPredicate {
         match self {
@@ -100,6 +257,24 @@ impl AbstractStartPredicate {
                 1 => StartPredicate::ByteSet1([vals[0]]),
                 _ => StartPredicate::ByteSeq(Box::new(memmem::Finder::new(&vals).into_owned())),
             },
+            Self::Literals(mut seqs) => {
+                seqs.sort_unstable();
+                seqs.dedup();
+                // A single byte sequence is no more selective than its first
+                // byte, which is faster to look for.
+                if let [seq] = seqs.as_slice() {
+                    return Self::Sequence(seq.clone()).resolve_to_insn();
+                }
+                #[cfg(feature = "perf-literal")]
+                if seqs.iter().all(|s| s.len() > 1)
+                    && let Some(set) = LiteralSet::new(&seqs)
+                {
+                    return StartPredicate::LiteralSet(Box::new(set));
+                }
+                // Otherwise look for their first bytes.
+                let first_bytes = seqs.iter().map(|s| s[0]).collect::<Vec<_>>();
+                Self::Set(Box::new(ByteBitmap::new(&first_bytes))).resolve_to_insn()
+            }
             Self::Set(bm) => match bm.count_bits() {
                 0 => StartPredicate::Arbitrary,
                 1 => StartPredicate::ByteSet1(bm.as_array()),
@@ -138,8 +313,29 @@ fn compute_start_predicate(n: &Node) -> Option<AbstractStartPredicate> {
             ))))
         }
 
-        // StringSets come from TC39 "sequence properties" and are very rare - not worth optimizing.
-        Node::StringSet { .. } => arbitrary,
+        // StringSets come from TC39 "sequence properties" and class string
+        // disjunctions, and may be large. Look for any of their strings.
+        Node::StringSet {
+            alternatives,
+            icase: false,
+        } => {
+            let seqs = alternatives
+                .iter()
+                .map(|alt| {
+                    let s = alt
+                        .iter()
+                        .map(|&c| char::from_u32(c))
+                        .collect::<Option<String>>()?;
+                    (!s.is_empty()).then(|| s.into_bytes())
+                })
+                .collect::<Option<Vec<_>>>();
+            match seqs {
+                Some(seqs) if !seqs.is_empty() => Some(AbstractStartPredicate::literals(seqs)),
+                // Some string is empty or a lone surrogate, or the set is empty.
+                _ => arbitrary,
+            }
+        }
+        Node::StringSet { icase: true, .. } => arbitrary,
 
         // We assume that most char nodes have been optimized to ByteSeq or AnyBytes2, so skip
         // these.
@@ -212,6 +408,133 @@ fn compute_start_predicate(n: &Node) -> Option<AbstractStartPredicate> {
     }
 }
 
+/// Append the nodes which \p n matches in sequence to \p out, looking through
+/// catenations and capture groups.
+fn flatten_cat<'a>(n: &'a Node, out: &mut Vec<&'a Node>) {
+    match n {
+        Node::Cat(nodes) => nodes.iter().for_each(|n| flatten_cat(n, out)),
+        Node::CaptureGroup { contents, .. } => flatten_cat(contents, out),
+        _ => out.push(n),
+    }
+}
+
+/// Add the code point \p cp to the bytes of \p bitmap: its first byte, and any
+/// continuation byte.
+fn add_code_point_bytes(cp: u32, bitmap: &mut ByteBitmap) {
+    bitmap.set(utf8_first_byte(cp));
+    if cp >= 0x80 {
+        (0x80..=0xBF).for_each(|b| bitmap.set(b));
+    }
+}
+
+/// Add every byte which a match of \p n may contain to \p bitmap.
+/// \return false if they are not known.
+fn add_node_bytes(n: &Node, bitmap: &mut ByteBitmap) -> bool {
+    match n {
+        Node::Empty
+        | Node::Goal
+        | Node::Anchor { .. }
+        | Node::WordBoundary { .. }
+        | Node::LookaroundAssertion { .. } => true,
+        Node::Char { c } => {
+            add_code_point_bytes(*c, bitmap);
+            true
+        }
+        Node::ByteSequence(bytes) | Node::ByteSet(bytes) => {
+            bytes.iter().for_each(|&b| bitmap.set(b));
+            true
+        }
+        Node::CharSet(cps) => {
+            cps.iter().for_each(|&cp| add_code_point_bytes(cp, bitmap));
+            true
+        }
+        Node::MatchAny => {
+            bitmap.bitor(ByteBitmap::default().bitnot());
+            true
+        }
+        Node::MatchAnyExceptLineTerminator => {
+            // The other line terminators are multibyte, and share their bytes
+            // with characters which are matched.
+            let mut newlines = ByteBitmap::new(b"\n\r");
+            bitmap.bitor(newlines.bitnot());
+            true
+        }
+        Node::Bracket(bc) => {
+            let storage;
+            let cps = if bc.invert {
+                storage = bc.cps.inverted();
+                &storage
+            } else {
+                &bc.cps
+            };
+            for iv in cps.intervals() {
+                add_utf8_first_bytes_to_bitmap(*iv, bitmap);
+                if iv.last >= 0x80 {
+                    add_code_point_bytes(iv.last, bitmap);
+                }
+            }
+            true
+        }
+        Node::StringSet {
+            alternatives,
+            icase: false,
+        } => {
+            for &cp in alternatives.iter().flat_map(|alt| alt.iter()) {
+                add_code_point_bytes(cp, bitmap);
+            }
+            true
+        }
+        Node::StringSet { icase: true, .. } | Node::BackRef { .. } => false,
+        Node::Cat(nodes) => nodes.iter().all(|n| add_node_bytes(n, bitmap)),
+        Node::Alt(left, right) => add_node_bytes(left, bitmap) && add_node_bytes(right, bitmap),
+        Node::CaptureGroup { contents, .. } => add_node_bytes(contents, bitmap),
+        Node::Loop { loopee, .. } | Node::Loop1CharBody { loopee, .. } => {
+            add_node_bytes(loopee, bitmap)
+        }
+    }
+}
+
+/// Compute a literal which every match of a node contains, or None if there
+/// is none. Of the literals matched in sequence by the node, prefer one at a
+/// bounded offset from the start of the match, and then the longest.
+fn compute_inner_literal(n: &Node) -> Option<InnerLiteral> {
+    let mut nodes = Vec::new();
+    flatten_cat(n, &mut nodes);
+    let mut best: Option<(usize, &[u8], Option<usize>)> = None;
+    let mut offset = Some(0);
+    for (idx, &node) in nodes.iter().enumerate() {
+        if let Node::ByteSequence(bytes) = node {
+            let better = best.is_none_or(|(_, best_bytes, best_offset)| {
+                (offset.is_some(), bytes.len()) > (best_offset.is_some(), best_bytes.len())
+            });
+            if better && !bytes.is_empty() {
+                best = Some((idx, bytes, offset));
+            }
+        }
+        let len = ir::match_len(node, &[])?;
+        offset = offset
+            .zip(len.max)
+            .and_then(|(offset, len)| offset.checked_add(len));
+    }
+    let (idx, bytes, max_offset) = best?;
+    let lead = match max_offset {
+        Some(max_offset) => Lead::Bounded(max_offset),
+        None => {
+            let mut bitmap = Box::<ByteBitmap>::default();
+            let known = nodes[..idx].iter().all(|n| add_node_bytes(n, &mut bitmap));
+            if known && bitmap.count_bits() < 256 {
+                Lead::Bytes(bitmap)
+            } else {
+                Lead::Unbounded
+            }
+        }
+    };
+    Some(InnerLiteral {
+        finder: memmem::Finder::new(bytes).into_owned(),
+        lead,
+    })
+}
+
 /// \return the start predicate for a Regex.
 pub fn predicate_for_re(re: &ir::Regex) -> StartPredicate {
     // Check if the regex is anchored to the start - if so, we can optimize
@@ -222,7 +545,105 @@ pub fn predicate_for_re(re: &ir::Regex) -> StartPredicate {
         return StartPredicate::StartAnchored;
     }
 
-    compute_start_predicate(&re.node)
-        .unwrap_or(AbstractStartPredicate::Arbitrary)
-        .resolve_to_insn()
+    let pred = compute_start_predicate(&re.node).unwrap_or(AbstractStartPredicate::Arbitrary);
+
+    // If the first bytes of a match say little, as for `.*ERROR` or
+    // `\w+@example\.com`, prefer to search for a literal within the match.
+    // Even one which does not skip anything finds when there is no match, but
+    // it should not replace a predicate which does.
+    let weak = match &pred {
+        AbstractStartPredicate::Arbitrary => true,
+        AbstractStartPredicate::Sequence(_) | AbstractStartPredicate::Literals(_) => false,
+        AbstractStartPredicate::Set(bm) => bm.count_bits() > 3,
+    };
+    if weak
+        && let Some(literal) = compute_inner_literal(&re.node)
+        && (literal.skips() || matches!(pred, AbstractStartPredicate::Arbitrary))
+    {
+        return StartPredicate::InnerLiteral(Box::new(literal));
+    }
+    pred.resolve_to_insn()
+}
+
+// Literals are only formed from UTF-8.
+#[cfg(all(test, not(feature = "utf16")))]
+mod tests {
+    use super::*;
+    use crate::{api, optimizer, parse};
+
+    /// \return the start predicate of \p pattern with \p flags.
+    fn predicate(pattern: &str, flags: &str) -> StartPredicate {
+        let mut re = parse::try_parse(pattern.chars().map(u32::from), api::Flags::from(flags))
+            .expect("pattern should parse");
+        optimizer::optimize(&mut re);
+        predicate_for_re(&re)
+    }
+
+    /// \return where the inner literal of \p pattern says the first match in
+    /// \p text may begin.
+    fn first_candidate(pattern: &str, flags: &str, text: &str) -> Option<usize> {
+        let StartPredicate::InnerLiteral(literal) = predicate(pattern, flags) else {
+            panic!("{pattern:?} should search for an inner literal");
+        };
+        literal.searcher().find_in(text.as_bytes())
+    }
+
+    #[test]
+    fn inner_literal_skips() {
+        // The start of a match is found by scanning back over the letters.
+        let text = "12 34 ab@example.com";
+        assert_eq!(first_candidate(r"[a-z]+@example\.com", "", text), Some(6));
+        assert_eq!(first_candidate(r"[a-z]+@example\.com", "", "1 2 3"), None);
+        // The scan stops at line terminators, and at character boundaries.
+        assert_eq!(first_candidate(r".*ERROR", "", "ok\nan ERROR"), Some(3));
+        assert_eq!(first_candidate(r"[a-z]+=", "", "éa="), Some(2));
+        assert_eq!(first_candidate(r"[a-zé]+=", "", "1éa="), Some(1));
+        // A literal at a bounded offset skips past the bytes before it.
+        assert_eq!(first_candidate(r"\w{1,2}-end", "", "abcd-end"), Some(2));
+    }
+
+    #[test]
+    fn inner_literal_scans_back_once() {
+        // Searches from within a lead reuse the scan of the first.
+        let StartPredicate::InnerLiteral(literal) = predicate(r"[a-z]+@example\.com", "") else {
+            panic!("should search for an inner literal");
+        };
+        let text = b"1 abc@example.com";
+        let searcher = literal.searcher();
+        assert_eq!(searcher.find_in(text), Some(2));
+        assert_eq!(searcher.find_in(&text[3..]), Some(0));
+        assert_eq!(searcher.find_in(&text[5..]), Some(0));
+        assert_eq!(searcher.find_in(&text[6..]), None);
+    }
+
+    #[test]
+    fn inner_literal_keeps_skipping_predicate() {
+        // A lead of unknown bytes does not replace a set of first bytes.
+        assert!(matches!(
+            predicate(r"([a-z])[a-z]*\1@example\.com", ""),
+            StartPredicate::ByteBracket(_)
+        ));
+        // But it still finds texts without a match, where there is no other
+        // predicate.
+        assert!(matches!(
+            predicate(r"(.)[a-z]*\1@example\.com", "s"),
+            StartPredicate::InnerLiteral(_)
+        ));
+    }
+
+    #[test]
+    fn literals_predicate() {
+        // Several literals are searched for together, or else by their first
+        // bytes.
+        let pred = predicate("foo|bar|baz", "");
+        #[cfg(feature = "perf-literal")]
+        assert!(matches!(pred, StartPredicate::LiteralSet(_)));
+        #[cfg(not(feature = "perf-literal"))]
+        assert!(matches!(pred, StartPredicate::ByteSet2([b'b', b'f'])));
+        // A literal of a single byte is no more selective than its byte.
+        assert!(matches!(
+            predicate("foo|b", ""),
+            StartPredicate::ByteSet2([b'b', b'f'])
+        ));
+    }
 }
@@ -0,0 +1,41 @@
+//! Checking the syntax of patterns without compiling them.
+
+use crate::api::{Error, Flags};
+use crate::parse;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// A problem found in a pattern.
+///
+/// This is the same as the [`Error`] returned when compiling a pattern: it
+/// has a kind, a span, and may be rendered with a caret using `{:#}`.
+pub type Diagnostic = Error;
+
+/// Check the syntax of `pattern` with `flags`, reporting every error found.
+///
+/// Unlike [`Regex::with_flags`](crate::Regex::with_flags), which stops at the
+/// first error, this recovers from each error by skipping to the end of the
+/// enclosing alternative, and keeps going. An empty result means the pattern
+/// is valid. Spans are in bytes.
+///
+/// ```rust
+/// use regress::{ErrorKind, syntax};
+/// let diagnostics = syntax::check("a**|(?<1>b)|c)", "");
+/// let kinds: Vec<ErrorKind> = diagnostics.iter().map(|d| d.kind()).collect();
+/// assert_eq!(
+///     kinds,
+///     [
+///         ErrorKind::InvalidAtomCharacter,
+///         ErrorKind::InvalidGroupName,
+///         ErrorKind::UnbalancedParenthesis
+///     ]
+/// );
+/// assert_eq!(diagnostics[2].span(), Some(13..14));
+/// assert!(syntax::check("a*|b", "").is_empty());
+/// ```
+pub fn check<F: Into<Flags>>(pattern: &str, flags: F) -> Vec<Diagnostic> {
+    parse::check_syntax(pattern.chars().map(u32::from), flags.into())
+        .into_iter()
+        .map(|err| err.with_utf8_span(pattern))
+        .collect()
+}
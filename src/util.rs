@@ -2,10 +2,16 @@ use crate::bytesearch::ByteBitmap;
 use crate::codepointset::CODE_POINT_MAX;
 use crate::codepointset::Interval;
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{alloc::dealloc, vec::Vec};
+use core::alloc::Layout;
 use core::cmp::Ordering;
+use core::fmt;
+use core::mem::{ManuallyDrop, size_of};
 use core::ops::{Index, IndexMut};
+use core::ptr::NonNull;
 use core::slice::SliceIndex;
+#[cfg(feature = "std")]
+use std::alloc::dealloc;
 
 // A macro which expresses either checked or unchecked reachability, depending on prohibit-unsafe.
 macro_rules! rs_unreachable {
@@ -89,6 +95,131 @@ pub fn to_char_sat(c: u32) -> char {
     char::from_u32(c).unwrap_or(core::char::MAX)
 }
 
+/// The allocation of an empty Vec, kept without its element type. It may be
+/// turned back into a Vec of any element type with the same size and
+/// alignment, reusing the allocation. This lets buffers of types bound to the
+/// lifetime of an input, like positions, be kept after the input is gone.
+///
+/// With the prohibit-unsafe feature the allocation is freed instead.
+pub struct SpareVec {
+    /// The allocation, which is dangling if `capacity` is 0.
+    ptr: NonNull<u8>,
+
+    /// The number of elements the allocation holds.
+    capacity: usize,
+
+    /// The layout of an element.
+    elem: Layout,
+}
+
+// A SpareVec holds no values, only memory.
+unsafe impl Send for SpareVec {}
+unsafe impl Sync for SpareVec {}
+
+impl SpareVec {
+    /// Clear \p v and keep its allocation.
+    pub fn new<T>(mut v: Vec<T>) -> SpareVec {
+        v.clear();
+        if cfg!(feature = "prohibit-unsafe") || v.capacity() == 0 || size_of::<T>() == 0 {
+            return SpareVec::default();
+        }
+        let mut v = ManuallyDrop::new(v);
+        SpareVec {
+            // Safety: a Vec's pointer is never null.
+            ptr: unsafe { NonNull::new_unchecked(v.as_mut_ptr().cast()) },
+            capacity: v.capacity(),
+            elem: Layout::new::<T>(),
+        }
+    }
+
+    /// \return an empty Vec with our allocation, or a new one if the layout
+    /// of T is not that of our elements.
+    pub fn into_vec<T>(self) -> Vec<T> {
+        if self.capacity == 0 || Layout::new::<T>() != self.elem {
+            return Vec::new();
+        }
+        let this = ManuallyDrop::new(self);
+        // Safety: the allocation was made by a Vec for `capacity` elements
+        // of the same layout as T, and holds none of them.
+        unsafe { Vec::from_raw_parts(this.ptr.as_ptr().cast(), 0, this.capacity) }
+    }
+}
+
+impl Default for SpareVec {
+    fn default() -> Self {
+        SpareVec {
+            ptr: NonNull::dangling(),
+            capacity: 0,
+            elem: Layout::new::<u8>(),
+        }
+    }
+}
+
+impl Drop for SpareVec {
+    fn drop(&mut self) {
+        if self.capacity == 0 {
+            return;
+        }
+        let size = self.elem.size() * self.capacity;
+        // Safety: this is the layout with which the Vec allocated its
+        // elements.
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(size, self.elem.align());
+            dealloc(self.ptr.as_ptr(), layout)
+        }
+    }
+}
+
+impl fmt::Debug for SpareVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpareVec")
+            .field("capacity", &self.capacity)
+            .field("elem", &self.elem)
+            .finish()
+    }
+}
+
+/// A value kept by a regex for searches which are not given one, so that
+/// successive searches need not allocate it again. Without std there is no
+/// pool, and each such search makes its own.
+#[derive(Debug, Default)]
+pub struct Pool<T> {
+    #[cfg(feature = "std")]
+    value: std::sync::Mutex<Option<T>>,
+    #[cfg(not(feature = "std"))]
+    value: core::marker::PhantomData<T>,
+}
+
+impl<T> Clone for Pool<T> {
+    /// A clone of a regex starts with its own, empty, pool.
+    fn clone(&self) -> Self {
+        Self {
+            value: Default::default(),
+        }
+    }
+}
+
+impl<T: Default> Pool<T> {
+    /// \return the pooled value if it is not in use, else a new one.
+    pub fn get(&self) -> T {
+        #[cfg(feature = "std")]
+        if let Ok(mut value) = self.value.try_lock() {
+            return value.take().unwrap_or_default();
+        }
+        T::default()
+    }
+
+    /// Return \p value to the pool, for the next search to use.
+    pub fn put(&self, value: T) {
+        #[cfg(feature = "std")]
+        if let Ok(mut pooled) = self.value.try_lock() {
+            *pooled = Some(value);
+        }
+        #[cfg(not(feature = "std"))]
+        drop(value);
+    }
+}
+
 /// \return the first byte of a UTF-8 encoded code point.
 /// We do not use char because we don't want to deal with failing on surrogates.
 #[inline(always)]
@@ -206,7 +337,9 @@ pub fn utf8_w4(b0: u8, b1: u8, b2: u8, b3: u8) -> u32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{ByteBitmap, Interval, SliceHelp, add_utf8_first_bytes_to_bitmap, utf8_first_byte};
+    use super::{
+        ByteBitmap, Interval, SliceHelp, SpareVec, add_utf8_first_bytes_to_bitmap, utf8_first_byte,
+    };
 
     #[test]
     fn ranges() {
@@ -283,4 +416,30 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_spare_vec() {
+        let text = [1u8, 2, 3];
+        let mut refs: Vec<(&u8, usize)> = Vec::with_capacity(16);
+        refs.push((&text[1], 1));
+        let ptr = refs.as_ptr() as usize;
+        let spare = SpareVec::new(refs);
+        if cfg!(feature = "prohibit-unsafe") {
+            assert_eq!(spare.capacity, 0);
+            return;
+        }
+        assert_eq!(spare.capacity, 16);
+        let refs: Vec<(&u8, usize)> = spare.into_vec();
+        assert!(refs.is_empty());
+        assert_eq!(refs.capacity(), 16);
+        assert_eq!(refs.as_ptr() as usize, ptr);
+
+        // A Vec of another layout gets a new allocation, and the old one is
+        // freed.
+        let bytes: Vec<u8> = SpareVec::new(refs).into_vec();
+        assert_eq!(bytes.capacity(), 0);
+        drop(SpareVec::new(vec![1u32; 5]));
+        let units: Vec<()> = SpareVec::new(vec![(); 5]).into_vec();
+        assert_eq!(units.len(), 0);
+    }
 }
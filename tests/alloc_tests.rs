@@ -0,0 +1,92 @@
+// With prohibit-unsafe, buffers are not kept between searches.
+#![cfg(not(feature = "prohibit-unsafe"))]
+
+use regress::{Engine, RegexBuilder};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// An allocator which counts the allocations made by each thread, so that
+/// tests running alongside do not disturb the count.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+const PATTERNS: &[&str] = &[
+    r"abc",
+    r"(a+)+b",
+    r"a{2,5}c",
+    r"\bfoo\b",
+    r"(\w)\1",
+    r"(?<=(\d+))px",
+    r"(?=(\w+))\1!",
+    r"(?<!a)b(?=c)",
+    r"^(?:(\w)\1)+$",
+];
+
+const INPUTS: &[&str] = &[
+    "",
+    "aaaaaaaaab",
+    "hello!! 10px xx",
+    "abcabc aabb",
+    "foo bar",
+];
+
+#[test]
+fn test_is_match_does_not_allocate() {
+    let engines = [
+        Engine::Backtrack,
+        Engine::MemoizedBacktrack,
+        #[cfg(feature = "backend-pikevm")]
+        Engine::PikeVM,
+        Engine::Auto,
+        #[cfg(feature = "backend-jit")]
+        Engine::Jit,
+    ];
+    for engine in engines {
+        for pattern in PATTERNS {
+            let re = RegexBuilder::new(pattern).engine(engine).build().unwrap();
+            let search = || {
+                for input in INPUTS {
+                    re.is_match(input);
+                    re.is_match_ascii(input);
+                }
+            };
+            // The first searches fill the regex's pools.
+            search();
+            let before = allocations();
+            for _ in 0..10 {
+                search();
+            }
+            assert_eq!(
+                allocations(),
+                before,
+                "engine: {engine:?} pattern: {pattern:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use regress::analysis::{RiskKind, redos_risk};
+use regress::{ErrorKind, MatchError, RegexBuilder};
+
+/// \return the kind and sub-pattern of each risk in \p pattern.
+#[track_caller]
+fn risks<'p>(pattern: &'p str, flags: &str) -> Vec<(RiskKind, &'p str)> {
+    redos_risk(pattern, flags)
+        .unwrap()
+        .iter()
+        .map(|risk| (risk.kind, &pattern[risk.span.clone()]))
+        .collect()
+}
+
+#[test]
+fn test_exponential() {
+    use RiskKind::Exponential;
+    let cases = [
+        (r"^(a+)+$", "", "(a+)+"),
+        (r"^(\w+\s?)+$", "", r"(\w+\s?)+"),
+        (r"^((ab)+)+$", "", "((ab)+)+"),
+        (r"^(a*)*$", "", "(a*)*"),
+        (r"(x+x+)+y", "", "(x+x+)+"),
+        (r"^(a|a)*$", "", "(a|a)*"),
+        (r"^(a|\w)*$", "", r"(a|\w)*"),
+        (r"^(\d|\d\d)+$", "", r"(\d|\d\d)+"),
+        (r"^(a|ab|b)*c", "", "(a|ab|b)*"),
+        (r"<!--(.|\s)*?-->", "", r"(.|\s)*?"),
+        (r"^(A|a)+$", "i", "(A|a)+"),
+        (r"^(a{1,2})+$", "", "(a{1,2})+"),
+        (r"^(.*a){10}$", "", "(.*a){10}"),
+        (r"^(?=(a+)+$)", "", "(a+)+"),
+        (r"^(\s*,\s*)*$", "", r"(\s*,\s*)*"),
+    ];
+    for (pattern, flags, span) in cases {
+        assert_eq!(risks(pattern, flags), [(Exponential, span)], "{}", pattern);
+    }
+}
+
+#[test]
+fn test_polynomial() {
+    use RiskKind::Polynomial;
+    let cases = [
+        (r"^a*a*$", "a*a*"),
+        (r"^\d+\.?\d+$", r"\d+\.?\d+"),
+        (r"^\w*\d\w*$", r"\w*\d\w*"),
+        (r"^.*.*=.*$", ".*.*=.*"),
+        (r"^(a?){25}a{25}$", "(a?){25}a{25}"),
+    ];
+    for (pattern, span) in cases {
+        assert_eq!(risks(pattern, ""), [(Polynomial, span)], "{}", pattern);
+    }
+}
+
+#[test]
+fn test_safe() {
+    let cases = [
+        r"^(\w+\s)+$",
+        r"^(a+b)+$",
+        r"^(?:a|b)*$",
+        r"^[a-z]+@[a-z]+\.com$",
+        r"^([a-z0-9]+\.)*[a-z0-9]+$",
+        r"^\d{1,3}\d{1,3}$",
+        r"^\d+-\d+$",
+        r"\s*#?$",
+        // These match any text which reaches the loops, so never backtrack.
+        r"(a+)+",
+        r"a*a*",
+        // Lookbehinds are not checked.
+        r"(?<=(a+)+)b",
+    ];
+    for pattern in cases {
+        assert_eq!(risks(pattern, ""), [], "{}", pattern);
+    }
+}
+
+#[test]
+fn test_several() {
+    use RiskKind::*;
+    assert_eq!(
+        risks(r"^(a+)+-(b|b)*-c*c*$", ""),
+        [
+            (Exponential, "(a+)+"),
+            (Exponential, "(b|b)*"),
+            (Polynomial, "c*c*")
+        ]
+    );
+    // Risks within worse ones are not reported.
+    assert_eq!(risks(r"^(a*a*)+$", ""), [(Exponential, "(a*a*)+")]);
+}
+
+#[test]
+fn test_witness() {
+    let risk = &redos_risk(r"^(a+)+$", "").unwrap()[0];
+    assert_eq!(
+        (
+            risk.prefix.as_str(),
+            risk.pump.as_str(),
+            risk.suffix.as_str()
+        ),
+        ("a", "a", "b")
+    );
+    assert_eq!(risk.witness(3), "aaaab");
+
+    // The witness backtracks catastrophically.
+    for (pattern, flags) in [
+        (r"^(a+)+$", ""),
+        (r"^(\w+\s?)+$", ""),
+        (r"^(\d|\d\d)+$", ""),
+        (r"<!--(.|\s)*?-->", ""),
+        (r"^(?=(a+)+$)", ""),
+        (r"^\w*\d\w*$", ""),
+        (r"^é(b|B)+!$", "i"),
+    ] {
+        let risk = &redos_risk(pattern, flags).unwrap()[0];
+        let repeats = match risk.kind {
+            RiskKind::Exponential => 32,
+            _ => 2000,
+        };
+        let re = RegexBuilder::new(pattern)
+            .flags(flags)
+            .backtrack_limit(1_000_000)
+            .build()
+            .unwrap();
+        assert!(
+            matches!(
+                re.try_find(&risk.witness(repeats)),
+                Err(MatchError::BacktrackLimitExceeded)
+            ),
+            "{}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn test_spans_in_bytes() {
+    let pattern = r"^é(a+)+$";
+    let risk = &redos_risk(pattern, "u").unwrap()[0];
+    assert_eq!(risk.span, 3..8);
+    assert_eq!(risk.prefix, "éa");
+}
+
+#[test]
+fn test_invalid_pattern() {
+    let err = redos_risk(r"^(a+)+(", "").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnbalancedParenthesis);
+    assert_eq!(err.span(), Some(6..7));
+}
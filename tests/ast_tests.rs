@@ -0,0 +1,798 @@
+pub mod common;
+use common::*;
+use regress::ast::{
+    self, Assertion, Backreference, Class, ClassEscapeKind, ClassItem, ClassItemKind, ClassSetOp,
+    GroupKind, Literal, LiteralKind, Modifiers, Node, NodeKind, QuantifierKind, Span,
+};
+
+/// Check that spans nest within their parents and that verbatim literals
+/// span exactly their code point.
+fn check_spans(pattern: &str, node: &Node, parent: Span) {
+    let within = |span: Span, parent: Span| {
+        assert!(
+            parent.start <= span.start && span.start <= span.end && span.end <= parent.end,
+            "Span {:?} is not within {:?} in '{}'",
+            span,
+            parent,
+            pattern
+        );
+        assert!(pattern.is_char_boundary(span.start) && pattern.is_char_boundary(span.end));
+    };
+    let check_literal = |lit: &Literal, parent: Span| {
+        within(lit.span, parent);
+        if lit.kind == LiteralKind::Verbatim {
+            let text = &pattern[lit.span.range()];
+            assert_eq!(text.chars().map(u32::from).collect::<Vec<_>>(), [lit.value]);
+        } else {
+            assert!(pattern[lit.span.range()].starts_with('\\'));
+        }
+    };
+    within(node.span, parent);
+    match &node.kind {
+        NodeKind::Alternation(nodes) | NodeKind::Concatenation(nodes) => {
+            for child in nodes {
+                check_spans(pattern, child, node.span);
+            }
+        }
+        NodeKind::Literal(lit) => check_literal(lit, node.span),
+        NodeKind::Group(group) => check_spans(pattern, &group.node, node.span),
+        NodeKind::Repetition(rep) => {
+            check_spans(pattern, &rep.node, node.span);
+            within(rep.quantifier.span, node.span);
+            assert!(rep.node.span.end <= rep.quantifier.span.start);
+        }
+        NodeKind::Class(class) => {
+            fn check_item(item: &ClassItem, parent: Span, check_literal: &dyn Fn(&Literal, Span)) {
+                assert!(parent.start <= item.span.start && item.span.end <= parent.end);
+                match &item.kind {
+                    ClassItemKind::Literal(lit) => check_literal(lit, item.span),
+                    ClassItemKind::Range { first, last } => {
+                        check_literal(first, item.span);
+                        check_literal(last, item.span);
+                    }
+                    ClassItemKind::Class(class) => {
+                        for child in &class.items {
+                            check_item(child, item.span, check_literal);
+                        }
+                    }
+                    ClassItemKind::Strings(strings) => {
+                        for string in strings {
+                            for lit in &string.literals {
+                                check_literal(lit, string.span);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            for item in &class.items {
+                check_item(item, node.span, &check_literal);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[track_caller]
+fn parse(pattern: &str, flags: &str) -> Node {
+    let ast = ast::parse(pattern, flags).unwrap();
+    assert_eq!(ast.node.span.range(), 0..pattern.len());
+    check_spans(pattern, &ast.node, ast.node.span);
+    ast.node
+}
+
+#[track_caller]
+fn cat(node: &Node) -> &[Node] {
+    match &node.kind {
+        NodeKind::Concatenation(nodes) => nodes,
+        kind => panic!("Expected a concatenation, got {:?}", kind),
+    }
+}
+
+#[track_caller]
+fn lit(node: &Node) -> (u32, LiteralKind) {
+    match &node.kind {
+        NodeKind::Literal(lit) => (lit.value, lit.kind),
+        kind => panic!("Expected a literal, got {:?}", kind),
+    }
+}
+
+#[track_caller]
+fn class(node: &Node) -> &Class {
+    match &node.kind {
+        NodeKind::Class(class) => class,
+        kind => panic!("Expected a class, got {:?}", kind),
+    }
+}
+
+#[test]
+fn test_ast_agrees_with_compile() {
+    let patterns = [
+        ("", ""),
+        ("a|b|", ""),
+        ("(a)(?<n>b)\\k<n>\\2", ""),
+        ("a{2,3}?b*c+?d?", ""),
+        ("a{", ""),
+        ("a{", "u"),
+        ("x{1,}{", ""),
+        ("]}", ""),
+        ("]", "u"),
+        ("\\c", ""),
+        ("\\c1", ""),
+        ("[\\c1]", ""),
+        ("[\\c]", ""),
+        ("\\8\\9\\18", ""),
+        ("(a)\\1\\2", "u"),
+        ("\\k", ""),
+        ("(?<a>x)\\k", ""),
+        ("\\u{41}\\u{110000}", ""),
+        ("\\uD83D\\uDE00\\uD83D\\u00", ""),
+        ("\\x4\\xg", ""),
+        ("\\x4", "u"),
+        ("\\p{L}\\P{Script=Greek}", "u"),
+        ("\\p{L}", ""),
+        ("[\\p{L}a-z\\d-x]", "u"),
+        ("[\\d-x]", ""),
+        ("[a-]", ""),
+        ("[-a]", ""),
+        ("[\\b\\-]", "u"),
+        ("(?i-m:a(?s:.))", ""),
+        ("(?=a)(?!b)(?<=c)(?<!d)", ""),
+        ("(?=a)*", ""),
+        ("[[a-z]&&[^aeiou]]", "v"),
+        ("[\\w--\\d--_]", "v"),
+        ("[\\q{abc|d|}a&b]", "v"),
+        ("[^\\q{a}]", "v"),
+        ("[\\p{RGI_Emoji}]", "v"),
+        ("[a&&&b]", "v"),
+        ("\\0\\00\\08\\101\\400\\477\\7", ""),
+        ("é+ü", "i"),
+        ("(?<\\u0061b>x)", ""),
+    ];
+    for (pattern, flags) in patterns {
+        match regress::Regex::with_flags(pattern, flags) {
+            Ok(_) => {
+                parse(pattern, flags);
+            }
+            Err(err) => assert_eq!(
+                ast::parse(pattern, flags).unwrap_err(),
+                err,
+                "Pattern '{}'",
+                pattern
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_ast_differential() {
+    // Random sequences of tokens, many of which only mean something in some
+    // contexts or with some flags, exercise the corners of the grammar. Every
+    // pattern which compiles must give a tree which spans it, and which
+    // prints to a pattern that matches the same.
+    const TOKENS: &[&str] = &[
+        "a",
+        "b",
+        "é",
+        "😀",
+        ".",
+        "|",
+        "(",
+        ")",
+        "(?:",
+        "(?<n>",
+        "(?=",
+        "(?!",
+        "(?<=",
+        "(?<!",
+        "(?i:",
+        "(?-i:",
+        "[",
+        "]",
+        "[^",
+        "-",
+        "{",
+        "}",
+        "{1}",
+        "{1,}",
+        "{1,2}",
+        "{0,1}",
+        "{2,2}",
+        ",",
+        "*",
+        "+",
+        "?",
+        "^",
+        "$",
+        "\\",
+        r"\b",
+        r"\B",
+        r"\d",
+        r"\W",
+        r"\c",
+        r"\cA",
+        r"\c1",
+        r"\k",
+        r"\k<n>",
+        r"\1",
+        r"\2",
+        r"\8",
+        r"\0",
+        r"\01",
+        r"\x4",
+        r"\x41",
+        r"\u",
+        r"é",
+        r"\u{1F600}",
+        r"😀",
+        r"\p{L}",
+        r"\P{Script=Greek}",
+        r"\q{ab|c}",
+        "&&",
+        "--",
+    ];
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut compiled = 0;
+    for _ in 0..8_000 {
+        let pattern: String = (0..1 + rng.below(8)).map(|_| rng.pick(TOKENS)).collect();
+        let flags = rng.pick(&["", "i", "u", "v", "iu", "m", "s"]);
+        let Ok(re) = regress::Regex::with_flags(&pattern, flags) else {
+            continue;
+        };
+        compiled += 1;
+        let ast = ast::parse(&pattern, flags)
+            .unwrap_or_else(|err| panic!("{pattern:?} /{flags} does not parse: {err}"));
+        let whole = Span {
+            start: 0,
+            end: pattern.len(),
+        };
+        check_spans(&pattern, &ast.node, whole);
+        assert_eq!(ast.node.span, whole, "{pattern:?} /{flags}");
+        let printed = ast.to_string();
+        let reprinted = regress::Regex::with_flags(&printed, flags)
+            .unwrap_or_else(|err| panic!("{pattern:?} /{flags} printed as {printed:?}: {err}"));
+        assert_eq!(
+            re.captures_len(),
+            reprinted.captures_len(),
+            "{pattern:?} /{flags}"
+        );
+        for text in ["", "ab", "éb 😀a", "a1\nAB", "aaab-b"] {
+            assert_eq!(
+                all_groups(&re, text),
+                all_groups(&reprinted, text),
+                "{pattern:?} /{flags} printed as {printed:?}, text {text:?}"
+            );
+        }
+    }
+    assert!(compiled > 1_000, "Only {compiled} patterns compiled");
+}
+
+#[test]
+fn test_ast_escapes() {
+    let node = parse(r"a\.\n\cJ\0\x41\u0041\u{41}\uD83D\uDE00\101", "");
+    let lits: Vec<_> = cat(&node).iter().map(lit).collect();
+    assert_eq!(
+        lits,
+        [
+            (0x61, LiteralKind::Verbatim),
+            (0x2E, LiteralKind::Identity),
+            (0x0A, LiteralKind::Control),
+            (0x0A, LiteralKind::ControlLetter),
+            (0x00, LiteralKind::Null),
+            (0x41, LiteralKind::Hex),
+            (0x41, LiteralKind::Unicode),
+            (0x41, LiteralKind::UnicodeBraced),
+            (0x1F600, LiteralKind::Unicode),
+            (0x41, LiteralKind::Octal),
+        ]
+    );
+    let spans: Vec<_> = cat(&node).iter().map(|n| n.span.range()).collect();
+    assert_eq!(
+        spans,
+        [
+            0..1,
+            1..3,
+            3..5,
+            5..8,
+            8..10,
+            10..14,
+            14..20,
+            20..26,
+            26..38,
+            38..42
+        ]
+    );
+
+    // Annex B: \8 is an identity escape, and a lone \c is a backslash and a c.
+    let node = parse(r"\8\c", "");
+    let lits: Vec<_> = cat(&node).iter().map(lit).collect();
+    assert_eq!(
+        lits,
+        [
+            (0x38, LiteralKind::Identity),
+            (0x5C, LiteralKind::Verbatim),
+            (0x63, LiteralKind::Verbatim),
+        ]
+    );
+}
+
+#[test]
+fn test_ast_quantifiers() {
+    let node = parse("a*b+?c?d{2}e{2,}?f{2,3}g{2,2}", "");
+    let quants: Vec<_> = cat(&node)
+        .iter()
+        .map(|n| match &n.kind {
+            NodeKind::Repetition(rep) => {
+                let q = rep.quantifier;
+                (q.kind, q.min, q.max, q.greedy, q.span.range())
+            }
+            kind => panic!("Expected a repetition, got {:?}", kind),
+        })
+        .collect();
+    use QuantifierKind::*;
+    assert_eq!(
+        quants,
+        [
+            (ZeroOrMore, 0, None, true, 1..2),
+            (OneOrMore, 1, None, false, 3..5),
+            (ZeroOrOne, 0, Some(1), true, 6..7),
+            (Exactly, 2, Some(2), true, 8..11),
+            (AtLeast, 2, None, false, 12..17),
+            (Between, 2, Some(3), true, 18..23),
+            (Between, 2, Some(2), true, 24..29),
+        ]
+    );
+
+    // A quantifier after a lone \c applies only to the c.
+    let node = parse(r"\c*", "");
+    let nodes = cat(&node);
+    assert_eq!(nodes.len(), 2);
+    assert!(matches!(&nodes[1].kind, NodeKind::Repetition(rep) if rep.node.span.range() == (1..2)));
+}
+
+#[test]
+fn test_ast_groups() {
+    let node = parse(r"(a)(?<name>b)(?:c)(?i-ms:d)(?=e)(?<!f)\k<name>\1", "");
+    let nodes = cat(&node);
+    let kinds: Vec<_> = nodes[..6]
+        .iter()
+        .map(|n| match &n.kind {
+            NodeKind::Group(group) => group.kind.clone(),
+            kind => panic!("Expected a group, got {:?}", kind),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            GroupKind::Capture {
+                index: 1,
+                name: None
+            },
+            GroupKind::Capture {
+                index: 2,
+                name: Some("name".into())
+            },
+            GroupKind::NonCapture,
+            GroupKind::Modifiers {
+                enable: Modifiers {
+                    icase: true,
+                    ..Modifiers::default()
+                },
+                disable: Modifiers {
+                    multiline: true,
+                    dot_all: true,
+                    ..Modifiers::default()
+                },
+            },
+            GroupKind::Lookahead { negated: false },
+            GroupKind::Lookbehind { negated: true },
+        ]
+    );
+    assert_eq!(nodes[1].span.range(), 3..13);
+    assert_eq!(
+        nodes[6].kind,
+        NodeKind::Backreference(Backreference::Name("name".into()))
+    );
+    assert_eq!(
+        nodes[7].kind,
+        NodeKind::Backreference(Backreference::Number(1))
+    );
+
+    let node = parse("^a|$|\\b", "");
+    let NodeKind::Alternation(alts) = &node.kind else {
+        panic!("Expected an alternation")
+    };
+    assert_eq!(alts.len(), 3);
+    assert_eq!(
+        cat(&alts[0])[0].kind,
+        NodeKind::Assertion(Assertion::StartOfLine)
+    );
+    assert_eq!(alts[1].kind, NodeKind::Assertion(Assertion::EndOfLine));
+    assert_eq!(alts[2].kind, NodeKind::Assertion(Assertion::WordBoundary));
+
+    // Empty alternatives are Empty nodes at their position.
+    let node = parse("a||", "");
+    let NodeKind::Alternation(alts) = &node.kind else {
+        panic!("Expected an alternation")
+    };
+    assert_eq!(alts[1].kind, NodeKind::Empty);
+    assert_eq!(alts[1].span.range(), 2..2);
+    assert_eq!(alts[2].span.range(), 3..3);
+}
+
+#[test]
+fn test_ast_classes() {
+    let node = parse(r"[^a-z\d\b-]", "");
+    let parsed = class(&node);
+    assert!(parsed.negated);
+    assert_eq!(parsed.op, ClassSetOp::Union);
+    let spans: Vec<_> = parsed.items.iter().map(|i| i.span.range()).collect();
+    assert_eq!(spans, [2..5, 5..7, 7..9, 9..10]);
+    assert!(matches!(
+        parsed.items[0].kind,
+        ClassItemKind::Range { first, last } if first.value == 0x61 && last.value == 0x7A
+    ));
+    assert!(matches!(
+        &parsed.items[1].kind,
+        ClassItemKind::ClassEscape(e) if e.kind == ClassEscapeKind::Digit && !e.negated
+    ));
+    assert!(matches!(
+        parsed.items[2].kind,
+        ClassItemKind::Literal(Literal {
+            value: 8,
+            kind: LiteralKind::Backspace,
+            ..
+        })
+    ));
+
+    // Outside of unicode mode, a class escape may not start a range.
+    let node = parse(r"[\w-a]", "");
+    assert_eq!(class(&node).items.len(), 3);
+
+    let node = parse(r"\P{Script=Greek}", "u");
+    let NodeKind::ClassEscape(escape) = &node.kind else {
+        panic!("Expected a class escape")
+    };
+    assert!(escape.negated);
+    assert_eq!(
+        escape.kind,
+        ClassEscapeKind::Property {
+            name: Some("Script".into()),
+            value: "Greek".into()
+        }
+    );
+}
+
+#[test]
+fn test_ast_class_sets() {
+    let node = parse(r"[[a-z]&&[^aeiou]&&\p{L}]", "v");
+    let outer = class(&node);
+    assert_eq!(outer.op, ClassSetOp::Intersection);
+    let spans: Vec<_> = outer.items.iter().map(|i| i.span.range()).collect();
+    assert_eq!(spans, [1..6, 8..16, 18..23]);
+    let ClassItemKind::Class(inner) = &outer.items[1].kind else {
+        panic!("Expected a nested class")
+    };
+    assert!(inner.negated);
+    assert_eq!(inner.items.len(), 5);
+
+    let node = parse(r"[\w--\d--_]", "v");
+    let outer = class(&node);
+    assert_eq!(outer.op, ClassSetOp::Subtraction);
+    assert_eq!(outer.items.len(), 3);
+
+    let node = parse(r"[\q{ab|\&|}x]", "v");
+    let items = &class(&node).items;
+    let ClassItemKind::Strings(strings) = &items[0].kind else {
+        panic!("Expected strings")
+    };
+    let strings: Vec<_> = strings
+        .iter()
+        .map(|s| {
+            let values: Vec<_> = s.literals.iter().map(|l| l.value).collect();
+            (values, s.span.range())
+        })
+        .collect();
+    assert_eq!(
+        strings,
+        [
+            (vec![0x61, 0x62], 4..6),
+            (vec![0x26], 7..9),
+            (vec![], 10..10)
+        ]
+    );
+    assert_eq!(items[0].span.range(), 1..11);
+    assert_eq!(items[1].span.range(), 11..12);
+}
+
+/// Clear every span in \p node, so trees may be compared by structure.
+fn erase_spans(node: &mut Node) {
+    fn erase_class(class: &mut Class) {
+        for item in &mut class.items {
+            item.span = Span { start: 0, end: 0 };
+            match &mut item.kind {
+                ClassItemKind::Literal(lit) => lit.span = item.span,
+                ClassItemKind::Range { first, last } => {
+                    first.span = item.span;
+                    last.span = item.span;
+                }
+                ClassItemKind::Class(class) => erase_class(class),
+                ClassItemKind::Strings(strings) => {
+                    for string in strings {
+                        string.span = item.span;
+                        for lit in &mut string.literals {
+                            lit.span = item.span;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    node.span = Span { start: 0, end: 0 };
+    match &mut node.kind {
+        NodeKind::Alternation(nodes) | NodeKind::Concatenation(nodes) => {
+            nodes.iter_mut().for_each(erase_spans)
+        }
+        NodeKind::Literal(lit) => lit.span = node.span,
+        NodeKind::Class(class) => erase_class(class),
+        NodeKind::Group(group) => erase_spans(&mut group.node),
+        NodeKind::Repetition(rep) => {
+            erase_spans(&mut rep.node);
+            rep.quantifier.span = node.span;
+        }
+        _ => {}
+    }
+}
+
+/// Check that printing \p ast gives a pattern which parses to the same tree.
+#[track_caller]
+fn check_round_trip(ast: &ast::Ast) -> String {
+    let printed = ast.to_string();
+    let mut reparsed = ast::parse(&printed, ast.flags)
+        .unwrap_or_else(|err| panic!("'{}' does not parse: {}", printed, err));
+    let mut expected = ast.clone();
+    erase_spans(&mut reparsed.node);
+    erase_spans(&mut expected.node);
+    assert_eq!(reparsed, expected, "Printed as '{}'", printed);
+    printed
+}
+
+#[test]
+fn test_print_round_trip() {
+    // These print exactly as written.
+    let patterns = [
+        ("", ""),
+        ("a|b|", ""),
+        (r"(a)(?<n>b)\k<n>\2", ""),
+        (r"a{2,3}?b*c+?d?e{2}f{0,}?", ""),
+        ("a{2,2}b{2,}c{0,1}d{1,}?e{0,}f{1}", ""),
+        ("a{", ""),
+        ("x{1,}{", ""),
+        ("]}", ""),
+        (r"\c", ""),
+        (r"\c*", ""),
+        (r"\c1", ""),
+        (r"[\c]", ""),
+        (r"[\c-e]", ""),
+        (r"\8\9", ""),
+        (r"(a)\1", "u"),
+        (r"\k", ""),
+        (r"😀\uD83Du00", ""),
+        (r"\x4\xg", ""),
+        (r"\p{L}\P{Script=Greek}", "u"),
+        (r"\p{L}", ""),
+        (r"[\p{L}a-z\d]", "u"),
+        (r"[\d-x]", ""),
+        ("[a-]", ""),
+        ("[-a]", ""),
+        ("[a-b-c]", ""),
+        (r"[\s--a]", ""),
+        ("[a^]", ""),
+        (r"[\b\-]", "u"),
+        (r"^\b\B$.", "m"),
+        ("(?i-m:a(?s:.))(?-s:b)", ""),
+        ("(?=a)(?!b)(?<=c)(?<!d)", ""),
+        ("(?=a)*", ""),
+        ("[[a-z]&&[^aeiou]]", "v"),
+        (r"[\w--\d--_]", "v"),
+        (r"[\q{abc|d|}a&b]", "v"),
+        (r"[^\q{a}]", "v"),
+        (r"[\p{RGI_Emoji}]", "v"),
+        (r"[\&&\!!]", "v"),
+        ("[m&$s]", "v"),
+        ("[a&&&]", "v"),
+        ("[^?--<]", "v"),
+        ("[*--]]", "v"),
+        (r"\0\08\001\101\377", ""),
+        (r"\0", "u"),
+        (r"\u{1F600}\u{41}", "u"),
+        (r"[\u{1F600}-\u{1F64F}]", "v"),
+        (r"\f\n\r\t\v\cJ\x0A\u000A", ""),
+        (r"\uD83D\u\u0041\uD83D\u\uDE00", ""),
+        ("\\\n\\\u{2028}", ""),
+        (r"\b?\B*", ""),
+        ("é+ü/\n", "i"),
+        ("[/]", ""),
+        (r"\/\[\]\{\}", "u"),
+    ];
+    for (pattern, flags) in patterns {
+        let ast = ast::parse(pattern, flags).unwrap();
+        assert_eq!(check_round_trip(&ast), pattern);
+    }
+
+    // These are normalized.
+    let patterns = [
+        (r"\1", "", r"\001"),
+        (r"\018", "", r"\0018"),
+        (r"\0101\4", "", r"\0101\004"),
+        (r"\x0aé\cj", "", r"\x0Aé\cJ"),
+        (r"[\c1\c_\c0]", "", r"[\cQ\c_\cP]"),
+        (r"\u{1f600}", "u", r"\u{1F600}"),
+        ("(?<\\u0061b>x)\\k<\\u{61}b>", "", "(?<ab>x)\\k<ab>"),
+        ("(?<=a)(?<a>b)", "u", "(?<=a)(?<a>b)"),
+    ];
+    for (pattern, flags, expected) in patterns {
+        let ast = ast::parse(pattern, flags).unwrap();
+        assert_eq!(check_round_trip(&ast), expected);
+    }
+}
+
+#[test]
+fn test_print_literal() {
+    let literal = |pattern: &str, flags: &str| {
+        let ast = ast::parse(pattern, flags).unwrap();
+        let printed = format!("{:#}", ast);
+        assert!(printed.starts_with('/'));
+        let end = printed.rfind('/').unwrap();
+        assert_eq!(&printed[end + 1..], ast.flags.to_string());
+        let mut reparsed = ast::parse(&printed[1..end], flags).unwrap();
+        let mut original = ast;
+        erase_spans(&mut reparsed.node);
+        erase_spans(&mut original.node);
+        if !pattern.is_empty() {
+            // Escaping changes how literals are written, but not what they are.
+            assert_eq!(
+                regress::Regex::with_flags(&printed[1..end], flags)
+                    .unwrap()
+                    .find(pattern)
+                    .is_some(),
+                regress::Regex::with_flags(pattern, flags)
+                    .unwrap()
+                    .find(pattern)
+                    .is_some()
+            );
+        }
+        printed
+    };
+    assert_eq!(literal("", ""), "/(?:)/");
+    assert_eq!(literal("a/b", "i"), r"/a\/b/i");
+    assert_eq!(literal("[/]", "m"), r"/[\/]/m");
+    assert_eq!(literal(r"\/", "u"), r"/\//u");
+    assert_eq!(literal(r"[^\/]", "v"), r"/[^\/]/v");
+    assert_eq!(
+        literal("a\nb\r\u{2028}\u{2029}", ""),
+        r"/a\nb\r\u2028\u2029/"
+    );
+    assert_eq!(literal("[\n]", "su"), r"/[\n]/su");
+    assert_eq!(literal(r"\\/", "y"), r"/\\\//y");
+}
+
+#[test]
+fn test_print_rewritten() {
+    let verbatim = |c: char| Literal {
+        value: u32::from(c),
+        kind: LiteralKind::Verbatim,
+        span: Span { start: 0, end: 0 },
+    };
+    let node = |kind: NodeKind| Node {
+        kind,
+        span: Span { start: 0, end: 0 },
+    };
+    let print = |pattern: &str, flags: &str, edit: &dyn Fn(&mut Node)| {
+        let mut ast = ast::parse(pattern, flags).unwrap();
+        edit(&mut ast.node);
+        // Code points which cannot be written as the tree says are escaped,
+        // so the reparsed tree may differ; but printing it again is stable.
+        let printed = ast.to_string();
+        let reparsed = ast::parse(&printed, flags)
+            .unwrap_or_else(|err| panic!("'{}' does not parse: {}", printed, err));
+        assert_eq!(check_round_trip(&reparsed), printed);
+        printed
+    };
+    let lit_node = move |c: char| node(NodeKind::Literal(verbatim(c)));
+    let push = |s: &'static str| {
+        move |node: &mut Node| {
+            for c in s.chars() {
+                match &mut node.kind {
+                    NodeKind::Concatenation(nodes) => nodes.push(lit_node(c)),
+                    NodeKind::Class(class) => class.items.push(ClassItem {
+                        kind: ClassItemKind::Literal(verbatim(c)),
+                        span: Span { start: 0, end: 0 },
+                    }),
+                    _ => {
+                        let inner = core::mem::replace(node, lit_node(c));
+                        node.kind = NodeKind::Concatenation(vec![inner, lit_node(c)]);
+                    }
+                }
+            }
+        }
+    };
+
+    // Rename a group.
+    let rename = |node: &mut Node| {
+        let NodeKind::Concatenation(nodes) = &mut node.kind else {
+            panic!()
+        };
+        let NodeKind::Group(group) = &mut nodes[0].kind else {
+            panic!()
+        };
+        group.kind = GroupKind::Capture {
+            index: 1,
+            name: Some("year".into()),
+        };
+        nodes[1].kind = NodeKind::Backreference(Backreference::Name("year".into()));
+    };
+    assert_eq!(
+        print(r"(?<y>\d{4})\k<y>", "u", &rename),
+        r"(?<year>\d{4})\k<year>"
+    );
+
+    // Normalize escapes.
+    let normalize = |node: &mut Node| {
+        let NodeKind::Concatenation(nodes) = &mut node.kind else {
+            panic!()
+        };
+        for node in nodes {
+            if let NodeKind::Literal(lit) = &mut node.kind {
+                lit.kind = LiteralKind::Verbatim;
+            }
+        }
+    };
+    assert_eq!(print(r"\x41\.é\n\*", "", &normalize), "A\\.é\n\\*");
+
+    // Digits are not absorbed by escapes before them.
+    assert_eq!(print(r"(a)\1", "", &push("2")), r"(a)\1\x32");
+    assert_eq!(print(r"\0", "", &push("1")), r"\0\x31");
+    assert_eq!(print(r"\0", "", &push("8")), r"\08");
+    assert_eq!(print(r"\0", "u", &push("8")), r"\0\x38");
+    assert_eq!(print(r"\c", "", &push("J")), r"\c\x4A");
+
+    // Code points which would be syntax are escaped.
+    assert_eq!(print("a", "", &push("*")), r"a\*");
+    assert_eq!(print("a", "", &push("{2,}")), r"a\{2,}");
+    assert_eq!(print("a", "", &push("{2,x}")), "a{2,x}");
+    assert_eq!(print("a", "u", &push("}")), r"a\}");
+    assert_eq!(print("[a]", "", &push("-")), "[a-]");
+    assert_eq!(print("[a-]", "", &push("z")), r"[a\-z]");
+    assert_eq!(print("[]", "", &push("^")), r"[\^]");
+    assert_eq!(print("[a]", "v", &push("-")), r"[a\-]");
+    assert_eq!(print("[&]", "v", &push("&")), r"[\&&]");
+    assert_eq!(print("[a]", "v", &push("(")), r"[a\(]");
+    assert_eq!(print("[a]", "v", &push("$&")), r"[a\$&]");
+    assert_eq!(print("[a-]", "", &push("-z")), r"[a\-\-z]");
+
+    // Nodes which cannot be quantified or concatenated are grouped.
+    let repeat_all = |n: &mut Node| {
+        let inner = core::mem::replace(n, node(NodeKind::Empty));
+        *n = node(NodeKind::Repetition(ast::Repetition {
+            node: Box::new(inner),
+            quantifier: ast::Quantifier {
+                kind: QuantifierKind::ZeroOrMore,
+                min: 0,
+                max: None,
+                greedy: false,
+                span: Span { start: 0, end: 0 },
+            },
+        }));
+    };
+    assert_eq!(print("ab", "", &repeat_all), "(?:ab)*?");
+    let concat_alternation = |n: &mut Node| {
+        let inner = core::mem::replace(n, node(NodeKind::Empty));
+        *n = node(NodeKind::Concatenation(vec![
+            node(NodeKind::Literal(verbatim('x'))),
+            inner,
+        ]));
+    };
+    assert_eq!(print("a|b", "", &concat_alternation), "x(?:a|b)");
+}
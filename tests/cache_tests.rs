@@ -0,0 +1,108 @@
+use regress::{Cache, Regex, RegexBuilder};
+
+const PATTERNS: &[&str] = &[
+    r"abc",
+    r"(a)(b)?c",
+    r"(?<=(\d+))px",
+    r"(?=(\w+))\1!",
+    r"(?!a)\w+",
+    r"(a|b)*?c",
+    r"^(?:(\w)\1)+$",
+    r"(?<first>\w)\w*\k<first>",
+    r"",
+];
+
+const INPUTS: &[&str] = &["", "abc", "xac", "10px", "hello!", "ababc", "aabb", "abca"];
+
+fn ranges(m: &regress::Match) -> Vec<Option<regress::Range>> {
+    std::iter::once(Some(m.range()))
+        .chain(m.groups().skip(1))
+        .collect()
+}
+
+#[test]
+fn test_cache_agrees_with_find() {
+    // One cache shared by every regex and input.
+    let mut cache = Cache::new();
+    for pattern in PATTERNS {
+        let re = Regex::new(pattern).unwrap();
+        for input in INPUTS {
+            for start in 0..=input.len() {
+                let expected = re.find_from(input, start).next();
+                let actual = re.find_from_with_cache(input, start, &mut cache);
+                assert_eq!(
+                    expected.as_ref().map(ranges),
+                    actual.as_ref().map(ranges),
+                    "pattern: {pattern:?} input: {input:?} start: {start}"
+                );
+            }
+            assert_eq!(
+                re.find_with_cache(input, &mut cache).map(|m| m.range()),
+                re.find(input).map(|m| m.range())
+            );
+            assert_eq!(
+                re.is_match_with_cache(input, &mut cache),
+                re.is_match(input)
+            );
+
+            #[cfg(feature = "utf16")]
+            {
+                let utf16: Vec<u16> = input.encode_utf16().collect();
+                let expected = re.find_from_utf16(&utf16, 0).next();
+                let actual = re.find_from_utf16_with_cache(&utf16, 0, &mut cache);
+                assert_eq!(expected.as_ref().map(ranges), actual.as_ref().map(ranges));
+                let expected = re.find_from_ucs2(&utf16, 0).next();
+                let actual = re.find_from_ucs2_with_cache(&utf16, 0, &mut cache);
+                assert_eq!(expected.as_ref().map(ranges), actual.as_ref().map(ranges));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_cache_after_abandoned_search() {
+    let mut cache = Cache::new();
+    let re = RegexBuilder::new(r"(?=(a+)+$)|(a)b")
+        .backtrack_limit(1_000)
+        .build()
+        .unwrap();
+    let text = "a".repeat(30) + "b";
+    assert!(re.try_find_from_with_cache(&text, 0, &mut cache).is_err());
+    assert!(re.find_with_cache(&text, &mut cache).is_none());
+
+    // The cache is still usable, and holds no stale capture groups.
+    let m = re.find_with_cache("ab", &mut cache).unwrap();
+    assert_eq!(ranges(&m), [Some(0..2), None, Some(0..1)]);
+    let re = Regex::new(r"(a)|(b)").unwrap();
+    let m = re.find_with_cache("b", &mut cache).unwrap();
+    assert_eq!(ranges(&m), [Some(0..1), None, Some(0..1)]);
+}
+
+#[test]
+#[cfg(feature = "backend-pikevm")]
+fn test_cache_regexset() {
+    let set = regress::RegexSet::new(PATTERNS).unwrap();
+    let mut cache = Cache::new();
+    for input in INPUTS {
+        assert_eq!(
+            set.matches_with_cache(input, &mut cache),
+            set.matches(input)
+        );
+        assert_eq!(
+            set.is_match_with_cache(input, &mut cache),
+            set.is_match(input)
+        );
+    }
+    // The same cache also works for a Regex.
+    let re = Regex::new(r"b+").unwrap();
+    assert_eq!(
+        re.find_with_cache("abbc", &mut cache).unwrap().range(),
+        1..3
+    );
+}
+
+#[test]
+fn test_cache_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Cache>();
+}
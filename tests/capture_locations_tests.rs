@@ -0,0 +1,94 @@
+use regress::Regex;
+
+fn expected_slots(re: &Regex, text: &str, start: usize) -> Vec<Option<regress::Range>> {
+    match re.find_from(text, start).next() {
+        Some(m) => m.groups().collect(),
+        None => vec![None; re.capture_locations().len()],
+    }
+}
+
+#[test]
+fn test_captures_read_agrees_with_find() {
+    let cases: &[(&str, &str)] = &[
+        (r"(a)(b)?c", "xacabc"),
+        (r"(\d+)-(\d+)?", "1- 22-33"),
+        (r"(?<=(\w))(\d)", "a1 2b3"),
+        (r"(?=(\w+))\1!", "hi! there"),
+        (r"(a|(b))+", "abab"),
+        (r"x", "abc"),
+        (r"", "ab"),
+        (r"(?<y>\d{4})-(?<m>\d\d)", "on 2020-05"),
+    ];
+    for &(pattern, text) in cases {
+        let re = Regex::new(pattern).unwrap();
+        let mut locs = re.capture_locations();
+        for start in 0..=text.len() {
+            let range = re.captures_read_at(&mut locs, text, start);
+            let expected = expected_slots(&re, text, start);
+            assert_eq!(range, expected[0], "pattern: {pattern:?} start: {start}");
+            let actual: Vec<_> = (0..locs.len()).map(|i| locs.get(i)).collect();
+            assert_eq!(actual, expected, "pattern: {pattern:?} start: {start}");
+        }
+        assert_eq!(
+            re.captures_read(&mut locs, text),
+            re.find(text).map(|m| m.range())
+        );
+    }
+}
+
+#[test]
+fn test_capture_locations_access() {
+    let re = Regex::new(r"(?<a>x)|(?<a>y)(z)?").unwrap();
+    let mut locs = re.capture_locations();
+    assert_eq!(locs.len(), 4);
+    assert_eq!(locs.get(0), None);
+
+    assert_eq!(re.captures_read(&mut locs, "-y"), Some(1..2));
+    assert_eq!(locs.get(0), Some(1..2));
+    assert_eq!(locs.get(1), None);
+    assert_eq!(locs.get(2), Some(1..2));
+    assert_eq!(locs.get(3), None);
+    assert_eq!(locs.get(4), None);
+    // Duplicate names refer to the group which participated.
+    assert_eq!(locs.name("a"), Some(1..2));
+    assert_eq!(locs.name(""), None);
+    assert_eq!(locs.name("b"), None);
+
+    assert_eq!(re.captures_read(&mut locs, "x"), Some(0..1));
+    assert_eq!(locs.name("a"), Some(0..1));
+
+    // A failed search clears every location.
+    assert_eq!(re.captures_read(&mut locs, "---"), None);
+    assert!((0..locs.len()).all(|i| locs.get(i).is_none()));
+}
+
+#[test]
+fn test_captures_read_sticky() {
+    let re = Regex::with_flags(r"(\d)", "y").unwrap();
+    let mut locs = re.capture_locations();
+    assert_eq!(re.captures_read_at(&mut locs, "a1", 0), None);
+    assert_eq!(re.captures_read_at(&mut locs, "a1", 1), Some(1..2));
+    assert_eq!(locs.get(1), Some(1..2));
+    assert_eq!(re.captures_read_at(&mut locs, "a1", 5), None);
+}
+
+#[test]
+#[should_panic]
+fn test_captures_read_wrong_regex() {
+    let re = Regex::new(r"(a)(b)").unwrap();
+    let mut locs = Regex::new(r"a").unwrap().capture_locations();
+    re.captures_read(&mut locs, "ab");
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_captures_read_utf16() {
+    let re = Regex::new(r"(.)(x)").unwrap();
+    let text: Vec<u16> = "😀x".encode_utf16().collect();
+    let mut locs = re.capture_locations();
+    assert_eq!(re.captures_read_utf16_at(&mut locs, &text, 0), Some(0..3));
+    assert_eq!(locs.get(1), Some(0..2));
+    assert_eq!(re.captures_read_ucs2_at(&mut locs, &text, 0), Some(1..3));
+    assert_eq!(locs.get(1), Some(1..2));
+    assert_eq!(locs.get(2), Some(2..3));
+}
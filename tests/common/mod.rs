@@ -24,6 +24,79 @@ fn format_match(r: &regress::Match, input: &str) -> String {
     result
 }
 
+/// \return the range and capture groups of each match in \p matches.
+#[allow(dead_code)]
+fn spans(matches: &[regress::Match]) -> Vec<Vec<Option<regress::Range>>> {
+    matches.iter().map(|m| m.groups().collect()).collect()
+}
+
+/// \return a regex for \p pattern with \p flags, searching with \p engine.
+pub fn build(pattern: &str, flags: &str, engine: regress::Engine) -> regress::Regex {
+    regress::RegexBuilder::new(pattern)
+        .flags(flags)
+        .engine(engine)
+        .build()
+        .unwrap()
+}
+
+/// \return the range and capture groups of each match of \p re in \p text.
+pub fn all_groups(re: &regress::Regex, text: &str) -> Vec<Vec<Option<regress::Range>>> {
+    re.find_iter(text).map(|m| m.groups().collect()).collect()
+}
+
+/// \return the range and capture groups of each match of \p re in \p text,
+/// found by the backtracker itself rather than the lazy DFA.
+pub fn backtrack_groups(re: &regress::Regex, text: &str) -> Vec<Vec<Option<regress::Range>>> {
+    use regress::backends as rbe;
+    rbe::find::<rbe::BacktrackExecutor>(re, text, 0)
+        .map(|m| m.groups().collect())
+        .collect()
+}
+
+/// Check that \p engine, and the lazy DFA, find the same matches and groups
+/// of \p pattern with \p flags in each of \p texts as the backtracker does
+/// by itself.
+#[track_caller]
+pub fn check_agrees(engine: regress::Engine, pattern: &str, flags: &str, texts: &[&str]) {
+    let re = build(pattern, flags, engine);
+    let backtrack = build(pattern, flags, regress::Engine::Backtrack);
+    for text in texts {
+        let context =
+            format!("engine: {engine:?} pattern: {pattern:?} flags: {flags:?} text: {text:?}");
+        let expected = backtrack_groups(&backtrack, text);
+        assert_eq!(all_groups(&re, text), expected, "{context}");
+        if engine != regress::Engine::Backtrack {
+            // The JIT is run by the backtracker's executor.
+            assert_eq!(backtrack_groups(&re, text), expected, "{context}");
+        }
+        assert_eq!(re.is_match(text), !expected.is_empty(), "{context}");
+        let mut locs = re.capture_locations();
+        re.captures_read(&mut locs, text);
+        let first = (0..locs.len()).map(|i| locs.get(i)).collect::<Vec<_>>();
+        let expected_first = match expected.first() {
+            Some(groups) => groups.clone(),
+            None => vec![None; locs.len()],
+        };
+        assert_eq!(first, expected_first, "{context}");
+    }
+}
+
+/// A xorshift generator, for reproducible random patterns and texts.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
 /// Encode a string as UTF16.
 pub fn to_utf16(input: &str) -> Vec<u16> {
     input.encode_utf16().collect()
@@ -71,6 +144,10 @@ impl VecTestHelpers for Vec<&str> {
 pub struct TestCompiledRegex {
     re: regress::Regex,
     tc: TestConfig,
+    // With the JIT backend, the same regex interpreted, against which every
+    // search is checked.
+    #[allow(dead_code)]
+    interpreted: Option<regress::Regex>,
 }
 
 impl TestCompiledRegex {
@@ -80,7 +157,6 @@ impl TestCompiledRegex {
         use regress::backends as rbe;
         #[cfg(feature = "utf16")]
         {
-            // We don't test the PikeVM backend with UTF16 or UCS2.
             if self.tc.encoding == Encoding::Utf16 {
                 return self.match_utf16(input, start);
             } else if self.tc.encoding == Encoding::Ucs2 {
@@ -104,13 +180,33 @@ impl TestCompiledRegex {
                 rbe::find::<rbe::PikeVMExecutor>(&self.re, input, start).collect()
             }
 
-            (true, Backend::Backtracking) => {
+            (true, Backend::Backtracking | Backend::MemoizedBacktracking) => {
                 rbe::find_ascii::<rbe::BacktrackExecutor>(&self.re, input, start).collect()
             }
 
-            (false, Backend::Backtracking) => {
+            (false, Backend::Backtracking | Backend::MemoizedBacktracking) => {
                 rbe::find::<rbe::BacktrackExecutor>(&self.re, input, start).collect()
             }
+
+            (_, Backend::LazyDfa) => {
+                rbe::find::<rbe::LazyDfaExecutor>(&self.re, input, start).collect()
+            }
+
+            #[cfg(feature = "backend-jit")]
+            (_, Backend::Jit) => {
+                let found: Vec<_> =
+                    rbe::find::<rbe::BacktrackExecutor>(&self.re, input, start).collect();
+                let interpreted = self.interpreted.as_ref().expect("Should have interpreter");
+                let expected: Vec<_> =
+                    rbe::find::<rbe::BacktrackExecutor>(interpreted, input, start).collect();
+                assert_eq!(
+                    spans(&found),
+                    spans(&expected),
+                    "JIT and interpreter differ on input {:?}",
+                    input
+                );
+                found
+            }
         }
     }
 
@@ -168,6 +264,23 @@ impl TestCompiledRegex {
         self.match_ucs2(input, 0).into_iter().next()
     }
 
+    /// Test whether self matches \p input, without computing the match.
+    pub fn is_match(&self, input: &str) -> bool {
+        #[cfg(feature = "utf16")]
+        {
+            if self.tc.encoding == Encoding::Utf16 {
+                return self.re.is_match_utf16(&to_utf16(input));
+            } else if self.tc.encoding == Encoding::Ucs2 && !input.chars().any(|c| c > '\u{FFFF}') {
+                return self.re.is_match_ucs2(&to_utf16(input));
+            }
+        }
+        if self.tc.use_ascii(input) {
+            self.re.is_match_ascii(input)
+        } else {
+            self.re.is_match(input)
+        }
+    }
+
     /// Match against a string, returning the first formatted match.
     #[track_caller]
     pub fn match1f(&self, input: &str) -> String {
@@ -206,13 +319,15 @@ impl TestCompiledRegex {
     /// Test that matching against \p input fails.
     #[track_caller]
     pub fn test_fails(&self, input: &str) {
-        assert!(self.find(input).is_none(), "Should not have matched")
+        assert!(self.find(input).is_none(), "Should not have matched");
+        assert!(!self.is_match(input), "is_match should not have matched")
     }
 
     /// Test that matching against \p input succeeds.
     #[track_caller]
     pub fn test_succeeds(&self, input: &str) {
-        assert!(self.find(input).is_some(), "Should have matched")
+        assert!(self.find(input).is_some(), "Should have matched");
+        assert!(self.is_match(input), "is_match should have matched")
     }
 
     /// Return a list of all non-overlapping total match ranges from a given
@@ -248,6 +363,10 @@ enum Backend {
     #[cfg(feature = "backend-pikevm")]
     PikeVM,
     Backtracking,
+    MemoizedBacktracking,
+    LazyDfa,
+    #[cfg(feature = "backend-jit")]
+    Jit,
 }
 
 /// Our encoding types.
@@ -292,7 +411,23 @@ impl TestConfig {
         let mut flags = regress::Flags::from(flags_str);
         flags.no_opt = !self.optimize;
 
-        let re = regress::Regex::with_flags(pattern, flags);
+        let re = match self.backend {
+            #[cfg(feature = "backend-pikevm")]
+            Backend::PikeVM => regress::RegexBuilder::new(pattern)
+                .flags(flags)
+                .engine(regress::Engine::PikeVM)
+                .build(),
+            Backend::MemoizedBacktracking => regress::RegexBuilder::new(pattern)
+                .flags(flags)
+                .engine(regress::Engine::MemoizedBacktrack)
+                .build(),
+            #[cfg(feature = "backend-jit")]
+            Backend::Jit => regress::RegexBuilder::new(pattern)
+                .flags(flags)
+                .engine(regress::Engine::Jit)
+                .build(),
+            _ => regress::Regex::with_flags(pattern, flags),
+        };
         assert!(
             re.is_ok(),
             "Failed to parse! flags: {} pattern: {}, error: {}",
@@ -300,9 +435,15 @@ impl TestConfig {
             pattern,
             re.unwrap_err()
         );
+        let interpreted = match self.backend {
+            #[cfg(feature = "backend-jit")]
+            Backend::Jit => Some(regress::Regex::with_flags(pattern, flags).unwrap()),
+            _ => None,
+        };
         TestCompiledRegex {
             re: re.unwrap(),
             tc: *self,
+            interpreted,
         }
     }
 
@@ -380,6 +521,45 @@ where
         backend: Backend::Backtracking,
         encoding,
     });
+    func(TestConfig {
+        ascii: true,
+        optimize: false,
+        backend: Backend::MemoizedBacktracking,
+        encoding,
+    });
+    func(TestConfig {
+        ascii: false,
+        optimize: true,
+        backend: Backend::MemoizedBacktracking,
+        encoding,
+    });
+    func(TestConfig {
+        ascii: false,
+        optimize: false,
+        backend: Backend::LazyDfa,
+        encoding,
+    });
+    func(TestConfig {
+        ascii: false,
+        optimize: true,
+        backend: Backend::LazyDfa,
+        encoding,
+    });
+    #[cfg(feature = "backend-jit")]
+    {
+        func(TestConfig {
+            ascii: false,
+            optimize: false,
+            backend: Backend::Jit,
+            encoding,
+        });
+        func(TestConfig {
+            ascii: false,
+            optimize: true,
+            backend: Backend::Jit,
+            encoding,
+        });
+    }
 
     // UTF16 and UCS2.
     if cfg!(feature = "utf16") {
@@ -410,6 +590,30 @@ where
             backend: Backend::Backtracking,
             encoding: Encoding::Ucs2,
         });
+
+        func(TestConfig {
+            ascii: false,
+            optimize: true,
+            backend: Backend::MemoizedBacktracking,
+            encoding: Encoding::Utf16,
+        });
+
+        #[cfg(feature = "backend-pikevm")]
+        {
+            func(TestConfig {
+                ascii: false,
+                optimize: true,
+                backend: Backend::PikeVM,
+                encoding: Encoding::Utf16,
+            });
+
+            func(TestConfig {
+                ascii: false,
+                optimize: true,
+                backend: Backend::PikeVM,
+                encoding: Encoding::Ucs2,
+            });
+        }
     }
 }
 
@@ -447,6 +651,25 @@ where
         backend: Backend::Backtracking,
         encoding: Encoding::Utf8,
     });
+    func(TestConfig {
+        ascii: false,
+        optimize: false,
+        backend: Backend::MemoizedBacktracking,
+        encoding: Encoding::Utf8,
+    });
+    func(TestConfig {
+        ascii: false,
+        optimize: true,
+        backend: Backend::MemoizedBacktracking,
+        encoding: Encoding::Utf8,
+    });
+    #[cfg(feature = "backend-jit")]
+    func(TestConfig {
+        ascii: false,
+        optimize: true,
+        backend: Backend::Jit,
+        encoding: Encoding::Utf8,
+    });
 
     // UTF16 and UCS2.
     if cfg!(feature = "utf16") {
@@ -477,5 +700,29 @@ where
             backend: Backend::Backtracking,
             encoding: Encoding::Ucs2,
         });
+
+        func(TestConfig {
+            ascii: false,
+            optimize: true,
+            backend: Backend::MemoizedBacktracking,
+            encoding: Encoding::Utf16,
+        });
+
+        #[cfg(feature = "backend-pikevm")]
+        {
+            func(TestConfig {
+                ascii: false,
+                optimize: true,
+                backend: Backend::PikeVM,
+                encoding: Encoding::Utf16,
+            });
+
+            func(TestConfig {
+                ascii: false,
+                optimize: true,
+                backend: Backend::PikeVM,
+                encoding: Encoding::Ucs2,
+            });
+        }
     }
 }
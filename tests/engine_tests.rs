@@ -0,0 +1,276 @@
+#![cfg(feature = "backend-pikevm")]
+
+pub mod common;
+use common::*;
+use regress::{Cache, Engine, MatchError, Regex, RegexBuilder};
+
+#[test]
+fn test_pikevm_agrees_with_backtracker() {
+    let cases = [
+        (r"a|ab", "", "xabab"),
+        (r"(a+)(b*)", "", "caabbaab"),
+        (r"(a|ab)(c|bcd)(d*)", "", "abcd abcd"),
+        (r"(a*)*b", "", "aaab ab b"),
+        (r"(?:a?)+?b", "", "aab"),
+        (r"(a*?)+x", "", "aaax"),
+        (r"(?:(a)|b)+", "", "abab"),
+        (r"(z)((a+)?(b+)?(c))*", "", "zaacbbbcac"),
+        (r"x{2,4}?y", "", "xxxxxy xy xxy"),
+        (r"(?:ab){2,3}", "", "abababab"),
+        (r"(\w)\1", "", "abccdeef"),
+        (r"(a)|\1b", "", "b ab"),
+        (r"(?<q>['\x22])\w*\k<q>", "", "say 'hi' and \"yo\""),
+        (r"\w+(?=!)", "", "hi there!"),
+        (r"(?=(a+))a*b\1", "", "baaabac"),
+        (r"(?!ab)a\w", "", "abacad"),
+        (r"(?<=\$)\d+", "", "$12 $34"),
+        (r"(?<=(\d+)(\d+))$", "", "1053"),
+        (r"(?<!x)y", "", "xy yy"),
+        (r"^\w+$", "m", "ab\ncd"),
+        (r"\bfoo\b", "", "foo food xfoo foo"),
+        (r"\u{1F600}+.", "u", "a\u{1F600}\u{1F600}\u{e9}"),
+        (r"k", "i", "K\u{212A}k"),
+        (r"caf\u{e9}|ca", "", "un caf\u{e9} ca"),
+        (r"", "", "abc"),
+        (r"a+", "y", "aab"),
+        (
+            r"(|[ab](.|a+|[ab]*?){2}a)+?c+|$a+?|",
+            "i",
+            "A\u{e9}cAacA\n \u{e9}a",
+        ),
+    ];
+    for (pattern, flags, text) in cases {
+        check_agrees(Engine::PikeVM, pattern, flags, &[text]);
+    }
+}
+
+#[test]
+fn test_pikevm_linear_time() {
+    // These take exponential time to fail by backtracking. The lookahead
+    // keeps the lazy DFA from finding the bounds of the match instead.
+    let text = "a".repeat(5_000) + "b";
+    for pattern in [r"(?=a)(a+)+$", r"(?=a)(a|a)*$", r"(?=a)(?:a*)*c"] {
+        let re = build(pattern, "", Engine::PikeVM);
+        assert!(matches!(re.try_find(&text), Ok(None)), "{pattern}");
+    }
+    let re = build(r"(?=a)(a+)+b$", "", Engine::PikeVM);
+    let m = re.try_find(&text).unwrap().unwrap();
+    assert_eq!(m.range(), 0..text.len());
+    assert_eq!(m.group(1), Some(0..text.len() - 1));
+}
+
+/// Append a random pattern of about \p depth levels to \p out, with
+/// quantified groups nested in one another.
+fn random_pattern(rng: &mut Rng, depth: usize, out: &mut String) {
+    const ATOMS: &[&str] = &["a", "b", "é", ".", "[ab]", "[^a]", "c", "", "$", "^"];
+    const QUANTIFIERS: &[&str] = &["", "", "*", "+", "?", "{2}", "{1,3}", "*?", "+?", "??"];
+    for _ in 0..1 + rng.below(3) {
+        if depth > 0 && rng.below(3) == 0 {
+            out.push_str(rng.pick(&["(", "(?:"]));
+            random_pattern(rng, depth - 1, out);
+            if rng.below(2) == 0 {
+                out.push('|');
+                random_pattern(rng, depth - 1, out);
+            }
+            out.push(')');
+            out.push_str(rng.pick(QUANTIFIERS));
+        } else {
+            let atom = rng.pick(ATOMS);
+            out.push_str(atom);
+            if !["", "$", "^"].contains(&atom) {
+                out.push_str(rng.pick(QUANTIFIERS));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_engines_agree_on_random_patterns() {
+    // The results of nested loops which may match the empty string depend on
+    // the bookkeeping of each engine, so check that they agree.
+    let try_groups = |re: &Regex, text: &str| {
+        re.try_find_iter(text)
+            .map(|m| m.ok().map(|m| m.groups().collect::<Vec<_>>()))
+            .collect::<Option<Vec<_>>>()
+    };
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..2_000 {
+        let mut pattern = String::new();
+        random_pattern(&mut rng, 3, &mut pattern);
+        let flags = rng.pick(&["", "i", "m", "s", "ms"]);
+        let text: String = (0..rng.below(12))
+            .map(|_| rng.pick(&["a", "b", "c", "é", "A", " ", "\n"]))
+            .collect();
+        let context = format!("pattern: {pattern:?} flags: {flags:?} text: {text:?}");
+        let pikevm = build(&pattern, flags, Engine::PikeVM);
+        let expected = all_groups(&pikevm, &text);
+        for engine in [Engine::Backtrack, Engine::MemoizedBacktrack] {
+            let re = RegexBuilder::new(&pattern)
+                .flags(flags)
+                .engine(engine)
+                .backtrack_limit(100_000)
+                .build()
+                .unwrap();
+            // Skip the patterns which backtrack catastrophically.
+            if let Some(groups) = try_groups(&re, &text) {
+                assert_eq!(groups, expected, "{engine:?} {context}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_engine_auto() {
+    // Auto chooses the PikeVM for patterns prone to catastrophic
+    // backtracking, which is not subject to the backtrack limit.
+    let text = "a".repeat(40) + "b";
+    let build_limited = |pattern: &str, engine| {
+        RegexBuilder::new(pattern)
+            .engine(engine)
+            .backtrack_limit(10_000)
+            .build()
+            .unwrap()
+    };
+    let re = build_limited(r"(a+)+$", Engine::Backtrack);
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+    let re = build_limited(r"(a+)+$", Engine::Auto);
+    assert!(matches!(re.try_find(&text), Ok(None)));
+
+    // Other patterns keep the backtracker, and find the same matches.
+    let re = build_limited(r"(a+)b", Engine::Auto);
+    assert_eq!(re.try_find(&text).unwrap().unwrap().range(), 0..text.len());
+}
+
+#[test]
+fn test_memoized_backtrack_linear_time() {
+    // Memoizing bounds the steps taken, so a small multiple of the length
+    // of the text suffices for the backtrack limit.
+    let text = "a".repeat(5_000) + "b";
+    let build_limited = |pattern: &str| {
+        RegexBuilder::new(pattern)
+            .engine(Engine::MemoizedBacktrack)
+            .backtrack_limit(100 * text.len())
+            .build()
+            .unwrap()
+    };
+    for pattern in [
+        r"(?=a)(a+)+$",
+        r"(?=a)(a|a)*$",
+        r"(?=a)(?:a*?)*c",
+        r"\w(?<=\d(?:a|a)*)",
+    ] {
+        let re = build_limited(pattern);
+        assert!(matches!(re.try_find(&text), Ok(None)), "{pattern}");
+    }
+    let re = build_limited(r"(?=a)(a+)+b$");
+    let m = re.try_find(&text).unwrap().unwrap();
+    assert_eq!(m.range(), 0..text.len());
+    assert_eq!(m.group(1), Some(0..text.len() - 1));
+}
+
+#[test]
+fn test_memoized_backtrack_agrees_with_backtracker() {
+    let cases = [
+        (r"(a|ab)(c|bcd)(d*)", "", "abcd abcd"),
+        (r"(?:(a)|b)+", "", "abab"),
+        (r"(a*?)+x", "", "aaax"),
+        (r"(?:a*?)*?b", "", "aab ab"),
+        (r"(?:(?:a){1,2}){2,3}", "", "aaaaaaa"),
+        (r"(?=(a+))a*b\1", "", "baaabac"),
+        (r"(?<=(\d+)(\d+))$", "", "1053"),
+        (r"(?!ab)a\w", "", "abacad"),
+        (r"(\w)\1+", "", "abccdeeef"),
+        (r"^\w+$", "m", "ab\ncd"),
+        (
+            r"A??[ab][^a]{2}|\u{e9}{1,3}?.^|((.c+)*?(..*c?){2}|)+?.{2}$",
+            "ms",
+            "\u{e9}\u{e9}bAc ",
+        ),
+    ];
+    for (pattern, flags, text) in cases {
+        let memoized = build(pattern, flags, Engine::MemoizedBacktrack);
+        let backtrack = build(pattern, flags, Engine::Backtrack);
+        assert_eq!(
+            all_groups(&memoized, text),
+            all_groups(&backtrack, text),
+            "pattern: {pattern:?} text: {text:?}"
+        );
+        let pikevm = build(pattern, flags, Engine::PikeVM);
+        assert_eq!(
+            all_groups(&memoized, text),
+            all_groups(&pikevm, text),
+            "pattern: {pattern:?} text: {text:?}"
+        );
+    }
+}
+
+#[test]
+fn test_engine_auto_memoizes() {
+    // The contents of lookbehinds are not checked for the risk of
+    // catastrophic backtracking, so Auto keeps the backtracker; but it starts
+    // memoizing once the search runs long.
+    let pattern = r"\w(?<=\d(?:a|a)*)";
+    assert!(
+        regress::analysis::redos_risk(pattern, "")
+            .unwrap()
+            .is_empty()
+    );
+    let text = "a".repeat(40);
+    let build_limited = |engine| {
+        RegexBuilder::new(pattern)
+            .engine(engine)
+            .backtrack_limit(1_000_000)
+            .build()
+            .unwrap()
+    };
+    let re = build_limited(Engine::Backtrack);
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+    let re = build_limited(Engine::Auto);
+    assert!(matches!(re.try_find(&text), Ok(None)));
+    let m = re.try_find(&(text + "1a")).unwrap().unwrap();
+    assert_eq!(m.range(), 40..41);
+}
+
+#[test]
+fn test_pikevm_interrupt() {
+    let text = "a".repeat(100_000) + "b";
+    let re = RegexBuilder::new(r"(a+)+$")
+        .engine(Engine::PikeVM)
+        .interrupt(|| true)
+        .build()
+        .unwrap();
+    assert!(matches!(re.try_find(&text), Err(MatchError::Cancelled)));
+    assert!(re.try_find("aab").unwrap().is_none());
+}
+
+#[test]
+fn test_pikevm_with_cache() {
+    let re = build(r"(\w+)@(\w+)", "", Engine::PikeVM);
+    let backtrack = build(r"(\w+)@(\w+)", "", Engine::Backtrack);
+    let mut cache = Cache::new();
+    for text in ["mail bob@example now", "none", "a@b c@d"] {
+        let m = re
+            .find_with_cache(text, &mut cache)
+            .map(|m| m.groups().collect::<Vec<_>>());
+        let expected = backtrack.find(text).map(|m| m.groups().collect::<Vec<_>>());
+        assert_eq!(m, expected, "{text}");
+        assert_eq!(re.is_match_with_cache(text, &mut cache), expected.is_some());
+    }
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_pikevm_utf16() {
+    let re = build(r"(\u{1F600}|.)\1", "u", Engine::PikeVM);
+    let text: Vec<u16> = "x\u{1F600}\u{1F600}yy".encode_utf16().collect();
+    let m = re.find_from_utf16(&text, 0).next().unwrap();
+    assert_eq!(m.range(), 1..5);
+    let m = re.find_from_ucs2(&text, 0).next().unwrap();
+    assert_eq!(m.range(), 5..7);
+}
@@ -0,0 +1,289 @@
+use regress::{Flags, Regex, RequiredLiteral};
+
+#[track_caller]
+fn regex(pattern: &str, flags: &str) -> Regex {
+    Regex::with_flags(pattern, flags).unwrap()
+}
+
+#[test]
+fn test_captures_len() {
+    let cases = [
+        ("abc", 1),
+        ("(a)(b)", 3),
+        ("(?:a)(b)", 2),
+        ("((a)|(?<x>b))*", 4),
+        ("(?=(a))(?<!(b))", 3),
+    ];
+    for (pattern, len) in cases {
+        let re = regex(pattern, "");
+        assert_eq!(re.captures_len(), len, "{}", pattern);
+        assert_eq!(re.capture_locations().len(), len, "{}", pattern);
+    }
+}
+
+#[test]
+fn test_capture_names() {
+    let re = regex(r"(?<year>\d{4})-(\d{2})-(?<day>\d{2})", "");
+    let names = re.capture_names();
+    assert_eq!(names.len(), 4);
+    assert_eq!(
+        names.collect::<Vec<_>>(),
+        [None, Some("year"), None, Some("day")]
+    );
+
+    let re = regex("(a)(b)", "");
+    assert_eq!(re.capture_names().collect::<Vec<_>>(), [None, None, None]);
+    assert_eq!(regex("ab", "").capture_names().collect::<Vec<_>>(), [None]);
+
+    // Duplicate names in alternatives are reported for each group.
+    let re = regex("(?<x>a)|(?<x>b)", "");
+    assert_eq!(
+        re.capture_names().collect::<Vec<_>>(),
+        [None, Some("x"), Some("x")]
+    );
+
+    let mut names = re.capture_names();
+    assert_eq!(names.size_hint(), (3, Some(3)));
+    names.next();
+    assert_eq!(names.len(), 2);
+    names.by_ref().for_each(drop);
+    assert_eq!(names.next(), None);
+    assert_eq!(names.len(), 0);
+}
+
+#[test]
+fn test_match_len() {
+    let cases = [
+        ("", "", Some(0), Some(0)),
+        ("abc", "", Some(3), Some(3)),
+        ("a|bcd", "", Some(1), Some(3)),
+        ("a*", "", Some(0), None),
+        ("a+", "", Some(1), None),
+        ("a{2,5}", "", Some(2), Some(5)),
+        ("(ab){3}", "", Some(6), Some(6)),
+        ("(?:a|bc)?d", "", Some(1), Some(3)),
+        ("^a$", "", Some(1), Some(1)),
+        (r"\bab\B", "", Some(2), Some(2)),
+        ("a(?=bcd)", "", Some(1), Some(1)),
+        ("a(?!b)", "", Some(1), Some(1)),
+        ("é", "", Some(2), Some(2)),
+        ("€", "", Some(3), Some(3)),
+        ("😀", "u", Some(4), Some(4)),
+        (".", "", Some(1), Some(4)),
+        (".", "s", Some(1), Some(4)),
+        ("[a-z]", "", Some(1), Some(1)),
+        ("[aé€]", "", Some(1), Some(3)),
+        ("[^a]", "u", Some(1), Some(4)),
+        (r"\d", "", Some(1), Some(1)),
+        (r"\w", "iu", Some(1), Some(3)),
+        (r"[\q{abc|d}]", "v", Some(1), Some(3)),
+        // The Kelvin sign folds to k.
+        ("k", "iu", Some(1), Some(3)),
+        // Backreferences are bounded by their groups.
+        (r"(a{1,2})\1", "", Some(1), Some(4)),
+        (r"(a+)\1", "", Some(1), None),
+        (r"(?<x>ab)\k<x>", "", Some(2), Some(4)),
+        (r"\1(a)", "", Some(1), Some(2)),
+        // Case insensitive backreferences may change length.
+        (r"(a)\1", "i", Some(1), None),
+        // Empty loop bodies do not make a loop unbounded.
+        ("(?:)*", "", Some(0), Some(0)),
+        ("(?:$)+a", "", Some(1), Some(1)),
+    ];
+    for (pattern, flags, min, max) in cases {
+        for optimize in [true, false] {
+            let mut flags = Flags::from(flags);
+            flags.no_opt = !optimize;
+            let re = Regex::with_flags(pattern, flags).unwrap();
+            assert_eq!(
+                (re.min_match_len(), re.max_match_len()),
+                (min, max),
+                "{}",
+                pattern
+            );
+        }
+    }
+}
+
+#[test]
+fn test_never_matches() {
+    for (pattern, flags) in [
+        ("[]", ""),
+        ("a[]", ""),
+        (r"[^\s\S]", "u"),
+        ("(?=[])a", ""),
+        ("[]+", ""),
+        ("[a&&b]", "v"),
+    ] {
+        let re = regex(pattern, flags);
+        assert_eq!(re.min_match_len(), None, "{}", pattern);
+        assert_eq!(re.max_match_len(), None, "{}", pattern);
+    }
+
+    // An optional body which never matches is skipped.
+    for (pattern, len) in [("a[]*", 1), ("a[]?b", 2), ("(?![])ab", 2), ("[]|a", 1)] {
+        let re = regex(pattern, "");
+        assert_eq!(re.min_match_len(), Some(len), "{}", pattern);
+        assert_eq!(re.max_match_len(), Some(len), "{}", pattern);
+    }
+}
+
+#[test]
+fn test_match_len_holds() {
+    let cases = [
+        (r"\w+@\w+\.com", "", "mail: someone@example.com!"),
+        ("(a|bé|€€)+x", "", "zzabé€€ax"),
+        (r"(?<x>é{2})\k<x>", "", "ééééé"),
+        ("[^a]{2,3}", "u", "a😀é€"),
+        ("K+", "iu", "kKK"),
+    ];
+    for (pattern, flags, text) in cases {
+        let re = regex(pattern, flags);
+        let len = re.find(text).unwrap().range().len();
+        assert!(re.min_match_len().unwrap() <= len, "{}", pattern);
+        assert!(
+            re.max_match_len().is_none_or(|max| len <= max),
+            "{}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn test_is_start_anchored() {
+    let cases = [
+        ("^abc", "", true),
+        ("^a|^b", "", true),
+        ("(^a)", "", true),
+        ("^abc", "m", false),
+        ("abc", "", false),
+        ("a^", "", false),
+        ("^a|b", "", false),
+        ("$", "", false),
+    ];
+    for (pattern, flags, anchored) in cases {
+        let re = regex(pattern, flags);
+        assert_eq!(re.is_start_anchored(), anchored, "{}", pattern);
+    }
+}
+
+/// \return the text of \p literals, with a trailing "/i" if case-insensitive.
+fn texts(literals: &[RequiredLiteral]) -> Vec<String> {
+    literals
+        .iter()
+        .map(|lit| match lit.icase {
+            true => format!("{}/i", lit.text),
+            false => lit.text.clone(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_required_literals() {
+    type Texts = &'static [&'static str];
+    let cases: &[(&str, &str, Texts, Texts, Texts)] = &[
+        ("hello", "", &["hello"], &["hello"], &["hello"]),
+        (
+            "colou?r",
+            "",
+            &["color", "colour"],
+            &["color", "colour"],
+            &["color", "colour"],
+        ),
+        (
+            r"(?:error|warning): \w+ failed",
+            "",
+            &["error: ", "warning: "],
+            &[" failed"],
+            &[" failed"],
+        ),
+        ("foo.*bar", "", &["foo"], &["bar"], &["bar"]),
+        (
+            r"\d+abc\d+",
+            "",
+            &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
+            &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
+            &["abc"],
+        ),
+        (
+            r"[a-c]+@example\.com",
+            "",
+            &["a", "b", "c"],
+            &["@example.com"],
+            &["@example.com"],
+        ),
+        (
+            "a[bc]d",
+            "",
+            &["abd", "acd"],
+            &["abd", "acd"],
+            &["abd", "acd"],
+        ),
+        ("(ab){2,}c", "", &["abab"], &["ababc"], &["ababc"]),
+        ("^(?=x)ab\\b$", "m", &["ab"], &["ab"], &["ab"]),
+        (
+            r"[\q{abc|dé}]x",
+            "v",
+            &["abcx", "déx"],
+            &["abcx", "déx"],
+            &["abcx", "déx"],
+        ),
+        (r"a\1(b)", "", &["a"], &["b"], &["b"]),
+        ("[^a]bc", "", &[], &["bc"], &["bc"]),
+    ];
+    for &(pattern, flags, prefixes, suffixes, inner) in cases {
+        for optimize in [true, false] {
+            let mut flags = Flags::from(flags);
+            flags.no_opt = !optimize;
+            let re = Regex::with_flags(pattern, flags).unwrap();
+            let literals = re.required_literals();
+            assert_eq!(texts(&literals.prefixes), prefixes, "{}", pattern);
+            assert_eq!(texts(&literals.suffixes), suffixes, "{}", pattern);
+            assert_eq!(texts(&literals.inner), inner, "{}", pattern);
+        }
+    }
+}
+
+#[test]
+fn test_required_literals_icase() {
+    let cases = [
+        ("Hello World", "i", "hello world/i"),
+        ("K", "iu", "k/i"),
+        ("ſ", "i", "s/i"),
+        ("[aA]bc", "", "abc/i"),
+        // Only the Kelvin sign is missing, so these are separate characters.
+        ("[kK]x", "u", "Kx"),
+        (r"a\p{RGI_Emoji_Flag_Sequence}", "vi", "a/i"),
+    ];
+    for (pattern, flags, inner) in cases {
+        let re = regex(pattern, flags);
+        assert_eq!(
+            texts(&re.required_literals().inner)[0],
+            inner,
+            "{}",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn test_required_literals_none() {
+    // Patterns which may match anything, or the empty string.
+    for pattern in ["", ".", "a*", "a|", "(?:ab)?", r"\w+", "[^a]", r"(.)\1"] {
+        let re = regex(pattern, "");
+        assert_eq!(re.required_literals(), &Default::default(), "{}", pattern);
+    }
+
+    // Long literals are shortened.
+    let long = "abcdefghij".repeat(10);
+    let literals = regex(&long, "").required_literals().clone();
+    assert_eq!(literals.prefixes[0].text, long[..64]);
+    assert_eq!(literals.suffixes[0].text, long[long.len() - 64..]);
+    assert_eq!(literals.inner[0].text.len(), 64);
+
+    // Large sets of alternatives are abandoned.
+    let re = regex("[a-z][a-z]", "");
+    assert_eq!(texts(&re.required_literals().inner).len(), 26);
+    let re = regex("[a-z]{2}xyz", "");
+    assert_eq!(texts(&re.required_literals().inner), ["xyz"]);
+}
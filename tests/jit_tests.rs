@@ -0,0 +1,173 @@
+#![cfg(feature = "backend-jit")]
+
+pub mod common;
+use common::*;
+use regress::{Engine, MatchError, Regex, RegexBuilder};
+
+#[test]
+fn test_jit_matches() {
+    let re = build(r"(\d{4})-(\d{2})-(\d{2})", "", Engine::Jit);
+    assert_eq!(
+        backtrack_groups(&re, "on 2020-10-05 and 2021-01-02"),
+        vec![
+            vec![Some(3..13), Some(3..7), Some(8..10), Some(11..13)],
+            vec![Some(18..28), Some(18..22), Some(23..25), Some(26..28)],
+        ]
+    );
+
+    let re = build(r"^(?:(a+)|(b))*?c$", "m", Engine::Jit);
+    assert_eq!(
+        backtrack_groups(&re, "aab c\naabc\n"),
+        vec![vec![Some(6..10), None, Some(8..9)]]
+    );
+
+    let re = build(r"(\w+)é", "u", Engine::Jit);
+    assert_eq!(
+        backtrack_groups(&re, "café thé"),
+        vec![vec![Some(0..5), Some(0..3)], vec![Some(6..10), Some(6..8)]]
+    );
+
+    // Patterns with backreferences and lookarounds are interpreted.
+    let re = build(r"(\w)\1(?=x)", "", Engine::Jit);
+    assert_eq!(
+        backtrack_groups(&re, "aabbx"),
+        vec![vec![Some(2..4), Some(2..3)]]
+    );
+}
+
+#[test]
+fn test_jit_agrees_with_interpreter() {
+    let texts = [
+        "",
+        "a",
+        "aaab",
+        "ab ba\nbab",
+        "xaé😀é\r\nb_1 😀",
+        "\u{2028}a\u{2029}b\u{212a}",
+        "AécAacA\n éa",
+        "ſtuff KELVIN",
+    ];
+    let cases = [
+        (r"a*", ""),
+        (r"(a|ab)(c|bcd)?(d*)", ""),
+        (r"(a+|b+)*c|.", ""),
+        (r"(?:a{2,3}?|b)+?\s", ""),
+        (r"(\w+)\s(\w+)", ""),
+        (r"^.*$", "m"),
+        (r"^.*$", "ms"),
+        (r"[^a]{2,}|\d", "u"),
+        (r"(é|😀)+", ""),
+        (r"[é😀]{1,2}?b?", "u"),
+        (r"\b.\B", ""),
+        (r"\b\w", "iu"),
+        (r"k+", "iu"),
+        (r"(?:(a)|(b)|x)*$", ""),
+        (r"(a*)*b", ""),
+        (r"(?:a*?)*?", ""),
+        (r"((a)|b){2,3}", "y"),
+        (r"\s*$", ""),
+        (r"(?:^|\r)\n?", "m"),
+        (r"[ ]| $", "m"),
+        (r"(|[ab](.|a+|[ab]*?){2}a)+?c+|$a+?|", "i"),
+    ];
+    for (pattern, flags) in cases {
+        check_agrees(Engine::Jit, pattern, flags, &texts);
+    }
+}
+
+/// Append a random pattern of about \p depth levels to \p out. Groups are
+/// only quantified if they are not \p nested in another group.
+fn random_pattern(rng: &mut Rng, depth: usize, nested: bool, out: &mut String) {
+    const ATOMS: &[&str] = &[
+        "a", "b", "é", "😀", "ab", ".", r"\w", r"\d", r"\s", "[ab]", "[^a]", "[a-é]", r"\b", r"\B",
+        "^", "$", "k",
+    ];
+    const QUANTIFIERS: &[&str] = &["", "", "*", "+", "?", "{2}", "{1,3}", "*?", "+?", "??"];
+    for _ in 0..1 + rng.below(3) {
+        // Quantified assertions and nested quantified groups which may match
+        // the empty string can make the backtracker exhaust memory.
+        let quantify = if depth > 0 && rng.below(3) == 0 {
+            out.push_str(rng.pick(&["(", "(?:"]));
+            random_pattern(rng, depth - 1, true, out);
+            if rng.below(2) == 0 {
+                out.push('|');
+                random_pattern(rng, depth - 1, true, out);
+            }
+            out.push(')');
+            !nested
+        } else {
+            let atom = rng.pick(ATOMS);
+            out.push_str(atom);
+            ![r"\b", r"\B", "^", "$"].contains(&atom)
+        };
+        if quantify {
+            out.push_str(rng.pick(QUANTIFIERS));
+        }
+    }
+}
+
+#[test]
+fn test_jit_random_patterns() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..300 {
+        let mut pattern = String::new();
+        random_pattern(&mut rng, 2, false, &mut pattern);
+        let flags = rng.pick(&["", "i", "m", "s", "u", "iu", "y", "ms"]);
+        // Assertions may not be quantified.
+        if Regex::with_flags(&pattern, flags).is_err() {
+            continue;
+        }
+        let texts: Vec<String> = (0..4)
+            .map(|_| {
+                (0..rng.below(12))
+                    .map(|_| rng.pick(&["a", "b", "é", "😀", " ", "\n", "1", "K", "_"]))
+                    .collect()
+            })
+            .collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        check_agrees(Engine::Jit, &pattern, flags, &texts);
+    }
+}
+
+#[test]
+fn test_jit_deep_stack() {
+    // Matches whose stack outgrows the compiled code's are interpreted.
+    let deep = "ab".repeat(100_000) + "c";
+    let shallow = ("ab".repeat(10) + "c\n").repeat(10);
+    check_agrees(Engine::Jit, r"^(a|b)*c", "m", &[&deep, &shallow]);
+    check_agrees(Engine::Jit, r"(?:(a)|b)*?c", "", &[&deep, &shallow]);
+}
+
+#[test]
+fn test_jit_backtrack_limit() {
+    // A backtrack limit is enforced by interpreting the regex.
+    let re = RegexBuilder::new(r"(?:(a)|b)*c")
+        .engine(Engine::Jit)
+        .backtrack_limit(10)
+        .build()
+        .unwrap();
+    let text = "ab".repeat(50) + "c";
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+}
+
+#[test]
+fn test_jit_shared_across_threads() {
+    let re = build(r"(\w+)@(\w+)\.com", "", Engine::Jit);
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let re = re.clone();
+            std::thread::spawn(move || {
+                let text = format!("user{i}@example.com");
+                let m = re.find(&text).unwrap();
+                assert_eq!(m.group(1), Some(0..5));
+            })
+        })
+        .collect();
+    drop(re);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+pub mod common;
+use common::*;
+use regress::{Cache, Engine, Regex};
+
+#[test]
+fn test_lazydfa_agrees_with_backtracker() {
+    let cases = [
+        (r"a|ab", "", "xabab"),
+        (r"(a+)(b*)", "", "caabbaab"),
+        (r"(a|ab)(c|bcd)(d*)", "", "abcd abcd"),
+        (r"\d+?", "", "a123b45"),
+        (r"^\w+$", "", "hello"),
+        (r"^\w+$", "", "hello world"),
+        (r"\bfoo\b", "", "foo food xfoo foo"),
+        (r"\Bo\B", "", "foo boo o"),
+        (r"o\b", "", "foo boo o"),
+        (r"x{2,4}?y", "", "xxxxxy xy xxy"),
+        (r"(?:ab){2}", "", "abababab"),
+        (r"[^a]+", "", "aa\u{e9}\u{1F600}xa"),
+        (r"\u{1F600}+.", "u", "a\u{1F600}\u{1F600}\u{e9}"),
+        (r"k", "i", "K\u{212A}k"),
+        (r"\w+", "iu", "a\u{17F}S"),
+        (r".+", "", "ab\ncd\r\n"),
+        (r".+", "s", "ab\ncd"),
+        (r"(a*)*b", "", "aaab ab b"),
+        (r"(?:a?)+?b", "", "aab"),
+        (r"()", "", "abc"),
+        (r"", "y", "abc"),
+        (r"a+", "y", "aab"),
+    ];
+    for (pattern, flags, text) in cases {
+        check_agrees(Engine::Backtrack, pattern, flags, &[text]);
+    }
+}
+
+#[test]
+fn test_lazydfa_unsupported_patterns() {
+    // Backreferences, lookarounds and multiline anchors use the backtracker.
+    let cases = [
+        (r"(\w)\1", "", "abccd"),
+        (r"\w+(?=!)", "", "hi there!"),
+        (r"(?<=\$)\d+", "", "$12 $34"),
+        (r"^\w+$", "m", "ab\ncd"),
+    ];
+    for (pattern, flags, text) in cases {
+        check_agrees(Engine::Backtrack, pattern, flags, &[text]);
+    }
+}
+
+#[test]
+fn test_lazydfa_gives_up() {
+    // This pattern has exponentially many DFA states, so the search clears
+    // its cache repeatedly, gives up, and falls back to backtracking.
+    let mut text = String::new();
+    let mut seed = 7u32;
+    for i in 1..=120_000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        text.push(if seed & 0x10000 == 0 { 'a' } else { 'b' });
+        // Keep the backtracker's work at each start position bounded.
+        if i % 40 == 0 {
+            text.push('d');
+        }
+    }
+    text.push_str(&format!("a{}c", "b".repeat(20)));
+    check_agrees(Engine::Backtrack, r"[ab]*a[ab]{20}c", "", &[&text]);
+}
+
+#[test]
+fn test_lazydfa_cache_reuse() {
+    let mut cache = Cache::new();
+    let digits = Regex::new(r"(\d+)-(\d+)").unwrap();
+    let words = Regex::new(r"\b\w+\b").unwrap();
+    for _ in 0..3 {
+        for re in [&digits, &words] {
+            let text = "call 555-1234 now";
+            let expected = re.find(text).map(|m| m.range());
+            assert_eq!(
+                re.find_with_cache(text, &mut cache).map(|m| m.range()),
+                expected
+            );
+            assert_eq!(re.is_match_with_cache(text, &mut cache), expected.is_some());
+        }
+    }
+    // A clone searches with its own states.
+    let clone = digits.clone();
+    assert_eq!(clone.find("1-2").map(|m| m.range()), Some(0..3));
+    assert_eq!(digits.find("x 3-4").map(|m| m.range()), Some(2..5));
+}
+
+#[test]
+fn test_lazydfa_threads() {
+    let re = Regex::new(r"[a-z]+\d").unwrap();
+    std::thread::scope(|scope| {
+        for i in 0..4 {
+            let re = &re;
+            scope.spawn(move || {
+                for _ in 0..1000 {
+                    let text = format!("{} abc{i}", "-".repeat(i));
+                    assert_eq!(re.find(&text).map(|m| m.range()), Some(i + 1..i + 5));
+                }
+            });
+        }
+    });
+}
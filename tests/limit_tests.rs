@@ -0,0 +1,219 @@
+use regress::{MatchError, Regex, RegexBuilder};
+
+fn limited(pattern: &str, limit: usize) -> Regex {
+    RegexBuilder::new(pattern)
+        .backtrack_limit(limit)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_backtrack_limit_exceeded() {
+    let text = "a".repeat(30) + "b";
+    for pattern in [r"(a+)+$", r"(a|aa)+$", r"(?:a*)*b$c", r"(?=(a+)+$)"] {
+        let re = limited(pattern, 10_000);
+        assert!(
+            matches!(re.try_find(&text), Err(MatchError::BacktrackLimitExceeded)),
+            "pattern: {pattern}"
+        );
+        assert_eq!(
+            re.try_is_match(&text),
+            Err(MatchError::BacktrackLimitExceeded)
+        );
+        assert!(matches!(
+            re.try_find_at_anchored(&text, 0),
+            Err(MatchError::BacktrackLimitExceeded)
+        ));
+        // Infallible methods report no match.
+        assert!(re.find(&text).is_none());
+        assert!(!re.is_match(&text));
+        assert!(re.find_at_anchored(&text, 0).is_none());
+    }
+
+    // A negative lookahead whose search is abandoned must not succeed.
+    let re = limited(r"(?!(a+)+$)a", 10_000);
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+    assert!(re.find(&text).is_none());
+
+    // Abandoning a lookaround must not disturb the groups around it.
+    let re = limited(r"(a(?!(?:a|a)*b))", 100);
+    assert!(matches!(
+        re.try_find(&"a".repeat(30)),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+    let re = limited(r"(a(?<!(?:a|a)*b))+c", 100);
+    assert!(matches!(
+        re.try_find(&"a".repeat(30)),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+}
+
+#[test]
+fn test_abandoned_lookbehind_in_analysis() {
+    // Both the analysis and the automatic engine run the backtracker with a
+    // limit, and so may abandon a lookbehind.
+    let pattern = r"(é*?.*.{1,}|(?:|b?A{2}A{1,3}?|){1,3}?(?<!a+?.+a*?||.)|)*a{2}";
+    assert!(regress::analysis::redos_risk(pattern, "s").is_ok());
+    let re = RegexBuilder::new(pattern)
+        .flags("s")
+        .engine(regress::Engine::Auto)
+        .build()
+        .unwrap();
+    assert_eq!(re.find("xaa").map(|m| m.range()), Some(0..3));
+}
+
+#[test]
+fn test_backtrack_limit_not_exceeded() {
+    let re = limited(r"(a+)+$", 10_000);
+    let m = re.try_find("xaaaa").unwrap().unwrap();
+    assert_eq!(m.range(), 1..5);
+    assert_eq!(m.group(1), Some(1..5));
+    assert_eq!(re.try_is_match("aaaa"), Ok(true));
+    assert!(matches!(re.try_find("bbb"), Ok(None)));
+    assert_eq!(re.try_find_ascii("aa").unwrap().unwrap().range(), 0..2);
+
+    // The budget is reset for each match.
+    let re = limited(r"a+b", 8);
+    let text = "aab ".repeat(50);
+    let matches: Result<Vec<_>, _> = re.try_find_iter(&text).collect();
+    assert_eq!(matches.unwrap().len(), 50);
+
+    // With no limit, everything completes.
+    let re = Regex::new(r"(a+)+$").unwrap();
+    assert!(matches!(re.try_find(&("a".repeat(18) + "b")), Ok(None)));
+}
+
+#[test]
+fn test_try_find_iter_stops_after_error() {
+    let re = limited(r"(a|b|ab)+$|x", 1_000);
+    let text = "x ".to_string() + &"ab".repeat(30) + "c x";
+    let results: Vec<_> = re.try_find_iter(&text).collect();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap().range(), 0..1);
+    assert!(matches!(
+        results[1],
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+
+    // The error is displayable.
+    assert_eq!(
+        MatchError::BacktrackLimitExceeded.to_string(),
+        "Backtrack limit exceeded"
+    );
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_backtrack_limit_utf16() {
+    let re = limited(r"(a+)+$", 10_000);
+    let text: Vec<u16> = ("a".repeat(30) + "b").encode_utf16().collect();
+    let mut results = re.try_find_from_utf16(&text, 0);
+    assert!(matches!(
+        results.next(),
+        Some(Err(MatchError::BacktrackLimitExceeded))
+    ));
+    assert!(results.next().is_none());
+    let mut results = re.try_find_from_ucs2(&text, 0);
+    assert!(matches!(
+        results.next(),
+        Some(Err(MatchError::BacktrackLimitExceeded))
+    ));
+    assert!(!re.is_match_utf16(&text));
+
+    let text: Vec<u16> = "aa".encode_utf16().collect();
+    let m = re.try_find_from_utf16(&text, 0).next().unwrap().unwrap();
+    assert_eq!(m.range(), 0..2);
+}
+
+#[test]
+fn test_interrupt() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let polls = Arc::new(AtomicUsize::new(0));
+    let re = {
+        let cancel = cancel.clone();
+        let polls = polls.clone();
+        RegexBuilder::new(r"(a+)+$")
+            .interrupt(move || {
+                polls.fetch_add(1, Ordering::Relaxed);
+                cancel.load(Ordering::Relaxed)
+            })
+            .build()
+            .unwrap()
+    };
+
+    // Short searches complete normally.
+    assert_eq!(re.try_find("aaaa").unwrap().unwrap().range(), 0..4);
+    let text = "a".repeat(16) + "b";
+    assert!(matches!(re.try_find(&text), Ok(None)));
+    assert!(polls.load(Ordering::Relaxed) > 0);
+
+    cancel.store(true, Ordering::Relaxed);
+    assert!(matches!(re.try_find(&text), Err(MatchError::Cancelled)));
+    assert!(matches!(re.try_is_match(&text), Err(MatchError::Cancelled)));
+    assert!(re.find(&text).is_none());
+    assert!(matches!(re.try_find("aaaa"), Ok(Some(_))));
+
+    // Cancellation from another thread.
+    cancel.store(false, Ordering::Relaxed);
+    let text = "a".repeat(100) + "b";
+    let canceller = {
+        let cancel = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            cancel.store(true, Ordering::Relaxed);
+        })
+    };
+    assert!(matches!(re.try_find(&text), Err(MatchError::Cancelled)));
+    canceller.join().unwrap();
+    assert_eq!(MatchError::Cancelled.to_string(), "Search cancelled");
+}
+
+#[test]
+fn test_interrupt_with_backtrack_limit() {
+    // Whichever of the limit and the interrupt triggers first is reported.
+    let re = RegexBuilder::new(r"(a+)+$")
+        .backtrack_limit(5_000)
+        .interrupt(|| false)
+        .build()
+        .unwrap();
+    let text = "a".repeat(30) + "b";
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+    let re = RegexBuilder::new(r"(a+)+$")
+        .backtrack_limit(5_000)
+        .interrupt(|| true)
+        .build()
+        .unwrap();
+    assert!(matches!(re.try_find(&text), Err(MatchError::Cancelled)));
+}
+
+#[test]
+#[cfg(feature = "backend-pikevm")]
+fn test_interrupt_pikevm() {
+    use regress::backends::{self, PikeVMExecutor};
+    let text = "x".repeat(5000) + "y";
+    let re = RegexBuilder::new(r"y").interrupt(|| true).build().unwrap();
+    assert!(
+        backends::find::<PikeVMExecutor>(&re, &text, 0)
+            .next()
+            .is_none()
+    );
+    assert!(
+        backends::find::<PikeVMExecutor>(&re, "xy", 0)
+            .next()
+            .is_some()
+    );
+    let re = RegexBuilder::new(r"y").interrupt(|| false).build().unwrap();
+    let m = backends::find::<PikeVMExecutor>(&re, &text, 0)
+        .next()
+        .unwrap();
+    assert_eq!(m.range(), 5000..5001);
+}
@@ -0,0 +1,110 @@
+pub mod common;
+use common::*;
+use regress::{MatchError, Regex, RegexBuilder};
+
+#[test]
+fn test_onepass_captures() {
+    let re = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$").unwrap();
+    assert_eq!(
+        all_groups(&re, "2020-10-05"),
+        vec![vec![Some(0..10), Some(0..4), Some(5..7), Some(8..10)]]
+    );
+    assert_eq!(all_groups(&re, "2020-10-051"), Vec::<Vec<_>>::new());
+
+    let re = Regex::new(r"^(\w+)=(\w*)$").unwrap();
+    assert_eq!(
+        all_groups(&re, "key=value"),
+        vec![vec![Some(0..9), Some(0..3), Some(4..9)]]
+    );
+    assert_eq!(
+        all_groups(&re, "key="),
+        vec![vec![Some(0..4), Some(0..3), Some(4..4)]]
+    );
+
+    // Groups are reset at each iteration of a loop.
+    let re = Regex::new(r"^(?:(a)|(b))*$").unwrap();
+    assert_eq!(
+        all_groups(&re, "aab"),
+        vec![vec![Some(0..3), None, Some(2..3)]]
+    );
+    assert_eq!(all_groups(&re, ""), vec![vec![Some(0..0), None, None]]);
+
+    // Sticky searches are anchored at each match.
+    let re = Regex::with_flags(r"(\d+)(?:,|$)", "y").unwrap();
+    assert_eq!(
+        all_groups(&re, "12,345,6"),
+        vec![
+            vec![Some(0..3), Some(0..2)],
+            vec![Some(3..7), Some(3..6)],
+            vec![Some(7..8), Some(7..8)],
+        ]
+    );
+    assert_eq!(
+        all_groups(&re, "12,x,6"),
+        vec![vec![Some(0..3), Some(0..2)]]
+    );
+
+    let re = Regex::with_flags(r"^(\S+)[ ]\u{e9}(x|y)?$", "m").unwrap();
+    assert_eq!(
+        all_groups(&re, "a \u{e9}x\nbc \u{e9}\n"),
+        vec![
+            vec![Some(0..5), Some(0..1), Some(4..5)],
+            vec![Some(6..11), Some(6..8), None],
+        ]
+    );
+}
+
+#[test]
+fn test_onepass_does_not_backtrack() {
+    // Each alternative fails at its first character, so a one-pass search
+    // only spends a step per iteration of the loop.
+    let text = "d".repeat(1000);
+    let build = |pattern: &str| {
+        RegexBuilder::new(pattern)
+            .backtrack_limit(2 * text.len())
+            .build()
+            .unwrap()
+    };
+    let re = build(r"^(?:(a)|(b)|(c)|(d))*$");
+    let m = re.try_find(&text).unwrap().unwrap();
+    assert_eq!(m.range(), 0..1000);
+    assert_eq!(m.group(4), Some(999..1000));
+
+    // Two alternatives begin with 'd', so this must backtrack.
+    let re = build(r"^(?:(a)|(b)|(c)|(d)|dd)*$");
+    assert!(matches!(
+        re.try_find(&text),
+        Err(MatchError::BacktrackLimitExceeded)
+    ));
+}
+
+#[test]
+#[cfg(feature = "backend-pikevm")]
+fn test_onepass_agrees_with_pikevm() {
+    use regress::Engine;
+    let cases = [
+        (r"^(\d{4})-(\d{2})-(\d{2})$", "", "2020-10-05"),
+        (r"^(\w+)=(\w*)$", "", "a=b"),
+        (r"^([a-z]+)@([a-z]+)\.com$", "", "me@example.com"),
+        (r"^(?:(ab)|(ac))+$", "", "abacab"),
+        (r"^(a|b)c", "", "bcd"),
+        (r"^(a|)a", "", "aa"),
+        (r"^(?:ab)*$", "", "ababa"),
+        (r"^(?:ab)*", "", "ababa"),
+        (r"^x*?(y)", "", "xxy"),
+        (r"^(\w+)\b(\s*)", "", "ab  c"),
+        (r"^(?:(a)|b)*?$", "m", "ab\nba"),
+        (r"(a)(b)?", "y", "aabab"),
+        (r"(\d+)(?:,|$)", "y", "1,22,333"),
+        (r"(?:(\u{1F600})|(\u{e9}))+", "y", "\u{1F600}\u{e9}\u{e9}x"),
+        (r"^([^a]{2,3})(a)", "", "\u{1F600}\u{1F600}\u{1F600}a"),
+        (r"^(?:(a)|(b)){2,3}$", "", "bab"),
+    ];
+    for (pattern, flags, text) in cases {
+        assert_eq!(
+            all_groups(&build(pattern, flags, Engine::Backtrack), text),
+            all_groups(&build(pattern, flags, Engine::PikeVM), text),
+            "pattern: {pattern:?} flags: {flags:?} text: {text:?}"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "backend-pikevm")]
+
+use regress::{Regex, RegexSet};
+
+const PATTERNS: &[&str] = &[
+    r"abc",
+    r"\d+",
+    r"^\s",
+    r"x$",
+    r"(a)(b)?\2\1",
+    r"(?<q>['\x22])\w*\k<q>",
+    r"(?<=(\d))px",
+    r"(?!ab)a\w",
+    r"(?:ab|cd){2,}",
+    r"",
+    r"[^\s\S]",
+    r"\bfoo\b",
+    r"é+",
+    r"(\w)\1",
+];
+
+const INPUTS: &[&str] = &[
+    "",
+    "abc",
+    " abc",
+    "xx",
+    "aba",
+    "say 'hi' and \"yo\"",
+    "10px",
+    "px",
+    "abcdab",
+    "cdcd",
+    "a foo b",
+    "food",
+    "caf\u{e9}\u{e9}",
+    "a\u{e9}\u{e9}x",
+];
+
+/// \return the indexes of the patterns which match, by matching each in turn.
+fn expected_matches(flags: &str, input: &str) -> Vec<usize> {
+    PATTERNS
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| Regex::with_flags(p, flags).unwrap().find(input).is_some())
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+#[test]
+fn test_regexset_agrees_with_regex() {
+    for flags in ["", "i", "m", "y", "u"] {
+        let set = RegexSet::with_flags(PATTERNS, flags).unwrap();
+        assert_eq!(set.len(), PATTERNS.len());
+        for input in INPUTS {
+            let expected = expected_matches(flags, input);
+            let matches = set.matches(input);
+            assert_eq!(
+                matches.iter().collect::<Vec<_>>(),
+                expected,
+                "flags: {flags:?} input: {input:?}"
+            );
+            assert_eq!(set.is_match(input), !expected.is_empty());
+            if input.is_ascii() {
+                assert_eq!(set.matches_ascii(input), matches);
+            }
+            #[cfg(feature = "utf16")]
+            {
+                let utf16: Vec<u16> = input.encode_utf16().collect();
+                assert_eq!(set.matches_utf16(&utf16), matches);
+                assert_eq!(set.matches_ucs2(&utf16), matches);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_regexset_basics() {
+    let set = RegexSet::new([r"^a", r"^b", r"c"]).unwrap();
+    let matches = set.matches("abc");
+    assert!(matches.matched_any());
+    assert!(matches.matched(0));
+    assert!(!matches.matched(1));
+    assert!(matches.matched(2));
+    assert_eq!(matches.len(), 3);
+    assert_eq!((&matches).into_iter().collect::<Vec<_>>(), vec![0, 2]);
+    assert_eq!(matches.iter().rev().collect::<Vec<_>>(), vec![2, 0]);
+    assert!(!set.matches("xyz").matched_any());
+    assert!(set.matches("xyz").iter().next().is_none());
+
+    // The empty set matches nothing, including the empty string.
+    let set = RegexSet::new(Vec::<&str>::new()).unwrap();
+    assert!(set.is_empty());
+    assert!(!set.is_match(""));
+    assert!(set.matches("abc").is_empty());
+
+    // Syntax errors in any member are reported, with its index.
+    let err = RegexSet::new([r"a", r"(b"]).unwrap_err();
+    assert_eq!(err.pattern_index(), Some(1));
+    assert_eq!(err.span(), Some(0..1));
+    assert_eq!(err.text(), "Unbalanced parenthesis");
+    assert_eq!(err.to_string(), "Unbalanced parenthesis in pattern 1");
+    assert_eq!(
+        format!("{:#}", err),
+        "Unbalanced parenthesis in pattern 1\n(b\n^"
+    );
+    let err = RegexSet::new([r"(a)".repeat(40_000), r"(b)".repeat(40_000)]).unwrap_err();
+    assert_eq!(err.kind(), regress::ErrorKind::CaptureGroupLimitExceeded);
+    assert_eq!(err.pattern_index(), Some(1));
+    assert_eq!(err.span(), Some(0..120_000));
+    let err = RegexSet::new([r"(?:ab)*".repeat(40_000), r"(?:ba)*".repeat(40_000)]).unwrap_err();
+    assert_eq!(err.kind(), regress::ErrorKind::LoopLimitExceeded);
+    assert_eq!(err.pattern_index(), Some(1));
+    assert_eq!(err.span(), Some(0..280_000));
+    assert!(
+        regress::Regex::new("ab(")
+            .unwrap_err()
+            .pattern_index()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_regexset_runs_members_together() {
+    // Members which match at different positions, and overlapping members,
+    // are all found in one pass.
+    let set = RegexSet::new([r"a+b", r"b+", r"(?:ab)+c", r"c$", r"\bz"]).unwrap();
+    let text = "xaab".repeat(100) + "ababc";
+    assert_eq!(
+        set.matches(&text).iter().collect::<Vec<_>>(),
+        vec![0, 1, 2, 3]
+    );
+    assert!(set.is_match(&text));
+    // Members' groups are kept apart.
+    let set = RegexSet::new([r"(a)\1", r"(b)\1", r"(?<x>c)\k<x>"]).unwrap();
+    assert_eq!(set.matches("abbcc").iter().collect::<Vec<_>>(), vec![1, 2]);
+}
+
+#[test]
+fn test_regexset_many_patterns() {
+    let patterns: Vec<String> = (0..500).map(|i| format!(r"\bw{i}\b")).collect();
+    let set = RegexSet::new(&patterns).unwrap();
+    let matches = set.matches("w7 w123 w1234 w499");
+    assert_eq!(matches.iter().collect::<Vec<_>>(), vec![7, 123, 499]);
+}
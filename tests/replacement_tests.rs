@@ -1,4 +1,5 @@
-use regress::Regex;
+use regress::{Regex, ReplacementTemplate};
+use std::borrow::Cow;
 
 #[test]
 fn test_replace_basic() {
@@ -22,9 +23,9 @@ fn test_replace_with_capture_groups() {
 }
 
 #[test]
-fn test_replace_with_group_zero() {
+fn test_replace_legacy_with_group_zero() {
     let re = Regex::new(r"\d+").unwrap();
-    let result = re.replace("Price: $123", "[$0]");
+    let result = re.replace_legacy("Price: $123", "[$0]");
     assert_eq!(result, "Price: $[123]");
 }
 
@@ -86,9 +87,9 @@ fn test_replace_all_with_closure() {
 }
 
 #[test]
-fn test_replace_named_groups() {
+fn test_replace_legacy_named_groups() {
     let re = Regex::new(r"(?<first>\w+)\s+(?<second>\w+)").unwrap();
-    let result = re.replace("hello world", "${second} ${first}");
+    let result = re.replace_legacy("hello world", "${second} ${first}");
     assert_eq!(result, "world hello");
 }
 
@@ -100,23 +101,23 @@ fn test_replace_named_groups_malformed() {
 }
 
 #[test]
-fn test_replace_nonexistent_group() {
+fn test_replace_legacy_nonexistent_group() {
     let re = Regex::new(r"(\w+)").unwrap();
-    let result = re.replace("hello", "$1 $2 $3");
+    let result = re.replace_legacy("hello", "$1 $2 $3");
     assert_eq!(result, "hello  ");
 }
 
 #[test]
-fn test_replace_high_group_numbers() {
+fn test_replace_legacy_high_group_numbers() {
     let re = Regex::new(r"(\w)(\w)(\w)").unwrap();
-    let result = re.replace("abc", "$3$2$1$0");
+    let result = re.replace_legacy("abc", "$3$2$1$0");
     assert_eq!(result, "cbaabc");
 }
 
 #[test]
-fn test_replace_large_group_number() {
+fn test_replace_legacy_large_group_number() {
     let re = Regex::new(r"(\w+)").unwrap();
-    let result = re.replace("hello", "$999");
+    let result = re.replace_legacy("hello", "$999");
     assert_eq!(result, "");
 }
 
@@ -169,3 +170,220 @@ fn test_complex_replacement() {
     let result = re.replace_all("Born on 12/25/1990 and graduated on 5/15/2012", "$3-$1-$2");
     assert_eq!(result, "Born on 1990-12-25 and graduated on 2012-5-15");
 }
+
+#[test]
+fn test_replace_special_patterns() {
+    let re = Regex::new(r"\d+").unwrap();
+    assert_eq!(re.replace("ab12cd", "[$&]"), "ab[12]cd");
+    assert_eq!(re.replace("ab12cd", "[$`]"), "ab[ab]cd");
+    assert_eq!(re.replace("ab12cd", "[$']"), "ab[cd]cd");
+    assert_eq!(re.replace("ab12cd", "[$$]"), "ab[$]cd");
+    assert_eq!(re.replace("ab12cd", "[$$&]"), "ab[$&]cd");
+    assert_eq!(re.replace("ab12cd", "$"), "ab$cd");
+    assert_eq!(re.replace("ab12cd", "$x$"), "ab$x$cd");
+    assert_eq!(re.replace_all("1a22", "<$`|$'>"), "<|a22>a<1a|>");
+}
+
+#[test]
+fn test_replace_group_numbers() {
+    let re = Regex::new(r"(\w)(\w)(\w)").unwrap();
+    // $0 and $00 are not groups.
+    assert_eq!(re.replace("abc", "$3$2$1$0"), "cba$0");
+    assert_eq!(re.replace("abc", "$00"), "$00");
+    assert_eq!(re.replace("abc", "$01$02"), "ab");
+    // Numbers beyond the group count are literal.
+    assert_eq!(re.replace("abc", "$4"), "$4");
+    assert_eq!(re.replace("abc", "$999"), "$999");
+    // A two digit number beyond the group count is one digit and a literal.
+    assert_eq!(re.replace("abc", "$10"), "a0");
+    assert_eq!(re.replace("abc", "$39"), "c9");
+
+    // Two digit group numbers.
+    let re = Regex::new(r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)").unwrap();
+    assert_eq!(re.replace("abcdefghijk", "$10$11$12"), "jka2");
+    assert_eq!(re.replace("abcdefghijk", "$011"), "a1");
+
+    // Groups which did not participate are empty.
+    let re = Regex::new(r"(a)|(b)").unwrap();
+    assert_eq!(re.replace("b", "[$1][$2]"), "[][b]");
+}
+
+#[test]
+fn test_replace_named_groups() {
+    let re = Regex::new(r"(?<first>\w+)\s+(?<second>\w+)").unwrap();
+    assert_eq!(
+        re.replace("hello world", "$<second> $<first>"),
+        "world hello"
+    );
+    // Unknown names are empty, and unterminated references are literal.
+    assert_eq!(re.replace("hello world", "[$<third>]"), "[]");
+    assert_eq!(re.replace("hello world", "[$<>]"), "[]");
+    assert_eq!(re.replace("hello world", "$<first"), "$<first");
+    // The braced syntax is not special.
+    assert_eq!(
+        re.replace("hello world", "${second} ${first}"),
+        "${second} ${first}"
+    );
+
+    // Without named groups, $< is literal.
+    let re = Regex::new(r"(\w+)").unwrap();
+    assert_eq!(re.replace("hello", "$<first>"), "$<first>");
+
+    // Duplicate names refer to the group which participated.
+    let re = Regex::new(r"(?<x>a)|(?<x>b)").unwrap();
+    assert_eq!(re.replace_all("ab", "[$<x>]"), "[a][b]");
+}
+
+#[test]
+fn test_replace_multibyte() {
+    let re = Regex::new(r"(é)").unwrap();
+    assert_eq!(re.replace("😀é😀", "$'$1$`"), "😀😀é😀😀");
+    assert_eq!(re.replace("é", "€$1€"), "€é€");
+}
+
+#[test]
+fn test_replacement_template() {
+    let re = Regex::new(r"(?<first>\w)(\w)?").unwrap();
+    let text = "abc d";
+    for template in [
+        "",
+        "x",
+        "$",
+        "$$",
+        "$&",
+        "$`",
+        "$'",
+        "$1$2",
+        "$3",
+        "$01",
+        "$10",
+        "$<first>",
+        "$<first",
+        "[$<first>$2]",
+        "€$1€",
+    ] {
+        let parsed = ReplacementTemplate::new(&re, template).unwrap();
+        assert_eq!(parsed.as_str(), template);
+        assert_eq!(
+            re.replace_all(text, &parsed),
+            re.replace_all(text, template),
+            "template: {template}"
+        );
+        assert_eq!(re.replace(text, parsed), re.replace(text, template));
+    }
+
+    // Unknown names are rejected, with the span of the first.
+    let err = ReplacementTemplate::new(&re, "é$<second>$<third>").unwrap_err();
+    assert_eq!(err.name(), "second");
+    assert_eq!(err.span(), 2..11);
+    assert_eq!(
+        format!("{:#}", err),
+        "Unknown capture group name in replacement: second\né$<second>$<third>\n ^^^^^^^^^"
+    );
+    let err = ReplacementTemplate::new(&re, "$<>").unwrap_err();
+    assert_eq!((err.name(), err.span()), ("", 0..3));
+    // Without named groups, $<...> is literal and so not validated.
+    let re = Regex::new(r"(\w)").unwrap();
+    let parsed = ReplacementTemplate::new(&re, "$<second>").unwrap();
+    assert_eq!(re.replace("a", &parsed), "$<second>");
+
+    // Duplicate names refer to the group which participated.
+    let re = Regex::new(r"(?<x>a)|(?<x>b)").unwrap();
+    let parsed = ReplacementTemplate::new(&re, "[$<x>]").unwrap();
+    assert_eq!(re.replace_all("ab", &parsed), "[a][b]");
+}
+
+#[test]
+fn test_replacer_closures_and_strings() {
+    let re = Regex::new(r"\d+").unwrap();
+    let text = "a1b22";
+    let mut count = 0;
+    let result = re.replace_all(text, |m: &regress::Match| {
+        count += 1;
+        format!("<{}:{}>", count, m.as_str(text))
+    });
+    assert_eq!(result, "a<1:1>b<2:22>");
+    assert_eq!(re.replace_all(text, |_: &regress::Match| "$&"), "a$&b$&");
+    assert_eq!(re.replace_all(text, String::from("[$&]")), "a[1]b[22]");
+    assert_eq!(re.replace_all(text, &String::from("[$&]")), "a[1]b[22]");
+}
+
+#[test]
+fn test_replacen() {
+    let re = Regex::new(r"\d").unwrap();
+    assert_eq!(re.replacen("1 2 3", 1, "#"), "# 2 3");
+    assert_eq!(re.replacen("1 2 3", 2, "#"), "# # 3");
+    assert_eq!(re.replacen("1 2 3", 3, "#"), "# # #");
+    assert_eq!(re.replacen("1 2 3", 4, "#"), "# # #");
+    assert_eq!(re.replacen("1 2 3", 0, "#"), "# # #");
+    assert!(matches!(
+        re.replacen("a b c", 0, "#"),
+        Cow::Borrowed("a b c")
+    ));
+    assert!(matches!(re.replacen("1", 0, "#"), Cow::Owned(_)));
+
+    // Empty matches.
+    let re = Regex::new(r"").unwrap();
+    assert_eq!(re.replacen("abc", 2, "-"), "-a-bc");
+    assert_eq!(re.replacen("", 0, "-"), "-");
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_replace_utf16() {
+    fn to_utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+    fn from_utf16(s: &[u16]) -> String {
+        String::from_utf16(s).unwrap()
+    }
+
+    let re = Regex::new(r"(?<word>\w+)@(\w+)").unwrap();
+    let text = to_utf16("😀 user@example, admin@test");
+    let template = to_utf16("$2 at $<word>");
+    assert_eq!(
+        from_utf16(&re.replace_utf16(&text, &template[..])),
+        "😀 example at user, admin@test"
+    );
+    assert_eq!(
+        from_utf16(&re.replace_all_utf16(&text, &template)),
+        "😀 example at user, test at admin"
+    );
+    assert_eq!(
+        from_utf16(&re.replace_all_ucs2(&text, template.clone())),
+        "😀 example at user, test at admin"
+    );
+    let parsed = ReplacementTemplate::new(&re, "[$`|$&|$']").unwrap();
+    assert_eq!(
+        from_utf16(&re.replace_ucs2(&to_utf16("x a@b y"), &parsed)),
+        "x [x |a@b| y] y"
+    );
+    let result = re.replace_all_utf16(&text, |m: &regress::Match| {
+        to_utf16(&format!("<{}>", m.range().len()))
+    });
+    assert_eq!(from_utf16(&result), "😀 <12>, <10>");
+
+    // Limits, and borrowing when nothing matched.
+    let re = Regex::new(r"\d").unwrap();
+    let text = to_utf16("1 2 3");
+    assert_eq!(
+        from_utf16(&re.replacen_utf16(&text, 2, &to_utf16("#"))),
+        "# # 3"
+    );
+    let text = to_utf16("a b");
+    assert!(matches!(
+        re.replacen_ucs2(&text, 0, &to_utf16("#")),
+        Cow::Borrowed(_)
+    ));
+
+    // UTF-16 steps over surrogate pairs; UCS-2 treats them as two units.
+    let re = Regex::new(r"").unwrap();
+    let text = to_utf16("😀");
+    assert_eq!(
+        re.replace_all_utf16(&text, &to_utf16("-")),
+        to_utf16("-😀-")
+    );
+    let mut expected = vec![u16::from(b'-'), text[0], u16::from(b'-')];
+    expected.extend([text[1], u16::from(b'-')]);
+    assert_eq!(re.replace_all_ucs2(&text, &to_utf16("-")), expected);
+}
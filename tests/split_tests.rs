@@ -0,0 +1,142 @@
+use regress::Regex;
+
+fn split<'t>(pattern: &str, flags: &str, text: &'t str) -> Vec<Option<&'t str>> {
+    Regex::with_flags(pattern, flags).unwrap().split(text)
+}
+
+#[test]
+fn test_split_basic() {
+    assert_eq!(split(r",", "", "a,b,c"), [Some("a"), Some("b"), Some("c")]);
+    assert_eq!(split(r",", "", "a,b,"), [Some("a"), Some("b"), Some("")]);
+    assert_eq!(split(r",", "", ",a"), [Some(""), Some("a")]);
+    assert_eq!(split(r"x", "", "abc"), [Some("abc")]);
+    assert_eq!(
+        split(r"\s+", "", "a  b\tc"),
+        [Some("a"), Some("b"), Some("c")]
+    );
+    assert_eq!(split(r"B", "i", "abAbc"), [Some("a"), Some("A"), Some("c")]);
+}
+
+#[test]
+fn test_split_empty_matches() {
+    assert_eq!(split(r"", "", "abc"), [Some("a"), Some("b"), Some("c")]);
+    assert_eq!(split(r"a*?", "", "ab"), [Some("a"), Some("b")]);
+    assert_eq!(split(r"a*", "", "ab"), [Some(""), Some("b")]);
+    assert_eq!(split(r"(?<=b)", "", "abc"), [Some("ab"), Some("c")]);
+    assert_eq!(split(r"^", "", "abc"), [Some("abc")]);
+    assert_eq!(split(r"$", "", "abc"), [Some("abc")]);
+    assert_eq!(split(r"^", "m", "a\nb"), [Some("a\n"), Some("b")]);
+    assert_eq!(split(r"", "", "a😀b"), [Some("a"), Some("😀"), Some("b")]);
+
+    // An empty input yields nothing if the regex matches the empty string.
+    assert_eq!(split(r"", "", ""), Vec::<Option<&str>>::new());
+    assert_eq!(split(r"a?", "", ""), Vec::<Option<&str>>::new());
+    assert_eq!(split(r"a", "", ""), [Some("")]);
+}
+
+#[test]
+fn test_split_captures() {
+    // Example from the ECMAScript specification.
+    assert_eq!(
+        split(r"<(\/)?([^<>]+)>", "", "A<B>bold</B>and<CODE>coded</CODE>"),
+        [
+            Some("A"),
+            None,
+            Some("B"),
+            Some("bold"),
+            Some("/"),
+            Some("B"),
+            Some("and"),
+            None,
+            Some("CODE"),
+            Some("coded"),
+            Some("/"),
+            Some("CODE"),
+            Some(""),
+        ]
+    );
+    assert_eq!(
+        split(r"(-)|(\+)", "", "1-2+3"),
+        [
+            Some("1"),
+            Some("-"),
+            None,
+            Some("2"),
+            None,
+            Some("+"),
+            Some("3"),
+        ]
+    );
+    assert_eq!(split(r"(?:)", "", "ab"), [Some("a"), Some("b")]);
+    assert_eq!(split(r"()", "", "ab"), [Some("a"), Some(""), Some("b")]);
+}
+
+#[test]
+fn test_split_limit() {
+    let re = Regex::new(r",").unwrap();
+    assert!(re.splitn("a,b,c", 0).is_empty());
+    assert_eq!(re.splitn("a,b,c", 1), [Some("a")]);
+    assert_eq!(re.splitn("a,b,c", 2), [Some("a"), Some("b")]);
+    assert_eq!(re.splitn("a,b,c", 3), [Some("a"), Some("b"), Some("c")]);
+    assert_eq!(re.splitn("a,b,c", 4), [Some("a"), Some("b"), Some("c")]);
+    assert!(re.splitn("", 0).is_empty());
+
+    // Captures count towards the limit.
+    let re = Regex::new(r"(,)(x)?").unwrap();
+    assert_eq!(re.splitn("a,b", 2), [Some("a"), Some(",")]);
+    assert_eq!(re.splitn("a,b", 3), [Some("a"), Some(","), None]);
+    assert_eq!(re.splitn("a,b", 4), [Some("a"), Some(","), None, Some("b")]);
+}
+
+#[test]
+fn test_split_sticky() {
+    // The sticky flag does not affect splitting.
+    assert_eq!(split(r",", "y", "a,b,c"), [Some("a"), Some("b"), Some("c")]);
+    assert_eq!(split(r"", "y", "ab"), [Some("a"), Some("b")]);
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_split_utf16() {
+    fn to_utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+    fn from_utf16(pieces: Vec<Option<&[u16]>>) -> Vec<Option<String>> {
+        pieces
+            .into_iter()
+            .map(|p| p.map(|p| String::from_utf16(p).unwrap()))
+            .collect()
+    }
+    fn strs(v: &[Option<&str>]) -> Vec<Option<String>> {
+        v.iter().map(|s| s.map(String::from)).collect()
+    }
+
+    let re = Regex::new(r"(,)|;").unwrap();
+    let text = to_utf16("a,😀;c");
+    assert_eq!(
+        from_utf16(re.split_utf16(&text)),
+        strs(&[Some("a"), Some(","), Some("😀"), None, Some("c")])
+    );
+    assert_eq!(
+        from_utf16(re.split_ucs2(&text)),
+        strs(&[Some("a"), Some(","), Some("😀"), None, Some("c")])
+    );
+    assert_eq!(
+        from_utf16(re.splitn_utf16(&text, 2)),
+        strs(&[Some("a"), Some(",")])
+    );
+    assert!(re.splitn_ucs2(&text, 0).is_empty());
+
+    // UTF-16 advances by code point, UCS-2 by code unit.
+    let re = Regex::new(r"").unwrap();
+    let text = to_utf16("😀x");
+    assert_eq!(
+        re.split_utf16(&text),
+        [Some(&text[0..2]), Some(&text[2..3])]
+    );
+    assert_eq!(
+        re.split_ucs2(&text),
+        [Some(&text[0..1]), Some(&text[1..2]), Some(&text[2..3])]
+    );
+    assert!(re.split_utf16(&[]).is_empty());
+}
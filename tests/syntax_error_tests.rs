@@ -1,11 +1,13 @@
 #![allow(clippy::uninlined_format_args)]
 
+use std::ops::Range;
+
 #[track_caller]
 fn test_1_error(pattern: &str, expected_err: &str) {
     let res = regress::Regex::with_flags(pattern, "u");
     assert!(res.is_err(), "Pattern should not have parsed: {}", pattern);
 
-    let err = res.err().unwrap().text;
+    let err = res.err().unwrap().text().to_string();
     assert!(
         err.contains(expected_err),
         "Error text '{}' did not contain '{}' for pattern '{}'",
@@ -47,7 +49,7 @@ fn test_excessive_nesting() {
     let res = regress::Regex::with_flags(&vclass, "v");
     assert!(res.is_err(), "Deeply nested v-mode class should not parse");
     assert!(
-        res.err().unwrap().text.contains("too deeply nested"),
+        res.err().unwrap().text().contains("too deeply nested"),
         "Expected nesting error for deeply nested v-mode class"
     );
 
@@ -102,7 +104,7 @@ fn test_invalid_hex_escape() {
     let res = regress::Regex::with_flags(r"\1\x(", "");
     assert!(res.is_err());
     assert!(
-        res.err().unwrap().text.contains("Unbalanced parenthesis"),
+        res.err().unwrap().text().contains("Unbalanced parenthesis"),
         "Expected the leftover '(' to be treated as an unterminated group"
     );
 
@@ -115,3 +117,207 @@ fn test_invalid_hex_escape() {
     let re = regress::Regex::with_flags(r"\xg1", "").unwrap();
     assert!(re.find("xg1").is_some());
 }
+
+#[track_caller]
+fn test_1_error_span(pattern: &str, flags: &str, kind: regress::ErrorKind, span: Range<usize>) {
+    let err = regress::Regex::with_flags(pattern, flags).unwrap_err();
+    assert_eq!(err.kind(), kind, "Wrong kind for pattern '{}'", pattern);
+    assert_eq!(
+        err.span(),
+        Some(span),
+        "Wrong span for pattern '{}'",
+        pattern
+    );
+}
+
+#[test]
+fn test_error_kinds_and_spans() {
+    use regress::ErrorKind::*;
+    test_1_error_span("a)", "", UnbalancedParenthesis, 1..2);
+    test_1_error_span("a(b", "", UnbalancedParenthesis, 1..2);
+    test_1_error_span("ab\\", "", IncompleteEscape, 2..3);
+    test_1_error_span("a**", "", InvalidAtomCharacter, 2..3);
+    test_1_error_span("^*", "", QuantifierNotAllowed, 1..2);
+    test_1_error_span("a{3,2}", "", InvalidQuantifier, 1..6);
+    test_1_error_span("x[abc", "", UnbalancedBracket, 1..2);
+    test_1_error_span("[az-a]", "", RangeOutOfOrder, 2..5);
+    test_1_error_span(r"[a-\d]", "u", InvalidCharacterRange, 1..5);
+    test_1_error_span("(?ii:a)", "", InvalidGroupModifier, 0..4);
+    test_1_error_span(r"a\p{Foo}", "u", InvalidPropertyName, 1..8);
+    test_1_error_span(r"\p", "u", InvalidPropertyEscapeStart, 0..2);
+    test_1_error_span(r"a\q", "u", InvalidCharacterEscape, 1..3);
+    test_1_error_span(r"(?<a>x)\k<b>", "", UnknownGroupName, 7..12);
+    test_1_error_span(r"\k<", "u", InvalidNamedBackreference, 0..3);
+    test_1_error_span("(?<a>x)(?<a>y)", "", DuplicateGroupName, 7..8);
+    test_1_error_span("(?<1>x)", "", InvalidGroupName, 0..3);
+    test_1_error_span("[a&&b|c]", "v", InvalidClassSetIntersection, 5..6);
+    test_1_error_span("[a--b|c]", "v", InvalidClassSetSubtraction, 5..6);
+    test_1_error_span("[[a]", "v", UnbalancedClassSetBracket, 0..1);
+    test_1_error_span("[z-a]", "v", InvalidClassSetRange, 1..4);
+    test_1_error_span("[(]", "v", InvalidClassSetCharacter, 1..2);
+}
+
+#[test]
+fn test_error_span_units() {
+    // Spans of &str patterns are in bytes.
+    let err = regress::Regex::new("é(").unwrap_err();
+    assert_eq!(err.span(), Some(2..3));
+
+    // Spans of from_unicode patterns are in code points.
+    let err = regress::Regex::from_unicode("é(".chars().map(u32::from), "").unwrap_err();
+    assert_eq!(err.span(), Some(1..2));
+
+    // Sets report spans within the offending pattern.
+    let err = regress::RegexSet::new(["abc", "é[a"]).unwrap_err();
+    assert_eq!(err.kind(), regress::ErrorKind::UnbalancedBracket);
+    assert_eq!(err.span(), Some(2..3));
+}
+
+#[test]
+fn test_error_caret_rendering() {
+    let err = regress::Regex::new("ab(c").unwrap_err();
+    assert_eq!(err.to_string(), "Unbalanced parenthesis");
+    assert_eq!(format!("{:#}", err), "Unbalanced parenthesis\nab(c\n  ^");
+
+    // The caret is aligned by characters, and covers the whole span.
+    let err = regress::Regex::new("é[z-a]").unwrap_err();
+    assert_eq!(
+        format!("{:#}", err),
+        "Range values reversed, start char code is greater than end char code.\né[z-a]\n  ^^^"
+    );
+
+    // Line breaks in the pattern do not break the alignment.
+    let err = regress::Regex::new("a\nb)").unwrap_err();
+    assert_eq!(format!("{:#}", err), "Unbalanced parenthesis\na b)\n   ^");
+
+    // An error at the end of the pattern gets a caret past its end.
+    let err = regress::Regex::with_flags("a{", "u").unwrap_err();
+    assert_eq!(err.kind(), regress::ErrorKind::InvalidQuantifier);
+    assert_eq!(format!("{:#}", err), "Invalid quantifier\na{\n ^");
+}
+
+#[test]
+fn test_check_agrees_with_compile() {
+    let patterns = [
+        ("abc", ""),
+        ("a(b|c)*d", "u"),
+        ("a)", ""),
+        ("(a", ""),
+        ("a**", "u"),
+        ("[z-a]", ""),
+        (r"\p{Foo}", "u"),
+        ("(?<a>x)(?<a>y)", ""),
+        ("(?<a>x)|(?<a>y)", ""),
+        ("[a&&b|c]", "v"),
+        ("((a|b**)", "u"),
+        ("é(?ii:x)", ""),
+    ];
+    for (pattern, flags) in patterns {
+        let diagnostics = regress::syntax::check(pattern, flags);
+        match regress::Regex::with_flags(pattern, flags) {
+            Ok(_) => assert!(
+                diagnostics.is_empty(),
+                "Unexpected diagnostics for '{}'",
+                pattern
+            ),
+            Err(err) => assert_eq!(diagnostics.first(), Some(&err), "Pattern '{}'", pattern),
+        }
+    }
+}
+
+#[test]
+fn test_check_reports_all_errors() {
+    use regress::ErrorKind::*;
+    let kinds_and_spans = |pattern: &str, flags: &str| {
+        regress::syntax::check(pattern, flags)
+            .iter()
+            .map(|d| (d.kind(), d.span().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    // Each alternative is reported separately.
+    assert_eq!(
+        kinds_and_spans("a**|b{2,1}|[z-a]", ""),
+        [
+            (InvalidAtomCharacter, 2..3),
+            (InvalidQuantifier, 5..10),
+            (RangeOutOfOrder, 12..15),
+        ]
+    );
+
+    // An error within a group resumes after the group.
+    assert_eq!(
+        kinds_and_spans(r"(a**)b\p{Foo}", "u"),
+        [(InvalidAtomCharacter, 3..4), (InvalidPropertyName, 6..13)]
+    );
+
+    // Skipped alternatives keep groups and classes balanced.
+    assert_eq!(
+        kinds_and_spans("x**(|)[)|]|y**", "u"),
+        [(InvalidAtomCharacter, 2..3), (InvalidAtomCharacter, 13..14)]
+    );
+
+    // Stray closing parentheses are each reported.
+    assert_eq!(
+        kinds_and_spans("a)b)c**", "u"),
+        [
+            (UnbalancedParenthesis, 1..2),
+            (UnbalancedParenthesis, 3..4),
+            (InvalidAtomCharacter, 6..7),
+        ]
+    );
+
+    // Unclosed groups and classes are reported once.
+    assert_eq!(kinds_and_spans("(a", ""), [(UnbalancedParenthesis, 0..1)]);
+    assert_eq!(kinds_and_spans("[a", ""), [(UnbalancedBracket, 0..1)]);
+
+    // Spans are in bytes, and diagnostics render with a caret.
+    let diagnostics = regress::syntax::check("é)", "");
+    assert_eq!(diagnostics[0].span(), Some(2..3));
+    assert_eq!(
+        format!("{:#}", diagnostics[0]),
+        "Unbalanced parenthesis\né)\n ^"
+    );
+}
+
+#[test]
+fn test_check_excessive_nesting() {
+    let deep = 5000;
+    let noncap = format!("{}a{}|b**", "(?:".repeat(deep), ")".repeat(deep));
+    let kinds: Vec<_> = regress::syntax::check(&noncap, "u")
+        .iter()
+        .map(|d| d.kind())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            regress::ErrorKind::TooDeeplyNested,
+            regress::ErrorKind::InvalidAtomCharacter
+        ]
+    );
+}
+
+#[test]
+fn test_check_counts_skipped_alternatives() {
+    use regress::ErrorKind::*;
+    let kinds = |pattern: &str| {
+        regress::syntax::check(pattern, "u")
+            .iter()
+            .map(|d| d.kind())
+            .collect::<Vec<_>>()
+    };
+
+    // The loops of a skipped alternative are not counted toward the limit.
+    let pattern = ("a*".repeat(33_000) + "b**|").repeat(2);
+    assert_eq!(kinds(&pattern), [InvalidAtomCharacter; 2]);
+
+    // The capture groups of a skipped alternative are counted, whether or
+    // not they were reached before the error.
+    let pattern = "(a)**(b)(?<n>b)(?<=b)|".to_string() + &"(c)".repeat(65_533);
+    assert_eq!(
+        kinds(&pattern),
+        [InvalidAtomCharacter, CaptureGroupLimitExceeded]
+    );
+    let pattern = "(a)**(b)(?<n>b)(?<=b)|".to_string() + &"(c)".repeat(65_532);
+    assert_eq!(kinds(&pattern), [InvalidAtomCharacter]);
+}
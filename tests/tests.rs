@@ -2520,3 +2520,213 @@ fn test_find_from_char_boundaries() {
     // An out-of-range ascii start likewise yields no matches, not a panic.
     assert!(re.find_from_ascii("abc", 4).next().is_none());
 }
+
+fn test_sticky_flag_tc(tc: TestConfig) {
+    // Sticky regexes only match at the start position.
+    tc.test_match_fails(r"b", "y", "ab");
+    tc.test_match_succeeds(r"a", "y", "ab");
+    assert_eq!(tc.compilef(r"b", "y").match_all_from("ab", 1), vec![1..2]);
+    assert_eq!(
+        tc.compilef(r"b", "y").match_all_from("abb", 1),
+        vec![1..2, 2..3]
+    );
+
+    // Iteration stops at the first position which does not match.
+    tc.compilef(r"\d", "y")
+        .match_all("12a3")
+        .test_eq(vec!["1", "2"]);
+    tc.compilef(r"a|", "y")
+        .match_all("aab")
+        .test_eq(vec!["a", "a", "", ""]);
+
+    // Sticky does not interfere with start anchors, even under multiline.
+    tc.test_match_fails(r"^b", "ym", "a\nb");
+    assert_eq!(
+        tc.compilef(r"^b", "ym").match_all_from("a\nb", 2),
+        vec![2..3]
+    );
+    assert!(tc.compilef(r"^b", "y").match_all_from("a\nb", 2).is_empty());
+
+    // Lookbehinds may look before the start position.
+    assert_eq!(
+        tc.compilef(r"(?<=a)b", "y").match_all_from("ab", 1),
+        vec![1..2]
+    );
+
+    // Captures are reported.
+    tc.compilef(r"(\w+)=(\d+)", "y")
+        .match1f("k=1 j=2")
+        .test_eq("k=1,k,1");
+}
+
+#[test]
+fn test_sticky_flag() {
+    test_with_configs(test_sticky_flag_tc)
+}
+
+#[test]
+fn test_find_at_anchored() {
+    let re = Regex::new(r"\d+").unwrap();
+    let text = "ab12 34";
+    assert!(re.find_at_anchored(text, 0).is_none());
+    assert!(re.find_at_anchored(text, 1).is_none());
+    assert_eq!(re.find_at_anchored(text, 2).unwrap().range(), 2..4);
+    assert_eq!(re.find_at_anchored(text, 3).unwrap().range(), 3..4);
+    assert!(re.find_at_anchored(text, 4).is_none());
+    assert_eq!(re.find_at_anchored(text, 5).unwrap().range(), 5..7);
+    assert!(re.find_at_anchored(text, 7).is_none());
+    assert!(re.find_at_anchored(text, 100).is_none());
+    assert_eq!(re.find_at_anchored_ascii(text, 5).unwrap().range(), 5..7);
+    assert!(re.find_at_anchored_ascii(text, 4).is_none());
+
+    // Empty matches at the end are permitted.
+    let re = Regex::new(r"x*").unwrap();
+    assert_eq!(re.find_at_anchored(text, 7).unwrap().range(), 7..7);
+
+    // Start anchors still refer to the start of the input.
+    let re = Regex::new(r"^\d+").unwrap();
+    assert!(re.find_at_anchored(text, 2).is_none());
+    let re = Regex::with_flags(r"^\d+", "m").unwrap();
+    assert!(re.find_at_anchored("a\n12", 1).is_none());
+    assert_eq!(re.find_at_anchored("a\n12", 2).unwrap().range(), 2..4);
+
+    // Multibyte characters.
+    let re = Regex::new(r".").unwrap();
+    assert_eq!(re.find_at_anchored("a😀b", 1).unwrap().range(), 1..5);
+
+    // The flag survives a round trip through Display.
+    assert_eq!(regress::Flags::from("iy").to_string(), "iy");
+}
+
+#[test]
+#[cfg(feature = "utf16")]
+fn test_find_at_anchored_utf16() {
+    let re = Regex::new(r"\d+").unwrap();
+    let text = to_utf16("ab12 😀3");
+    assert!(re.find_at_anchored_utf16(&text, 1).is_none());
+    assert_eq!(re.find_at_anchored_utf16(&text, 2).unwrap().range(), 2..4);
+    assert_eq!(re.find_at_anchored_utf16(&text, 7).unwrap().range(), 7..8);
+    assert!(re.find_at_anchored_utf16(&text, 20).is_none());
+    assert_eq!(re.find_at_anchored_ucs2(&text, 2).unwrap().range(), 2..4);
+
+    // UCS-2 sees the halves of a surrogate pair as separate characters.
+    let re = Regex::new(r".").unwrap();
+    assert_eq!(re.find_at_anchored_utf16(&text, 5).unwrap().range(), 5..7);
+    assert_eq!(re.find_at_anchored_ucs2(&text, 5).unwrap().range(), 5..6);
+    assert_eq!(re.find_at_anchored_ucs2(&text, 6).unwrap().range(), 6..7);
+}
+
+fn test_is_match_tc(tc: TestConfig) {
+    // Capture groups which are not referenced may be elided.
+    let re = tc.compile(r"(a)(b)?(?:(c)|d)+");
+    assert!(re.is_match("xxabcdc"));
+    assert!(re.is_match("ad"));
+    assert!(!re.is_match("xbcd"));
+
+    // Lookarounds containing capture groups.
+    let re = tc.compile(r"(?=(\w+))\w(?<!(x))y");
+    assert!(re.is_match("ay"));
+    assert!(!re.is_match("xy"));
+
+    // Backreferences still depend on their groups.
+    let re = tc.compile(r"(\w)(?=\1)");
+    assert!(re.is_match("abb"));
+    assert!(!re.is_match("abc"));
+    let re = tc.compile(r"(?<=(\w))\1");
+    assert!(re.is_match("abb"));
+    assert!(!re.is_match("abc"));
+
+    // Empty matches.
+    assert!(tc.compile(r"x*").is_match(""));
+    assert!(!tc.compile(r"x+").is_match(""));
+
+    // Sticky regexes must match at the start.
+    assert!(!tc.compilef(r"b", "y").is_match("ab"));
+    assert!(tc.compilef(r"a", "y").is_match("ab"));
+}
+
+#[test]
+fn test_is_match() {
+    test_with_configs(test_is_match_tc)
+}
+
+fn test_inner_literal_tc(tc: TestConfig) {
+    // A literal at an unbounded offset.
+    let re = tc.compile(r"\w+@example\.com");
+    assert_eq!(
+        re.match_all("mail bob@example.com, é@example.com or x@example.org"),
+        vec!["bob@example.com"]
+    );
+    assert_eq!(re.match_all("@example.com"), Vec::<&str>::new());
+    assert_eq!(
+        tc.compile(r".*ERROR")
+            .match_all("ok\nan ERROR\nERRO\nERROR ERROR"),
+        vec!["an ERROR", "ERROR ERROR"]
+    );
+
+    // A literal at a bounded offset, after multibyte characters.
+    let re = tc.compile(r"(\d|é){1,3}-([a-z]+)-end");
+    assert_eq!(
+        re.match_all("1234-ab-end éé1-x-end 5-y-en -z-end"),
+        vec!["234-ab-end", "éé1-x-end"]
+    );
+    assert_eq!(re.match1f("xé9é-é-end é-q-end"), "é-q-end,é,q");
+
+    // The longest literal is searched for, and earlier ones still checked.
+    let re = tc.compile(r"[a-z]+=\d+;[\s\S]*?trailer");
+    assert_eq!(
+        re.match_all("a=1;trailer bb=22 ;trailer cc=3;\ntrailer"),
+        vec!["a=1;trailer", "cc=3;\ntrailer"]
+    );
+
+    // Literals in a lookahead are not required to be matched.
+    let re = tc.compile(r"\w+(?=abc)ab");
+    assert_eq!(re.match_all("xabc yab zzabc"), vec!["xab", "zzab"]);
+}
+
+#[test]
+fn test_inner_literal() {
+    test_with_configs(test_inner_literal_tc)
+}
+
+fn test_literal_set_tc(tc: TestConfig) {
+    // Alternations of literals without a shared prefix.
+    let re = tc.compile(r"foo|bar|baz\d");
+    assert_eq!(
+        re.match_all("fo ba baz bar1 baz2 xfoo"),
+        vec!["bar", "baz2", "foo"]
+    );
+    let re = tc.compile(r"(?:été|😀x|ab)+!");
+    assert_eq!(
+        re.match_all("ét été! ab😀xab! 😀!"),
+        vec!["été!", "ab😀xab!"]
+    );
+
+    // Literals of one byte are looked for by their first bytes.
+    assert_eq!(
+        tc.compile(r"a|bc|d").match_all("xbxdbca"),
+        vec!["d", "bc", "a"]
+    );
+
+    // Class string disjunctions, with too many strings to pack.
+    let strings: Vec<_> = (0..300).map(|i| format!("<{i}>")).collect();
+    let re = tc.compilef(&format!(r"[\q{{{}}}]", strings.join("|")), "v");
+    assert_eq!(
+        re.match_all("<300> <29> <3000> <2999><299>"),
+        vec!["<29>", "<299>"]
+    );
+    let re = tc.compilef(r"[\q{éa|xyz|😀😀}]\d", "v");
+    assert_eq!(
+        re.match_all("xyz éa1 😀😀😀2 xyz3"),
+        vec!["éa1", "😀😀2", "xyz3"]
+    );
+
+    // Properties of strings.
+    let re = tc.compilef(r"\p{RGI_Emoji_Flag_Sequence}", "v");
+    assert_eq!(re.match_all("a 🇫🇷 b 🇩🇪🇯"), vec!["🇫🇷", "🇩🇪"]);
+}
+
+#[test]
+fn test_literal_set() {
+    test_with_configs_no_ascii(test_literal_set_tc)
+}